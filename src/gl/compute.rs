@@ -0,0 +1,264 @@
+use bytemuck::Pod;
+use glow::HasContext;
+use log::*;
+use std::marker::PhantomData;
+
+use super::context::*;
+use super::mesh::*;
+use super::program::*;
+use super::resources::*;
+use super::uniforms::*;
+
+/// A program containing a compute shader. Compute shaders are only available where
+/// `GlContext::supports_compute` is true, so apps that also target WebGL need another way to do
+/// the same work.
+///
+/// The shader's source shouldn't include a `#version` line; one is added for the context. Storage
+/// buffers should declare their binding points, which `ShaderStorageBuffer`s are bound to:
+/// ```glsl
+/// layout(local_size_x = 64) in;
+/// layout(std430, binding = 0) buffer Particles {
+///     vec4 positions[];
+/// };
+/// ```
+pub struct ComputeProgram<U: GlUniforms> {
+    program: GlProgramId,
+    shader: GlShader,
+    gl_uniforms: U,
+    id: ProgramId,
+    context: GlContext,
+}
+
+impl<U: GlUniforms> Drop for ComputeProgram<U> {
+    fn drop(&mut self) {
+        self.context.track_deleted(ResourceKind::Program, 0);
+        unsafe {
+            self.context.inner().delete_program(self.program);
+            self.context.inner().delete_shader(self.shader);
+        }
+    }
+}
+
+impl<U: GlUniforms> ComputeProgram<U> {
+    /// Creates a compute program. Panics if the shader fails to compile or link; see `try_new`
+    /// to handle errors.
+    pub fn new(context: &GlContext, source: &str) -> Self {
+        Self::try_new(context, source).unwrap_or_else(|err| {
+            error!("{}", err);
+            panic!("{}", err)
+        })
+    }
+
+    /// Like `new`, but returns an error if the shader fails to compile or link.
+    ///
+    /// Panics if compute shaders aren't supported; check `GlContext::supports_compute` first.
+    pub fn try_new(context: &GlContext, source: &str) -> Result<Self, ShaderError> {
+        assert!(context.supports_compute(), "Compute shaders aren't supported by this context");
+        let version = if context.is_gles() {
+            "#version 310 es\nprecision highp float;\n"
+        } else {
+            "#version 430 core\n"
+        };
+        let shader = load_shader(context, ShaderType::Compute, &format!("{}{}", version, source))?;
+
+        let program = unsafe {
+            let program = context.inner().create_program().unwrap();
+            context.inner().attach_shader(program, shader);
+            context.inner().link_program(program);
+            if !context.inner().get_program_link_status(program) {
+                let log = context.inner().get_program_info_log(program);
+                context.inner().delete_program(program);
+                context.inner().delete_shader(shader);
+                return Err(ShaderError::Link { log });
+            }
+            program
+        };
+
+        let gl_uniforms = U::new(context, program);
+        context.track_created(ResourceKind::Program, 0);
+        Ok(Self { program, shader, gl_uniforms, id: ProgramId::new(), context: context.clone() })
+    }
+
+    /// Runs the compute shader with the given number of work groups in each dimension.
+    ///
+    /// The results aren't guaranteed to be visible to later commands until
+    /// `GlContext::memory_barrier` is called.
+    pub fn dispatch(&self, uniforms: &impl Uniforms<GlUniforms = U>, groups: [u32; 3]) {
+        self.bind();
+        uniforms.update(&self.context, &self.gl_uniforms);
+        self.context.record_stats(|stats| stats.compute_dispatches += 1);
+        unsafe {
+            self.context.inner().dispatch_compute(groups[0], groups[1], groups[2]);
+        }
+    }
+
+    fn bind(&self) {
+        let mut cache = self.context.cache.borrow_mut();
+        if cache.bound_program != Some(self.id) {
+            cache.bound_program = Some(self.id);
+            self.context.record_stats(|stats| stats.program_switches += 1);
+            unsafe {
+                self.context.inner().use_program(Some(self.program));
+            }
+        }
+    }
+}
+
+/// Which kinds of access must see the writes made by compute shaders; see
+/// `GlContext::memory_barrier`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MemoryBarrier {
+    /// Reads and writes of shader storage buffers in later shaders.
+    ShaderStorage,
+    /// Vertex and instance data read from a buffer written by a compute shader.
+    VertexAttribArray,
+    /// Indices read from a buffer written by a compute shader.
+    ElementArray,
+    UniformBuffer,
+    /// Buffer reads and writes through the API, such as `ShaderStorageBuffer::read`.
+    BufferUpdate,
+    All,
+}
+
+impl MemoryBarrier {
+    fn as_gl(self) -> u32 {
+        match self {
+            MemoryBarrier::ShaderStorage => glow::SHADER_STORAGE_BARRIER_BIT,
+            MemoryBarrier::VertexAttribArray => glow::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+            MemoryBarrier::ElementArray => glow::ELEMENT_ARRAY_BARRIER_BIT,
+            MemoryBarrier::UniformBuffer => glow::UNIFORM_BARRIER_BIT,
+            MemoryBarrier::BufferUpdate => glow::BUFFER_UPDATE_BARRIER_BIT,
+            MemoryBarrier::All => glow::ALL_BARRIER_BITS,
+        }
+    }
+}
+
+impl GlContext {
+    /// Makes writes from previous compute shader dispatches visible to the given kinds of access.
+    pub fn memory_barrier(&self, barriers: &[MemoryBarrier]) {
+        let bits = barriers.iter().fold(0, |bits, barrier| bits | barrier.as_gl());
+        if bits != 0 {
+            unsafe {
+                self.inner().memory_barrier(bits);
+            }
+        }
+    }
+}
+
+/// A buffer of `T`s that compute shaders can read and write, declared in GLSL as a `buffer`
+/// block. `T` must match the block's std430 layout.
+pub struct ShaderStorageBuffer<T: Pod> {
+    buffer: GlBuffer,
+    binding: u32,
+    len: usize,
+    usage: MeshUsage,
+    context: GlContext,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Pod> Drop for ShaderStorageBuffer<T> {
+    fn drop(&mut self) {
+        self.context.track_deleted(ResourceKind::Buffer, self.len * std::mem::size_of::<T>());
+        unsafe {
+            self.context.inner().delete_buffer(self.buffer);
+        }
+    }
+}
+
+impl<T: Pod> ShaderStorageBuffer<T> {
+    /// Creates a storage buffer containing `data` and binds it to the given binding point.
+    ///
+    /// Panics if storage buffers aren't supported; see `GlContext::supports_compute`.
+    pub fn new(context: &GlContext, binding: u32, data: &[T], usage: MeshUsage) -> Self {
+        assert!(context.supports_compute(), "Storage buffers aren't supported by this context");
+        let buffer = unsafe { context.inner().create_buffer().unwrap() };
+        context.track_created(ResourceKind::Buffer, 0);
+        let mut res =
+            Self { buffer, binding, len: 0, usage, context: context.clone(), phantom: PhantomData };
+        res.set(data);
+        res.bind();
+        res
+    }
+
+    /// Binds the buffer to its binding point again. This is only needed if another buffer was
+    /// bound to the same binding point.
+    pub fn bind(&self) {
+        unsafe {
+            self.context.inner().bind_buffer_base(
+                glow::SHADER_STORAGE_BUFFER,
+                self.binding,
+                Some(self.buffer),
+            );
+        }
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    /// The number of `T`s in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Replaces the contents of the buffer, which may change its length.
+    pub fn set(&mut self, data: &[T]) {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        self.context.track_resized(
+            ResourceKind::Buffer,
+            self.len * std::mem::size_of::<T>(),
+            bytes.len(),
+        );
+        self.len = data.len();
+        self.context.record_stats(|stats| {
+            stats.buffer_uploads += 1;
+            stats.buffer_upload_bytes += bytes.len();
+        });
+        unsafe {
+            let context = self.context.inner();
+            context.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.buffer));
+            context.buffer_data_u8_slice(glow::SHADER_STORAGE_BUFFER, bytes, self.usage.as_gl());
+        }
+    }
+
+    /// Replaces the `T`s starting at index `start`.
+    pub fn update(&self, start: usize, data: &[T]) {
+        assert!(start + data.len() <= self.len, "Storage buffer update out of range");
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        self.context.record_stats(|stats| {
+            stats.buffer_uploads += 1;
+            stats.buffer_upload_bytes += bytes.len();
+        });
+        unsafe {
+            let context = self.context.inner();
+            context.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.buffer));
+            context.buffer_sub_data_u8_slice(
+                glow::SHADER_STORAGE_BUFFER,
+                (start * std::mem::size_of::<T>()) as i32,
+                bytes,
+            );
+        }
+    }
+
+    /// Reads the buffer's contents back from the GPU. This waits for all commands that write to
+    /// the buffer to finish, so it stalls the pipeline. Call
+    /// `GlContext::memory_barrier(&[MemoryBarrier::BufferUpdate])` after dispatching the compute
+    /// shaders that write to it.
+    pub fn read(&self) -> Vec<T> {
+        let mut data = vec![T::zeroed(); self.len];
+        unsafe {
+            let context = self.context.inner();
+            context.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.buffer));
+            context.get_buffer_sub_data(
+                glow::SHADER_STORAGE_BUFFER,
+                0,
+                bytemuck::cast_slice_mut(&mut data),
+            );
+        }
+        data
+    }
+}
@@ -4,12 +4,19 @@ use std::rc::Rc;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsCast;
 #[cfg(target_arch = "wasm32")]
-use web_sys::{window, HtmlCanvasElement, WebGl2RenderingContext, WebGlContextAttributes};
+use web_sys::{
+    window, HtmlCanvasElement, WebGl2RenderingContext, WebGlContextAttributes,
+    WebGlRenderingContext,
+};
 
 use super::framebuffer::*;
 use super::mesh::*;
 use super::program::*;
+use super::query::*;
 use super::rect::*;
+use super::resources::*;
+use super::stats::*;
+use super::stream_buffer::*;
 use super::surface::*;
 use super::texture::*;
 #[cfg(not(target_arch = "wasm32"))]
@@ -20,28 +27,81 @@ use crate::glfw::*;
 pub struct GlContext {
     inner: Rc<RefCell<glow::Context>>,
     pub cache: Rc<RefCell<GlContextCache>>,
-    // A VBO that is currently used for all instanced rendering
-    // TODO: this isn't suitable for all cases of instanced rendering; some apps will want to
-    // use static data for the instances rather than recreating them each frame.
+    resources: Rc<RefCell<ResourceTracker>>,
+    stats: Rc<RefCell<FrameStatsTracker>>,
+    shader_snippets: Rc<RefCell<Vec<ShaderSnippet>>>,
+    // A VBO that's used for instanced rendering with `Mesh::draw_instanced`. Static instance data
+    // should use an `InstanceBuffer` instead.
     pub instanced_vbo: GlBuffer,
+    // Used instead of `instanced_vbo` where persistently mapped buffers are supported.
+    stream_buffer: Option<Rc<RefCell<StreamBuffer>>>,
+    webgl1: bool,
+    gles: bool,
+    u32_indices: bool,
+    half_float_textures: bool,
+    occlusion_queries: bool,
+    timer_queries: bool,
+    compute: bool,
+    glsl_version: &'static str,
 }
 
+/// A piece of GLSL registered with `GlContext::add_shader_snippet`.
+struct ShaderSnippet {
+    name: String,
+    shader_type: Option<ShaderType>,
+    source: String,
+}
+
+/// OpenGL state that's been set through this library. `None` means the state is unknown, so it
+/// must be set before it's relied on.
 pub struct GlContextCache {
-    pub draw_mode: Option<DrawMode>,
+    /// The last `PipelineState` that was bound. This is reset whenever any of the state it
+    /// includes is changed individually.
+    pub pipeline_state: Option<PipelineState>,
     pub bound_program: Option<ProgramId>,
     pub bound_framebuffer: Option<FramebufferId>,
     pub bound_read_framebuffer: Option<FramebufferId>,
     pub bound_textures: [Option<(u32, TextureId)>; 32],
+    pub active_texture: Option<u32>,
+    pub bound_vertex_array: Option<MeshId>,
+    pub flags: [Option<bool>; GlFlag::COUNT],
+    pub blend_func: Option<(BlendFactor, BlendFactor)>,
+    pub depth_func: Option<DepthFunc>,
+    pub depth_write: Option<bool>,
+    pub cull_face: Option<CullFace>,
+    pub front_face: Option<FrontFace>,
+    pub color_mask: Option<[bool; 4]>,
+    pub polygon_offset: Option<PolygonOffset>,
+    pub stencil_func: Option<(DepthFunc, i32, u32)>,
+    pub stencil_ops: Option<(StencilOp, StencilOp, StencilOp)>,
+    pub stencil_write_mask: Option<u32>,
+    pub viewport: Option<Rect<i32>>,
+    pub scissor: Option<Rect<i32>>,
 }
 
 impl GlContextCache {
     fn new() -> Self {
         Self {
-            draw_mode: None,
+            pipeline_state: None,
             bound_program: None,
             bound_framebuffer: None,
             bound_read_framebuffer: None,
             bound_textures: [None; 32],
+            active_texture: None,
+            bound_vertex_array: None,
+            flags: [None; GlFlag::COUNT],
+            blend_func: None,
+            depth_func: None,
+            depth_write: None,
+            cull_face: None,
+            front_face: None,
+            color_mask: None,
+            polygon_offset: None,
+            stencil_func: None,
+            stencil_ops: None,
+            stencil_write_mask: None,
+            viewport: None,
+            scissor: None,
         }
     }
 
@@ -54,13 +114,240 @@ impl GlContextCache {
 pub enum GlFlag {
     DepthTest,
     CullFace,
+    Blend,
+    ScissorTest,
+    StencilTest,
+    PolygonOffsetFill,
 }
 
 impl GlFlag {
+    const COUNT: usize = 6;
+
     fn as_gl(self) -> u32 {
         match self {
             GlFlag::DepthTest => glow::DEPTH_TEST,
             GlFlag::CullFace => glow::CULL_FACE,
+            GlFlag::Blend => glow::BLEND,
+            GlFlag::ScissorTest => glow::SCISSOR_TEST,
+            GlFlag::StencilTest => glow::STENCIL_TEST,
+            GlFlag::PolygonOffsetFill => glow::POLYGON_OFFSET_FILL,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+impl BlendFactor {
+    fn as_gl(self) -> u32 {
+        match self {
+            BlendFactor::Zero => glow::ZERO,
+            BlendFactor::One => glow::ONE,
+            BlendFactor::SrcColor => glow::SRC_COLOR,
+            BlendFactor::OneMinusSrcColor => glow::ONE_MINUS_SRC_COLOR,
+            BlendFactor::DstColor => glow::DST_COLOR,
+            BlendFactor::OneMinusDstColor => glow::ONE_MINUS_DST_COLOR,
+            BlendFactor::SrcAlpha => glow::SRC_ALPHA,
+            BlendFactor::OneMinusSrcAlpha => glow::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::DstAlpha => glow::DST_ALPHA,
+            BlendFactor::OneMinusDstAlpha => glow::ONE_MINUS_DST_ALPHA,
+        }
+    }
+}
+
+/// The comparison used for depth testing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DepthFunc {
+    Never,
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
+
+impl DepthFunc {
+    fn as_gl(self) -> u32 {
+        match self {
+            DepthFunc::Never => glow::NEVER,
+            DepthFunc::Less => glow::LESS,
+            DepthFunc::Equal => glow::EQUAL,
+            DepthFunc::LessOrEqual => glow::LEQUAL,
+            DepthFunc::Greater => glow::GREATER,
+            DepthFunc::NotEqual => glow::NOTEQUAL,
+            DepthFunc::GreaterOrEqual => glow::GEQUAL,
+            DepthFunc::Always => glow::ALWAYS,
+        }
+    }
+}
+
+/// Which faces are culled when `GlFlag::CullFace` is enabled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CullFace {
+    Back,
+    Front,
+    FrontAndBack,
+}
+
+impl CullFace {
+    fn as_gl(self) -> u32 {
+        match self {
+            CullFace::Back => glow::BACK,
+            CullFace::Front => glow::FRONT,
+            CullFace::FrontAndBack => glow::FRONT_AND_BACK,
+        }
+    }
+}
+
+/// Which winding order is considered front-facing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrontFace {
+    CounterClockwise,
+    Clockwise,
+}
+
+impl FrontFace {
+    fn as_gl(self) -> u32 {
+        match self {
+            FrontFace::CounterClockwise => glow::CCW,
+            FrontFace::Clockwise => glow::CW,
+        }
+    }
+}
+
+/// Offsets the depth of polygons, which avoids z-fighting for decals and shadow maps. The offset
+/// is `factor * slope + units * r`, where `slope` is the polygon's depth slope and `r` is the
+/// smallest resolvable depth difference.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PolygonOffset {
+    pub factor: f32,
+    pub units: f32,
+}
+
+/// What happens to a stencil value during stencil testing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    /// Replaces the value with the `StencilState`'s `reference`.
+    Replace,
+    /// Increments the value, clamping it to the maximum.
+    Increment,
+    /// Increments the value, wrapping it to 0 if it overflows.
+    IncrementWrap,
+    /// Decrements the value, clamping it to 0.
+    Decrement,
+    /// Decrements the value, wrapping it to the maximum if it underflows.
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOp {
+    fn as_gl(self) -> u32 {
+        match self {
+            StencilOp::Keep => glow::KEEP,
+            StencilOp::Zero => glow::ZERO,
+            StencilOp::Replace => glow::REPLACE,
+            StencilOp::Increment => glow::INCR,
+            StencilOp::IncrementWrap => glow::INCR_WRAP,
+            StencilOp::Decrement => glow::DECR,
+            StencilOp::DecrementWrap => glow::DECR_WRAP,
+            StencilOp::Invert => glow::INVERT,
+        }
+    }
+}
+
+/// The stencil test used when drawing; see `PipelineState::stencil`. The surface being drawn to
+/// must have a stencil buffer, such as a framebuffer created with `with_depth_stencil`.
+///
+/// For example, an outline can be drawn by writing 1 wherever an object is drawn:
+/// ```ignore
+/// StencilState { func: DepthFunc::Always, pass: StencilOp::Replace, ..StencilState::new(1) }
+/// ```
+/// and then drawing a slightly larger version of the object where the stencil value isn't 1:
+/// ```ignore
+/// StencilState { func: DepthFunc::NotEqual, write_mask: 0, ..StencilState::new(1) }
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StencilState {
+    /// The comparison between `reference` and the stencil value. A fragment is only drawn if
+    /// `reference <func> stencil_value` is true.
+    pub func: DepthFunc,
+    pub reference: i32,
+    /// Which bits of `reference` and the stencil value are compared.
+    pub read_mask: u32,
+    /// Which bits of the stencil value can be written.
+    pub write_mask: u32,
+    /// The operation used when the stencil test fails.
+    pub fail: StencilOp,
+    /// The operation used when the stencil test passes but the depth test fails.
+    pub depth_fail: StencilOp,
+    /// The operation used when both tests pass.
+    pub pass: StencilOp,
+}
+
+impl StencilState {
+    /// Creates a stencil state with the given reference value that always passes and doesn't
+    /// modify the stencil buffer.
+    pub fn new(reference: i32) -> Self {
+        Self {
+            func: DepthFunc::Always,
+            reference,
+            read_mask: !0,
+            write_mask: !0,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+/// A version of OpenGL or OpenGL ES.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GlVersion {
+    /// Desktop OpenGL. Versions 3.2 and later use a forward-compatible core profile.
+    Gl(u32, u32),
+    /// OpenGL ES, created through EGL. This can be used with ANGLE.
+    Gles(u32, u32),
+}
+
+/// Options for creating a `GlContext` and its window.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct ContextOptions {
+    pub grab_cursor: bool,
+    pub debug_context: bool,
+    /// Whether the window's framebuffer is transparent, so the desktop shows through wherever
+    /// the alpha is less than 1. Colors are blended with premultiplied alpha, so the screen
+    /// should be cleared with `ClearBuffer::premultiplied_color`. Not supported on all
+    /// platforms.
+    pub transparent: bool,
+    /// The versions to request, in order of preference. The first one that's supported is used.
+    pub gl_versions: Vec<GlVersion>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self {
+            grab_cursor: false,
+            debug_context: false,
+            transparent: false,
+            gl_versions: vec![GlVersion::Gl(3, 2), GlVersion::Gl(3, 0), GlVersion::Gles(3, 0)],
         }
     }
 }
@@ -77,21 +364,35 @@ impl GlContext {
         window_mode: WindowMode,
         grab_cursor: bool,
         debug_context: bool,
+    ) -> Result<(Self, ScreenSurface, EventReceiver), &'static str> {
+        Self::with_options(
+            window_mode,
+            ContextOptions { grab_cursor, debug_context, ..Default::default() },
+        )
+    }
+
+    /// Like `new`, but takes a `ContextOptions` for less common settings.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_options(
+        window_mode: WindowMode,
+        options: ContextOptions,
     ) -> Result<(Self, ScreenSurface, EventReceiver), &'static str> {
         let mut glfw = get_glfw();
-        let (mut window, event_receiver) =
-            create_window_inner(&mut glfw, &window_mode, grab_cursor, debug_context);
+        let (mut window, event_receiver) = create_window_inner(&mut glfw, &window_mode, &options)?;
 
         let context =
             unsafe { glow::Context::from_loader_function(|s| window.get_proc_address(s)) };
 
-        let screen_surface = ScreenSurface::new(window, window_mode, grab_cursor);
+        let screen_surface = ScreenSurface::new(window, window_mode, options.grab_cursor);
 
-        Ok((Self::new_inner(context, debug_context), screen_surface, event_receiver))
+        Ok((Self::new_inner(context, options.debug_context, false), screen_surface, event_receiver))
     }
 
     /// Creates a `GlContext` and associated surface.
     ///
+    /// WebGL2 is used if it's available; otherwise this falls back to WebGL1. See `is_webgl1` for
+    /// the limitations of WebGL1.
+    ///
     /// Returns an error if the context couldn't be created.
     #[cfg(target_arch = "wasm32")]
     pub fn new(canvas_id: &str) -> Result<(Self, ScreenSurface), &'static str> {
@@ -101,30 +402,60 @@ impl GlContext {
             .expect("Unable to find canvas element")
             .dyn_into::<HtmlCanvasElement>()
             .unwrap();
-        let context = glow::Context::from_webgl2_context(
+        let get_context = |name| {
             canvas
                 .get_context_with_context_options(
-                    "webgl2",
-                    WebGlContextAttributes::new().antialias(true).as_ref(),
+                    name,
+                    WebGlContextAttributes::new().antialias(true).stencil(true).as_ref(),
                 )
                 .expect("Unable to create canvas")
+        };
+        // glow's version parsing can't tell WebGL1 apart from WebGL2, so it has to be recorded
+        // here.
+        let (context, webgl1) = if let Some(context) = get_context("webgl2") {
+            let context = context.dyn_into::<WebGl2RenderingContext>().unwrap();
+            // Needed for `QueryKind::TimeElapsed`.
+            let _ = context.get_extension("EXT_disjoint_timer_query_webgl2");
+            // Needed to render to `TextureFormat::RGBA16F` textures.
+            let _ = context.get_extension("EXT_color_buffer_float");
+            (glow::Context::from_webgl2_context(context), false)
+        } else {
+            log::warn!("WebGL2 isn't available; falling back to WebGL1");
+            let context = get_context("webgl")
                 .ok_or("Unable to create canvas")?
-                .dyn_into::<WebGl2RenderingContext>()
-                .unwrap(),
-        );
-        Ok((Self::new_inner(context, false), ScreenSurface::new(canvas)))
+                .dyn_into::<WebGlRenderingContext>()
+                .unwrap();
+            // Needed for meshes with more than 65536 vertices; WebGL1 extensions have to be
+            // enabled before they're used.
+            let _ = context.get_extension("OES_element_index_uint");
+            // Needed for `TextureFormat::RGBA16F` textures.
+            let _ = context.get_extension("OES_texture_half_float");
+            (glow::Context::from_webgl1_context(context), true)
+        };
+        Ok((Self::new_inner(context, false, webgl1), ScreenSurface::new(canvas)))
     }
 
-    fn new_inner(context: glow::Context, debug_context: bool) -> Self {
+    pub(crate) fn new_inner(context: glow::Context, debug_context: bool, webgl1: bool) -> Self {
         unsafe {
-            context.enable(glow::BLEND);
-            context.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
             context.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            // Pixels are read back tightly packed too; otherwise RGB rows would be padded to a
+            // multiple of 4 bytes.
+            context.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
 
             let instanced_vbo = context.create_buffer().unwrap();
             context.bind_buffer(glow::ARRAY_BUFFER, Some(instanced_vbo));
 
-            if debug_context {
+            let version = context.version();
+            let extensions = context.supported_extensions();
+            let gles = version.is_embedded;
+            let has_version = |major, minor| (version.major, version.minor) >= (major, minor);
+
+            // Debug output is core in OpenGL 4.3 and OpenGL ES 3.2; older versions need KHR_debug.
+            let debug_supported = if gles { has_version(3, 2) } else { has_version(4, 3) }
+                || extensions.contains("GL_KHR_debug");
+            if debug_context && !debug_supported {
+                log::warn!("Debug output isn't supported by this context");
+            } else if debug_context {
                 context.enable(glow::DEBUG_OUTPUT);
                 context.enable(glow::DEBUG_OUTPUT_SYNCHRONOUS);
                 context.debug_message_control(
@@ -137,11 +468,68 @@ impl GlContext {
                 context.debug_message_callback(debug_callback);
             }
 
-            GlContext {
+            let stream_buffer = StreamBuffer::new(&context);
+            let u32_indices = !webgl1 || extensions.contains("OES_element_index_uint");
+            let half_float_textures = !webgl1 || extensions.contains("OES_texture_half_float");
+            // Occlusion queries are core in OpenGL 3.3 and OpenGL ES 3.0. WebGL1's timer query
+            // extension uses a different API, so queries aren't supported there at all.
+            let occlusion_queries = !webgl1
+                && (gles || has_version(3, 3) || extensions.contains("GL_ARB_occlusion_query2"));
+            let timer_queries = !webgl1
+                && if gles {
+                    extensions.contains("GL_EXT_disjoint_timer_query")
+                        || extensions.contains("EXT_disjoint_timer_query_webgl2")
+                } else {
+                    has_version(3, 3) || extensions.contains("GL_ARB_timer_query")
+                };
+            // Compute shaders are core in OpenGL 4.3 and OpenGL ES 3.1. WebGL doesn't have them.
+            let compute = !cfg!(target_arch = "wasm32")
+                && if gles { has_version(3, 1) } else { has_version(4, 3) };
+            // Shaders are written in GLSL ES 3.00. Desktop OpenGL only accepts that with
+            // ARB_ES3_compatibility, so otherwise they're compiled as the closest desktop version.
+            let glsl_version =
+                if gles || has_version(4, 3) || extensions.contains("GL_ARB_ES3_compatibility") {
+                    "#version 300 es"
+                } else if has_version(3, 3) {
+                    "#version 330 core"
+                } else if has_version(3, 2) {
+                    "#version 150"
+                } else {
+                    "#version 130"
+                };
+            log::info!(
+                "Using {} {}.{} ({})",
+                if gles { "OpenGL ES" } else { "OpenGL" },
+                version.major,
+                version.minor,
+                glsl_version
+            );
+            let stream_buffer_created = stream_buffer.is_some();
+
+            let res = GlContext {
                 inner: Rc::new(RefCell::new(context)),
                 cache: Rc::new(RefCell::new(GlContextCache::new())),
+                resources: Rc::new(RefCell::new(ResourceTracker::default())),
+                stats: Rc::new(RefCell::new(FrameStatsTracker::default())),
+                shader_snippets: Rc::new(RefCell::new(vec![])),
                 instanced_vbo,
+                stream_buffer: stream_buffer.map(|buffer| Rc::new(RefCell::new(buffer))),
+                webgl1,
+                gles,
+                u32_indices,
+                half_float_textures,
+                occlusion_queries,
+                timer_queries,
+                compute,
+                glsl_version,
+            };
+            res.track_created(ResourceKind::Buffer, 0);
+            if stream_buffer_created {
+                res.track_created(ResourceKind::Buffer, StreamBuffer::SIZE);
             }
+            res.enable(GlFlag::Blend);
+            res.set_blend_func(BlendFactor::One, BlendFactor::OneMinusSrcAlpha);
+            res
         }
     }
 
@@ -153,8 +541,209 @@ impl GlContext {
         self.inner.borrow_mut()
     }
 
+    /// True if the context uses WebGL1, which is used when WebGL2 isn't available. WebGL1 has
+    /// several limitations:
+    /// - Shaders are translated to GLSL ES 1.00, which only works for simple shaders.
+    /// - sRGB textures are treated as linear, and single-channel textures use `LUMINANCE`.
+    /// - Renderbuffers aren't multisampled, and `Framebuffer::blit_to` and `PickingBuffer` aren't
+    ///   supported.
+    pub fn is_webgl1(&self) -> bool {
+        self.webgl1
+    }
+
+    /// True if meshes can use 32-bit indices, which is needed for meshes with more than 65536
+    /// vertices. This is always true except in WebGL1 without `OES_element_index_uint`.
+    pub fn supports_u32_indices(&self) -> bool {
+        self.u32_indices
+    }
+
+    /// True if `TextureFormat::RGBA16F` textures have 16-bit floating-point channels. This is
+    /// always true except in WebGL1 without `OES_texture_half_float`, where they fall back to 8
+    /// bits per channel.
+    pub fn supports_half_float_textures(&self) -> bool {
+        self.half_float_textures
+    }
+
+    /// True if queries of the given kind can be created.
+    pub fn supports_query(&self, kind: QueryKind) -> bool {
+        match kind {
+            QueryKind::AnySamplesPassed => self.occlusion_queries,
+            QueryKind::TimeElapsed => self.timer_queries,
+        }
+    }
+
+    /// True if compute shaders and shader storage buffers are supported. This requires OpenGL 4.3
+    /// or OpenGL ES 3.1, so it's always false in WebGL.
+    pub fn supports_compute(&self) -> bool {
+        self.compute
+    }
+
+    /// True if the context uses OpenGL ES, which includes WebGL.
+    pub fn is_gles(&self) -> bool {
+        self.gles
+    }
+
+    /// The `#version` directive that shaders are compiled with.
+    pub(crate) fn glsl_version(&self) -> &'static str {
+        self.glsl_version
+    }
+
+    /// Binds a framebuffer for drawing, or the default framebuffer if `framebuffer` is `None`, and
+    /// sets the viewport.
+    pub(crate) fn bind_draw_framebuffer(
+        &self,
+        id: FramebufferId,
+        framebuffer: Option<GlFramebuffer>,
+        viewport: &Rect<i32>,
+    ) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.bound_framebuffer != Some(id) {
+            cache.bound_framebuffer = Some(id);
+            // WebGL1 only has a single framebuffer binding for both drawing and reading.
+            let target = if self.webgl1 {
+                cache.bound_read_framebuffer = None;
+                glow::FRAMEBUFFER
+            } else {
+                glow::DRAW_FRAMEBUFFER
+            };
+            drop(cache);
+            unsafe {
+                self.inner().bind_framebuffer(target, framebuffer);
+            }
+            self.viewport(viewport);
+        }
+    }
+
+    /// Binds a framebuffer for reading, or the default framebuffer if `framebuffer` is `None`.
+    pub(crate) fn bind_read_framebuffer(
+        &self,
+        id: FramebufferId,
+        framebuffer: Option<GlFramebuffer>,
+    ) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.bound_read_framebuffer != Some(id) {
+            cache.bound_read_framebuffer = Some(id);
+            let target = if self.webgl1 {
+                cache.bound_framebuffer = None;
+                glow::FRAMEBUFFER
+            } else {
+                glow::READ_FRAMEBUFFER
+            };
+            unsafe {
+                self.inner().bind_framebuffer(target, framebuffer);
+            }
+        }
+    }
+
+    /// Returns the GPU resources that currently exist, with estimates of how much memory they
+    /// use. Creation and deletion of each resource is also logged at the `debug` level.
+    pub fn resource_report(&self) -> ResourceReport {
+        self.resources.borrow().report().clone()
+    }
+
+    pub(crate) fn track_created(&self, kind: ResourceKind, bytes: usize) {
+        self.resources.borrow_mut().created(kind, bytes);
+    }
+
+    pub(crate) fn track_deleted(&self, kind: ResourceKind, bytes: usize) {
+        self.resources.borrow_mut().deleted(kind, bytes);
+    }
+
+    pub(crate) fn track_resized(&self, kind: ResourceKind, old_bytes: usize, new_bytes: usize) {
+        self.resources.borrow_mut().resized(kind, old_bytes, new_bytes);
+    }
+
+    /// Records the new size of `instanced_vbo`.
+    pub(crate) fn track_instanced_vbo_size(&self, bytes: usize) {
+        let mut resources = self.resources.borrow_mut();
+        let old_bytes = std::mem::replace(&mut resources.instanced_vbo_bytes, bytes);
+        resources.resized(ResourceKind::Buffer, old_bytes, bytes);
+    }
+
+    /// Writes streaming data to the persistently mapped stream buffer, if it's supported. Returns
+    /// the buffer and the offset the data was written to, which is a multiple of `align`.
+    pub(crate) fn write_stream_buffer(
+        &self,
+        data: &[u8],
+        align: usize,
+    ) -> Option<(GlBuffer, usize)> {
+        let mut stream_buffer = self.stream_buffer.as_ref()?.borrow_mut();
+        let offset = stream_buffer.write(&self.inner(), data, align)?;
+        Some((stream_buffer.buffer, offset))
+    }
+
+    /// Registers a named piece of GLSL, such as noise or dithering functions, that's added to
+    /// every program created with `GlProgramWithHeader::new_with_header` afterwards. It's added
+    /// after the common header, to the given shader type or to both if `shader_type` is `None`.
+    /// Snippets are added in the order they were registered; registering a snippet with an
+    /// existing name replaces it, keeping its position.
+    ///
+    /// Programs that already exist aren't affected.
+    pub fn add_shader_snippet(&self, name: &str, shader_type: Option<ShaderType>, source: &str) {
+        let snippet =
+            ShaderSnippet { name: name.to_owned(), shader_type, source: source.to_owned() };
+        let mut snippets = self.shader_snippets.borrow_mut();
+        match snippets.iter_mut().find(|snippet| snippet.name == name) {
+            Some(existing) => *existing = snippet,
+            None => snippets.push(snippet),
+        }
+    }
+
+    /// Removes a snippet added with `add_shader_snippet`. Returns false if there's no snippet
+    /// with that name.
+    pub fn remove_shader_snippet(&self, name: &str) -> bool {
+        let mut snippets = self.shader_snippets.borrow_mut();
+        let len = snippets.len();
+        snippets.retain(|snippet| snippet.name != name);
+        snippets.len() != len
+    }
+
+    /// Returns the source of all registered snippets for the given shader type.
+    pub(crate) fn shader_snippets(&self, shader_type: ShaderType) -> String {
+        let mut res = String::new();
+        for snippet in self.shader_snippets.borrow().iter() {
+            if snippet.shader_type.is_none() || snippet.shader_type == Some(shader_type) {
+                res.push_str(&format!("\n// Snippet: {}\n{}\n", snippet.name, snippet.source));
+            }
+        }
+        res
+    }
+
+    /// Finishes counting the current frame's `FrameStats` and returns them. This should be called
+    /// once per frame, typically at the end of `App::render_frame`.
+    pub fn end_frame(&self) -> FrameStats {
+        let mut stats = self.stats.borrow_mut();
+        stats.last = std::mem::take(&mut stats.current);
+        stats.last
+    }
+
+    /// Returns the stats for the most recent frame that was finished with `end_frame`.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.stats.borrow().last
+    }
+
+    /// Returns the stats that have been counted so far in the current frame.
+    pub fn current_frame_stats(&self) -> FrameStats {
+        self.stats.borrow().current
+    }
+
+    pub(crate) fn record_stats(&self, f: impl FnOnce(&mut FrameStats)) {
+        f(&mut self.stats.borrow_mut().current);
+    }
+
+    /// Forgets all cached OpenGL state, so it will be set again the next time it's needed. This
+    /// must be called after modifying state through `inner()`.
+    pub fn invalidate_cache(&self) {
+        *self.cache.borrow_mut() = GlContextCache::new();
+    }
+
     /// Sets the viewport. This is primarily intended to be used by the `Surface` trait.
     pub fn viewport(&self, viewport: &Rect<i32>) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.viewport == Some(*viewport) {
+            return;
+        }
+        cache.viewport = Some(*viewport);
         unsafe {
             self.inner().viewport(
                 viewport.start.x,
@@ -166,14 +755,194 @@ impl GlContext {
     }
 
     pub fn enable(&self, flag: GlFlag) {
+        self.set_flag(flag, true);
+    }
+
+    pub fn disable(&self, flag: GlFlag) {
+        self.set_flag(flag, false);
+    }
+
+    fn set_flag(&self, flag: GlFlag, enabled: bool) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.flags[flag as usize] == Some(enabled) {
+            return;
+        }
+        cache.flags[flag as usize] = Some(enabled);
+        cache.pipeline_state = None;
         unsafe {
-            self.inner().enable(flag.as_gl());
+            if enabled {
+                self.inner().enable(flag.as_gl());
+            } else {
+                self.inner().disable(flag.as_gl());
+            }
         }
     }
 
-    pub fn disable(&self, flag: GlFlag) {
+    /// Sets the blend function. The default is `(One, OneMinusSrcAlpha)`, for premultiplied
+    /// alpha.
+    ///
+    /// This and the other state setters are overridden by the `PipelineState` of the next mesh
+    /// that's drawn; they're mainly useful when drawing through the internal context.
+    pub fn set_blend_func(&self, src: BlendFactor, dst: BlendFactor) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.blend_func == Some((src, dst)) {
+            return;
+        }
+        cache.blend_func = Some((src, dst));
+        cache.pipeline_state = None;
+        unsafe {
+            self.inner().blend_func(src.as_gl(), dst.as_gl());
+        }
+    }
+
+    /// Sets the depth comparison function. The default is `Less`.
+    pub fn set_depth_func(&self, func: DepthFunc) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.depth_func == Some(func) {
+            return;
+        }
+        cache.depth_func = Some(func);
+        cache.pipeline_state = None;
+        unsafe {
+            self.inner().depth_func(func.as_gl());
+        }
+    }
+
+    /// Sets whether depth values are written when drawing. The default is `true`.
+    pub fn set_depth_write(&self, enabled: bool) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.depth_write == Some(enabled) {
+            return;
+        }
+        cache.depth_write = Some(enabled);
+        cache.pipeline_state = None;
+        unsafe {
+            self.inner().depth_mask(enabled);
+        }
+    }
+
+    /// Sets which faces are culled, or disables culling if `None`.
+    pub fn set_cull_face(&self, cull_face: Option<CullFace>) {
+        match cull_face {
+            Some(face) => {
+                self.enable(GlFlag::CullFace);
+                let mut cache = self.cache.borrow_mut();
+                if cache.cull_face != Some(face) {
+                    cache.cull_face = Some(face);
+                    cache.pipeline_state = None;
+                    unsafe {
+                        self.inner().cull_face(face.as_gl());
+                    }
+                }
+            }
+            None => self.disable(GlFlag::CullFace),
+        }
+    }
+
+    /// Sets which winding order is front-facing. The default is `CounterClockwise`.
+    pub fn set_front_face(&self, front_face: FrontFace) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.front_face == Some(front_face) {
+            return;
+        }
+        cache.front_face = Some(front_face);
+        cache.pipeline_state = None;
         unsafe {
-            self.inner().disable(flag.as_gl());
+            self.inner().front_face(front_face.as_gl());
+        }
+    }
+
+    /// Sets which color channels (red, green, blue, alpha) are written when drawing. The default
+    /// is to write all of them.
+    pub fn set_color_mask(&self, mask: [bool; 4]) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.color_mask == Some(mask) {
+            return;
+        }
+        cache.color_mask = Some(mask);
+        cache.pipeline_state = None;
+        unsafe {
+            self.inner().color_mask(mask[0], mask[1], mask[2], mask[3]);
+        }
+    }
+
+    /// Sets the depth offset for filled polygons, or disables it if `None`.
+    pub fn set_polygon_offset(&self, offset: Option<PolygonOffset>) {
+        match offset {
+            Some(offset) => {
+                self.enable(GlFlag::PolygonOffsetFill);
+                let mut cache = self.cache.borrow_mut();
+                if cache.polygon_offset != Some(offset) {
+                    cache.polygon_offset = Some(offset);
+                    cache.pipeline_state = None;
+                    unsafe {
+                        self.inner().polygon_offset(offset.factor, offset.units);
+                    }
+                }
+            }
+            None => self.disable(GlFlag::PolygonOffsetFill),
+        }
+    }
+
+    /// Sets the stencil test, or disables stencil testing if `None`.
+    pub fn set_stencil(&self, stencil: Option<StencilState>) {
+        match stencil {
+            Some(state) => {
+                self.enable(GlFlag::StencilTest);
+                let func = (state.func, state.reference, state.read_mask);
+                let ops = (state.fail, state.depth_fail, state.pass);
+                let mut cache = self.cache.borrow_mut();
+                if cache.stencil_func != Some(func) {
+                    cache.stencil_func = Some(func);
+                    cache.pipeline_state = None;
+                    unsafe {
+                        self.inner().stencil_func(func.0.as_gl(), func.1, func.2);
+                    }
+                }
+                if cache.stencil_ops != Some(ops) {
+                    cache.stencil_ops = Some(ops);
+                    cache.pipeline_state = None;
+                    unsafe {
+                        self.inner().stencil_op(ops.0.as_gl(), ops.1.as_gl(), ops.2.as_gl());
+                    }
+                }
+                drop(cache);
+                self.set_stencil_write_mask(state.write_mask);
+            }
+            None => self.disable(GlFlag::StencilTest),
+        }
+    }
+
+    /// Sets which bits of the stencil buffer can be written, including when it's cleared.
+    pub fn set_stencil_write_mask(&self, mask: u32) {
+        let mut cache = self.cache.borrow_mut();
+        if cache.stencil_write_mask == Some(mask) {
+            return;
+        }
+        cache.stencil_write_mask = Some(mask);
+        cache.pipeline_state = None;
+        unsafe {
+            self.inner().stencil_mask(mask);
+        }
+    }
+
+    /// Restricts rendering to the given rectangle, in framebuffer pixels with the origin at the
+    /// bottom left, or removes the restriction if `None`.
+    pub fn set_scissor(&self, scissor: Option<Rect<i32>>) {
+        match scissor {
+            Some(rect) => {
+                self.enable(GlFlag::ScissorTest);
+                let mut cache = self.cache.borrow_mut();
+                if cache.scissor != Some(rect) {
+                    cache.scissor = Some(rect);
+                    cache.pipeline_state = None;
+                    let size = rect.size();
+                    unsafe {
+                        self.inner().scissor(rect.start.x, rect.start.y, size.x, size.y);
+                    }
+                }
+            }
+            None => self.disable(GlFlag::ScissorTest),
         }
     }
 
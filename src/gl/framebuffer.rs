@@ -5,10 +5,11 @@ use uid::*;
 
 use super::context::*;
 use super::rect::*;
+use super::resources::*;
 use super::surface::*;
 use super::texture::*;
 
-type GlFramebuffer = <glow::Context as HasContext>::Framebuffer;
+pub(crate) type GlFramebuffer = <glow::Context as HasContext>::Framebuffer;
 type GlRenderbuffer = <glow::Context as HasContext>::Renderbuffer;
 
 #[doc(hidden)]
@@ -21,11 +22,15 @@ pub type FramebufferId = Id<FramebufferId_>;
 pub struct Renderbuffer {
     renderbuffer: GlRenderbuffer,
     size: Vector2<u32>,
+    samples: i32,
     context: GlContext,
+    // The estimated GPU memory used by the renderbuffer
+    bytes: usize,
 }
 
 impl Drop for Renderbuffer {
     fn drop(&mut self) {
+        self.context.track_deleted(ResourceKind::Renderbuffer, self.bytes);
         unsafe {
             self.context.inner().delete_renderbuffer(self.renderbuffer);
         }
@@ -37,16 +42,66 @@ impl Renderbuffer {
         unsafe {
             let renderbuffer = context.inner().create_renderbuffer().unwrap();
             context.inner().bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
-            let max_samples = context.inner().get_parameter_i32(glow::MAX_SAMPLES);
-            let samples = max_samples; // TODO: make this configurable
-            context.inner().renderbuffer_storage_multisample(
-                glow::RENDERBUFFER,
-                samples,
-                format.to_gl_internal_format(),
-                size.x as i32,
-                size.y as i32,
-            );
-            Renderbuffer { renderbuffer, size, context: context.clone() }
+            let samples = if context.is_webgl1() {
+                // WebGL1 doesn't support multisampled renderbuffers, and only supports a few
+                // 16-bit color formats.
+                let internal_format = match format {
+                    TextureFormat::RGB | TextureFormat::SRGB => glow::RGB565,
+                    _ => glow::RGBA4,
+                };
+                context.inner().renderbuffer_storage(
+                    glow::RENDERBUFFER,
+                    internal_format,
+                    size.x as i32,
+                    size.y as i32,
+                );
+                0
+            } else {
+                let max_samples = context.inner().get_parameter_i32(glow::MAX_SAMPLES);
+                let samples = max_samples; // TODO: make this configurable
+                context.inner().renderbuffer_storage_multisample(
+                    glow::RENDERBUFFER,
+                    samples,
+                    format.to_gl_internal_format(),
+                    size.x as i32,
+                    size.y as i32,
+                );
+                samples
+            };
+            let bytes = size.x as usize
+                * size.y as usize
+                * format.bytes_per_pixel()
+                * samples.max(1) as usize;
+            context.track_created(ResourceKind::Renderbuffer, bytes);
+            Renderbuffer { renderbuffer, size, samples, context: context.clone(), bytes }
+        }
+    }
+
+    /// Creates a combined depth and stencil renderbuffer, with the given number of samples so it
+    /// matches the framebuffer's color attachments.
+    fn new_depth_stencil(context: &GlContext, size: Vector2<u32>, samples: i32) -> Self {
+        unsafe {
+            let renderbuffer = context.inner().create_renderbuffer().unwrap();
+            context.inner().bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+            if context.is_webgl1() {
+                context.inner().renderbuffer_storage(
+                    glow::RENDERBUFFER,
+                    glow::DEPTH_STENCIL,
+                    size.x as i32,
+                    size.y as i32,
+                );
+            } else {
+                context.inner().renderbuffer_storage_multisample(
+                    glow::RENDERBUFFER,
+                    samples,
+                    glow::DEPTH24_STENCIL8,
+                    size.x as i32,
+                    size.y as i32,
+                );
+            }
+            let bytes = size.x as usize * size.y as usize * 4 * samples.max(1) as usize;
+            context.track_created(ResourceKind::Renderbuffer, bytes);
+            Renderbuffer { renderbuffer, size, samples, context: context.clone(), bytes }
         }
     }
 }
@@ -55,8 +110,16 @@ impl Renderbuffer {
 pub trait FramebufferAttachment {
     fn size(&self) -> Vector2<u32>;
 
+    /// Attaches this to the bound framebuffer at the given attachment point, such as
+    /// `COLOR_ATTACHMENT0`.
     #[doc(hidden)]
-    fn attach_to_framebuffer(&self);
+    fn attach_to_framebuffer(&self, attachment_point: u32);
+
+    /// The number of samples per pixel, or 0 if this isn't multisampled.
+    #[doc(hidden)]
+    fn samples(&self) -> i32 {
+        0
+    }
 
     #[doc(hidden)]
     fn context(&self) -> &GlContext;
@@ -68,11 +131,11 @@ impl FramebufferAttachment for Texture2d {
     }
 
     #[doc(hidden)]
-    fn attach_to_framebuffer(&self) {
+    fn attach_to_framebuffer(&self, attachment_point: u32) {
         unsafe {
             self.context.inner().framebuffer_texture_2d(
                 glow::FRAMEBUFFER,
-                glow::COLOR_ATTACHMENT0,
+                attachment_point,
                 glow::TEXTURE_2D,
                 Some(self.texture),
                 0,
@@ -92,17 +155,22 @@ impl FramebufferAttachment for Renderbuffer {
     }
 
     #[doc(hidden)]
-    fn attach_to_framebuffer(&self) {
+    fn attach_to_framebuffer(&self, attachment_point: u32) {
         unsafe {
             self.context.inner().framebuffer_renderbuffer(
                 glow::FRAMEBUFFER,
-                glow::COLOR_ATTACHMENT0,
+                attachment_point,
                 glow::RENDERBUFFER,
                 Some(self.renderbuffer),
             );
         }
     }
 
+    #[doc(hidden)]
+    fn samples(&self) -> i32 {
+        self.samples
+    }
+
     #[doc(hidden)]
     fn context(&self) -> &GlContext {
         &self.context
@@ -111,19 +179,27 @@ impl FramebufferAttachment for Renderbuffer {
 
 /// A framebuffer.
 ///
-/// Framebuffers currently have only one attachment, either a texture or a renderbuffer.
+/// Framebuffers have one or more color attachments, which are all textures or all
+/// renderbuffers. With multiple attachments, a fragment shader can write to each of them in a
+/// single pass; see `Framebuffer::new_multiple`.
 pub struct Framebuffer<A: FramebufferAttachment> {
     framebuffer: GlFramebuffer,
     // TODO: this shouldn't be public
+    /// The first color attachment.
     pub attachment: A,
+    // The color attachments after the first one
+    additional_attachments: Vec<A>,
+    depth_stencil: Option<Renderbuffer>,
     viewport: Rect<i32>,
     id: FramebufferId,
 }
 
 impl<A: FramebufferAttachment> Drop for Framebuffer<A> {
     fn drop(&mut self) {
+        let context = self.attachment.context();
+        context.track_deleted(ResourceKind::Framebuffer, 0);
         unsafe {
-            self.attachment.context().inner().delete_framebuffer(self.framebuffer);
+            context.inner().delete_framebuffer(self.framebuffer);
         }
     }
 }
@@ -140,6 +216,25 @@ impl Framebuffer<Texture2d> {
         let texture = Texture2d::empty(context, size, format, min_filter, mag_filter, wrap_mode);
         Self::new(context, texture)
     }
+
+    /// Creates a framebuffer with a texture attachment for each format, e.g. for the G-buffer
+    /// used in deferred shading.
+    pub fn new_with_textures(
+        context: &GlContext,
+        size: Vector2<u32>,
+        formats: &[TextureFormat],
+        min_filter: MinFilter,
+        mag_filter: MagFilter,
+        wrap_mode: WrapMode,
+    ) -> Self {
+        let textures = formats
+            .iter()
+            .map(|&format| {
+                Texture2d::empty(context, size, format, min_filter, mag_filter, wrap_mode)
+            })
+            .collect();
+        Self::new_multiple(context, textures)
+    }
 }
 
 impl Framebuffer<Renderbuffer> {
@@ -155,37 +250,114 @@ impl Framebuffer<Renderbuffer> {
 
 impl<A: FramebufferAttachment> Framebuffer<A> {
     pub fn new(context: &GlContext, attachment: A) -> Self {
+        Self::new_multiple(context, vec![attachment])
+    }
+
+    /// Creates a framebuffer with multiple color attachments, which must all be the same size.
+    /// Attachment `i` is written by the fragment shader output at location `i`:
+    /// ```glsl
+    /// // `outColor` is declared by the standard header at location 0
+    /// layout(location = 1) out vec4 outNormal;
+    /// ```
+    /// Multiple attachments aren't supported in WebGL1.
+    pub fn new_multiple(context: &GlContext, attachments: Vec<A>) -> Self {
+        assert!(!attachments.is_empty(), "Framebuffers need at least one attachment");
+        let size = attachments[0].size();
+        assert!(
+            attachments.iter().all(|attachment| attachment.size() == size),
+            "Framebuffer attachments must be the same size"
+        );
+        if attachments.len() > 1 {
+            assert!(!context.is_webgl1(), "Multiple render targets aren't supported in WebGL1");
+            let max_draw_buffers =
+                unsafe { context.inner().get_parameter_i32(glow::MAX_DRAW_BUFFERS) };
+            assert!(
+                attachments.len() <= max_draw_buffers as usize,
+                "Too many framebuffer attachments; the maximum is {}",
+                max_draw_buffers
+            );
+        }
+
         unsafe {
             let framebuffer = context.inner().create_framebuffer().unwrap();
-            context.inner().bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
-            attachment.attach_to_framebuffer();
-
-            let framebuffer_status = context.inner().check_framebuffer_status(glow::FRAMEBUFFER);
-            if framebuffer_status != glow::FRAMEBUFFER_COMPLETE {
-                let reason = match framebuffer_status {
-                    glow::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "incomplete attachment",
-                    glow::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
-                        "incomplete missing attachment"
-                    }
-                    glow::FRAMEBUFFER_UNSUPPORTED => "unsupported",
-                    _ => "unknown reason",
-                };
-                error!("Framebuffer not complete: {}", reason);
-                panic!()
+            bind_for_setup(context, framebuffer);
+
+            let attachment_points: Vec<u32> =
+                (0..attachments.len() as u32).map(|i| glow::COLOR_ATTACHMENT0 + i).collect();
+            for (attachment, &attachment_point) in attachments.iter().zip(&attachment_points) {
+                attachment.attach_to_framebuffer(attachment_point);
+            }
+            if attachments.len() > 1 {
+                // This is part of the framebuffer's state, so it only needs to be set once.
+                context.inner().draw_buffers(&attachment_points);
+            }
+
+            check_framebuffer_status(context);
+
+            let viewport = Rect::new(Point2::origin(), Point2::from_vec(size.cast().unwrap()));
+            context.track_created(ResourceKind::Framebuffer, 0);
+
+            let mut attachments = attachments.into_iter();
+            let attachment = attachments.next().unwrap();
+            Framebuffer {
+                framebuffer,
+                attachment,
+                additional_attachments: attachments.collect(),
+                depth_stencil: None,
+                viewport,
+                id: FramebufferId::new(),
             }
+        }
+    }
+
+    /// Adds a combined depth and stencil buffer to the framebuffer, which is needed for depth
+    /// testing or `StencilState` when drawing to it.
+    pub fn with_depth_stencil(mut self) -> Self {
+        let context = self.attachment.context().clone();
+        let depth_stencil =
+            Renderbuffer::new_depth_stencil(&context, self.size(), self.attachment.samples());
+        unsafe {
+            bind_for_setup(&context, self.framebuffer);
+            context.inner().framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_STENCIL_ATTACHMENT,
+                glow::RENDERBUFFER,
+                Some(depth_stencil.renderbuffer),
+            );
+            check_framebuffer_status(&context);
+        }
+        self.depth_stencil = Some(depth_stencil);
+        self
+    }
 
-            let viewport =
-                Rect::new(Point2::origin(), Point2::from_vec(attachment.size().cast().unwrap()));
+    pub fn has_depth_stencil(&self) -> bool {
+        self.depth_stencil.is_some()
+    }
+
+    /// Returns the number of color attachments.
+    pub fn num_attachments(&self) -> usize {
+        1 + self.additional_attachments.len()
+    }
 
-            Framebuffer { framebuffer, attachment, viewport, id: FramebufferId::new() }
+    /// Returns the color attachment at the given index, which corresponds to the fragment shader
+    /// output at that location.
+    pub fn attachment(&self, index: usize) -> &A {
+        match index {
+            0 => &self.attachment,
+            _ => &self.additional_attachments[index - 1],
         }
     }
 
+    /// Copies the first attachment to `surface`.
+    ///
     /// Note: this only works if the destination framebuffer isn't multisampled.
     // TODO: add parameters to set src/dest rects
     pub fn blit_to(&self, context: &GlContext, surface: &impl Surface) {
+        assert!(!context.is_webgl1(), "Blitting isn't supported in WebGL1");
         self.bind_read(context);
         surface.bind(context);
+        // Blits are limited by the scissor test too.
+        context.set_scissor(None);
         let size = self.attachment.size().cast().unwrap();
         unsafe {
             context.inner().blit_framebuffer(
@@ -204,28 +376,44 @@ impl<A: FramebufferAttachment> Framebuffer<A> {
     }
 }
 
+/// Binds a framebuffer so its attachments can be changed.
+unsafe fn bind_for_setup(context: &GlContext, framebuffer: GlFramebuffer) {
+    context.inner().bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+    // The framebuffer was bound directly, so the cached bindings are no longer valid.
+    let mut cache = context.cache.borrow_mut();
+    cache.bound_framebuffer = None;
+    cache.bound_read_framebuffer = None;
+}
+
+/// Panics if the bound framebuffer isn't complete.
+unsafe fn check_framebuffer_status(context: &GlContext) {
+    let framebuffer_status = context.inner().check_framebuffer_status(glow::FRAMEBUFFER);
+    if framebuffer_status != glow::FRAMEBUFFER_COMPLETE {
+        let reason = match framebuffer_status {
+            glow::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "incomplete attachment",
+            glow::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "incomplete missing attachment",
+            glow::FRAMEBUFFER_UNSUPPORTED => "unsupported",
+            _ => "unknown reason",
+        };
+        error!("Framebuffer not complete: {}", reason);
+        panic!()
+    }
+}
+
 impl<A: FramebufferAttachment> Surface for Framebuffer<A> {
     #[doc(hidden)]
     fn bind(&self, context: &GlContext) {
-        let mut cache = context.cache.borrow_mut();
-        if cache.bound_framebuffer != Some(self.id) {
-            cache.bound_framebuffer = Some(self.id);
-            unsafe {
-                context.inner().bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(self.framebuffer));
-                context.viewport(&self.viewport);
-            }
-        }
+        context.bind_draw_framebuffer(self.id, Some(self.framebuffer), &self.viewport);
     }
 
     #[doc(hidden)]
     fn bind_read(&self, context: &GlContext) {
-        let mut cache = context.cache.borrow_mut();
-        if cache.bound_read_framebuffer != Some(self.id) {
-            cache.bound_read_framebuffer = Some(self.id);
-            unsafe {
-                context.inner().bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
-            }
-        }
+        context.bind_read_framebuffer(self.id, Some(self.framebuffer));
+    }
+
+    #[doc(hidden)]
+    fn id(&self) -> FramebufferId {
+        self.id
     }
 
     fn size(&self) -> Vector2<u32> {
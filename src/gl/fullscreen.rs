@@ -0,0 +1,63 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::*;
+
+use super::context::*;
+use super::mesh::*;
+use super::program::*;
+use super::surface::*;
+use super::uniforms::*;
+
+/// The vertex type of a `FullscreenQuad`. `pos` is in clip space.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FullscreenVert {
+    pub pos: Point2<f32>,
+}
+
+unsafe impl Zeroable for FullscreenVert {}
+unsafe impl Pod for FullscreenVert {}
+
+impl VertexData for FullscreenVert {
+    const ATTRIBUTES: Attributes = &[("pos", 2)];
+}
+
+impl VertexComponent for FullscreenVert {
+    fn add_to_mesh(&self, f: &mut dyn FnMut(f32)) {
+        self.pos.add_to_mesh(f);
+    }
+}
+
+/// A mesh that covers an entire surface, for full-screen passes such as post-processing and tone
+/// mapping. It's actually a single triangle that extends past the edges, which avoids shading
+/// the pixels along a quad's diagonal twice.
+///
+/// The vertex shader receives the clip space position as `in vec2 pos`, so `pos * 0.5 + 0.5`
+/// gives UV coordinates that go from 0 to 1 across the surface. The quad can be drawn with any
+/// program that uses `FullscreenVert`, not just the one it was created with. Blending is
+/// disabled, so the output's contents are replaced.
+pub struct FullscreenQuad<U: GlUniforms> {
+    mesh: Mesh<FullscreenVert, U, Triangles>,
+}
+
+impl<U: GlUniforms> FullscreenQuad<U> {
+    pub fn new(context: &GlContext, program: &GlProgram<FullscreenVert, U>) -> Self {
+        let mut mesh_builder = MeshBuilder::new();
+        let a = mesh_builder.vert(FullscreenVert { pos: point2(-1.0, -1.0) });
+        let b = mesh_builder.vert(FullscreenVert { pos: point2(3.0, -1.0) });
+        let c = mesh_builder.vert(FullscreenVert { pos: point2(-1.0, 3.0) });
+        mesh_builder.triangle(a, b, c);
+        let pipeline_state = PipelineState { blend: false, ..Default::default() };
+        let mesh = mesh_builder.build(context, program, MeshUsage::StaticDraw, pipeline_state);
+        Self { mesh }
+    }
+
+    /// Draws the quad to `surface` with the given program.
+    pub fn draw<U2: GlUniforms>(
+        &self,
+        program: &GlProgram<FullscreenVert, U2>,
+        surface: &(impl Surface + ?Sized),
+        uniforms: &impl Uniforms<GlUniforms = U2>,
+    ) {
+        self.mesh.draw_range_with_program(program, surface, uniforms, 0..self.mesh.num_indices());
+    }
+}
@@ -0,0 +1,112 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use cgmath::*;
+use glow::HasContext;
+use image::RgbaImage;
+use std::path::Path;
+
+use super::context::*;
+use super::framebuffer::*;
+use super::surface::*;
+use super::texture::*;
+use crate::glfw::*;
+
+/// An offscreen surface that stands in for the screen when there's no visible window; created
+/// with `GlContext::new_headless`.
+///
+/// It's backed by an RGBA texture with a depth and stencil buffer. Unlike the screen, it isn't
+/// multisampled, and nothing is presented, so `read_image` is the way to get the results.
+pub struct HeadlessSurface {
+    framebuffer: Framebuffer<Texture2d>,
+    // The hidden window that owns the OpenGL context. It's declared last so that it's destroyed
+    // after the framebuffer.
+    _window: glfw::Window,
+}
+
+impl HeadlessSurface {
+    /// Returns the texture that's rendered to, e.g. to use it in another pass.
+    pub fn texture(&self) -> &Texture2d {
+        &self.framebuffer.attachment
+    }
+
+    /// Reads the surface's contents, waiting for rendering to finish. Unlike the pixels from
+    /// `read_pixels_async`, the image's first row is the top of the surface.
+    pub fn read_image(&self, context: &GlContext) -> RgbaImage {
+        let size = self.size();
+        let mut pixels = vec![0; (size.x * size.y * 4) as usize];
+        self.bind_read(context);
+        unsafe {
+            context.inner().read_buffer(glow::COLOR_ATTACHMENT0);
+            context.inner().read_pixels(
+                0,
+                0,
+                size.x as i32,
+                size.y as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+        }
+        let image = RgbaImage::from_raw(size.x, size.y, pixels).unwrap();
+        image::imageops::flip_vertical(&image)
+    }
+
+    /// Saves the surface's contents to an image file; the format is chosen from the extension.
+    pub fn save_image(
+        &self,
+        context: &GlContext,
+        path: impl AsRef<Path>,
+    ) -> image::ImageResult<()> {
+        self.read_image(context).save(path)
+    }
+}
+
+impl Surface for HeadlessSurface {
+    #[doc(hidden)]
+    fn bind(&self, context: &GlContext) {
+        self.framebuffer.bind(context);
+    }
+
+    #[doc(hidden)]
+    fn bind_read(&self, context: &GlContext) {
+        self.framebuffer.bind_read(context);
+    }
+
+    #[doc(hidden)]
+    fn id(&self) -> FramebufferId {
+        self.framebuffer.id()
+    }
+
+    fn size(&self) -> Vector2<u32> {
+        self.framebuffer.size()
+    }
+}
+
+impl GlContext {
+    /// Creates a `GlContext` that renders offscreen, along with a surface of the given size to
+    /// render to in place of the screen. This is useful for tests and batch rendering.
+    ///
+    /// The context belongs to a window that's never shown. GLFW still needs a display server for
+    /// that, so on a headless Linux machine such as a CI runner, use something like Xvfb.
+    ///
+    /// Returns an error if the context couldn't be created.
+    pub fn new_headless(size: Vector2<u32>) -> Result<(Self, HeadlessSurface), &'static str> {
+        let options = ContextOptions::default();
+        let mut window = create_hidden_window(&mut get_glfw(), &options)?;
+
+        let context =
+            unsafe { glow::Context::from_loader_function(|s| window.get_proc_address(s)) };
+        let context = Self::new_inner(context, options.debug_context, false);
+
+        let framebuffer = Framebuffer::new_with_texture(
+            &context,
+            size,
+            TextureFormat::RGBA,
+            MinFilter::Nearest,
+            MagFilter::Nearest,
+            WrapMode::ClampToEdge,
+        )
+        .with_depth_stencil();
+        Ok((context, HeadlessSurface { framebuffer, _window: window }))
+    }
+}
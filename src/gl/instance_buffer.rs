@@ -0,0 +1,105 @@
+use glow::HasContext;
+use std::marker::PhantomData;
+
+use super::context::*;
+use super::mesh::*;
+use super::program::*;
+use super::resources::*;
+
+/// A buffer of instance data that can be drawn with `Mesh::draw_instanced_with_buffer`.
+///
+/// Unlike `Mesh::draw_instanced`, which uploads the instances every time it's called, the data
+/// stays on the GPU until it's changed, so this is best for instances that rarely change. As
+/// with `draw_instanced`, `I` must use `#[repr(C)]`.
+pub struct InstanceBuffer<I: VertexData> {
+    pub(crate) buffer: GlBuffer,
+    len: usize,
+    // The number of instances the buffer has room for
+    capacity: usize,
+    usage: MeshUsage,
+    context: GlContext,
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: VertexData> Drop for InstanceBuffer<I> {
+    fn drop(&mut self) {
+        self.context.track_deleted(ResourceKind::Buffer, self.capacity * Self::stride());
+        unsafe {
+            self.context.inner().delete_buffer(self.buffer);
+        }
+    }
+}
+
+impl<I: VertexData> InstanceBuffer<I> {
+    pub fn new(context: &GlContext, instances: &[I], usage: MeshUsage) -> Self {
+        let buffer = unsafe { context.inner().create_buffer().unwrap() };
+        context.track_created(ResourceKind::Buffer, 0);
+        let mut res = Self {
+            buffer,
+            len: 0,
+            capacity: 0,
+            usage,
+            context: context.clone(),
+            phantom: PhantomData,
+        };
+        res.set(instances);
+        res
+    }
+
+    /// Replaces the contents of the buffer. The buffer is only reallocated if it's too small.
+    pub fn set(&mut self, instances: &[I]) {
+        if instances.len() > self.capacity {
+            let bytes = instances.len() * Self::stride();
+            self.context.track_resized(ResourceKind::Buffer, self.capacity * Self::stride(), bytes);
+            self.capacity = instances.len();
+            unsafe {
+                let context = self.context.inner();
+                context.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+                context.buffer_data_size(glow::ARRAY_BUFFER, bytes as i32, self.usage.as_gl());
+            }
+        }
+        self.len = instances.len();
+        self.update(0, instances);
+    }
+
+    /// Replaces the instances starting at index `start`. The updated range must be within the
+    /// buffer's current contents.
+    pub fn update(&self, start: usize, instances: &[I]) {
+        assert!(start + instances.len() <= self.len, "Instance buffer update out of range");
+        if instances.is_empty() {
+            return;
+        }
+        let data = unsafe {
+            std::slice::from_raw_parts(
+                instances.as_ptr() as *const u8,
+                std::mem::size_of_val(instances),
+            )
+        };
+        self.context.record_stats(|stats| {
+            stats.buffer_uploads += 1;
+            stats.buffer_upload_bytes += data.len();
+        });
+        unsafe {
+            let context = self.context.inner();
+            context.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            context.buffer_sub_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                (start * Self::stride()) as i32,
+                data,
+            );
+        }
+    }
+
+    /// The number of instances in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn stride() -> usize {
+        std::mem::size_of::<I>()
+    }
+}
@@ -1,18 +1,38 @@
+use bytemuck::Pod;
 use glow::HasContext;
+use std::any::TypeId;
+use std::cell::Cell;
 use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::mpsc;
+use uid::*;
 
 use super::context::*;
+use super::instance_buffer::*;
 use super::program::*;
+use super::rect::*;
+use super::resources::*;
 use super::surface::*;
 use super::uniforms::*;
 
 pub type GlBuffer = <glow::Context as HasContext>::Buffer;
 pub type GlVertexArrayObject = <glow::Context as HasContext>::VertexArray;
 
+#[doc(hidden)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct MeshId_(());
+
+pub type MeshId = Id<MeshId_>;
+
 /// An OpenGL primitive.
 #[doc(hidden)]
 pub trait Primitive {
     const AS_GL: u32;
+
+    /// The number of triangles drawn from the given number of indices.
+    fn num_triangles(_num_indices: usize) -> usize {
+        0
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -23,7 +43,7 @@ pub enum MeshUsage {
 }
 
 impl MeshUsage {
-    fn as_gl(self) -> u32 {
+    pub(crate) fn as_gl(self) -> u32 {
         match self {
             MeshUsage::StaticDraw => glow::STATIC_DRAW,
             MeshUsage::DynamicDraw => glow::DYNAMIC_DRAW,
@@ -32,38 +52,125 @@ impl MeshUsage {
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Presets for a mesh's `PipelineState`.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum DrawMode {
     Draw2D,
-    Draw3D { depth: bool },
+    Draw3D {
+        depth: bool,
+    },
+    /// Allows finer control over the state, for things like transparent 3D objects and decals.
+    Custom(PipelineState),
 }
 
 impl DrawMode {
     pub fn bind(self, context: &GlContext) {
-        let mut cache = context.cache.borrow_mut();
-        if cache.draw_mode != Some(self) {
-            cache.draw_mode = Some(self);
-
-            match self {
-                DrawMode::Draw2D => {
-                    context.disable(GlFlag::CullFace);
-                    context.disable(GlFlag::DepthTest);
-                }
-                DrawMode::Draw3D { depth } => {
-                    context.enable(GlFlag::CullFace);
-                    if depth {
-                        context.enable(GlFlag::DepthTest);
-                    } else {
-                        context.disable(GlFlag::DepthTest);
-                    }
-                }
-            }
+        self.pipeline_state().bind(context);
+    }
+
+    /// Returns the state this draw mode uses.
+    pub fn pipeline_state(self) -> PipelineState {
+        match self {
+            DrawMode::Draw2D => PipelineState::default(),
+            DrawMode::Draw3D { depth } => PipelineState {
+                depth_test: depth,
+                cull_face: Some(CullFace::Back),
+                ..Default::default()
+            },
+            DrawMode::Custom(state) => state,
         }
     }
 }
 
+impl From<DrawMode> for PipelineState {
+    fn from(draw_mode: DrawMode) -> Self {
+        draw_mode.pipeline_state()
+    }
+}
+
+/// All the fixed-function OpenGL state used when drawing a mesh. The default matches
+/// `DrawMode::Draw2D`.
+///
+/// Binding a `PipelineState` only makes the GL calls needed to change the state that differs
+/// from the current state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PipelineState {
+    /// Whether blending is enabled.
+    pub blend: bool,
+    /// The source and destination blend factors. The default is `(One, OneMinusSrcAlpha)`, for
+    /// premultiplied alpha.
+    pub blend_func: (BlendFactor, BlendFactor),
+    pub depth_test: bool,
+    pub depth_func: DepthFunc,
+    /// Whether depth values are written. This is typically disabled for transparent objects,
+    /// which should be depth tested against opaque objects without hiding each other.
+    pub depth_write: bool,
+    /// Which faces are culled, or `None` to draw both sides.
+    pub cull_face: Option<CullFace>,
+    pub front_face: FrontFace,
+    /// Restricts drawing to a rectangle, in framebuffer pixels with the origin at the bottom
+    /// left, or `None` to draw to the whole surface.
+    pub scissor: Option<Rect<i32>>,
+    /// Which color channels (red, green, blue, alpha) are written.
+    pub color_mask: [bool; 4],
+    pub polygon_offset: Option<PolygonOffset>,
+    /// The stencil test, or `None` to disable it.
+    pub stencil: Option<StencilState>,
+}
+
+impl Default for PipelineState {
+    fn default() -> Self {
+        Self {
+            blend: true,
+            blend_func: (BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            depth_test: false,
+            depth_func: DepthFunc::Less,
+            depth_write: true,
+            cull_face: None,
+            front_face: FrontFace::CounterClockwise,
+            scissor: None,
+            color_mask: [true; 4],
+            polygon_offset: None,
+            stencil: None,
+        }
+    }
+}
+
+impl PipelineState {
+    pub fn bind(&self, context: &GlContext) {
+        if context.cache.borrow().pipeline_state.as_ref() == Some(self) {
+            return;
+        }
+
+        if self.blend {
+            context.enable(GlFlag::Blend);
+            context.set_blend_func(self.blend_func.0, self.blend_func.1);
+        } else {
+            context.disable(GlFlag::Blend);
+        }
+        if self.depth_test {
+            context.enable(GlFlag::DepthTest);
+        } else {
+            context.disable(GlFlag::DepthTest);
+        }
+        context.set_depth_func(self.depth_func);
+        context.set_depth_write(self.depth_write);
+        context.set_cull_face(self.cull_face);
+        context.set_front_face(self.front_face);
+        context.set_scissor(self.scissor);
+        context.set_color_mask(self.color_mask);
+        context.set_polygon_offset(self.polygon_offset);
+        context.set_stencil(self.stencil);
+
+        context.cache.borrow_mut().pipeline_state = Some(*self);
+    }
+}
+
 /// An index into a mesh.
-pub type MeshIndex = u16;
+///
+/// Meshes whose indices all fit in a `u16` are uploaded with 16-bit indices; larger meshes use
+/// 32-bit indices, which WebGL1 only supports with `OES_element_index_uint`.
+pub type MeshIndex = u32;
 
 /// A struct that builds a mesh from a collection of primitives.
 ///
@@ -73,7 +180,8 @@ pub struct MeshBuilder<V: Vertex, P: Primitive> {
     vertex_data: Vec<f32>,
     indices: Vec<MeshIndex>,
     next_index: MeshIndex,
-    phantom: PhantomData<(V, P)>,
+    // `fn() -> _` so the builder is always `Send` and `Sync`; it only stores plain data.
+    phantom: PhantomData<fn() -> (V, P)>,
 }
 
 impl<V: Vertex> MeshBuilder<V, Triangles> {
@@ -89,7 +197,7 @@ impl<V: Vertex> MeshBuilder<V, Triangles> {
         Self {
             vertex_data,
             indices: vertex_buffers.indices,
-            next_index: vertex_buffers.vertices.len() as u16,
+            next_index: vertex_buffers.vertices.len() as MeshIndex,
             phantom: PhantomData,
         }
     }
@@ -104,7 +212,7 @@ impl<V: Vertex, P: Primitive> MeshBuilder<V, P> {
     /// (currently either `Triangles`, `Lines`, or `Points`, each of which adds a method to this
     /// struct to add the corresponding primitive).
     pub fn vert(&mut self, vert: V) -> MeshIndex {
-        assert!(self.next_index < MeshIndex::max_value());
+        assert!(self.next_index < MeshIndex::MAX);
         let index = self.next_index;
         self.next_index += 1;
         vert.add_to_mesh(&mut |data| self.vertex_data.push(data));
@@ -125,9 +233,9 @@ impl<V: Vertex, P: Primitive> MeshBuilder<V, P> {
         context: &GlContext,
         program: &GlProgram<V, U>,
         usage: MeshUsage,
-        draw_mode: DrawMode,
+        pipeline_state: impl Into<PipelineState>,
     ) -> Mesh<V, U, P> {
-        let mut mesh = Mesh::new(context, program, draw_mode);
+        let mut mesh = Mesh::new(context, program, pipeline_state);
         mesh.build_from(self, usage);
         mesh
     }
@@ -154,11 +262,36 @@ impl<V: Vertex, P: Primitive> MeshBuilder<V, P> {
         self.next_index
     }
 
+    /// The number of indices that have been added to the mesh.
+    pub fn num_indices(&self) -> usize {
+        self.indices.len()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.vertex_data.is_empty()
     }
 }
 
+impl<V: Vertex + Pod, P: Primitive> MeshBuilder<V, P> {
+    /// Like `vert`, but copies the vertex into the mesh directly rather than going through
+    /// `add_to_mesh`. `V` must be `#[repr(C)]`, with its fields in the same order as its
+    /// `ATTRIBUTES`.
+    pub fn vert_pod(&mut self, vert: V) -> MeshIndex {
+        self.verts_pod(std::slice::from_ref(&vert))
+    }
+
+    /// Adds several vertices to the mesh with a single copy. Returns the index of the first
+    /// vertex; the rest have consecutive indices.
+    pub fn verts_pod(&mut self, verts: &[V]) -> MeshIndex {
+        debug_assert_eq!(std::mem::size_of::<V>(), V::stride() as usize * 4);
+        assert!(self.next_index as usize + verts.len() <= MeshIndex::MAX as usize);
+        let index = self.next_index;
+        self.next_index += verts.len() as MeshIndex;
+        self.vertex_data.extend_from_slice(bytemuck::cast_slice(verts));
+        index
+    }
+}
+
 impl<V: Vertex, P: Primitive> Default for MeshBuilder<V, P> {
     fn default() -> Self {
         Self::new()
@@ -170,6 +303,10 @@ pub struct Triangles;
 
 impl Primitive for Triangles {
     const AS_GL: u32 = glow::TRIANGLES;
+
+    fn num_triangles(num_indices: usize) -> usize {
+        num_indices / 3
+    }
 }
 
 impl<V: Vertex> MeshBuilder<V, Triangles> {
@@ -186,6 +323,10 @@ pub struct TriangleStrip;
 
 impl Primitive for TriangleStrip {
     const AS_GL: u32 = glow::TRIANGLE_STRIP;
+
+    fn num_triangles(num_indices: usize) -> usize {
+        num_indices.saturating_sub(2)
+    }
 }
 
 impl<V: Vertex> MeshBuilder<V, TriangleStrip> {
@@ -200,6 +341,10 @@ pub struct TriangleFan;
 
 impl Primitive for TriangleFan {
     const AS_GL: u32 = glow::TRIANGLE_FAN;
+
+    fn num_triangles(num_indices: usize) -> usize {
+        num_indices.saturating_sub(2)
+    }
 }
 
 impl<V: Vertex> MeshBuilder<V, TriangleFan> {
@@ -266,85 +411,208 @@ impl<V: Vertex> MeshBuilder<V, Points> {
     }
 }
 
-/// A mesh; built using a `MeshBuilder`.
-pub struct Mesh<V: Vertex, U: GlUniforms, P: Primitive> {
-    vao: GlVertexArrayObject,
-    vbo: GlBuffer,
-    ibo: GlBuffer,
-    context: GlContext,
-    program: GlProgram<V, U>,
-    num_indices: i32,
-    phantom: PhantomData<P>,
-    // TODO: can this be inferred from the vertex/uniforms types?
-    draw_mode: DrawMode,
+/// Passes `MeshBuilder`s built on other threads to the thread that owns the `GlContext`.
+///
+/// Each builder is sent with a key of type `T` identifying what it's for (such as a chunk
+/// position), so the resulting meshes can be matched up with it.
+pub struct MeshUploadQueue<T, V: Vertex, P: Primitive> {
+    sender: mpsc::Sender<(T, MeshBuilder<V, P>)>,
+    receiver: mpsc::Receiver<(T, MeshBuilder<V, P>)>,
 }
 
-impl<V: Vertex, U: GlUniforms, P: Primitive> Drop for Mesh<V, U, P> {
-    fn drop(&mut self) {
-        unsafe {
-            self.context.inner().delete_vertex_array(self.vao);
-            self.context.inner().delete_buffer(self.vbo);
-            self.context.inner().delete_buffer(self.ibo);
-        }
+impl<T, V: Vertex, P: Primitive> MeshUploadQueue<T, V, P> {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver }
+    }
+
+    /// Returns a sender that can be moved to another thread and used to submit finished builders.
+    pub fn sender(&self) -> mpsc::Sender<(T, MeshBuilder<V, P>)> {
+        self.sender.clone()
+    }
+
+    /// Uploads all builders that have been submitted so far. This never blocks waiting for more
+    /// builders, so it can be called once per frame.
+    pub fn upload_pending<U: GlUniforms>(
+        &self,
+        context: &GlContext,
+        program: &GlProgram<V, U>,
+        usage: MeshUsage,
+        pipeline_state: impl Into<PipelineState>,
+    ) -> Vec<(T, Mesh<V, U, P>)> {
+        let pipeline_state = pipeline_state.into();
+        self.receiver
+            .try_iter()
+            .map(|(key, builder)| {
+                let mut mesh = Mesh::new(context, program, pipeline_state);
+                mesh.build_from(&builder, usage);
+                (key, mesh)
+            })
+            .collect()
     }
 }
 
-impl<V: Vertex, U: GlUniforms, P: Primitive> Mesh<V, U, P> {
-    /// Creates an empty `Mesh`. It must have data written via `build_from` before it's usable.
-    pub fn new(context: &GlContext, program: &GlProgram<V, U>, draw_mode: DrawMode) -> Self {
+impl<T, V: Vertex, P: Primitive> Default for MeshUploadQueue<T, V, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a `Mesh` reuses its GPU buffers when its contents are rebuilt.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MeshBuffering {
+    /// A single set of buffers is reallocated on each rebuild. Rebuilding may stall if the GPU is
+    /// still drawing the previous contents.
+    Single,
+    /// The buffers are orphaned before each rebuild, so the driver can allocate new storage
+    /// rather than waiting for earlier draws to finish.
+    Orphan,
+    /// Rotates between the given number of buffer sets (typically 2 or 3), so a rebuild writes to
+    /// buffers that the GPU is unlikely to still be using.
+    Rotate(usize),
+}
+
+/// A VAO and the buffers it refers to.
+struct MeshBuffers {
+    vao: GlVertexArrayObject,
+    vbo: GlBuffer,
+    ibo: GlBuffer,
+    id: MeshId,
+    // Vertex attribute pointers are stored in the VAO, so they only need to be set up once for
+    // the vertex data and again whenever the instance data type or buffer changes.
+    attribs_set_up: Cell<bool>,
+    instance_attribs: Cell<Option<(TypeId, GlBuffer, ProgramId)>>,
+    vbo_bytes: Cell<usize>,
+    ibo_bytes: Cell<usize>,
+}
+
+impl MeshBuffers {
+    fn new(context: &GlContext) -> Self {
         unsafe {
+            let id = MeshId::new();
             let vao = context.inner().create_vertex_array().unwrap();
             context.inner().bind_vertex_array(Some(vao));
+            context.cache.borrow_mut().bound_vertex_array = Some(id);
 
             let vbo = context.inner().create_buffer().unwrap();
             let ibo = context.inner().create_buffer().unwrap();
             context.inner().bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
             context.inner().bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ibo));
 
-            Mesh {
+            context.track_created(ResourceKind::VertexArray, 0);
+            context.track_created(ResourceKind::Buffer, 0);
+            context.track_created(ResourceKind::Buffer, 0);
+
+            Self {
                 vao,
                 vbo,
                 ibo,
-                context: context.clone(),
-                program: program.clone(),
-                num_indices: 0,
-                phantom: PhantomData,
-                draw_mode,
+                id,
+                attribs_set_up: Cell::new(false),
+                instance_attribs: Cell::new(None),
+                vbo_bytes: Cell::new(0),
+                ibo_bytes: Cell::new(0),
             }
         }
     }
 
-    /// Clears the mesh's current contents and updates it with the contents of the `MeshBuilder`.
-    pub fn build_from(&mut self, builder: &MeshBuilder<V, P>, usage: MeshUsage) {
-        self.num_indices = builder.indices.len() as i32;
-        if self.num_indices == 0 {
-            return;
+    /// Records the sizes the buffers have been allocated with.
+    fn set_sizes(&self, context: &GlContext, vbo_bytes: usize, ibo_bytes: usize) {
+        context.track_resized(ResourceKind::Buffer, self.vbo_bytes.replace(vbo_bytes), vbo_bytes);
+        context.track_resized(ResourceKind::Buffer, self.ibo_bytes.replace(ibo_bytes), ibo_bytes);
+    }
+
+    fn delete(&self, context: &GlContext) {
+        let mut cache = context.cache.borrow_mut();
+        if cache.bound_vertex_array == Some(self.id) {
+            cache.bound_vertex_array = None;
+        }
+        context.track_deleted(ResourceKind::VertexArray, 0);
+        context.track_deleted(ResourceKind::Buffer, self.vbo_bytes.get());
+        context.track_deleted(ResourceKind::Buffer, self.ibo_bytes.get());
+        unsafe {
+            context.inner().delete_vertex_array(self.vao);
+            context.inner().delete_buffer(self.vbo);
+            context.inner().delete_buffer(self.ibo);
         }
+    }
+}
 
-        self.bind();
+/// A mesh; built using a `MeshBuilder`.
+pub struct Mesh<V: Vertex, U: GlUniforms, P: Primitive> {
+    buffers: Vec<MeshBuffers>,
+    // The index into `buffers` of the buffers holding the current contents.
+    current: usize,
+    buffering: MeshBuffering,
+    context: GlContext,
+    program: GlProgram<V, U>,
+    num_indices: i32,
+    // Either `UNSIGNED_SHORT` or `UNSIGNED_INT`, depending on the largest index
+    index_type: u32,
+    phantom: PhantomData<P>,
+    // TODO: can this be inferred from the vertex/uniforms types?
+    pipeline_state: PipelineState,
+}
 
-        setup_vertex_attribs::<V, _, _>(&self.program, false);
+impl<V: Vertex, U: GlUniforms, P: Primitive> Drop for Mesh<V, U, P> {
+    fn drop(&mut self) {
+        for buffers in &self.buffers {
+            buffers.delete(&self.context);
+        }
+    }
+}
 
-        unsafe {
-            self.context.inner().buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                // TODO: find a better way to convert a &[f32] to a &[u8], or modify `glow` so the conversion isn't needed
-                std::slice::from_raw_parts(
-                    (&builder.vertex_data).as_ptr() as *const u8,
-                    builder.vertex_data.len() * std::mem::size_of::<f32>(),
-                ),
-                usage.as_gl(),
-            );
+impl<V: Vertex, U: GlUniforms, P: Primitive> Mesh<V, U, P> {
+    /// Creates an empty `Mesh`. It must have data written via `build_from` before it's usable.
+    /// `pipeline_state` is usually a `DrawMode`.
+    pub fn new(
+        context: &GlContext,
+        program: &GlProgram<V, U>,
+        pipeline_state: impl Into<PipelineState>,
+    ) -> Self {
+        Mesh {
+            buffers: vec![MeshBuffers::new(context)],
+            current: 0,
+            buffering: MeshBuffering::Single,
+            context: context.clone(),
+            program: program.clone(),
+            num_indices: 0,
+            index_type: glow::UNSIGNED_SHORT,
+            phantom: PhantomData,
+            pipeline_state: pipeline_state.into(),
+        }
+    }
 
-            self.context.inner().buffer_data_u8_slice(
-                glow::ELEMENT_ARRAY_BUFFER,
-                std::slice::from_raw_parts(
-                    (&builder.indices).as_ptr() as *const u8,
-                    builder.indices.len() * std::mem::size_of::<u16>(),
-                ),
-                usage.as_gl(),
-            );
+    /// Sets how the mesh's buffers are reused when it's rebuilt. Meshes that are rebuilt every
+    /// frame, such as those used by `Draw2d`, should use `Orphan` or `Rotate`.
+    ///
+    /// The mesh's current contents are discarded.
+    pub fn set_buffering(&mut self, buffering: MeshBuffering) {
+        let num_buffers = match buffering {
+            MeshBuffering::Single | MeshBuffering::Orphan => 1,
+            MeshBuffering::Rotate(num_buffers) => {
+                assert!(num_buffers > 0);
+                num_buffers
+            }
+        };
+        for buffers in self.buffers.drain(num_buffers.min(self.buffers.len())..) {
+            buffers.delete(&self.context);
+        }
+        while self.buffers.len() < num_buffers {
+            self.buffers.push(MeshBuffers::new(&self.context));
         }
+        self.buffering = buffering;
+        self.current = 0;
+        self.num_indices = 0;
+    }
+
+    pub fn buffering(&self) -> MeshBuffering {
+        self.buffering
+    }
+
+    /// Clears the mesh's current contents and updates it with the contents of the `MeshBuilder`.
+    pub fn build_from(&mut self, builder: &MeshBuilder<V, P>, usage: MeshUsage) {
+        self.upload(bytemuck::cast_slice(&builder.vertex_data), &builder.indices, usage);
     }
 
     /// Clears the mesh's current contents and updates it with the contents of the given vertex and index slices
@@ -354,63 +622,235 @@ impl<V: Vertex, U: GlUniforms, P: Primitive> Mesh<V, U, P> {
         indices: &[MeshIndex],
         usage: MeshUsage,
     ) {
+        // TODO: find a better way to convert a &[T] to a &[u8]
+        let vertices = unsafe {
+            std::slice::from_raw_parts(
+                vertices.as_ptr() as *const u8,
+                std::mem::size_of_val(vertices),
+            )
+        };
+        self.upload(vertices, indices, usage);
+    }
+
+    fn upload(&mut self, vertices: &[u8], indices: &[MeshIndex], usage: MeshUsage) {
         self.num_indices = indices.len() as i32;
         if self.num_indices == 0 {
             return;
         }
 
-        self.bind();
-
-        setup_vertex_attribs::<V, _, _>(&self.program, false);
+        self.next_buffers();
+        self.bind_for_upload();
 
-        unsafe {
-            self.context.inner().buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                // TODO: find a better way to convert a &[T] to a &[u8]
-                std::slice::from_raw_parts(
-                    vertices.as_ptr() as *const u8,
-                    vertices.len() * std::mem::size_of::<T>(),
-                ),
-                usage.as_gl(),
+        // Most meshes are small enough for 16-bit indices, which use half as much memory.
+        let short_indices: Vec<u16>;
+        let indices = if indices.iter().all(|&index| index <= u16::MAX as MeshIndex) {
+            self.index_type = glow::UNSIGNED_SHORT;
+            short_indices = indices.iter().map(|&index| index as u16).collect();
+            bytemuck::cast_slice(&short_indices)
+        } else {
+            assert!(
+                self.context.supports_u32_indices(),
+                "Meshes with more than 65536 vertices require OES_element_index_uint in WebGL1"
             );
+            self.index_type = glow::UNSIGNED_INT;
+            bytemuck::cast_slice(indices)
+        };
+        self.buffers().set_sizes(&self.context, vertices.len(), indices.len());
+        self.context.record_stats(|stats| {
+            stats.buffer_uploads += 2;
+            stats.buffer_upload_bytes += vertices.len() + indices.len();
+        });
+        unsafe {
+            let context = self.context.inner();
+            if self.buffering == MeshBuffering::Orphan {
+                context.buffer_data_size(glow::ARRAY_BUFFER, vertices.len() as i32, usage.as_gl());
+                context.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, 0, vertices);
+                context.buffer_data_size(
+                    glow::ELEMENT_ARRAY_BUFFER,
+                    indices.len() as i32,
+                    usage.as_gl(),
+                );
+                context.buffer_sub_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, 0, indices);
+            } else {
+                context.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertices, usage.as_gl());
+                context.buffer_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, indices, usage.as_gl());
+            }
+        }
+    }
 
-            self.context.inner().buffer_data_u8_slice(
-                glow::ELEMENT_ARRAY_BUFFER,
-                std::slice::from_raw_parts(
-                    indices.as_ptr() as *const u8,
-                    indices.len() * std::mem::size_of::<u16>(),
+    /// Clears the mesh's current contents and returns a `MappedMeshBuilder` that writes the new
+    /// contents directly into the mesh's buffers, avoiding the intermediate copy made by
+    /// `MeshBuilder`. At most `max_verts` vertices and `max_indices` indices can be added. The
+    /// mesh is updated when the `MappedMeshBuilder` is dropped.
+    pub fn build_mapped(
+        &mut self,
+        max_verts: usize,
+        max_indices: usize,
+        usage: MeshUsage,
+    ) -> MappedMeshBuilder<'_, V, U, P> {
+        assert!(
+            self.context.supports_u32_indices(),
+            "build_mapped requires OES_element_index_uint in WebGL1"
+        );
+        self.num_indices = 0;
+        self.index_type = glow::UNSIGNED_INT;
+        self.next_buffers();
+        self.bind_for_upload();
+        let (vertex_data, indices) = unsafe {
+            (
+                MappedBuffer::map(
+                    &self.context,
+                    glow::ARRAY_BUFFER,
+                    max_verts * V::stride() as usize,
+                    usage,
                 ),
-                usage.as_gl(),
-            );
+                MappedBuffer::map(&self.context, glow::ELEMENT_ARRAY_BUFFER, max_indices, usage),
+            )
+        };
+        self.buffers().set_sizes(
+            &self.context,
+            vertex_data.capacity * std::mem::size_of::<f32>(),
+            indices.capacity * std::mem::size_of::<MeshIndex>(),
+        );
+        MappedMeshBuilder { mesh: self, vertex_data, indices, next_index: 0 }
+    }
+
+    /// The size in bytes of each index in the index buffer.
+    fn index_size(&self) -> usize {
+        match self.index_type {
+            glow::UNSIGNED_SHORT => std::mem::size_of::<u16>(),
+            _ => std::mem::size_of::<u32>(),
         }
     }
 
-    fn bind(&self) {
+    /// Switches to the next set of buffers if the mesh rotates between several.
+    fn next_buffers(&mut self) {
+        self.current = (self.current + 1) % self.buffers.len();
+    }
+
+    fn buffers(&self) -> &MeshBuffers {
+        &self.buffers[self.current]
+    }
+
+    fn bind_for_upload(&self) {
+        self.bind();
         unsafe {
-            self.context.inner().bind_vertex_array(Some(self.vao));
-            // The ELEMENT_ARRAY_BUFFER doesn't need to be bound here, but the ARRAY_BUFFER does (https://stackoverflow.com/a/21652930)
-            self.context.inner().bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+            // The ELEMENT_ARRAY_BUFFER is part of the VAO's state, but the ARRAY_BUFFER isn't (https://stackoverflow.com/a/21652930)
+            self.context.inner().bind_buffer(glow::ARRAY_BUFFER, Some(self.buffers().vbo));
+        }
+        if !self.buffers().attribs_set_up.replace(true) {
+            setup_vertex_attribs::<V, _, _>(&self.program, false);
         }
     }
 
+    fn bind(&self) {
+        let buffers = self.buffers();
+        let mut cache = self.context.cache.borrow_mut();
+        if cache.bound_vertex_array != Some(buffers.id) {
+            cache.bound_vertex_array = Some(buffers.id);
+            unsafe {
+                self.context.inner().bind_vertex_array(Some(buffers.vao));
+            }
+        }
+    }
+
+    /// The number of indices in the mesh.
+    pub fn num_indices(&self) -> usize {
+        self.num_indices as usize
+    }
+
+    /// Sets the state used when drawing the mesh.
+    pub fn set_pipeline_state(&mut self, pipeline_state: impl Into<PipelineState>) {
+        self.pipeline_state = pipeline_state.into();
+    }
+
+    pub fn pipeline_state(&self) -> &PipelineState {
+        &self.pipeline_state
+    }
+
     /// Draws the mesh.
     pub fn draw(
         &self,
         surface: &(impl Surface + ?Sized),
         uniforms: &impl Uniforms<GlUniforms = U>,
     ) {
-        if self.num_indices == 0 {
+        self.draw_range_with_program(
+            &self.program,
+            surface,
+            uniforms,
+            0..self.num_indices as usize,
+        );
+    }
+
+    /// Draws part of the mesh, using the given range of indices.
+    pub fn draw_range(
+        &self,
+        surface: &(impl Surface + ?Sized),
+        uniforms: &impl Uniforms<GlUniforms = U>,
+        indices: Range<usize>,
+    ) {
+        self.draw_range_with_program(&self.program, surface, uniforms, indices);
+    }
+
+    /// Draws the mesh with a different `PipelineState`, e.g. to draw an outline using the stencil
+    /// buffer.
+    pub fn draw_with_state(
+        &self,
+        surface: &(impl Surface + ?Sized),
+        uniforms: &impl Uniforms<GlUniforms = U>,
+        pipeline_state: &PipelineState,
+    ) {
+        self.draw_inner(
+            &self.program,
+            surface,
+            uniforms,
+            pipeline_state,
+            0..self.num_indices as usize,
+        );
+    }
+
+    /// Draws part of the mesh with a different program. Programs with the same vertex type
+    /// share attribute locations, so this doesn't require the mesh to be rebuilt.
+    pub fn draw_range_with_program<U2: GlUniforms>(
+        &self,
+        program: &GlProgram<V, U2>,
+        surface: &(impl Surface + ?Sized),
+        uniforms: &impl Uniforms<GlUniforms = U2>,
+        indices: Range<usize>,
+    ) {
+        self.draw_inner(program, surface, uniforms, &self.pipeline_state, indices);
+    }
+
+    fn draw_inner<U2: GlUniforms>(
+        &self,
+        program: &GlProgram<V, U2>,
+        surface: &(impl Surface + ?Sized),
+        uniforms: &impl Uniforms<GlUniforms = U2>,
+        pipeline_state: &PipelineState,
+        indices: Range<usize>,
+    ) {
+        assert!(indices.end <= self.num_indices as usize);
+        if indices.is_empty() {
             return;
         }
 
         self.bind();
-        self.program.bind(&self.context);
-        uniforms.update(&self.context, &self.program.inner.gl_uniforms);
+        program.bind(&self.context);
+        uniforms.update(&self.context, &program.inner().gl_uniforms);
         surface.bind(&self.context);
-        self.draw_mode.bind(&self.context);
+        pipeline_state.bind(&self.context);
 
+        self.context.record_stats(|stats| {
+            stats.draw_calls += 1;
+            stats.triangles += P::num_triangles(indices.len());
+        });
         unsafe {
-            self.context.inner().draw_elements(P::AS_GL, self.num_indices, glow::UNSIGNED_SHORT, 0);
+            self.context.inner().draw_elements(
+                P::AS_GL,
+                indices.len() as i32,
+                self.index_type,
+                (indices.start * self.index_size()) as i32,
+            );
         }
     }
 
@@ -418,6 +858,9 @@ impl<V: Vertex, U: GlUniforms, P: Primitive> Mesh<V, U, P> {
     /// can be passed in the `instances` parameter and the mesh will be drawn once for each
     /// instance. The instance data's fields must be in the same order as its `VertexData` impl
     /// specifies, and it must use `#[repr(C)]`.
+    ///
+    /// The instances are uploaded every time this is called; use `draw_instanced_with_buffer` for
+    /// instance data that rarely changes.
     pub fn draw_instanced<I: VertexData>(
         &self,
         surface: &(impl Surface + ?Sized),
@@ -428,33 +871,266 @@ impl<V: Vertex, U: GlUniforms, P: Primitive> Mesh<V, U, P> {
             return;
         }
 
+        let stride = std::mem::size_of::<I>();
+        let instance_data = unsafe {
+            std::slice::from_raw_parts(
+                instances.as_ptr() as *const u8,
+                std::mem::size_of_val(instances),
+            )
+        };
+        let stream_offset = self.context.write_stream_buffer(instance_data, stride);
+        let (buffer, base_instance) = match stream_offset {
+            Some((buffer, offset)) => (buffer, offset / stride),
+            None => (self.context.instanced_vbo, 0),
+        };
+
+        unsafe {
+            if stream_offset.is_none() {
+                self.context.inner().bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+                self.context.inner().buffer_data_u8_slice(
+                    glow::ARRAY_BUFFER,
+                    instance_data,
+                    MeshUsage::StreamDraw.as_gl(),
+                );
+                self.context.track_instanced_vbo_size(instance_data.len());
+            }
+        }
+        self.context.record_stats(|stats| {
+            stats.buffer_uploads += 1;
+            stats.buffer_upload_bytes += instance_data.len();
+        });
+
+        self.draw_instanced_from::<I>(surface, uniforms, buffer, base_instance, instances.len());
+    }
+
+    /// Like `draw_instanced`, but reads the instances from an `InstanceBuffer`, so they don't have
+    /// to be uploaded each frame.
+    pub fn draw_instanced_with_buffer<I: VertexData>(
+        &self,
+        surface: &(impl Surface + ?Sized),
+        uniforms: &impl Uniforms<GlUniforms = U>,
+        instances: &InstanceBuffer<I>,
+    ) {
+        if self.num_indices == 0 || instances.is_empty() {
+            return;
+        }
+        self.draw_instanced_from::<I>(surface, uniforms, instances.buffer, 0, instances.len());
+    }
+
+    fn draw_instanced_from<I: VertexData>(
+        &self,
+        surface: &(impl Surface + ?Sized),
+        uniforms: &impl Uniforms<GlUniforms = U>,
+        buffer: GlBuffer,
+        base_instance: usize,
+        num_instances: usize,
+    ) {
         self.bind();
         self.program.bind(&self.context);
-        uniforms.update(&self.context, &self.program.inner.gl_uniforms);
+        uniforms.update(&self.context, &self.program.inner().gl_uniforms);
         surface.bind(&self.context);
-        self.draw_mode.bind(&self.context);
+        self.pipeline_state.bind(&self.context);
 
         unsafe {
-            self.context.inner().bind_buffer(glow::ARRAY_BUFFER, Some(self.context.instanced_vbo));
+            self.context.inner().bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+
+            // The attribute pointers refer to the bound buffer, so they have to be set up again if
+            // the instance data moves to a different buffer. Instance attribute locations are
+            // queried, so they may also change if the program is replaced.
+            let program_id = self.program.inner().id();
+            let instance_attribs = Some((TypeId::of::<I>(), buffer, program_id));
+            if self.buffers().instance_attribs.replace(instance_attribs) != instance_attribs {
+                setup_vertex_attribs::<I, _, _>(&self.program, true);
+            }
 
-            setup_vertex_attribs::<I, _, _>(&self.program, true);
+            self.context.record_stats(|stats| {
+                stats.draw_calls += 1;
+                stats.triangles += P::num_triangles(self.num_indices as usize) * num_instances;
+            });
+
+            if base_instance == 0 {
+                self.context.inner().draw_elements_instanced(
+                    P::AS_GL,
+                    self.num_indices,
+                    self.index_type,
+                    0,
+                    num_instances as i32,
+                );
+            } else {
+                self.context.inner().draw_elements_instanced_base_vertex_base_instance(
+                    P::AS_GL,
+                    self.num_indices,
+                    self.index_type,
+                    0,
+                    num_instances as i32,
+                    0,
+                    base_instance as u32,
+                );
+            }
+        }
+    }
+}
 
-            self.context.inner().buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                std::slice::from_raw_parts(
-                    instances.as_ptr() as *const u8,
-                    instances.len() * std::mem::size_of::<I>(),
-                ),
-                // TODO: make this configurable
-                MeshUsage::StreamDraw.as_gl(),
+/// Writes a mesh's contents directly into its buffers; created with `Mesh::build_mapped`.
+///
+/// On desktop, the buffers are mapped so vertices are written straight to GPU-visible memory.
+/// WebGL2 can't map buffers, so the buffers are orphaned instead and the contents are uploaded
+/// when the builder is dropped.
+pub struct MappedMeshBuilder<'a, V: Vertex, U: GlUniforms, P: Primitive> {
+    mesh: &'a mut Mesh<V, U, P>,
+    vertex_data: MappedBuffer<f32>,
+    indices: MappedBuffer<MeshIndex>,
+    next_index: MeshIndex,
+}
+
+impl<'a, V: Vertex, U: GlUniforms, P: Primitive> Drop for MappedMeshBuilder<'a, V, U, P> {
+    fn drop(&mut self) {
+        // Other meshes may have been bound while this one was being built.
+        self.mesh.bind();
+        unsafe {
+            self.mesh
+                .context
+                .inner()
+                .bind_buffer(glow::ARRAY_BUFFER, Some(self.mesh.buffers().vbo));
+            self.vertex_data.unmap(&self.mesh.context);
+            self.indices.unmap(&self.mesh.context);
+        }
+        self.mesh.num_indices = self.indices.len as i32;
+    }
+}
+
+impl<'a, V: Vertex, U: GlUniforms, P: Primitive> MappedMeshBuilder<'a, V, U, P> {
+    /// Adds a vertex to the mesh. See `MeshBuilder::vert`.
+    pub fn vert(&mut self, vert: V) -> MeshIndex {
+        let index = self.next_vert_index(1);
+        let vertex_data = &mut self.vertex_data;
+        vert.add_to_mesh(&mut |data| vertex_data.extend_from_slice(&[data]));
+        index
+    }
+
+    /// Adds indices to the mesh. These are interpreted according to the mesh's primitive type.
+    pub fn indices(&mut self, indices: &[MeshIndex]) {
+        self.indices.extend_from_slice(indices);
+    }
+
+    pub fn next_index(&self) -> MeshIndex {
+        self.next_index
+    }
+
+    fn next_vert_index(&mut self, num_verts: usize) -> MeshIndex {
+        assert!(self.next_index as usize + num_verts <= MeshIndex::MAX as usize);
+        let index = self.next_index;
+        self.next_index += num_verts as MeshIndex;
+        index
+    }
+}
+
+impl<'a, V: Vertex + Pod, U: GlUniforms, P: Primitive> MappedMeshBuilder<'a, V, U, P> {
+    /// Adds a vertex to the mesh. See `MeshBuilder::vert_pod`.
+    pub fn vert_pod(&mut self, vert: V) -> MeshIndex {
+        self.verts_pod(std::slice::from_ref(&vert))
+    }
+
+    /// Adds several vertices to the mesh. See `MeshBuilder::verts_pod`.
+    pub fn verts_pod(&mut self, verts: &[V]) -> MeshIndex {
+        debug_assert_eq!(std::mem::size_of::<V>(), V::stride() as usize * 4);
+        let index = self.next_vert_index(verts.len());
+        self.vertex_data.extend_from_slice(bytemuck::cast_slice(verts));
+        index
+    }
+}
+
+impl<'a, V: Vertex, U: GlUniforms> MappedMeshBuilder<'a, V, U, Triangles> {
+    /// Adds a triangle to the mesh.
+    pub fn triangle(&mut self, a: MeshIndex, b: MeshIndex, c: MeshIndex) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+}
+
+impl<'a, V: Vertex, U: GlUniforms> MappedMeshBuilder<'a, V, U, Lines> {
+    /// Adds a line to the mesh.
+    pub fn line(&mut self, a: MeshIndex, b: MeshIndex) {
+        self.indices.extend_from_slice(&[a, b]);
+    }
+}
+
+impl<'a, V: Vertex, U: GlUniforms> MappedMeshBuilder<'a, V, U, Points> {
+    /// Adds a point to the mesh.
+    pub fn point(&mut self, a: MeshIndex) {
+        self.indices.extend_from_slice(&[a]);
+    }
+}
+
+/// A buffer that's being written by a `MappedMeshBuilder`.
+struct MappedBuffer<T: Pod> {
+    target: u32,
+    #[cfg(not(target_arch = "wasm32"))]
+    ptr: *mut T,
+    #[cfg(target_arch = "wasm32")]
+    data: Vec<T>,
+    len: usize,
+    capacity: usize,
+}
+
+impl<T: Pod> MappedBuffer<T> {
+    /// Orphans the buffer that's bound to `target` and maps it for writing.
+    unsafe fn map(context: &GlContext, target: u32, capacity: usize, usage: MeshUsage) -> Self {
+        let size = (capacity * std::mem::size_of::<T>()) as i32;
+        context.inner().buffer_data_size(target, size, usage.as_gl());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let ptr = if size == 0 {
+            std::ptr::null_mut()
+        } else {
+            let ptr = context.inner().map_buffer_range(
+                target,
+                0,
+                size,
+                glow::MAP_WRITE_BIT | glow::MAP_INVALIDATE_BUFFER_BIT,
             );
+            assert!(!ptr.is_null(), "Failed to map buffer");
+            ptr as *mut T
+        };
 
-            self.context.inner().draw_elements_instanced(
-                P::AS_GL,
-                self.num_indices,
-                glow::UNSIGNED_SHORT,
+        Self {
+            target,
+            #[cfg(not(target_arch = "wasm32"))]
+            ptr,
+            #[cfg(target_arch = "wasm32")]
+            data: Vec::with_capacity(capacity),
+            len: 0,
+            capacity,
+        }
+    }
+
+    fn extend_from_slice(&mut self, values: &[T]) {
+        assert!(self.len + values.len() <= self.capacity, "Mapped buffer is full");
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            std::ptr::copy_nonoverlapping(values.as_ptr(), self.ptr.add(self.len), values.len());
+        }
+        #[cfg(target_arch = "wasm32")]
+        self.data.extend_from_slice(values);
+        self.len += values.len();
+    }
+
+    /// Finishes writing to the buffer. The buffer must be bound to `target`.
+    unsafe fn unmap(&mut self, context: &GlContext) {
+        context.record_stats(|stats| {
+            stats.buffer_uploads += 1;
+            stats.buffer_upload_bytes += self.len * std::mem::size_of::<T>();
+        });
+        #[cfg(not(target_arch = "wasm32"))]
+        if !self.ptr.is_null() {
+            context.inner().unmap_buffer(self.target);
+            self.ptr = std::ptr::null_mut();
+        }
+        #[cfg(target_arch = "wasm32")]
+        if !self.data.is_empty() {
+            context.inner().buffer_sub_data_u8_slice(
+                self.target,
                 0,
-                instances.len() as i32,
+                bytemuck::cast_slice(&self.data),
             );
         }
     }
@@ -464,14 +1140,12 @@ fn setup_vertex_attribs<D: VertexData, V: Vertex, U: GlUniforms>(
     program: &GlProgram<V, U>,
     instanced: bool,
 ) {
-    let context = &program.inner.context;
+    let inner = program.inner();
+    let context = &inner.context;
     let stride = D::stride();
+    let locations = inner.attrib_locations::<D>();
     let mut offset = 0;
-    for (attr, size) in D::ATTRIBUTES.iter() {
-        let loc = unsafe {
-            context.inner().get_attrib_location(program.inner.program, attr).unwrap() as u32
-        };
-
+    for (&loc, (_, size)) in locations.iter().zip(D::ATTRIBUTES.iter()) {
         // Matrices take up 4 attributes and each row has to be specified separately.
         if *size == 16 {
             setup_vertex_attrib(context, loc, 4, stride, offset, instanced);
@@ -1,17 +1,43 @@
+mod compute;
 mod context;
 mod framebuffer;
+mod fullscreen;
+mod headless;
+mod instance_buffer;
 mod mesh;
+mod profiler;
 mod program;
+mod query;
+mod readback;
 mod rect;
+mod render_graph;
+mod resources;
+mod shader_watcher;
+pub mod shapes;
+mod stats;
+mod stream_buffer;
 mod surface;
 mod texture;
 pub mod uniforms;
 
+pub use self::compute::*;
 pub use self::context::*;
 pub use self::framebuffer::*;
+pub use self::fullscreen::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::headless::*;
+pub use self::instance_buffer::*;
 pub use self::mesh::*;
+pub use self::profiler::*;
 pub use self::program::*;
+pub use self::query::*;
+pub use self::readback::*;
 pub use self::rect::*;
+pub use self::render_graph::*;
+pub use self::resources::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::shader_watcher::*;
+pub use self::stats::*;
 pub use self::surface::*;
 pub use self::texture::*;
 pub use self::uniforms::{GlUniforms, Uniforms};
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use super::context::*;
+use super::query::*;
+
+/// Measures how much GPU time is spent in named parts of each frame, using timer queries.
+///
+/// ```ignore
+/// profiler.scope("shadow pass", || draw_shadows(&context));
+/// profiler.scope("main pass", || draw_scene(&context));
+/// profiler.end_frame();
+/// ```
+///
+/// Results arrive a few frames late, and are averaged over the last `GpuProfiler::NUM_SAMPLES`
+/// results for each scope. Use `Draw2d::draw_gpu_profiler` to show them. If timer queries aren't
+/// supported, the scopes still run but nothing is measured.
+pub struct GpuProfiler {
+    context: GlContext,
+    supported: bool,
+    scopes: Vec<ProfilerScope>,
+    // Queries that have been issued but whose results haven't been read, with the index of the
+    // scope they belong to
+    pending: Vec<(usize, GlQuery)>,
+    free_queries: Vec<GlQuery>,
+    in_scope: bool,
+}
+
+struct ProfilerScope {
+    name: String,
+    samples: VecDeque<Duration>,
+}
+
+impl GpuProfiler {
+    /// The number of results each scope's average is calculated from.
+    pub const NUM_SAMPLES: usize = 60;
+
+    pub fn new(context: &GlContext) -> Self {
+        Self {
+            context: context.clone(),
+            supported: context.supports_query(QueryKind::TimeElapsed),
+            scopes: vec![],
+            pending: vec![],
+            free_queries: vec![],
+            in_scope: false,
+        }
+    }
+
+    /// True if timer queries are supported, so the profiler can measure anything.
+    pub fn is_supported(&self) -> bool {
+        self.supported
+    }
+
+    /// Runs `f`, measuring the GPU time spent on the commands it issues. Only one scope can be
+    /// measured at a time, so scopes nested inside other scopes aren't measured.
+    pub fn scope<R>(&mut self, name: &str, f: impl FnOnce() -> R) -> R {
+        if !self.supported || self.in_scope {
+            return f();
+        }
+
+        let index = match self.scopes.iter().position(|scope| scope.name == name) {
+            Some(index) => index,
+            None => {
+                self.scopes.push(ProfilerScope { name: name.to_owned(), samples: VecDeque::new() });
+                self.scopes.len() - 1
+            }
+        };
+        let mut query = self
+            .free_queries
+            .pop()
+            .unwrap_or_else(|| GlQuery::new(&self.context, QueryKind::TimeElapsed));
+
+        self.in_scope = true;
+        let res = {
+            let _scope = query.begin();
+            f()
+        };
+        self.in_scope = false;
+
+        self.pending.push((index, query));
+        res
+    }
+
+    /// Collects the results that have become available. This should be called once per frame.
+    pub fn end_frame(&mut self) {
+        let mut i = 0;
+        while i < self.pending.len() {
+            let (index, query) = &mut self.pending[i];
+            let time = query.time_elapsed();
+            if query.is_pending() {
+                i += 1;
+                continue;
+            }
+            // Results that were disrupted by the GPU's clock changing are dropped.
+            if let Some(time) = time {
+                let samples = &mut self.scopes[*index].samples;
+                if samples.len() == Self::NUM_SAMPLES {
+                    samples.pop_front();
+                }
+                samples.push_back(time);
+            }
+            let (_, query) = self.pending.swap_remove(i);
+            self.free_queries.push(query);
+        }
+    }
+
+    /// Returns the average time of each scope that has any results, in the order the scopes were
+    /// first used.
+    pub fn averages(&self) -> Vec<(&str, Duration)> {
+        self.scopes
+            .iter()
+            .filter(|scope| !scope.samples.is_empty())
+            .map(|scope| {
+                let total: Duration = scope.samples.iter().sum();
+                (scope.name.as_str(), total / scope.samples.len() as u32)
+            })
+            .collect()
+    }
+
+    /// Discards all results.
+    pub fn reset(&mut self) {
+        for scope in &mut self.scopes {
+            scope.samples.clear();
+        }
+    }
+}
+
+impl fmt::Display for GpuProfiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.supported {
+            return write!(f, "GPU timing isn't supported");
+        }
+        let averages = self.averages();
+        let total: Duration = averages.iter().map(|(_, time)| *time).sum();
+        for (name, time) in averages {
+            writeln!(f, "{}: {:.2} ms", name, time.as_secs_f64() * 1000.0)?;
+        }
+        write!(f, "Total: {:.2} ms", total.as_secs_f64() * 1000.0)
+    }
+}
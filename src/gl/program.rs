@@ -1,11 +1,17 @@
 use cgmath::*;
+use fxhash::*;
 use glow::HasContext;
 use log::*;
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
 use std::marker::PhantomData;
 use std::rc::Rc;
 use uid::*;
 
 use super::context::*;
+use super::resources::*;
 use super::uniforms::*;
 
 #[doc(hidden)]
@@ -14,14 +20,16 @@ pub struct ProgramId_(());
 
 pub type ProgramId = Id<ProgramId_>;
 
-type GlShader = <glow::Context as HasContext>::Shader;
+pub(crate) type GlShader = <glow::Context as HasContext>::Shader;
 /// An identifier representing an OpenGL program, used when the full `GlProgram` can't be used.
 pub type GlProgramId = <glow::Context as HasContext>::Program;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ShaderType {
     Vertex,
     Fragment,
+    /// Only supported where `GlContext::supports_compute` is true.
+    Compute,
 }
 
 impl ShaderType {
@@ -29,13 +37,75 @@ impl ShaderType {
         match self {
             ShaderType::Vertex => glow::VERTEX_SHADER,
             ShaderType::Fragment => glow::FRAGMENT_SHADER,
+            ShaderType::Compute => glow::COMPUTE_SHADER,
         }
     }
 }
 
+/// An error from compiling or linking a program.
+#[derive(Clone, Debug)]
+pub enum ShaderError {
+    Compile {
+        stage: ShaderType,
+        /// The driver's info log.
+        log: String,
+        /// The source lines that the log refers to, if they could be determined. These are
+        /// 1-based, and include any header added to the source.
+        lines: Vec<u32>,
+    },
+    Link {
+        /// The driver's info log.
+        log: String,
+    },
+}
+
+impl ShaderError {
+    /// The driver's info log.
+    pub fn log(&self) -> &str {
+        match self {
+            ShaderError::Compile { log, .. } | ShaderError::Link { log } => log,
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Compile { stage, log, .. } => {
+                write!(f, "Error compiling {:?} shader: {}", stage, log)
+            }
+            ShaderError::Link { log } => write!(f, "Error linking program: {}", log),
+        }
+    }
+}
+
+impl Error for ShaderError {}
+
+/// Finds the line numbers in a shader info log. Drivers report locations as `0:12:` (Mesa, AMD,
+/// ANGLE) or `0(12)` (NVIDIA), where the first number is the source string index.
+fn parse_error_lines(log: &str) -> Vec<u32> {
+    let mut lines = vec![];
+    for log_line in log.lines() {
+        let line = log_line.split(|c: char| c.is_whitespace()).find_map(|word| {
+            let rest = word.strip_prefix("0:").or_else(|| word.strip_prefix("0("))?;
+            let end = rest.find(|c: char| !c.is_ascii_digit())?;
+            rest[..end].parse().ok()
+        });
+        if let Some(line) = line {
+            if !lines.contains(&line) {
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
 /// An OpenGL program.
+///
+/// Clones of a program share it, and `replace_with` changes the program used by all of them,
+/// which allows shaders to be reloaded while meshes keep drawing with them.
 pub struct GlProgram<V: Vertex, U: GlUniforms> {
-    pub inner: Rc<GlProgramInner<V, U>>,
+    inner: Rc<RefCell<Rc<GlProgramInner<V, U>>>>,
 }
 
 impl<V: Vertex, U: GlUniforms> Clone for GlProgram<V, U> {
@@ -52,10 +122,13 @@ pub struct GlProgramInner<V: Vertex, U: GlUniforms> {
     pub context: GlContext,
     vert_shader: GlShader,
     frag_shader: GlShader,
+    // Attribute locations for each `VertexData` type used with this program, keyed by its `TypeId`.
+    attrib_locations: RefCell<FxHashMap<TypeId, Rc<[u32]>>>,
 }
 
 impl<V: Vertex, U: GlUniforms> Drop for GlProgramInner<V, U> {
     fn drop(&mut self) {
+        self.context.track_deleted(ResourceKind::Program, 0);
         unsafe {
             self.context.inner().delete_program(self.program);
             self.context.inner().delete_shader(self.vert_shader);
@@ -64,67 +137,152 @@ impl<V: Vertex, U: GlUniforms> Drop for GlProgramInner<V, U> {
     }
 }
 
+impl<V: Vertex, U: GlUniforms> GlProgramInner<V, U> {
+    pub(crate) fn id(&self) -> ProgramId {
+        self.id
+    }
+
+    /// Returns the location of each of `D`'s attributes, in the same order as `D::ATTRIBUTES`.
+    /// Locations are only queried from OpenGL the first time a type is used.
+    pub fn attrib_locations<D: VertexData>(&self) -> Rc<[u32]> {
+        self.attrib_locations
+            .borrow_mut()
+            .entry(TypeId::of::<D>())
+            .or_insert_with(|| {
+                D::ATTRIBUTES
+                    .iter()
+                    .map(|(attr, _)| unsafe {
+                        self.context.inner().get_attrib_location(self.program, attr).unwrap()
+                    })
+                    .collect()
+            })
+            .clone()
+    }
+}
+
 impl<V: Vertex, U: GlUniforms> GlProgram<V, U> {
+    /// Creates a program. Panics if a shader fails to compile or the program fails to link; see
+    /// `try_new` to handle errors.
     pub fn new(context: &GlContext, vert_shader_source: &str, frag_shader_source: &str) -> Self {
-        let vert_shader = Self::load_shader(context, ShaderType::Vertex, vert_shader_source);
-        let frag_shader = Self::load_shader(context, ShaderType::Fragment, frag_shader_source);
+        Self::try_new(context, vert_shader_source, frag_shader_source).unwrap_or_else(|err| {
+            error!("{}", err);
+            panic!("{}", err)
+        })
+    }
+
+    /// Like `new`, but returns an error if a shader fails to compile or the program fails to
+    /// link, e.g. so that a shader can be reloaded or a simpler one can be used instead.
+    pub fn try_new(
+        context: &GlContext,
+        vert_shader_source: &str,
+        frag_shader_source: &str,
+    ) -> Result<Self, ShaderError> {
+        let vert_shader = load_shader(context, ShaderType::Vertex, vert_shader_source)?;
+        let frag_shader = match load_shader(context, ShaderType::Fragment, frag_shader_source) {
+            Ok(shader) => shader,
+            Err(err) => {
+                unsafe { context.inner().delete_shader(vert_shader) };
+                return Err(err);
+            }
+        };
 
+        let mut vertex_locations = vec![];
         let program = unsafe {
             let program = context.inner().create_program().unwrap();
             context.inner().attach_shader(program, vert_shader);
             context.inner().attach_shader(program, frag_shader);
+            // Programs with the same vertex type get the same attribute locations, so a mesh can
+            // be drawn with any of them.
+            let mut loc = 0;
+            for &(attr, size) in V::ATTRIBUTES {
+                vertex_locations.push(loc);
+                context.inner().bind_attrib_location(program, loc, attr);
+                // Matrices take up 4 attributes.
+                loc += if size == 16 { 4 } else { 1 };
+            }
             context.inner().link_program(program);
 
             let link_status = context.inner().get_program_link_status(program);
             if !link_status {
-                error!("Error linking program: {}", context.inner().get_program_info_log(program));
-                panic!();
+                let log = context.inner().get_program_info_log(program);
+                context.inner().delete_program(program);
+                context.inner().delete_shader(vert_shader);
+                context.inner().delete_shader(frag_shader);
+                return Err(ShaderError::Link { log });
             }
             program
         };
 
         let gl_uniforms = U::new(context, program);
-
-        GlProgram {
-            inner: Rc::new(GlProgramInner {
-                program,
-                gl_uniforms,
-                phantom: PhantomData,
-                id: ProgramId::new(),
-                context: context.clone(),
-                vert_shader,
-                frag_shader,
-            }),
-        }
+        context.track_created(ResourceKind::Program, 0);
+
+        // The vertex type's locations are known, so they don't need to be queried. This also
+        // allows programs that don't use every attribute, such as picking programs.
+        let mut attrib_locations = FxHashMap::default();
+        attrib_locations.insert(TypeId::of::<V>(), vertex_locations.into());
+
+        let inner = GlProgramInner {
+            program,
+            gl_uniforms,
+            phantom: PhantomData,
+            id: ProgramId::new(),
+            context: context.clone(),
+            vert_shader,
+            frag_shader,
+            attrib_locations: RefCell::new(attrib_locations),
+        };
+        Ok(GlProgram { inner: Rc::new(RefCell::new(Rc::new(inner))) })
     }
 
-    fn load_shader(context: &GlContext, shader_type: ShaderType, source: &str) -> GlShader {
-        unsafe {
-            let shader = context.inner().create_shader(shader_type.as_gl()).unwrap();
-            context.inner().shader_source(shader, source);
-            context.inner().compile_shader(shader);
-
-            let compile_status = context.inner().get_shader_compile_status(shader);
-            if !compile_status {
-                error!("Error compiling shader: {}", context.inner().get_shader_info_log(shader));
-                panic!();
-            }
+    /// Returns the current OpenGL program and its uniforms.
+    pub fn inner(&self) -> Rc<GlProgramInner<V, U>> {
+        self.inner.borrow().clone()
+    }
 
-            shader
-        }
+    /// Makes this program, and every clone of it, use `other`'s shaders from now on. Meshes
+    /// created with this program keep working, since programs with the same vertex type use the
+    /// same attribute locations.
+    pub fn replace_with(&self, other: &GlProgram<V, U>) {
+        let new_inner = other.inner();
+        *self.inner.borrow_mut() = new_inner;
     }
 
     pub fn bind(&self, context: &GlContext) {
+        let inner = self.inner();
         let mut cache = context.cache.borrow_mut();
-        if cache.bound_program != Some(self.inner.id) {
-            cache.bound_program = Some(self.inner.id);
+        if cache.bound_program != Some(inner.id) {
+            cache.bound_program = Some(inner.id);
+            context.record_stats(|stats| stats.program_switches += 1);
             unsafe {
-                context.inner().use_program(Some(self.inner.program));
+                context.inner().use_program(Some(inner.program));
             }
         }
     }
 }
 
+/// Compiles a shader, returning the driver's error if it fails.
+pub(crate) fn load_shader(
+    context: &GlContext,
+    shader_type: ShaderType,
+    source: &str,
+) -> Result<GlShader, ShaderError> {
+    unsafe {
+        let shader = context.inner().create_shader(shader_type.as_gl()).unwrap();
+        context.inner().shader_source(shader, source);
+        context.inner().compile_shader(shader);
+
+        let compile_status = context.inner().get_shader_compile_status(shader);
+        if !compile_status {
+            let log = context.inner().get_shader_info_log(shader);
+            context.inner().delete_shader(shader);
+            let lines = parse_error_lines(&log);
+            return Err(ShaderError::Compile { stage: shader_type, log, lines });
+        }
+
+        Ok(shader)
+    }
+}
+
 /// A list of all OpenGL attributes for a given program.
 ///
 /// Each pair is (attribute name, attribute size).
@@ -156,7 +314,9 @@ pub trait Vertex: VertexData + VertexComponent {}
 
 impl<T: VertexData + VertexComponent> Vertex for T {}
 
-pub trait VertexData {
+/// Vertex types are `'static` so that they can be told apart by `TypeId`, which is used to
+/// cache their attribute locations.
+pub trait VertexData: 'static {
     /// A list of all OpenGL attributes that each vertex contains.
     const ATTRIBUTES: Attributes;
 
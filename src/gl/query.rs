@@ -0,0 +1,144 @@
+use glow::HasContext;
+use std::time::Duration;
+
+use super::context::*;
+
+type GlQueryObject = <glow::Context as HasContext>::Query;
+
+// From EXT_disjoint_timer_query; not included in glow.
+const GPU_DISJOINT_EXT: u32 = 0x8FBB;
+
+/// What a `GlQuery` measures.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum QueryKind {
+    /// Whether any samples passed the depth and stencil tests, for occlusion culling.
+    AnySamplesPassed,
+    /// The time the GPU spent on the commands in the query, for profiling.
+    TimeElapsed,
+}
+
+impl QueryKind {
+    fn as_gl(self) -> u32 {
+        match self {
+            QueryKind::AnySamplesPassed => glow::ANY_SAMPLES_PASSED,
+            QueryKind::TimeElapsed => glow::TIME_ELAPSED,
+        }
+    }
+}
+
+/// Measures something about the commands issued between `begin` and the end of the returned
+/// scope. The result becomes available a few frames later, without stalling the GPU; poll for
+/// it with `any_samples_passed` or `time_elapsed`.
+///
+/// Only one query of each kind can be active at a time, so queries can't be nested. To measure
+/// every frame, use a few queries in rotation, since a query can't be restarted until its result
+/// has been read.
+pub struct GlQuery {
+    query: GlQueryObject,
+    kind: QueryKind,
+    // True if the query has ended but its result hasn't been read yet
+    pending: bool,
+    context: GlContext,
+}
+
+impl Drop for GlQuery {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.inner().delete_query(self.query);
+        }
+    }
+}
+
+impl GlQuery {
+    /// Creates a query. Panics if the kind of query isn't supported; see
+    /// `GlContext::supports_query`.
+    pub fn new(context: &GlContext, kind: QueryKind) -> Self {
+        Self::try_new(context, kind)
+            .unwrap_or_else(|| panic!("{:?} queries aren't supported", kind))
+    }
+
+    /// Like `new`, but returns `None` if the kind of query isn't supported.
+    pub fn try_new(context: &GlContext, kind: QueryKind) -> Option<Self> {
+        if !context.supports_query(kind) {
+            return None;
+        }
+        let query = unsafe { context.inner().create_query().unwrap() };
+        Some(Self { query, kind, pending: false, context: context.clone() })
+    }
+
+    pub fn kind(&self) -> QueryKind {
+        self.kind
+    }
+
+    /// Starts the query. It ends when the returned scope is dropped. Any result that hasn't been
+    /// read yet is discarded.
+    pub fn begin(&mut self) -> QueryScope<'_> {
+        self.pending = false;
+        unsafe {
+            if self.kind == QueryKind::TimeElapsed && self.context.is_gles() {
+                // Clears the disjoint flag, so it only reflects this query.
+                self.context.inner().get_parameter_i32(GPU_DISJOINT_EXT);
+            }
+            self.context.inner().begin_query(self.kind.as_gl(), self.query);
+        }
+        QueryScope { query: self }
+    }
+
+    /// True if the query has ended and its result hasn't been read yet.
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Returns whether any samples were drawn, or `None` if the result isn't available yet. The
+    /// query must be an `AnySamplesPassed` query.
+    pub fn any_samples_passed(&mut self) -> Option<bool> {
+        assert_eq!(self.kind, QueryKind::AnySamplesPassed);
+        self.poll().map(|samples| samples != 0)
+    }
+
+    /// Returns the GPU time spent on the query's commands, or `None` if the result isn't available
+    /// yet. The query must be a `TimeElapsed` query.
+    ///
+    /// On OpenGL ES and WebGL, the result is also `None` if the timer was disrupted while the
+    /// query was active, e.g. because the GPU's clock changed.
+    pub fn time_elapsed(&mut self) -> Option<Duration> {
+        assert_eq!(self.kind, QueryKind::TimeElapsed);
+        let nanoseconds = self.poll()?;
+        let disjoint = self.context.is_gles()
+            && unsafe { self.context.inner().get_parameter_i32(GPU_DISJOINT_EXT) != 0 };
+        if disjoint {
+            None
+        } else {
+            Some(Duration::from_nanos(nanoseconds as u64))
+        }
+    }
+
+    /// Returns the raw result if it's available, and marks it as read.
+    fn poll(&mut self) -> Option<u32> {
+        if !self.pending {
+            return None;
+        }
+        let context = self.context.inner();
+        unsafe {
+            if context.get_query_parameter_u32(self.query, glow::QUERY_RESULT_AVAILABLE) == 0 {
+                return None;
+            }
+            self.pending = false;
+            Some(context.get_query_parameter_u32(self.query, glow::QUERY_RESULT))
+        }
+    }
+}
+
+/// An active query; the query ends when this is dropped.
+pub struct QueryScope<'a> {
+    query: &'a mut GlQuery,
+}
+
+impl<'a> Drop for QueryScope<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            self.query.context.inner().end_query(self.query.kind.as_gl());
+        }
+        self.query.pending = true;
+    }
+}
@@ -0,0 +1,160 @@
+use cgmath::*;
+use glow::HasContext;
+
+use super::context::*;
+use super::mesh::*;
+use super::rect::*;
+use super::resources::*;
+use super::surface::*;
+
+type GlFence = <glow::Context as HasContext>::Fence;
+
+/// Pixels being read from a surface without waiting for the GPU; created with
+/// `Surface::read_pixels_async`.
+///
+/// The pixels are copied into a pixel buffer object, and a fence is used to find out when the
+/// copy has finished. Poll for the result once per frame with `poll`; it's typically ready after
+/// a frame or two. WebGL1 doesn't have pixel buffer objects, so there the pixels are read
+/// immediately.
+pub struct PixelReadback {
+    state: ReadbackState,
+    size: Vector2<u32>,
+    include_alpha: bool,
+    context: GlContext,
+}
+
+enum ReadbackState {
+    Pending { buffer: GlBuffer, fence: GlFence, bytes: usize },
+    Ready(Vec<u8>),
+    Taken,
+}
+
+impl Drop for PixelReadback {
+    fn drop(&mut self) {
+        if let ReadbackState::Pending { buffer, fence, bytes } =
+            std::mem::replace(&mut self.state, ReadbackState::Taken)
+        {
+            Self::delete(&self.context, buffer, fence, bytes);
+        }
+    }
+}
+
+impl PixelReadback {
+    pub(crate) fn new(
+        context: &GlContext,
+        surface: &(impl Surface + ?Sized),
+        rect: Rect<i32>,
+        include_alpha: bool,
+    ) -> Self {
+        let size = rect.size();
+        assert!(size.x >= 0 && size.y >= 0);
+        let size = size.cast().unwrap();
+        let bytes = (size.x * size.y) as usize * if include_alpha { 4 } else { 3 };
+        let format = if include_alpha { glow::RGBA } else { glow::RGB };
+
+        surface.bind_read(context);
+        let state = unsafe {
+            if context.is_webgl1() {
+                let mut pixels = vec![0; bytes];
+                context.inner().read_pixels(
+                    rect.start.x,
+                    rect.start.y,
+                    size.x as i32,
+                    size.y as i32,
+                    format,
+                    glow::UNSIGNED_BYTE,
+                    glow::PixelPackData::Slice(&mut pixels),
+                );
+                ReadbackState::Ready(pixels)
+            } else {
+                let inner = context.inner();
+                let buffer = inner.create_buffer().unwrap();
+                inner.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(buffer));
+                inner.buffer_data_size(glow::PIXEL_PACK_BUFFER, bytes as i32, glow::STREAM_READ);
+                inner.read_pixels(
+                    rect.start.x,
+                    rect.start.y,
+                    size.x as i32,
+                    size.y as i32,
+                    format,
+                    glow::UNSIGNED_BYTE,
+                    glow::PixelPackData::BufferOffset(0),
+                );
+                inner.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+                let fence = inner.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).unwrap();
+                // Without a flush, the fence might not be submitted until something else flushes.
+                inner.flush();
+                drop(inner);
+                context.track_created(ResourceKind::Buffer, bytes);
+                ReadbackState::Pending { buffer, fence, bytes }
+            }
+        };
+        Self { state, size, include_alpha, context: context.clone() }
+    }
+
+    /// The size of the area being read, in pixels.
+    pub fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    /// True if the pixels have 4 bytes each (RGBA) rather than 3 (RGB).
+    pub fn has_alpha(&self) -> bool {
+        self.include_alpha
+    }
+
+    /// Returns the pixels if they're ready, or `None` if the GPU hasn't finished copying them or
+    /// they've already been returned. Rows start from the bottom of the area.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        match std::mem::replace(&mut self.state, ReadbackState::Taken) {
+            ReadbackState::Pending { buffer, fence, bytes } => {
+                let status = unsafe { self.context.inner().get_sync_status(fence) };
+                if status != glow::SIGNALED {
+                    self.state = ReadbackState::Pending { buffer, fence, bytes };
+                    return None;
+                }
+                let mut pixels = vec![0; bytes];
+                unsafe {
+                    let inner = self.context.inner();
+                    inner.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(buffer));
+                    read_buffer(&inner, &mut pixels);
+                    inner.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+                }
+                Self::delete(&self.context, buffer, fence, bytes);
+                Some(pixels)
+            }
+            ReadbackState::Ready(pixels) => Some(pixels),
+            ReadbackState::Taken => None,
+        }
+    }
+
+    /// True if the pixels haven't been returned by `poll` yet.
+    pub fn is_pending(&self) -> bool {
+        !matches!(self.state, ReadbackState::Taken)
+    }
+
+    fn delete(context: &GlContext, buffer: GlBuffer, fence: GlFence, bytes: usize) {
+        context.track_deleted(ResourceKind::Buffer, bytes);
+        unsafe {
+            context.inner().delete_buffer(buffer);
+            context.inner().delete_sync(fence);
+        }
+    }
+}
+
+/// Copies the contents of the bound pixel pack buffer into `dst`. OpenGL ES doesn't have
+/// `glGetBufferSubData`, so natively the buffer is mapped instead, which works with both
+/// OpenGL and OpenGL ES. WebGL2 has `getBufferSubData` but can't map buffers.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn read_buffer(inner: &glow::Context, dst: &mut [u8]) {
+    let data =
+        inner.map_buffer_range(glow::PIXEL_PACK_BUFFER, 0, dst.len() as i32, glow::MAP_READ_BIT);
+    assert!(!data.is_null(), "Unable to map pixel buffer");
+    std::ptr::copy_nonoverlapping(data, dst.as_mut_ptr(), dst.len());
+    inner.unmap_buffer(glow::PIXEL_PACK_BUFFER);
+}
+
+/// Copies the contents of the bound pixel pack buffer into `dst`.
+#[cfg(target_arch = "wasm32")]
+unsafe fn read_buffer(inner: &glow::Context, dst: &mut [u8]) {
+    inner.get_buffer_sub_data(glow::PIXEL_PACK_BUFFER, 0, dst);
+}
@@ -45,6 +45,16 @@ impl<T: BaseNum> Rect<T> {
             && self.start.y <= point.y
             && self.end.y >= point.y
     }
+
+    /// Returns the area covered by both `Rect`s. If they don't overlap, the result has a size
+    /// of 0.
+    pub fn intersection(&self, other: &Rect<T>) -> Rect<T> {
+        let max = |a: T, b: T| if a > b { a } else { b };
+        let min = |a: T, b: T| if a < b { a } else { b };
+        let start = point2(max(self.start.x, other.start.x), max(self.start.y, other.start.y));
+        let end = point2(min(self.end.x, other.end.x), min(self.end.y, other.end.y));
+        Rect::new(start, point2(max(start.x, end.x), max(start.y, end.y)))
+    }
 }
 
 impl<T: NumCast + Copy> Rect<T> {
@@ -0,0 +1,135 @@
+use log::*;
+
+use super::context::*;
+use super::framebuffer::*;
+use super::surface::*;
+
+type DrawFn<'a> = Box<dyn FnOnce(&dyn Surface) + 'a>;
+
+/// A step of rendering that draws to a single surface, declared as part of a `RenderGraph`.
+///
+/// Example usage:
+/// ```
+/// let mut graph = RenderGraph::new();
+/// graph.add_pass(
+///     RenderPass::new("world", &world_framebuffer)
+///         .clear(&[ClearBuffer::Color([0.0, 0.0, 0.0, 1.0]), ClearBuffer::Depth(1.0)])
+///         .draw(|surface| world.draw(surface)),
+/// );
+/// graph.add_pass(
+///     RenderPass::new("composite", &screen_surface)
+///         .reads(&world_framebuffer)
+///         .draw(|surface| post_process.render(&world_framebuffer.attachment, surface)),
+/// );
+/// graph.execute(&context);
+/// ```
+pub struct RenderPass<'a> {
+    name: &'static str,
+    target: &'a dyn Surface,
+    clear: Vec<ClearBuffer>,
+    reads: Vec<FramebufferId>,
+    draw: Option<DrawFn<'a>>,
+}
+
+impl<'a> RenderPass<'a> {
+    /// Creates a pass that renders to `target`. The name is used in error messages.
+    pub fn new(name: &'static str, target: &'a dyn Surface) -> Self {
+        Self { name, target, clear: vec![], reads: vec![], draw: None }
+    }
+
+    /// Clears the given buffers of the target before drawing.
+    pub fn clear(mut self, buffers: &[ClearBuffer]) -> Self {
+        self.clear.extend_from_slice(buffers);
+        self
+    }
+
+    /// Declares that the pass reads the contents of `surface`, so it must run after every pass
+    /// that draws to it.
+    pub fn reads(mut self, surface: &dyn Surface) -> Self {
+        self.reads.push(surface.id());
+        self
+    }
+
+    /// Sets the function that does the pass's drawing. It's given the pass's target.
+    pub fn draw(mut self, f: impl FnOnce(&dyn Surface) + 'a) -> Self {
+        self.draw = Some(Box::new(f));
+        self
+    }
+}
+
+/// A set of `RenderPass`es for a frame.
+///
+/// Passes are run in the order they were added, except that a pass that reads a surface is moved
+/// after all passes that draw to it. Panics if a pass reads its own target, or if the passes'
+/// dependencies form a cycle.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    passes: Vec<RenderPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self { passes: vec![] }
+    }
+
+    pub fn add_pass(&mut self, pass: RenderPass<'a>) {
+        self.passes.push(pass);
+    }
+
+    /// Runs all passes, binding and clearing each pass's target before calling its draw function.
+    pub fn execute(self, context: &GlContext) {
+        for pass in self.sorted() {
+            pass.target.bind(context);
+            if !pass.clear.is_empty() {
+                pass.target.clear(context, &pass.clear);
+            }
+            if let Some(draw) = pass.draw {
+                draw(pass.target);
+            }
+        }
+    }
+
+    /// Orders the passes so each one runs after the passes it depends on.
+    fn sorted(self) -> Vec<RenderPass<'a>> {
+        let passes = self.passes;
+        let targets: Vec<FramebufferId> = passes.iter().map(|pass| pass.target.id()).collect();
+
+        // `dependencies[i]` contains the passes that must run before pass `i`.
+        let mut dependencies = vec![vec![]; passes.len()];
+        for (i, pass) in passes.iter().enumerate() {
+            if pass.reads.contains(&targets[i]) {
+                panic!("Render pass '{}' reads from its own target", pass.name);
+            }
+            for (j, &target) in targets.iter().enumerate() {
+                // Passes that draw to the same surface stay in the order they were added.
+                let earlier_same_target = j < i && target == targets[i];
+                if earlier_same_target || pass.reads.contains(&target) {
+                    dependencies[i].push(j);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(passes.len());
+        let mut done = vec![false; passes.len()];
+        while order.len() < passes.len() {
+            let next = (0..passes.len())
+                .find(|&i| !done[i] && dependencies[i].iter().all(|&j| done[j]))
+                .unwrap_or_else(|| {
+                    let names: Vec<_> =
+                        (0..passes.len()).filter(|&i| !done[i]).map(|i| passes[i].name).collect();
+                    panic!("Render passes have cyclic dependencies: {:?}", names)
+                });
+            done[next] = true;
+            order.push(next);
+        }
+        if order.iter().enumerate().any(|(i, &pass)| i != pass) {
+            debug!(
+                "Reordered render passes: {:?}",
+                order.iter().map(|&i| passes[i].name).collect::<Vec<_>>()
+            );
+        }
+
+        let mut passes: Vec<_> = passes.into_iter().map(Some).collect();
+        order.into_iter().map(|i| passes[i].take().unwrap()).collect()
+    }
+}
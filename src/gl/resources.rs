@@ -0,0 +1,108 @@
+use log::*;
+use std::fmt;
+
+/// A kind of GPU resource that's tracked by `GlContext::resource_report`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ResourceKind {
+    Texture,
+    Renderbuffer,
+    Buffer,
+    VertexArray,
+    Framebuffer,
+    Program,
+}
+
+impl ResourceKind {
+    const COUNT: usize = 6;
+
+    pub const ALL: [ResourceKind; ResourceKind::COUNT] = [
+        ResourceKind::Texture,
+        ResourceKind::Renderbuffer,
+        ResourceKind::Buffer,
+        ResourceKind::VertexArray,
+        ResourceKind::Framebuffer,
+        ResourceKind::Program,
+    ];
+}
+
+/// Statistics for one kind of GPU resource.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ResourceCount {
+    /// The number of resources that haven't been deleted yet.
+    pub live: usize,
+    /// The number of resources that have been created, including deleted ones.
+    pub created: usize,
+    /// The estimated GPU memory used by live resources, in bytes. This is always 0 for resources
+    /// that don't store data themselves, such as framebuffers.
+    pub bytes: usize,
+}
+
+/// The GPU resources that have been created through a `GlContext`.
+///
+/// Resources that are created each frame and never dropped show up as a steadily increasing
+/// `live` count.
+#[derive(Clone, Debug, Default)]
+pub struct ResourceReport {
+    counts: [ResourceCount; ResourceKind::COUNT],
+}
+
+impl ResourceReport {
+    pub fn get(&self, kind: ResourceKind) -> ResourceCount {
+        self.counts[kind as usize]
+    }
+
+    /// The estimated GPU memory used by all live resources, in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.counts.iter().map(|count| count.bytes).sum()
+    }
+}
+
+impl fmt::Display for ResourceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for kind in ResourceKind::ALL {
+            let count = self.get(kind);
+            writeln!(
+                f,
+                "{:?}: {} live ({} created), {} KiB",
+                kind,
+                count.live,
+                count.created,
+                count.bytes / 1024
+            )?;
+        }
+        write!(f, "Total: {} KiB", self.total_bytes() / 1024)
+    }
+}
+
+/// Keeps a `ResourceReport` up to date as resources are created and deleted.
+#[derive(Default)]
+pub(crate) struct ResourceTracker {
+    report: ResourceReport,
+    pub instanced_vbo_bytes: usize,
+}
+
+impl ResourceTracker {
+    pub fn report(&self) -> &ResourceReport {
+        &self.report
+    }
+
+    pub fn created(&mut self, kind: ResourceKind, bytes: usize) {
+        let count = &mut self.report.counts[kind as usize];
+        count.live += 1;
+        count.created += 1;
+        count.bytes += bytes;
+        debug!("Created {:?} ({} bytes); {} live", kind, bytes, count.live);
+    }
+
+    pub fn deleted(&mut self, kind: ResourceKind, bytes: usize) {
+        let count = &mut self.report.counts[kind as usize];
+        count.live -= 1;
+        count.bytes -= bytes;
+        debug!("Deleted {:?} ({} bytes); {} live", kind, bytes, count.live);
+    }
+
+    pub fn resized(&mut self, kind: ResourceKind, old_bytes: usize, new_bytes: usize) {
+        let count = &mut self.report.counts[kind as usize];
+        count.bytes = count.bytes - old_bytes + new_bytes;
+    }
+}
@@ -0,0 +1,125 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use log::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::context::*;
+use super::program::*;
+use super::uniforms::*;
+
+type ReloadFn = Box<dyn Fn(&str, &str) -> Result<(), ShaderError>>;
+
+struct WatchedProgram {
+    vert_path: PathBuf,
+    frag_path: PathBuf,
+    // The modification times of the vertex and fragment shaders when they were last loaded
+    modified: [Option<SystemTime>; 2],
+    reload: ReloadFn,
+}
+
+/// Watches shader source files and recompiles programs when they change, for iterating on
+/// shaders without restarting the app. Only available on desktop.
+///
+/// A program that's recompiled successfully replaces the old one with `GlProgram::replace_with`,
+/// so existing meshes use it immediately. If compilation fails, the old program is kept.
+///
+/// Example usage:
+/// ```
+/// let mut watcher = ShaderWatcher::new();
+/// watcher.watch(
+///     &context,
+///     &program,
+///     "shaders/vert.glsl",
+///     "shaders/frag.glsl",
+///     |context, vert, frag| GlProgram::try_new_with_header(context, vert, frag, true),
+/// );
+/// // Each frame, or by returning it from `App::shader_watcher`:
+/// watcher.poll();
+/// ```
+#[derive(Default)]
+pub struct ShaderWatcher {
+    programs: Vec<WatchedProgram>,
+    last_poll: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    /// The minimum time between checks of the files' modification times.
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts watching the given shader files. When either changes, both are read and passed to
+    /// `build`, and the result replaces `program`. The watcher keeps `program` alive.
+    pub fn watch<V: Vertex + 'static, U: GlUniforms + 'static>(
+        &mut self,
+        context: &GlContext,
+        program: &GlProgram<V, U>,
+        vert_path: impl Into<PathBuf>,
+        frag_path: impl Into<PathBuf>,
+        build: impl Fn(&GlContext, &str, &str) -> Result<GlProgram<V, U>, ShaderError> + 'static,
+    ) {
+        let vert_path = vert_path.into();
+        let frag_path = frag_path.into();
+        let context = context.clone();
+        let program = program.clone();
+        self.programs.push(WatchedProgram {
+            modified: [modified_time(&vert_path), modified_time(&frag_path)],
+            vert_path,
+            frag_path,
+            reload: Box::new(move |vert, frag| {
+                let new_program = build(&context, vert, frag)?;
+                program.replace_with(&new_program);
+                Ok(())
+            }),
+        });
+    }
+
+    /// Reloads any programs whose shaders have changed. This does nothing if it was last called
+    /// less than a quarter of a second ago. Errors are logged and returned.
+    pub fn poll(&mut self) -> Vec<ShaderError> {
+        let now = Instant::now();
+        if matches!(self.last_poll, Some(last_poll) if now - last_poll < Self::POLL_INTERVAL) {
+            return vec![];
+        }
+        self.last_poll = Some(now);
+
+        let mut errors = vec![];
+        for program in &mut self.programs {
+            let modified = [modified_time(&program.vert_path), modified_time(&program.frag_path)];
+            if modified == program.modified {
+                continue;
+            }
+            program.modified = modified;
+
+            let sources = fs::read_to_string(&program.vert_path)
+                .and_then(|vert| Ok((vert, fs::read_to_string(&program.frag_path)?)));
+            let (vert, frag) = match sources {
+                Ok(sources) => sources,
+                Err(err) => {
+                    // The file may be in the middle of being written; it'll be retried when its
+                    // modification time changes again.
+                    warn!("Unable to read shaders {:?}: {}", program.vert_path, err);
+                    continue;
+                }
+            };
+            match (program.reload)(&vert, &frag) {
+                Ok(()) => {
+                    info!("Reloaded shaders {:?} and {:?}", program.vert_path, program.frag_path)
+                }
+                Err(err) => {
+                    error!("Unable to reload shaders {:?}: {}", program.frag_path, err);
+                    errors.push(err);
+                }
+            }
+        }
+        errors
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,296 @@
+//! Generators for common 3D shapes.
+//!
+//! Each generator adds a shape to a `MeshBuilder`, centered at the origin. The vertex type is up
+//! to the caller; `vert` maps each vertex's position, unit normal and UV coordinates to a `V`:
+//! ```ignore
+//! let mut builder = MeshBuilder::new();
+//! shapes::uv_sphere(&mut builder, 1.0, 32, 16, |pos, normal, uv| Vert { pos, normal, uv });
+//! ```
+//! Triangles are wound counter-clockwise when viewed from outside, and Y is up.
+
+use cgmath::*;
+use fxhash::FxHashMap;
+use std::f32::consts::PI;
+
+use super::mesh::*;
+use super::program::*;
+
+// The direction at the given angle around the Y axis; an angle of 0 points along +X, and
+// positive angles turn towards -Z.
+fn around_y(angle: f32) -> Vector3<f32> {
+    let (sin, cos) = angle.sin_cos();
+    vec3(cos, 0.0, -sin)
+}
+
+// Adds a flat grid of quads centered on `center`. `u` and `v` are half the grid's extent along
+// each axis, and `u.cross(v)` must point the same way as `normal`.
+fn grid<V: Vertex>(
+    builder: &mut MeshBuilder<V, Triangles>,
+    center: Point3<f32>,
+    u: Vector3<f32>,
+    v: Vector3<f32>,
+    normal: Vector3<f32>,
+    subdivisions: Vector2<u32>,
+    vert: &mut impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+) {
+    let subdivisions = vec2(subdivisions.x.max(1), subdivisions.y.max(1));
+    let first = builder.next_index();
+    for j in 0..=subdivisions.y {
+        for i in 0..=subdivisions.x {
+            let uv = point2(i as f32 / subdivisions.x as f32, j as f32 / subdivisions.y as f32);
+            let pos = center + u * (uv.x * 2.0 - 1.0) + v * (uv.y * 2.0 - 1.0);
+            builder.vert(vert(pos, normal, uv));
+        }
+    }
+    let index = |i: u32, j: u32| first + j * (subdivisions.x + 1) + i;
+    for j in 0..subdivisions.y {
+        for i in 0..subdivisions.x {
+            let (a, b, c, d) = (index(i, j), index(i + 1, j), index(i + 1, j + 1), index(i, j + 1));
+            builder.triangle(a, b, c);
+            builder.triangle(a, c, d);
+        }
+    }
+}
+
+/// Adds a box with the given size along each axis. Each face has its own vertices, with UV
+/// coordinates covering the whole texture.
+pub fn cube<V: Vertex>(
+    builder: &mut MeshBuilder<V, Triangles>,
+    size: Vector3<f32>,
+    mut vert: impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+) {
+    let half = size / 2.0;
+    // The normal, and the directions of increasing U and V, of each face
+    let faces = [
+        (Vector3::unit_x(), -Vector3::unit_z(), Vector3::unit_y()),
+        (-Vector3::unit_x(), Vector3::unit_z(), Vector3::unit_y()),
+        (Vector3::unit_y(), Vector3::unit_x(), -Vector3::unit_z()),
+        (-Vector3::unit_y(), Vector3::unit_x(), Vector3::unit_z()),
+        (Vector3::unit_z(), Vector3::unit_x(), Vector3::unit_y()),
+        (-Vector3::unit_z(), -Vector3::unit_x(), Vector3::unit_y()),
+    ];
+    for (normal, u, v) in faces {
+        let center = Point3::from_vec(normal.mul_element_wise(half));
+        let u = u.mul_element_wise(half);
+        let v = v.mul_element_wise(half);
+        grid(builder, center, u, v, normal, vec2(1, 1), &mut vert);
+    }
+}
+
+/// Adds a flat rectangle in the XZ plane, facing +Y, divided into the given number of quads
+/// along X and Z. V increases towards -Z.
+pub fn plane<V: Vertex>(
+    builder: &mut MeshBuilder<V, Triangles>,
+    size: Vector2<f32>,
+    subdivisions: Vector2<u32>,
+    mut vert: impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+) {
+    let u = vec3(size.x / 2.0, 0.0, 0.0);
+    let v = vec3(0.0, 0.0, -size.y / 2.0);
+    grid(builder, Point3::origin(), u, v, Vector3::unit_y(), subdivisions, &mut vert);
+}
+
+/// Adds a sphere made of `segments` slices around the Y axis and `rings` stacks from pole to
+/// pole. U goes around the Y axis and V goes from the bottom pole (0) to the top (1).
+pub fn uv_sphere<V: Vertex>(
+    builder: &mut MeshBuilder<V, Triangles>,
+    radius: f32,
+    segments: u32,
+    rings: u32,
+    mut vert: impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+) {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+    let first = builder.next_index();
+    for ring in 0..=rings {
+        // The angle from the top pole
+        let (sin_polar, cos_polar) = (PI * ring as f32 / rings as f32).sin_cos();
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let normal = around_y(u * 2.0 * PI) * sin_polar + Vector3::unit_y() * cos_polar;
+            let uv = point2(u, 1.0 - ring as f32 / rings as f32);
+            builder.vert(vert(Point3::from_vec(normal * radius), normal, uv));
+        }
+    }
+    let index = |segment: u32, ring: u32| first + ring * (segments + 1) + segment;
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = index(segment, ring);
+            let b = index(segment, ring + 1);
+            let c = index(segment + 1, ring + 1);
+            let d = index(segment + 1, ring);
+            // The triangles touching the poles would be degenerate.
+            if ring != rings - 1 {
+                builder.triangle(a, b, c);
+            }
+            if ring != 0 {
+                builder.triangle(a, c, d);
+            }
+        }
+    }
+}
+
+/// Adds a sphere made by repeatedly subdividing an icosahedron, which has more evenly sized
+/// triangles than `uv_sphere`. Each subdivision multiplies the number of triangles by 4.
+///
+/// UV coordinates are a spherical projection like `uv_sphere`'s, but vertices aren't
+/// duplicated along the seam, so textures are distorted on the triangles that cross it.
+pub fn icosphere<V: Vertex>(
+    builder: &mut MeshBuilder<V, Triangles>,
+    radius: f32,
+    subdivisions: u32,
+    mut vert: impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+) {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vector3<f32>> = [
+        vec3(-1.0, t, 0.0),
+        vec3(1.0, t, 0.0),
+        vec3(-1.0, -t, 0.0),
+        vec3(1.0, -t, 0.0),
+        vec3(0.0, -1.0, t),
+        vec3(0.0, 1.0, t),
+        vec3(0.0, -1.0, -t),
+        vec3(0.0, 1.0, -t),
+        vec3(t, 0.0, -1.0),
+        vec3(t, 0.0, 1.0),
+        vec3(-t, 0.0, -1.0),
+        vec3(-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|pos| pos.normalize())
+    .collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // Edges that have already been split, so neighboring triangles share their midpoints
+        let mut midpoints = FxHashMap::default();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push((positions[a as usize] + positions[b as usize]).normalize());
+                positions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let first = builder.next_index();
+    for normal in positions {
+        let u = (-normal.z).atan2(normal.x) / (2.0 * PI);
+        let uv = point2(if u < 0.0 { u + 1.0 } else { u }, 0.5 + normal.y.asin() / PI);
+        builder.vert(vert(Point3::from_vec(normal * radius), normal, uv));
+    }
+    for [a, b, c] in triangles {
+        builder.triangle(first + a, first + b, first + c);
+    }
+}
+
+/// Adds a cylinder along the Y axis, with `segments` sides and a cap on each end. On the sides,
+/// U goes around the Y axis and V goes from the bottom (0) to the top (1); the caps' UV
+/// coordinates map the cap's circle onto the texture.
+pub fn cylinder<V: Vertex>(
+    builder: &mut MeshBuilder<V, Triangles>,
+    radius: f32,
+    height: f32,
+    segments: u32,
+    mut vert: impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+) {
+    let segments = segments.max(3);
+    let half_height = height / 2.0;
+
+    let first = builder.next_index();
+    for segment in 0..=segments {
+        let u = segment as f32 / segments as f32;
+        let normal = around_y(u * 2.0 * PI);
+        let bottom = Point3::from_vec(normal * radius) - Vector3::unit_y() * half_height;
+        builder.vert(vert(bottom, normal, point2(u, 0.0)));
+        builder.vert(vert(bottom + Vector3::unit_y() * height, normal, point2(u, 1.0)));
+    }
+    for segment in 0..segments {
+        let a = first + segment * 2;
+        let (b, c, d) = (a + 2, a + 3, a + 1);
+        builder.triangle(a, b, c);
+        builder.triangle(a, c, d);
+    }
+
+    for normal in [Vector3::unit_y(), -Vector3::unit_y()] {
+        let center = Point3::from_vec(normal * half_height);
+        let center_index = builder.vert(vert(center, normal, point2(0.5, 0.5)));
+        let first = builder.next_index();
+        for segment in 0..segments {
+            let dir = around_y(segment as f32 / segments as f32 * 2.0 * PI);
+            let uv = point2(0.5 + dir.x * 0.5, 0.5 - dir.z * 0.5);
+            builder.vert(vert(center + dir * radius, normal, uv));
+        }
+        for segment in 0..segments {
+            let a = first + segment;
+            let b = first + (segment + 1) % segments;
+            if normal.y > 0.0 {
+                builder.triangle(center_index, a, b);
+            } else {
+                builder.triangle(center_index, b, a);
+            }
+        }
+    }
+}
+
+/// Adds a torus around the Y axis. `major_radius` is the distance from the center to the middle
+/// of the tube, and `minor_radius` is the tube's radius. U goes around the Y axis and V goes
+/// around the tube.
+pub fn torus<V: Vertex>(
+    builder: &mut MeshBuilder<V, Triangles>,
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+    mut vert: impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+) {
+    let major_segments = major_segments.max(3);
+    let minor_segments = minor_segments.max(3);
+    let first = builder.next_index();
+    for i in 0..=major_segments {
+        let u = i as f32 / major_segments as f32;
+        let radial = around_y(u * 2.0 * PI);
+        let tube_center = Point3::from_vec(radial * major_radius);
+        for j in 0..=minor_segments {
+            let v = j as f32 / minor_segments as f32;
+            let (sin, cos) = (v * 2.0 * PI).sin_cos();
+            let normal = radial * cos + Vector3::unit_y() * sin;
+            builder.vert(vert(tube_center + normal * minor_radius, normal, point2(u, v)));
+        }
+    }
+    let index = |i: u32, j: u32| first + i * (minor_segments + 1) + j;
+    for i in 0..major_segments {
+        for j in 0..minor_segments {
+            let (a, b, c, d) = (index(i, j), index(i + 1, j), index(i + 1, j + 1), index(i, j + 1));
+            builder.triangle(a, b, c);
+            builder.triangle(a, c, d);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// Counts of the work submitted to OpenGL during a frame; see `GlContext::end_frame`.
+///
+/// Binds and program switches are only counted when they actually reach OpenGL, not when they're
+/// skipped because the state was already set.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FrameStats {
+    pub draw_calls: usize,
+    pub compute_dispatches: usize,
+    /// The number of triangles drawn, including all instances of instanced draws. Lines and
+    /// points aren't counted.
+    pub triangles: usize,
+    /// The number of times data was written to a vertex, index, or instance buffer.
+    pub buffer_uploads: usize,
+    pub buffer_upload_bytes: usize,
+    pub texture_binds: usize,
+    pub program_switches: usize,
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Draw calls: {}", self.draw_calls)?;
+        if self.compute_dispatches > 0 {
+            writeln!(f, "Compute dispatches: {}", self.compute_dispatches)?;
+        }
+        writeln!(f, "Triangles: {}", self.triangles)?;
+        writeln!(
+            f,
+            "Buffer uploads: {} ({} KiB)",
+            self.buffer_uploads,
+            self.buffer_upload_bytes / 1024
+        )?;
+        writeln!(f, "Texture binds: {}", self.texture_binds)?;
+        write!(f, "Program switches: {}", self.program_switches)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct FrameStatsTracker {
+    pub current: FrameStats,
+    pub last: FrameStats,
+}
@@ -0,0 +1,117 @@
+use glow::HasContext;
+use log::*;
+
+use super::mesh::*;
+
+type GlFence = <glow::Context as HasContext>::Fence;
+
+/// The number of sections the buffer is split into. Each section is fenced when it fills up, and
+/// the fence is waited on before the section is written to again.
+const NUM_SECTIONS: usize = 3;
+
+/// A buffer that's persistently mapped, so streaming data can be written into it without any
+/// calls to `glBufferData`. Writes go through a ring, and fences keep data from being overwritten
+/// while the GPU may still be reading it.
+///
+/// This requires `glBufferStorage` and `glDrawElementsInstancedBaseInstance`, so it's only used on
+/// OpenGL 4.4+ or when the equivalent extensions are available.
+pub(crate) struct StreamBuffer {
+    pub buffer: GlBuffer,
+    ptr: *mut u8,
+    section: usize,
+    offset: usize,
+    fences: [Option<GlFence>; NUM_SECTIONS],
+}
+
+impl StreamBuffer {
+    /// The size of the buffer in bytes.
+    pub const SIZE: usize = 16 * 1024 * 1024;
+
+    /// Returns `None` if persistently mapped buffers aren't supported.
+    pub fn new(context: &glow::Context) -> Option<Self> {
+        if !Self::is_supported(context) {
+            return None;
+        }
+
+        let flags = glow::MAP_WRITE_BIT | glow::MAP_PERSISTENT_BIT | glow::MAP_COHERENT_BIT;
+        unsafe {
+            let buffer = context.create_buffer().unwrap();
+            context.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+            context.buffer_storage(glow::ARRAY_BUFFER, Self::SIZE as i32, None, flags);
+            let ptr = context.map_buffer_range(glow::ARRAY_BUFFER, 0, Self::SIZE as i32, flags);
+            if ptr.is_null() {
+                warn!("Unable to map stream buffer; falling back to glBufferData");
+                context.delete_buffer(buffer);
+                return None;
+            }
+            info!("Using a persistently mapped stream buffer");
+
+            Some(Self { buffer, ptr, section: 0, offset: 0, fences: [None; NUM_SECTIONS] })
+        }
+    }
+
+    fn is_supported(context: &glow::Context) -> bool {
+        // WebGL doesn't support buffer storage at all.
+        if cfg!(target_arch = "wasm32") {
+            return false;
+        }
+        let version = context.version();
+        if version.is_embedded {
+            return false;
+        }
+        if (version.major, version.minor) >= (4, 4) {
+            return true;
+        }
+        let extensions = context.supported_extensions();
+        extensions.contains("GL_ARB_buffer_storage") && extensions.contains("GL_ARB_base_instance")
+    }
+
+    /// Copies `data` into the buffer and returns the offset it was written to, which is a multiple
+    /// of `align`. Returns `None` if the data doesn't fit in a section of the buffer.
+    pub fn write(&mut self, context: &glow::Context, data: &[u8], align: usize) -> Option<usize> {
+        let section_size = Self::SIZE / NUM_SECTIONS;
+        if data.len() + align > section_size {
+            return None;
+        }
+
+        let mut offset = align_up(self.offset, align);
+        if offset + data.len() > (self.section + 1) * section_size {
+            unsafe {
+                // Fence all commands that use the current section, then move to the next one.
+                self.fences[self.section] =
+                    context.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).ok();
+                self.section = (self.section + 1) % NUM_SECTIONS;
+                if let Some(fence) = self.fences[self.section].take() {
+                    wait_for_fence(context, fence);
+                }
+            }
+            offset = align_up(self.section * section_size, align);
+        }
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.add(offset), data.len());
+        }
+        self.offset = offset + data.len();
+        Some(offset)
+    }
+}
+
+fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+unsafe fn wait_for_fence(context: &glow::Context, fence: GlFence) {
+    // The timeout is in nanoseconds.
+    const TIMEOUT: i32 = 100_000_000;
+    loop {
+        match context.client_wait_sync(fence, glow::SYNC_FLUSH_COMMANDS_BIT, TIMEOUT) {
+            glow::TIMEOUT_EXPIRED => continue,
+            glow::WAIT_FAILED => {
+                error!("Failed to wait for stream buffer fence");
+                break;
+            }
+            _ => break,
+        }
+    }
+    context.delete_sync(fence);
+}
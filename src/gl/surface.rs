@@ -2,15 +2,24 @@ use cgmath::*;
 use glow::HasContext;
 
 #[cfg(target_arch = "wasm32")]
-use web_sys::HtmlCanvasElement;
+use web_sys::{window, HtmlCanvasElement};
 
 #[cfg(not(target_arch = "wasm32"))]
 use crate::glfw::*;
 #[cfg(not(target_arch = "wasm32"))]
+use image::DynamicImage;
+#[cfg(not(target_arch = "wasm32"))]
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::*;
 
 use super::context::*;
 use super::framebuffer::*;
+use super::readback::*;
 use super::rect::*;
 
 /// A trait for things that can be rendered to.
@@ -23,23 +32,51 @@ pub trait Surface {
     #[doc(hidden)]
     fn bind_read(&self, context: &GlContext);
 
+    /// An ID that identifies this surface's framebuffer.
+    #[doc(hidden)]
+    fn id(&self) -> FramebufferId;
+
     /// Clears one or more buffers.
     ///
     /// Example usage:
     /// ```
-    /// surface.clear(&context, &[ClearBuffer::Color([0.0, 0.0, 0.0, 0.0])]);
+    /// surface.clear(
+    ///     &context,
+    ///     &[ClearBuffer::Color([0.0, 0.0, 0.0, 0.0]), ClearBuffer::Depth(1.0)],
+    /// );
     /// ```
     fn clear(&self, context: &GlContext, buffers: &[ClearBuffer]) {
         assert!(!buffers.is_empty());
         self.bind(context);
+        // Clearing is limited by the scissor test, but it should always clear the whole surface.
+        context.set_scissor(None);
 
         let mut bits = 0;
         for buffer in buffers {
             bits |= buffer.as_gl();
 
-            if let Some(color) = buffer.color() {
-                unsafe {
-                    context.inner().clear_color(color[0], color[1], color[2], color[3]);
+            match *buffer {
+                ClearBuffer::Color(color) => {
+                    // Clearing also respects the color mask.
+                    context.set_color_mask([true; 4]);
+                    unsafe {
+                        context.inner().clear_color(color[0], color[1], color[2], color[3]);
+                    }
+                }
+                ClearBuffer::Depth(depth) => {
+                    // Clearing respects the depth mask, so make sure it's enabled. The next draw
+                    // will set it back to what its `PipelineState` needs.
+                    context.set_depth_write(true);
+                    unsafe {
+                        context.inner().clear_depth_f32(depth);
+                    }
+                }
+                ClearBuffer::Stencil(stencil) => {
+                    // Like the depth mask, the stencil write mask affects clearing.
+                    context.set_stencil_write_mask(!0);
+                    unsafe {
+                        context.inner().clear_stencil(stencil);
+                    }
                 }
             }
         }
@@ -49,6 +86,17 @@ pub trait Surface {
         }
     }
 
+    /// Starts reading the pixels in `rect`, in pixels with the origin at the bottom left, without
+    /// waiting for rendering to finish. Poll the returned `PixelReadback` for the result.
+    fn read_pixels_async(
+        &self,
+        context: &GlContext,
+        rect: Rect<i32>,
+        include_alpha: bool,
+    ) -> PixelReadback {
+        PixelReadback::new(context, self, rect, include_alpha)
+    }
+
     /// Returns the size of the surface.
     fn size(&self) -> Vector2<u32>;
 
@@ -81,21 +129,100 @@ impl ClearColor for [f32; 4] {
 #[derive(Copy, Clone)]
 pub enum ClearBuffer {
     Color([f32; 4]),
-    Depth,
+    /// Clears the depth buffer to the given value. 1.0 is the far plane, which is usually what's
+    /// wanted.
+    Depth(f32),
+    Stencil(i32),
 }
 
 impl ClearBuffer {
+    /// Clears the color buffer to the given non-premultiplied color. This is mainly useful for
+    /// transparent windows, where the alpha is visible.
+    pub fn premultiplied_color(color: [f32; 4]) -> Self {
+        let [r, g, b, a] = color;
+        ClearBuffer::Color([r * a, g * a, b * a, a])
+    }
+
     fn as_gl(&self) -> u32 {
         match self {
             ClearBuffer::Color(_) => glow::COLOR_BUFFER_BIT,
-            ClearBuffer::Depth => glow::DEPTH_BUFFER_BIT,
+            ClearBuffer::Depth(_) => glow::DEPTH_BUFFER_BIT,
+            ClearBuffer::Stencil(_) => glow::STENCIL_BUFFER_BIT,
+        }
+    }
+}
+
+/// Requests a pointer lock with `unadjustedMovement`, which turns off mouse acceleration. It
+/// isn't in web-sys yet, so it's called dynamically. Browsers that don't support options ignore
+/// them; ones that support options but not this one reject the request, in which case a plain
+/// pointer lock is requested instead.
+#[cfg(target_arch = "wasm32")]
+fn request_unadjusted_pointer_lock(canvas: &HtmlCanvasElement) {
+    use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+    let options = js_sys::Object::new();
+    js_sys::Reflect::set(&options, &"unadjustedMovement".into(), &JsValue::TRUE).unwrap();
+    let request = js_sys::Reflect::get(canvas, &"requestPointerLock".into())
+        .ok()
+        .and_then(|request| request.dyn_into::<js_sys::Function>().ok());
+    let result = match request.map(|request| request.call1(canvas, &options)) {
+        Some(Ok(result)) => result,
+        _ => {
+            canvas.request_pointer_lock();
+            return;
+        }
+    };
+    if let Some(promise) = result.dyn_ref::<js_sys::Promise>() {
+        let canvas = canvas.clone();
+        let fallback = Closure::once(move |_: JsValue| canvas.request_pointer_lock());
+        let _ = promise.catch(&fallback);
+        fallback.forget();
+    }
+}
+
+/// The shape of the mouse cursor while it's over the window; see `ScreenSurface::set_cursor_icon`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub enum CursorIcon {
+    #[default]
+    Arrow,
+    /// A pointing hand, for things that can be clicked, such as buttons.
+    Hand,
+    /// An I-beam, for text that can be selected or edited.
+    Text,
+    Crosshair,
+    /// A horizontal resize arrow, such as for the edge of a table column.
+    ResizeHorizontal,
+    /// A vertical resize arrow.
+    ResizeVertical,
+    /// No cursor at all. Unlike grabbing the cursor, this doesn't stop it from moving freely or
+    /// leaving the window.
+    Hidden,
+}
+
+impl CursorIcon {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn to_glfw(self) -> Option<glfw::StandardCursor> {
+        match self {
+            CursorIcon::Arrow => Some(glfw::StandardCursor::Arrow),
+            CursorIcon::Hand => Some(glfw::StandardCursor::Hand),
+            CursorIcon::Text => Some(glfw::StandardCursor::IBeam),
+            CursorIcon::Crosshair => Some(glfw::StandardCursor::Crosshair),
+            CursorIcon::ResizeHorizontal => Some(glfw::StandardCursor::HResize),
+            CursorIcon::ResizeVertical => Some(glfw::StandardCursor::VResize),
+            CursorIcon::Hidden => None,
         }
     }
 
-    fn color(&self) -> Option<[f32; 4]> {
+    #[cfg(target_arch = "wasm32")]
+    fn to_css(self) -> &'static str {
         match self {
-            ClearBuffer::Color(color) => Some(*color),
-            _ => None,
+            CursorIcon::Arrow => "default",
+            CursorIcon::Hand => "pointer",
+            CursorIcon::Text => "text",
+            CursorIcon::Crosshair => "crosshair",
+            CursorIcon::ResizeHorizontal => "ew-resize",
+            CursorIcon::ResizeVertical => "ns-resize",
+            CursorIcon::Hidden => "none",
         }
     }
 }
@@ -107,6 +234,7 @@ pub struct ScreenSurface {
     size: Vector2<u32>,
     canvas: HtmlCanvasElement,
     id: FramebufferId,
+    cursor_icon: CursorIcon,
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -117,7 +245,13 @@ impl ScreenSurface {
             Point2::from_vec(vec2(canvas.width() as i32, canvas.height() as i32)),
         );
         let size = vec2(canvas.width(), canvas.height());
-        ScreenSurface { viewport, size, canvas, id: FramebufferId::new() }
+        ScreenSurface {
+            viewport,
+            size,
+            canvas,
+            id: FramebufferId::new(),
+            cursor_icon: CursorIcon::Arrow,
+        }
     }
 
     /// Resizes the canvas.
@@ -130,8 +264,7 @@ impl ScreenSurface {
         );
         self.size = new_size;
         // Resizing requires that we also change the viewport to match
-        let cache = context.cache.borrow();
-        if cache.bound_framebuffer == Some(self.id) {
+        if context.cache.borrow().bound_framebuffer == Some(self.id) {
             context.viewport(&self.viewport);
         }
     }
@@ -140,11 +273,152 @@ impl ScreenSurface {
     pub fn canvas(&self) -> &HtmlCanvasElement {
         &self.canvas
     }
+
+    /// Sets the shape of the cursor while it's over the canvas, using the canvas's CSS `cursor`
+    /// style. It can be called every frame, such as with `GuiResult::cursor_icon`.
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        if icon != self.cursor_icon {
+            self.cursor_icon = icon;
+            self.canvas.style().set_property("cursor", icon.to_css()).unwrap();
+        }
+    }
+
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.cursor_icon
+    }
+
+    /// Returns the ratio between physical pixels and logical units (`devicePixelRatio`).
+    pub fn content_scale(&self) -> f32 {
+        window().unwrap().device_pixel_ratio() as f32
+    }
+
+    /// Returns the size of the surface in logical units, i.e. `size()` divided by the content
+    /// scale.
+    pub fn logical_size(&self) -> Vector2<f32> {
+        self.size.cast::<f32>().unwrap() / self.content_scale()
+    }
+
+    /// Requests or releases a pointer lock on the canvas. `Event::PointerLocked` or
+    /// `Event::PointerUnlocked` is sent when the lock actually changes. Browsers only grant the
+    /// lock in response to a user action such as a click.
+    ///
+    /// Unaccelerated movement is requested for `Event::RawMouseMotion` where the browser
+    /// supports it.
+    pub fn set_pointer_lock(&mut self, locked: bool) {
+        if locked {
+            request_unadjusted_pointer_lock(&self.canvas);
+        } else {
+            window().unwrap().document().unwrap().exit_pointer_lock();
+        }
+    }
+
+    /// Makes the canvas fullscreen, or exits fullscreen, using the Fullscreen API. The canvas is
+    /// resized automatically, and `Event::WindowResized` is sent. Like
+    /// `WindowMode::Fullscreen` on desktop, but browsers only allow entering fullscreen in
+    /// response to a user action such as a key press.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        if fullscreen {
+            if let Err(err) = self.canvas.request_fullscreen() {
+                log::warn!("Unable to enter fullscreen: {:?}", err);
+            }
+        } else if self.is_fullscreen() {
+            window().unwrap().document().unwrap().exit_fullscreen();
+        }
+    }
+
+    /// True if the canvas is currently fullscreen. This may be changed by the user as well, e.g.
+    /// by pressing Escape.
+    pub fn is_fullscreen(&self) -> bool {
+        window().unwrap().document().unwrap().fullscreen_element().is_some()
+    }
+
+    /// Returns the title of the page.
+    pub fn title(&self) -> String {
+        window().unwrap().document().unwrap().title()
+    }
+
+    /// Sets the title of the page.
+    pub fn set_title(&mut self, title: &str) {
+        window().unwrap().document().unwrap().set_title(title);
+    }
+
+    /// Sets the minimum and maximum size of the canvas, in CSS pixels, with its `min-width`,
+    /// `max-width`, `min-height` and `max-height` styles. `None` means there's no limit.
+    pub fn set_size_limits(&mut self, min: Option<Vector2<u32>>, max: Option<Vector2<u32>>) {
+        let style = self.canvas.style();
+        let to_css = |size: Option<u32>, default: &str| match size {
+            Some(size) => format!("{}px", size),
+            None => default.to_owned(),
+        };
+        style.set_property("min-width", &to_css(min.map(|min| min.x), "0")).unwrap();
+        style.set_property("min-height", &to_css(min.map(|min| min.y), "0")).unwrap();
+        style.set_property("max-width", &to_css(max.map(|max| max.x), "none")).unwrap();
+        style.set_property("max-height", &to_css(max.map(|max| max.y), "none")).unwrap();
+    }
+
+    /// Sets the opacity of the canvas, from 0 to 1, with its CSS `opacity` style.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.canvas.style().set_property("opacity", &opacity.to_string()).unwrap();
+    }
+
+    /// Returns the opacity set with `set_opacity`.
+    pub fn opacity(&self) -> f32 {
+        self.canvas.style().get_property_value("opacity").unwrap().parse().unwrap_or(1.0)
+    }
+
+    /// Does nothing; pages can't keep their window above others.
+    pub fn set_always_on_top(&mut self, _always_on_top: bool) {}
+
+    /// Always false; see `set_always_on_top`.
+    pub fn is_always_on_top(&self) -> bool {
+        false
+    }
+}
+
+/// A video mode supported by a monitor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VideoMode {
+    pub size: Vector2<u32>,
+    pub refresh_rate: u32,
+}
+
+/// A connected monitor.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct Monitor {
+    /// The index of the monitor, for use in `WindowMode::FullscreenOnMonitor`.
+    pub index: usize,
+    pub name: String,
+    /// The position of the monitor on the virtual screen, in screen coordinates.
+    pub position: Point2<i32>,
+    pub current_video_mode: VideoMode,
+    pub video_modes: Vec<VideoMode>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Monitor {
+    /// Returns all connected monitors. The primary monitor is always first.
+    pub fn all() -> Vec<Monitor> {
+        connected_monitors()
+    }
 }
 
 #[derive(Clone)]
 pub enum WindowMode {
+    /// Fullscreen on the primary monitor, using its current video mode.
     Fullscreen,
+    /// Fullscreen on the monitor with the given index (see `Monitor::all`). If `video_mode` is
+    /// `None`, the monitor's current video mode is used. Falls back to the primary monitor if
+    /// the monitor isn't connected.
+    FullscreenOnMonitor {
+        monitor: usize,
+        video_mode: Option<VideoMode>,
+    },
+    /// An undecorated window covering the work area of the monitor with the given index. Unlike
+    /// exclusive fullscreen, this doesn't change the video mode, so alt-tabbing is fast.
+    BorderlessFullscreen {
+        monitor: usize,
+    },
     Windowed(Vector2<u32>, String),
 }
 
@@ -152,11 +426,36 @@ impl WindowMode {
     pub fn is_windowed(&self) -> bool {
         match self {
             WindowMode::Windowed(_, _) => true,
-            WindowMode::Fullscreen => false,
+            WindowMode::Fullscreen
+            | WindowMode::FullscreenOnMonitor { .. }
+            | WindowMode::BorderlessFullscreen { .. } => false,
         }
     }
 }
 
+/// The position and size of a window, in screen coordinates.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub position: Point2<i32>,
+    pub size: Vector2<u32>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WindowGeometry {
+    /// Loads window geometry saved with `save`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Saves the window geometry as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = File::create(path)?;
+        Ok(serde_json::to_writer(file, self)?)
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 /// A surface that represents the screen/default framebuffer.
 pub struct ScreenSurface {
@@ -166,19 +465,27 @@ pub struct ScreenSurface {
     pub grab_cursor: bool,
     size: Vector2<u32>,
     id: FramebufferId,
+    title: String,
+    cursor_icon: CursorIcon,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 impl ScreenSurface {
     pub fn new(window: glfw::Window, window_mode: WindowMode, grab_cursor: bool) -> Self {
         let (window_width, window_height) = window.get_framebuffer_size();
+        let title = match window_mode {
+            WindowMode::Windowed(_, ref title) => title.clone(),
+            _ => String::new(),
+        };
         Self {
+            title,
             inner: window,
             viewport: Rect::new(Point2::origin(), point2(window_width, window_height)),
             window_mode,
             grab_cursor,
             size: vec2(window_width as u32, window_height as u32),
             id: FramebufferId::new(),
+            cursor_icon: CursorIcon::Arrow,
         }
     }
 
@@ -190,12 +497,139 @@ impl ScreenSurface {
         );
         self.size = new_size;
         // Resizing requires that we also change the viewport to match
-        let cache = context.cache.borrow();
-        if cache.bound_framebuffer == Some(self.id) {
+        if context.cache.borrow().bound_framebuffer == Some(self.id) {
             context.viewport(&self.viewport);
         }
     }
 
+    /// Sets the minimum and maximum size of the window's client area, in screen coordinates.
+    /// `None` means there's no limit. This only applies to windowed mode.
+    pub fn set_size_limits(&mut self, min: Option<Vector2<u32>>, max: Option<Vector2<u32>>) {
+        self.inner.set_size_limits(
+            min.map(|min| min.x),
+            min.map(|min| min.y),
+            max.map(|max| max.x),
+            max.map(|max| max.y),
+        );
+    }
+
+    /// Constrains the window's client area to the given aspect ratio (width, height) when it's
+    /// resized, or removes the constraint if `None`. This only applies to windowed mode.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: Option<(u32, u32)>) {
+        let (numer, denom) =
+            aspect_ratio.unwrap_or((glfw::ffi::DONT_CARE as u32, glfw::ffi::DONT_CARE as u32));
+        self.inner.set_aspect_ratio(numer, denom);
+    }
+
+    /// Sets the window's icon. Several sizes can be given, and the platform picks the closest
+    /// to the one it needs; 16x16, 32x32 and 48x48 are typical. An empty slice restores the
+    /// default icon. This does nothing on macOS, where the icon comes from the app bundle.
+    pub fn set_icon(&mut self, images: &[DynamicImage]) {
+        let images = images
+            .iter()
+            .map(|image| {
+                let image = image.to_rgba8();
+                glfw::PixelImage {
+                    width: image.width(),
+                    height: image.height(),
+                    // GLFW reads the pixels as bytes in RGBA order.
+                    pixels: image.pixels().map(|pixel| u32::from_ne_bytes(pixel.0)).collect(),
+                }
+            })
+            .collect();
+        self.inner.set_icon_from_pixels(images);
+    }
+
+    /// Keeps the window above other windows, or stops doing so.
+    pub fn set_always_on_top(&mut self, always_on_top: bool) {
+        self.inner.set_floating(always_on_top);
+    }
+
+    pub fn is_always_on_top(&self) -> bool {
+        self.inner.is_floating()
+    }
+
+    /// Sets the opacity of the whole window, including its decorations, from 0 to 1. This is
+    /// unrelated to `ContextOptions::transparent`, which makes the framebuffer's alpha channel
+    /// show through. Not every platform supports it.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.inner.set_opacity(opacity);
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.inner.get_opacity()
+    }
+
+    /// Returns the position of the window's client area, in screen coordinates.
+    pub fn position(&self) -> Point2<i32> {
+        let (x, y) = self.inner.get_pos();
+        point2(x, y)
+    }
+
+    /// Moves the window's client area to the given position, in screen coordinates.
+    pub fn set_position(&mut self, position: Point2<i32>) {
+        self.inner.set_pos(position.x, position.y);
+    }
+
+    /// Returns the window's current position and size, so it can be restored with
+    /// `set_geometry`, e.g. the next time the app runs.
+    pub fn geometry(&self) -> WindowGeometry {
+        let (width, height) = self.inner.get_size();
+        WindowGeometry { position: self.position(), size: vec2(width as u32, height as u32) }
+    }
+
+    /// Restores the window's position and size. This does nothing unless the window is in
+    /// windowed mode. The position is ignored if it isn't on any connected monitor.
+    pub fn set_geometry(&mut self, geometry: &WindowGeometry) {
+        if !self.window_mode.is_windowed() {
+            return;
+        }
+        self.inner.set_size(geometry.size.x as i32, geometry.size.y as i32);
+        let on_screen = Monitor::all().iter().any(|monitor| {
+            let size = monitor.current_video_mode.size.cast().unwrap();
+            Rect::new(monitor.position, monitor.position + size).contains_point(geometry.position)
+        });
+        if on_screen {
+            self.set_position(geometry.position);
+        }
+    }
+
+    /// Requests the user's attention, e.g. by flashing the window in the taskbar, without taking
+    /// focus. Useful to notify the user that a long-running operation finished.
+    pub fn request_attention(&mut self) {
+        self.inner.request_attention();
+    }
+
+    /// Brings the window to the front and gives it input focus.
+    pub fn focus(&mut self) {
+        self.inner.focus();
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.inner.is_focused()
+    }
+
+    pub fn minimize(&mut self) {
+        self.inner.iconify();
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.inner.is_iconified()
+    }
+
+    pub fn maximize(&mut self) {
+        self.inner.maximize();
+    }
+
+    pub fn is_maximized(&self) -> bool {
+        self.inner.is_maximized()
+    }
+
+    /// Restores the window after it was minimized or maximized.
+    pub fn restore(&mut self) {
+        self.inner.restore();
+    }
+
     pub fn close_window(&mut self) {
         self.inner.set_should_close(true);
     }
@@ -206,17 +640,79 @@ impl ScreenSurface {
 
     pub fn set_window_mode(&mut self, window_mode: WindowMode) {
         update_window_mode(&mut self.inner, &window_mode);
+        if let WindowMode::Windowed(_, ref title) = window_mode {
+            self.title = title.clone();
+        }
         self.window_mode = window_mode;
     }
 
+    /// Returns the ratio between physical pixels and logical units, e.g. 2.0 on a monitor with
+    /// 200% scaling.
+    pub fn content_scale(&self) -> f32 {
+        self.inner.get_content_scale().0
+    }
+
+    /// Returns the size of the surface in logical units, i.e. `size()` divided by the content
+    /// scale. `size()` is always the framebuffer size in physical pixels, which is also the
+    /// coordinate space used for mouse events.
+    pub fn logical_size(&self) -> Vector2<f32> {
+        self.size.cast::<f32>().unwrap() / self.content_scale()
+    }
+
+    /// Returns the title of the window.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Sets the title of the window. The title is kept when switching to a fullscreen mode.
+    /// Switching to `WindowMode::Windowed` replaces it with the title given there.
+    pub fn set_title(&mut self, title: &str) {
+        self.inner.set_title(title);
+        self.title = title.to_owned();
+        if let WindowMode::Windowed(_, ref mut mode_title) = self.window_mode {
+            *mode_title = title.to_owned();
+        }
+    }
+
+    /// Locks the cursor to the window and hides it, or releases it. This is the same as
+    /// `set_grab_cursor`; `Event::PointerLocked` or `Event::PointerUnlocked` is sent by the main
+    /// loop when the lock changes.
+    pub fn set_pointer_lock(&mut self, locked: bool) {
+        self.set_grab_cursor(locked);
+    }
+
     pub fn get_grab_cursor(&self) -> bool {
         self.grab_cursor
     }
 
     pub fn set_grab_cursor(&mut self, grab_cursor: bool) {
         self.grab_cursor = grab_cursor;
-        self.inner.set_cursor_mode(if grab_cursor {
+        self.update_cursor_mode();
+    }
+
+    /// Sets the shape of the cursor while it's over the window, using GLFW's standard cursors.
+    /// It can be called every frame, such as with `GuiResult::cursor_icon`. While the cursor is
+    /// grabbed it's hidden regardless, and the icon is shown once it's released.
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        if icon == self.cursor_icon {
+            return;
+        }
+        self.cursor_icon = icon;
+        if let Some(cursor) = icon.to_glfw() {
+            self.inner.set_cursor(Some(glfw::Cursor::standard(cursor)));
+        }
+        self.update_cursor_mode();
+    }
+
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.cursor_icon
+    }
+
+    fn update_cursor_mode(&mut self) {
+        self.inner.set_cursor_mode(if self.grab_cursor {
             glfw::CursorMode::Disabled
+        } else if self.cursor_icon == CursorIcon::Hidden {
+            glfw::CursorMode::Hidden
         } else {
             glfw::CursorMode::Normal
         });
@@ -227,30 +723,40 @@ impl ScreenSurface {
     pub fn take_screenshot(&self, context: &GlContext, path: Option<PathBuf>, include_alpha: bool) {
         crate::screenshot::take_screenshot(context, self, path, include_alpha);
     }
+
+    /// Like `take_screenshot`, but doesn't stall while waiting for rendering to finish. This
+    /// reads the back buffer, so it should be called after a frame is drawn but before it's
+    /// presented. Call `poll` on the result each frame until it's saved.
+    pub fn take_screenshot_async(
+        &self,
+        context: &GlContext,
+        path: Option<PathBuf>,
+        include_alpha: bool,
+    ) -> crate::screenshot::PendingScreenshot {
+        self.bind_read(context);
+        unsafe {
+            context.inner().read_buffer(glow::BACK);
+        }
+        let rect = Rect::new(Point2::origin(), Point2::from_vec(self.size().cast().unwrap()));
+        let readback = self.read_pixels_async(context, rect, include_alpha);
+        crate::screenshot::PendingScreenshot::new(readback, path)
+    }
 }
 
 impl Surface for ScreenSurface {
     #[doc(hidden)]
     fn bind(&self, context: &GlContext) {
-        let mut cache = context.cache.borrow_mut();
-        if cache.bound_framebuffer != Some(self.id) {
-            cache.bound_framebuffer = Some(self.id);
-            unsafe {
-                context.inner().bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
-            }
-            context.viewport(&self.viewport);
-        }
+        context.bind_draw_framebuffer(self.id, None, &self.viewport);
     }
 
     #[doc(hidden)]
     fn bind_read(&self, context: &GlContext) {
-        let mut cache = context.cache.borrow_mut();
-        if cache.bound_read_framebuffer != Some(self.id) {
-            cache.bound_read_framebuffer = Some(self.id);
-            unsafe {
-                context.inner().bind_framebuffer(glow::READ_FRAMEBUFFER, None);
-            }
-        }
+        context.bind_read_framebuffer(self.id, None);
+    }
+
+    #[doc(hidden)]
+    fn id(&self) -> FramebufferId {
+        self.id
     }
 
     fn size(&self) -> Vector2<u32> {
@@ -269,8 +775,7 @@ impl ScreenSurface {
 
     pub fn set_viewport(&mut self, context: &GlContext, viewport: Rect<i32>) {
         self.viewport = viewport;
-        let cache = context.cache.borrow();
-        if cache.bound_framebuffer == Some(self.id) {
+        if context.cache.borrow().bound_framebuffer == Some(self.id) {
             context.viewport(&self.viewport);
         }
     }
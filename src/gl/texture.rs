@@ -1,10 +1,14 @@
 use super::context::*;
+use super::rect::*;
+use super::resources::*;
+use super::surface::*;
 use cgmath::*;
 use glow::HasContext;
 #[cfg(not(target_arch = "wasm32"))]
 use image::DynamicImage;
 #[cfg(not(target_arch = "wasm32"))]
 use image::GenericImageView;
+use std::borrow::Cow;
 use uid::*;
 #[cfg(target_arch = "wasm32")]
 use web_sys::HtmlImageElement;
@@ -18,13 +22,18 @@ pub type TextureId = Id<TextureId_>;
 type GlTexture = <glow::Context as HasContext>::Texture;
 
 // TODO: TextureFormat should support other formats such as U8U8U8
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum TextureFormat {
     Red,
     RGB,
     RGBA,
     SRGB,
     SRGBA,
+    /// Half-precision floating-point RGBA, for HDR rendering. Data is given as 16-bit floats.
+    /// WebGL2 can only render to it with `EXT_color_buffer_float`. WebGL1 needs
+    /// `OES_texture_half_float`; without it, this falls back to 8 bits per channel. See
+    /// `GlContext::supports_half_float_textures`.
+    RGBA16F,
 }
 
 impl TextureFormat {
@@ -35,6 +44,7 @@ impl TextureFormat {
             TextureFormat::RGBA => glow::RGBA8,
             TextureFormat::SRGB => glow::SRGB8,
             TextureFormat::SRGBA => glow::SRGB8_ALPHA8,
+            TextureFormat::RGBA16F => glow::RGBA16F,
         }
     }
 
@@ -45,15 +55,90 @@ impl TextureFormat {
             TextureFormat::RGBA => glow::RGBA,
             TextureFormat::SRGB => glow::RGB,
             TextureFormat::SRGBA => glow::RGBA,
+            TextureFormat::RGBA16F => glow::RGBA,
+        }
+    }
+
+    /// The type of each component of the data used with this format.
+    pub fn to_gl_type(self) -> u32 {
+        match self {
+            TextureFormat::RGBA16F => glow::HALF_FLOAT,
+            _ => glow::UNSIGNED_BYTE,
         }
     }
 
     pub fn is_srgb(self) -> bool {
         matches!(self, TextureFormat::SRGB | TextureFormat::SRGBA)
     }
+
+    /// Returns the internal format and format to use with `context`. WebGL1 requires these to
+    /// match and doesn't support sRGB or single-channel formats, so similar formats are used
+    /// instead. `renderable` should be true if the texture will be attached to a framebuffer.
+    pub(crate) fn gl_formats(self, context: &GlContext, renderable: bool) -> (u32, u32) {
+        if !context.is_webgl1() {
+            return (self.to_gl_internal_format(), self.to_gl_format());
+        }
+        let format = match self {
+            TextureFormat::Red if renderable => glow::RGBA,
+            TextureFormat::Red => glow::LUMINANCE,
+            TextureFormat::RGB | TextureFormat::SRGB => glow::RGB,
+            TextureFormat::RGBA | TextureFormat::SRGBA => glow::RGBA,
+            TextureFormat::RGBA16F => glow::RGBA,
+        };
+        (format, format)
+    }
+
+    /// Returns the type of each component of the data to use with `context`. WebGL1 has its own
+    /// constant for half floats, and `RGBA16F` data is converted to bytes where half floats
+    /// aren't supported; see `gl_data`.
+    pub(crate) fn gl_type(self, context: &GlContext) -> u32 {
+        match self {
+            TextureFormat::RGBA16F if !context.supports_half_float_textures() => {
+                glow::UNSIGNED_BYTE
+            }
+            TextureFormat::RGBA16F if context.is_webgl1() => HALF_FLOAT_OES,
+            _ => self.to_gl_type(),
+        }
+    }
+
+    /// Converts data in this format to match `gl_type`.
+    pub(crate) fn gl_data<'a>(self, context: &GlContext, data: &'a [u8]) -> Cow<'a, [u8]> {
+        if self == TextureFormat::RGBA16F && !context.supports_half_float_textures() {
+            let halves = data.chunks_exact(2).map(|x| f16_to_f32(u16::from_ne_bytes([x[0], x[1]])));
+            Cow::Owned(halves.map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8).collect())
+        } else {
+            Cow::Borrowed(data)
+        }
+    }
+
+    /// The number of bytes used by each pixel of this format.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            TextureFormat::Red => 1,
+            TextureFormat::RGB | TextureFormat::SRGB => 3,
+            TextureFormat::RGBA | TextureFormat::SRGBA => 4,
+            TextureFormat::RGBA16F => 8,
+        }
+    }
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+// `HALF_FLOAT` from `OES_texture_half_float`, which has a different value than WebGL2's.
+const HALF_FLOAT_OES: u32 = 0x8D61;
+
+/// Converts an IEEE 754 half-precision float to an `f32`.
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
 pub enum MinFilter {
     Nearest,
     Linear,
@@ -87,7 +172,7 @@ impl MinFilter {
     }
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
 pub enum MagFilter {
     Nearest,
     Linear,
@@ -101,7 +186,7 @@ impl MagFilter {
         }
     }
 }
-#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
 pub enum WrapMode {
     ClampToEdge,
     Repeat,
@@ -116,6 +201,44 @@ impl WrapMode {
     }
 }
 
+/// Settings used when creating a texture from an image.
+///
+/// Mipmaps are generated if `min_filter` uses them.
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub struct TextureSettings {
+    /// Images only have 8 bits per channel, so `RGBA16F` is treated as `RGBA`.
+    pub format: TextureFormat,
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    pub wrap_mode: WrapMode,
+}
+
+impl Default for TextureSettings {
+    fn default() -> Self {
+        Self {
+            format: TextureFormat::SRGBA,
+            min_filter: MinFilter::Linear,
+            mag_filter: MagFilter::Linear,
+            wrap_mode: WrapMode::ClampToEdge,
+        }
+    }
+}
+
+/// Estimates the GPU memory used by a texture.
+fn estimate_texture_bytes(
+    size: Vector2<u32>,
+    format: TextureFormat,
+    min_filter: MinFilter,
+) -> usize {
+    let bytes = size.x as usize * size.y as usize * format.bytes_per_pixel();
+    // A full mipmap chain adds another third
+    if min_filter.has_mipmap() {
+        bytes * 4 / 3
+    } else {
+        bytes
+    }
+}
+
 /// A 2D texture.
 pub struct Texture2d {
     pub texture: GlTexture,
@@ -123,10 +246,13 @@ pub struct Texture2d {
     id: TextureId,
     pub context: GlContext,
     is_srgb: bool,
+    // The estimated GPU memory used by the texture
+    bytes: usize,
 }
 
 impl Drop for Texture2d {
     fn drop(&mut self) {
+        self.context.track_deleted(ResourceKind::Texture, self.bytes);
         unsafe {
             self.context.inner().delete_texture(self.texture);
         }
@@ -146,6 +272,7 @@ impl Texture2d {
         // TODO: add a method to generate mipmaps after data has been written to the texture
         assert!(!min_filter.has_mipmap());
 
+        let (internal_format, gl_format) = format.gl_formats(context, true);
         let texture = unsafe {
             let texture = context.inner().create_texture().unwrap();
             context.inner().bind_texture(glow::TEXTURE_2D, Some(texture));
@@ -153,24 +280,27 @@ impl Texture2d {
             context.inner().tex_image_2d(
                 glow::TEXTURE_2D,
                 0,
-                format.to_gl_internal_format() as i32,
+                internal_format as i32,
                 size.x as i32,
                 size.y as i32,
                 0,
-                format.to_gl_format(),
-                glow::UNSIGNED_BYTE,
+                gl_format,
+                format.gl_type(context),
                 None,
             );
             texture
         };
         Self::set_tex_parameters(context, min_filter, mag_filter, wrap_mode);
 
+        let bytes = estimate_texture_bytes(size, format, min_filter);
+        context.track_created(ResourceKind::Texture, bytes);
         Self {
             texture,
             size,
             id: TextureId::new(),
             context: context.clone(),
-            is_srgb: format.is_srgb(),
+            is_srgb: format.is_srgb() && !context.is_webgl1(),
+            bytes,
         }
     }
 
@@ -184,6 +314,7 @@ impl Texture2d {
         mag_filter: MagFilter,
         wrap_mode: WrapMode,
     ) -> Self {
+        let (internal_format, gl_format) = format.gl_formats(context, false);
         let texture = unsafe {
             let texture = context.inner().create_texture().unwrap();
             context.inner().bind_texture(glow::TEXTURE_2D, Some(texture));
@@ -191,8 +322,8 @@ impl Texture2d {
             context.inner().tex_image_2d_with_html_image(
                 glow::TEXTURE_2D,
                 0,
-                format.to_gl_internal_format() as i32,
-                format.to_gl_format(),
+                internal_format as i32,
+                gl_format,
                 glow::UNSIGNED_BYTE,
                 image,
             );
@@ -201,12 +332,16 @@ impl Texture2d {
 
         Self::set_tex_parameters(context, min_filter, mag_filter, wrap_mode);
 
+        let size = vec2(image.width(), image.height());
+        let bytes = estimate_texture_bytes(size, format, min_filter);
+        context.track_created(ResourceKind::Texture, bytes);
         Self {
             texture,
-            size: vec2(image.width(), image.height()),
+            size,
             id: TextureId::new(),
             context: context.clone(),
-            is_srgb: format.is_srgb(),
+            is_srgb: format.is_srgb() && !context.is_webgl1(),
+            bytes,
         }
     }
 
@@ -241,6 +376,7 @@ impl Texture2d {
         mag_filter: MagFilter,
         wrap_mode: WrapMode,
     ) -> Self {
+        let (internal_format, gl_format) = format.gl_formats(context, false);
         let texture = unsafe {
             let texture = context.inner().create_texture().unwrap();
             context.inner().bind_texture(glow::TEXTURE_2D, Some(texture));
@@ -248,25 +384,96 @@ impl Texture2d {
             context.inner().tex_image_2d(
                 glow::TEXTURE_2D,
                 0,
-                format.to_gl_internal_format() as i32,
+                internal_format as i32,
                 size.x as i32,
                 size.y as i32,
                 0,
-                format.to_gl_format(),
-                glow::UNSIGNED_BYTE,
-                Some(data),
+                gl_format,
+                format.gl_type(context),
+                Some(&format.gl_data(context, data)),
             );
             texture
         };
 
         Self::set_tex_parameters(context, min_filter, mag_filter, wrap_mode);
 
+        let bytes = estimate_texture_bytes(size, format, min_filter);
+        context.track_created(ResourceKind::Texture, bytes);
+        Self {
+            texture,
+            size,
+            id: TextureId::new(),
+            context: context.clone(),
+            is_srgb: format.is_srgb() && !context.is_webgl1(),
+            bytes,
+        }
+    }
+
+    /// Creates a `Texture2d` containing a copy of part of `surface`, such as the current frame.
+    /// `rect` is in pixels, with the origin at the bottom left.
+    ///
+    /// The surface must not be multisampled. If it uses an sRGB format, `format` must too.
+    pub fn from_surface(
+        context: &GlContext,
+        surface: &(impl Surface + ?Sized),
+        rect: Rect<i32>,
+        format: TextureFormat,
+    ) -> Self {
+        let size = rect.size().cast().unwrap();
+        surface.bind_read(context);
+        let texture = unsafe {
+            let texture = context.inner().create_texture().unwrap();
+            context.inner().bind_texture(glow::TEXTURE_2D, Some(texture));
+            context.cache.borrow_mut().clear_bound_textures();
+            context.inner().copy_tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                format.gl_formats(context, false).0,
+                rect.start.x,
+                rect.start.y,
+                size.x as i32,
+                size.y as i32,
+                0,
+            );
+            texture
+        };
+
+        Self::set_tex_parameters(
+            context,
+            MinFilter::Linear,
+            MagFilter::Linear,
+            WrapMode::ClampToEdge,
+        );
+
+        let bytes = estimate_texture_bytes(size, format, MinFilter::Linear);
+        context.track_created(ResourceKind::Texture, bytes);
         Self {
             texture,
             size,
             id: TextureId::new(),
             context: context.clone(),
-            is_srgb: format.is_srgb(),
+            is_srgb: format.is_srgb() && !context.is_webgl1(),
+            bytes,
+        }
+    }
+
+    /// Replaces the texture's contents with a copy of part of `surface`, starting at `pos` (in
+    /// pixels from the bottom left). This avoids reallocating the texture when capturing a
+    /// surface every frame. The same restrictions as `from_surface` apply.
+    pub fn copy_from_surface(&self, surface: &(impl Surface + ?Sized), pos: Point2<i32>) {
+        surface.bind_read(&self.context);
+        self.bind(0);
+        unsafe {
+            self.context.inner().copy_tex_sub_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                0,
+                0,
+                pos.x,
+                pos.y,
+                self.size.x as i32,
+                self.size.y as i32,
+            );
         }
     }
 
@@ -281,9 +488,9 @@ impl Texture2d {
                 0,
                 self.size.x as i32,
                 self.size.y as i32,
-                format.to_gl_format(),
-                glow::UNSIGNED_BYTE,
-                glow::PixelUnpackData::Slice(data),
+                format.gl_formats(&self.context, false).1,
+                format.gl_type(&self.context),
+                glow::PixelUnpackData::Slice(&format.gl_data(&self.context, data)),
             );
         }
     }
@@ -306,9 +513,9 @@ impl Texture2d {
                 yoffset,
                 width,
                 height,
-                format.to_gl_format(),
-                glow::UNSIGNED_BYTE,
-                glow::PixelUnpackData::Slice(data),
+                format.gl_formats(&self.context, false).1,
+                format.gl_type(&self.context),
+                glow::PixelUnpackData::Slice(&format.gl_data(&self.context, data)),
             );
         }
     }
@@ -352,9 +559,13 @@ impl Texture2d {
         if cache.bound_textures[texture_unit as usize] != Some((glow::TEXTURE_2D, self.id)) {
             cache.bound_textures[texture_unit as usize] = Some((glow::TEXTURE_2D, self.id));
             unsafe {
-                self.context.inner().active_texture(glow::TEXTURE0 + texture_unit);
+                if cache.active_texture != Some(texture_unit) {
+                    cache.active_texture = Some(texture_unit);
+                    self.context.inner().active_texture(glow::TEXTURE0 + texture_unit);
+                }
                 self.context.inner().bind_texture(glow::TEXTURE_2D, Some(self.texture));
             }
+            self.context.record_stats(|stats| stats.texture_binds += 1);
         }
     }
 
@@ -363,3 +574,20 @@ impl Texture2d {
         self.is_srgb
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(f16_to_f32(0x0000), 0.0);
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+}
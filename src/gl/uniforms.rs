@@ -1,7 +1,11 @@
+use bytemuck::{Pod, Zeroable};
 use glow::HasContext;
+use std::marker::PhantomData;
 
 use super::context::*;
+use super::mesh::*;
 use super::program::*;
+use super::resources::*;
 use super::texture::*;
 
 type GlUniformLocation = <glow::Context as HasContext>::UniformLocation;
@@ -81,6 +85,11 @@ impl TextureUniform {
         Self { loc: unsafe { context.inner().get_uniform_location(program, name).unwrap() } }
     }
 
+    /// Like `new`, but returns `None` if the program doesn't use the uniform.
+    pub fn try_new(name: &str, context: &GlContext, program: GlProgramId) -> Option<Self> {
+        unsafe { context.inner().get_uniform_location(program, name) }.map(|loc| Self { loc })
+    }
+
     // TODO: guarantee that the program is bound when this is called
     pub fn set(&self, context: &GlContext, texture: &Texture2d, texture_unit: u32) {
         unsafe {
@@ -153,6 +162,11 @@ impl Array2Uniform {
         Self { loc: unsafe { context.inner().get_uniform_location(program, name).unwrap() } }
     }
 
+    /// Like `new`, but returns `None` if the program doesn't use the uniform.
+    pub fn try_new(name: &str, context: &GlContext, program: GlProgramId) -> Option<Self> {
+        unsafe { context.inner().get_uniform_location(program, name) }.map(|loc| Self { loc })
+    }
+
     // TODO: guarantee that the program is bound when this is called
     pub fn set(&self, context: &GlContext, val: [f32; 2]) {
         unsafe {
@@ -187,6 +201,11 @@ impl Array4Uniform {
         Self { loc: unsafe { context.inner().get_uniform_location(program, name).unwrap() } }
     }
 
+    /// Like `new`, but returns `None` if the program doesn't use the uniform.
+    pub fn try_new(name: &str, context: &GlContext, program: GlProgramId) -> Option<Self> {
+        unsafe { context.inner().get_uniform_location(program, name) }.map(|loc| Self { loc })
+    }
+
     // TODO: guarantee that the program is bound when this is called
     pub fn set(&self, context: &GlContext, val: [f32; 4]) {
         unsafe {
@@ -212,6 +231,196 @@ impl F32Uniform {
     }
 }
 
+/// A uniform buffer object holding a `T`, which can be shared by many programs and draws instead
+/// of setting each uniform separately.
+///
+/// `T` must match the block's std140 layout: `vec3`s and arrays need padding, and each member
+/// must be aligned to its base alignment. The `Std140*` types help with this. For example,
+/// ```glsl
+/// uniform Camera {
+///     mat4 viewProjection;
+///     vec3 position;
+///     float time;
+/// };
+/// ```
+/// corresponds to
+/// ```
+/// #[repr(C)]
+/// #[derive(Copy, Clone)]
+/// struct Camera {
+///     view_projection: [[f32; 4]; 4],
+///     position: [f32; 3], // A `vec3` followed by a `float` is packed into 16 bytes
+///     time: f32,
+/// }
+/// ```
+///
+/// The block is bound to a binding point, and programs refer to it through a
+/// `UniformBlockBinding` in their `GlUniforms`. Uniform blocks aren't supported in WebGL1.
+pub struct UniformBlock<T: Pod> {
+    buffer: GlBuffer,
+    binding: u32,
+    context: GlContext,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Pod> Drop for UniformBlock<T> {
+    fn drop(&mut self) {
+        self.context.track_deleted(ResourceKind::Buffer, std::mem::size_of::<T>());
+        unsafe {
+            self.context.inner().delete_buffer(self.buffer);
+        }
+    }
+}
+
+impl<T: Pod> UniformBlock<T> {
+    /// Creates a uniform buffer containing `data` and binds it to the given binding point.
+    pub fn new(context: &GlContext, binding: u32, data: &T, usage: MeshUsage) -> Self {
+        assert!(!context.is_webgl1(), "Uniform blocks aren't supported in WebGL1");
+        let bytes = bytemuck::bytes_of(data);
+        context.record_stats(|stats| {
+            stats.buffer_uploads += 1;
+            stats.buffer_upload_bytes += bytes.len();
+        });
+        let buffer = unsafe {
+            let buffer = context.inner().create_buffer().unwrap();
+            context.inner().bind_buffer(glow::UNIFORM_BUFFER, Some(buffer));
+            context.inner().buffer_data_u8_slice(glow::UNIFORM_BUFFER, bytes, usage.as_gl());
+            buffer
+        };
+        context.track_created(ResourceKind::Buffer, bytes.len());
+        let res = Self { buffer, binding, context: context.clone(), phantom: PhantomData };
+        res.bind();
+        res
+    }
+
+    /// Binds the buffer to its binding point again. This is only needed if another buffer was
+    /// bound to the same binding point.
+    pub fn bind(&self) {
+        unsafe {
+            self.context.inner().bind_buffer_base(
+                glow::UNIFORM_BUFFER,
+                self.binding,
+                Some(self.buffer),
+            );
+        }
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    /// Replaces the contents of the buffer.
+    pub fn set(&self, data: &T) {
+        self.set_partial(0, data);
+    }
+
+    /// Replaces part of the buffer, starting at `offset` bytes from the start of `T`. This is
+    /// useful for updating one field of a large block.
+    pub fn set_partial<F: Pod>(&self, offset: usize, value: &F) {
+        let bytes = bytemuck::bytes_of(value);
+        assert!(
+            offset + bytes.len() <= std::mem::size_of::<T>(),
+            "Uniform block update out of range"
+        );
+        self.context.record_stats(|stats| {
+            stats.buffer_uploads += 1;
+            stats.buffer_upload_bytes += bytes.len();
+        });
+        unsafe {
+            self.context.inner().bind_buffer(glow::UNIFORM_BUFFER, Some(self.buffer));
+            self.context.inner().buffer_sub_data_u8_slice(
+                glow::UNIFORM_BUFFER,
+                offset as i32,
+                bytes,
+            );
+        }
+    }
+}
+
+/// Connects a program's named uniform block to a binding point, so it reads from the
+/// `UniformBlock` bound there. Unlike other uniforms, this only needs to be set up once, when
+/// the `GlUniforms` is created.
+pub struct UniformBlockBinding {
+    binding: u32,
+}
+
+impl UniformBlockBinding {
+    pub fn new(name: &str, context: &GlContext, program: GlProgramId, binding: u32) -> Self {
+        Self::try_new(name, context, program, binding)
+            .unwrap_or_else(|| panic!("Uniform block {:?} not found", name))
+    }
+
+    /// Like `new`, but returns `None` if the program doesn't use the uniform block.
+    pub fn try_new(
+        name: &str,
+        context: &GlContext,
+        program: GlProgramId,
+        binding: u32,
+    ) -> Option<Self> {
+        unsafe {
+            let index = context.inner().get_uniform_block_index(program, name)?;
+            context.inner().uniform_block_binding(program, index, binding);
+        }
+        Some(Self { binding })
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+}
+
+/// A `vec3` padded to 16 bytes, as in std140 arrays or when a `vec3` isn't followed by a
+/// `float`.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Std140Vec3 {
+    pub value: [f32; 3],
+    _padding: f32,
+}
+
+impl Std140Vec3 {
+    pub fn new(value: [f32; 3]) -> Self {
+        Self { value, _padding: 0.0 }
+    }
+}
+
+unsafe impl Zeroable for Std140Vec3 {}
+unsafe impl Pod for Std140Vec3 {}
+
+/// A `float` padded to 16 bytes, since each element of a std140 array is aligned to 16 bytes.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Std140Float {
+    pub value: f32,
+    _padding: [f32; 3],
+}
+
+impl Std140Float {
+    pub fn new(value: f32) -> Self {
+        Self { value, _padding: [0.0; 3] }
+    }
+}
+
+unsafe impl Zeroable for Std140Float {}
+unsafe impl Pod for Std140Float {}
+
+/// A `mat3`, whose columns are each padded to 16 bytes in std140.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Std140Mat3 {
+    pub columns: [Std140Vec3; 3],
+}
+
+impl Std140Mat3 {
+    pub fn new(matrix: cgmath::Matrix3<f32>) -> Self {
+        let column = |v: cgmath::Vector3<f32>| Std140Vec3::new(v.into());
+        Self { columns: [column(matrix.x), column(matrix.y), column(matrix.z)] }
+    }
+}
+
+unsafe impl Zeroable for Std140Mat3 {}
+unsafe impl Pod for Std140Mat3 {}
+
 /// An instance of `Uniforms` that contains no data.
 pub struct EmptyUniforms {}
 
@@ -1,89 +1,263 @@
 #![cfg(not(target_arch = "wasm32"))]
 
 use crate::gl::*;
+use cgmath::*;
 use glfw::Context as GlfwContext;
 use glfw::Glfw;
-use std::sync::mpsc::Receiver;
+use log::*;
+use std::cell::Cell;
 
-thread_local!(static GLOBAL_GLFW: Glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap());
+// Errors are logged rather than panicking so that context creation can fall back to other
+// OpenGL versions.
+thread_local!(static GLOBAL_GLFW: Glfw = glfw::init(glfw::LOG_ERRORS).unwrap());
+
+thread_local! {
+    // Set by GLFW's monitor callback, which the main loop sends as `Event::MonitorsChanged`
+    static MONITORS_CHANGED: Cell<bool> = const { Cell::new(false) };
+}
 
 pub fn get_glfw() -> Glfw {
     GLOBAL_GLFW.with(|glfw| glfw.clone())
 }
 
-fn set_window_hints(glfw: &mut Glfw, debug_context: bool) {
+fn set_window_hints(glfw: &mut Glfw, options: &ContextOptions, version: GlVersion) {
     glfw.window_hint(glfw::WindowHint::Visible(false));
-    glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(debug_context));
+    glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(options.debug_context));
+    glfw.window_hint(glfw::WindowHint::TransparentFramebuffer(options.transparent));
     glfw.window_hint(glfw::WindowHint::Samples(Some(4))); // TODO: make this configurable
     glfw.window_hint(glfw::WindowHint::Resizable(true));
 
-    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 2));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
-    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+    match version {
+        GlVersion::Gl(major, minor) => {
+            // Profiles are only supported by OpenGL 3.2 and later.
+            let core = (major, minor) >= (3, 2);
+            glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::OpenGl));
+            glfw.window_hint(glfw::WindowHint::ContextCreationApi(
+                glfw::ContextCreationApi::Native,
+            ));
+            glfw.window_hint(glfw::WindowHint::ContextVersion(major, minor));
+            glfw.window_hint(glfw::WindowHint::OpenGlProfile(if core {
+                glfw::OpenGlProfileHint::Core
+            } else {
+                glfw::OpenGlProfileHint::Any
+            }));
+            glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(core));
+        }
+        GlVersion::Gles(major, minor) => {
+            glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::OpenGlEs));
+            glfw.window_hint(glfw::WindowHint::ContextCreationApi(glfw::ContextCreationApi::Egl));
+            glfw.window_hint(glfw::WindowHint::ContextVersion(major, minor));
+            glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Any));
+            glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(false));
+        }
+    }
+}
+
+/// Calls `f` with the monitor with the given index, or the primary monitor if `index` is `None`
+/// or the monitor isn't connected.
+fn with_monitor<T>(
+    glfw: &mut Glfw,
+    index: Option<usize>,
+    f: impl FnOnce(&mut Glfw, &glfw::Monitor) -> T,
+) -> T {
+    glfw.with_connected_monitors(|glfw, monitors| {
+        let index = match index {
+            Some(index) if index < monitors.len() => index,
+            Some(index) => {
+                warn!("Monitor {} isn't connected; using the primary monitor", index);
+                0
+            }
+            None => 0,
+        };
+        let monitor: &glfw::Monitor = monitors.get(index).expect("Failed to find primary monitor.");
+        f(glfw, monitor)
+    })
+}
+
+impl From<glfw::VidMode> for VideoMode {
+    fn from(mode: glfw::VidMode) -> Self {
+        Self { size: vec2(mode.width, mode.height), refresh_rate: mode.refresh_rate }
+    }
+}
+
+pub fn connected_monitors() -> Vec<Monitor> {
+    get_glfw().with_connected_monitors(|_glfw, monitors| {
+        monitors
+            .iter()
+            .enumerate()
+            .map(|(index, monitor)| {
+                let (x, y) = monitor.get_pos();
+                Monitor {
+                    index,
+                    name: monitor.get_name().unwrap_or_default(),
+                    position: point2(x, y),
+                    current_video_mode: monitor
+                        .get_video_mode()
+                        .expect("Failed to get video mode.")
+                        .into(),
+                    video_modes: monitor.get_video_modes().into_iter().map(Into::into).collect(),
+                }
+            })
+            .collect()
+    })
+}
+
+fn monitor_callback(_monitor: glfw::Monitor, _event: glfw::MonitorEvent, _data: &()) {
+    MONITORS_CHANGED.with(|changed| changed.set(true));
+}
+
+/// Returns true if a monitor has been connected or disconnected since the last call.
+pub(crate) fn take_monitors_changed() -> bool {
+    MONITORS_CHANGED.with(|changed| changed.replace(false))
+}
+
+/// Returns the monitor index and video mode to use for the given window mode.
+fn fullscreen_params(window_mode: &WindowMode) -> (Option<usize>, Option<VideoMode>) {
+    match *window_mode {
+        WindowMode::FullscreenOnMonitor { monitor, video_mode } => (Some(monitor), video_mode),
+        WindowMode::BorderlessFullscreen { monitor } => (Some(monitor), None),
+        WindowMode::Fullscreen | WindowMode::Windowed(_, _) => (None, None),
+    }
+}
+
+/// Calls `create_window` with the window hints for each of the requested OpenGL versions until
+/// it succeeds.
+fn create_with_any_version<T>(
+    glfw: &mut Glfw,
+    options: &ContextOptions,
+    mut create_window: impl FnMut(&mut Glfw) -> Option<T>,
+) -> Result<T, &'static str> {
+    options
+        .gl_versions
+        .iter()
+        .find_map(|&version| {
+            set_window_hints(glfw, options, version);
+            let res = create_window(glfw);
+            match res {
+                Some(_) => info!("Created {:?} context", version),
+                None => warn!("Unable to create {:?} context", version),
+            }
+            res
+        })
+        .ok_or("Unable to create an OpenGL context with any of the requested versions")
+}
+
+/// Creates a window that's never shown, for a context that only renders offscreen. Its events
+/// are discarded.
+pub fn create_hidden_window(
+    glfw: &mut Glfw,
+    options: &ContextOptions,
+) -> Result<glfw::Window, &'static str> {
+    let (mut window, _events) = create_with_any_version(glfw, options, |glfw| {
+        glfw.create_window(1, 1, "", glfw::WindowMode::Windowed)
+    })?;
+    window.make_current();
+    Ok(window)
 }
 
 pub fn create_window_inner(
     glfw: &mut Glfw,
     window_mode: &WindowMode,
-    grab_cursor: bool,
-    debug_context: bool,
-) -> (glfw::Window, Receiver<(f64, glfw::WindowEvent)>) {
-    set_window_hints(glfw, debug_context);
-    glfw.with_primary_monitor(|glfw, m| {
-        let monitor = m.expect("Failed to find primary monitor.");
-        let mode = monitor.get_video_mode().expect("Failed to get video mode (1).");
-        let mut res = match *window_mode {
-            WindowMode::Fullscreen => glfw
-                .create_window(mode.width, mode.height, "", glfw::WindowMode::FullScreen(monitor))
-                .expect("Failed to create GLFW window."),
+    options: &ContextOptions,
+) -> Result<(glfw::Window, EventReceiver), &'static str> {
+    let (monitor_index, video_mode) = fullscreen_params(window_mode);
+    glfw.window_hint(glfw::WindowHint::RefreshRate(video_mode.map(|mode| mode.refresh_rate)));
+    glfw.window_hint(glfw::WindowHint::Decorated(!matches!(
+        window_mode,
+        WindowMode::BorderlessFullscreen { .. }
+    )));
+    with_monitor(glfw, monitor_index, |glfw, monitor| {
+        let mode: VideoMode =
+            monitor.get_video_mode().expect("Failed to get video mode (1).").into();
+        let create_window = |glfw: &mut Glfw| match *window_mode {
+            WindowMode::Fullscreen | WindowMode::FullscreenOnMonitor { .. } => {
+                let size = video_mode.unwrap_or(mode).size;
+                glfw.create_window(size.x, size.y, "", glfw::WindowMode::FullScreen(monitor))
+            }
+            WindowMode::BorderlessFullscreen { .. } => {
+                let (x, y, width, height) = monitor.get_workarea();
+                let (mut window, events) = glfw.create_window(
+                    width as u32,
+                    height as u32,
+                    "",
+                    glfw::WindowMode::Windowed,
+                )?;
+                window.set_pos(x, y);
+                Some((window, events))
+            }
             WindowMode::Windowed(size, ref title) => {
-                let (mut window, events) = glfw
-                    .create_window(size.x, size.y, title, glfw::WindowMode::Windowed)
-                    .expect("Failed to create GLFW window.");
-                let (posx, posy) = ((mode.width - size.x) / 2, (mode.height - size.y) / 2);
-                window.set_pos(posx as i32, posy as i32);
-                (window, events)
+                let (mut window, events) =
+                    glfw.create_window(size.x, size.y, title, glfw::WindowMode::Windowed)?;
+                let pos = (mode.size - size) / 2;
+                window.set_pos(pos.x as i32, pos.y as i32);
+                Some((window, events))
             }
         };
 
+        let mut res = create_with_any_version(glfw, options, create_window)?;
+
         let window = &mut res.0;
         if !window.is_visible() {
             window.show();
         }
         window.make_current();
-        // TODO: see if vsync can be made to work
+        // `start_main_loop_with_config` turns vsync on if it's asked to; see `FramePacing`.
         glfw.set_swap_interval(glfw::SwapInterval::None);
         window.set_all_polling(true);
-        window.set_cursor_mode(if grab_cursor {
+        window.set_cursor_mode(if options.grab_cursor {
             glfw::CursorMode::Disabled
         } else {
             glfw::CursorMode::Normal
         });
+        // Raw motion only applies while the cursor is grabbed, so it can be left on.
+        if glfw.supports_raw_motion() {
+            window.set_raw_mouse_motion(true);
+        }
+        glfw.set_monitor_callback(Some(glfw::Callback {
+            f: monitor_callback as fn(glfw::Monitor, glfw::MonitorEvent, &()),
+            data: (),
+        }));
 
-        res
+        Ok(res)
     })
 }
 
 pub fn update_window_mode(window: &mut glfw::Window, window_mode: &WindowMode) {
-    get_glfw().with_primary_monitor(|_glfw, m| {
-        let monitor = m.expect("Failed to find primary monitor.");
-        let mode = monitor.get_video_mode().expect("Failed to get video mode (2).");
+    let (monitor_index, video_mode) = fullscreen_params(window_mode);
+    with_monitor(&mut get_glfw(), monitor_index, |_glfw, monitor| {
+        let mode: VideoMode =
+            monitor.get_video_mode().expect("Failed to get video mode (2).").into();
+        window.set_decorated(!matches!(window_mode, WindowMode::BorderlessFullscreen { .. }));
         match *window_mode {
-            WindowMode::Fullscreen => window.set_monitor(
-                glfw::WindowMode::FullScreen(monitor),
-                0,
-                0,
-                mode.width,
-                mode.height,
-                None,
-            ),
-            // TODO: update the window title
-            WindowMode::Windowed(size, ref _title) => {
-                let (posx, posy) = ((mode.width - size.x) / 2, (mode.height - size.y) / 2);
+            WindowMode::Fullscreen | WindowMode::FullscreenOnMonitor { .. } => {
+                let mode = video_mode.unwrap_or(mode);
+                window.set_monitor(
+                    glfw::WindowMode::FullScreen(monitor),
+                    0,
+                    0,
+                    mode.size.x,
+                    mode.size.y,
+                    Some(mode.refresh_rate),
+                )
+            }
+            WindowMode::BorderlessFullscreen { .. } => {
+                let (x, y, width, height) = monitor.get_workarea();
+                window.set_monitor(
+                    glfw::WindowMode::Windowed,
+                    x,
+                    y,
+                    width as u32,
+                    height as u32,
+                    None,
+                );
+            }
+            WindowMode::Windowed(size, ref title) => {
+                window.set_title(title);
+                let pos = (mode.size - size) / 2;
                 window.set_monitor(
                     glfw::WindowMode::Windowed,
-                    posx as i32,
-                    posy as i32,
+                    pos.x as i32,
+                    pos.y as i32,
                     size.x,
                     size.y,
                     None,
@@ -1,4 +1,16 @@
+use crate::gl::*;
+use serde::de::DeserializeOwned;
+use std::any::{Any, TypeId};
+use std::cell::Cell;
 use std::collections::*;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::rc::Rc;
+
+#[cfg(feature = "gltf")]
+use super::gltf_scene::*;
+use super::obj::*;
 
 // TODO: see if these `cfg`s can be avoided/merged
 #[cfg(target_arch = "wasm32")]
@@ -6,13 +18,7 @@ use futures_util::future::*;
 #[cfg(target_arch = "wasm32")]
 use js_sys::*;
 #[cfg(target_arch = "wasm32")]
-use std::cell::RefCell;
-#[cfg(target_arch = "wasm32")]
-use std::mem;
-#[cfg(target_arch = "wasm32")]
-use std::ops::*;
-#[cfg(target_arch = "wasm32")]
-use std::rc::Rc;
+use log::*;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
@@ -22,42 +28,319 @@ use wasm_bindgen_futures::JsFuture;
 #[cfg(target_arch = "wasm32")]
 use web_sys::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+use cgmath::*;
+#[cfg(not(target_arch = "wasm32"))]
+use image::GenericImageView;
 #[cfg(not(target_arch = "wasm32"))]
 use std::fs::File;
 #[cfg(not(target_arch = "wasm32"))]
 use std::io::Read;
 
+#[cfg(target_arch = "wasm32")]
+type Image = HtmlImageElement;
+#[cfg(not(target_arch = "wasm32"))]
+type Image = image::DynamicImage;
+
+struct CacheEntry<T: ?Sized> {
+    value: Rc<T>,
+    /// The estimated size of the entry, in bytes.
+    size: usize,
+    last_used: Cell<u64>,
+}
+
+/// A map of reference-counted assets that keeps track of when each one was last used, so the
+/// least recently used ones can be evicted.
+struct AssetCache<K, T: ?Sized> {
+    entries: HashMap<K, CacheEntry<T>>,
+}
+
+impl<K: Hash + Eq + Clone, T: ?Sized> AssetCache<K, T> {
+    fn new() -> Self {
+        Self { entries: HashMap::new() }
+    }
+
+    fn get(&self, key: &K, time: u64) -> Option<&Rc<T>> {
+        let entry = self.entries.get(key)?;
+        entry.last_used.set(time);
+        Some(&entry.value)
+    }
+
+    fn insert(&mut self, key: K, value: Rc<T>, size: usize, time: u64) {
+        self.entries.insert(key, CacheEntry { value, size, last_used: Cell::new(time) });
+    }
+
+    fn remove(&mut self, key: &K) -> Option<Rc<T>> {
+        self.entries.remove(key).map(|entry| entry.value)
+    }
+
+    fn size(&self) -> usize {
+        self.entries.values().map(|entry| entry.size).sum()
+    }
+
+    /// Returns the key, last use time, and size of each entry that isn't used outside the cache.
+    fn unused_entries(&self) -> impl Iterator<Item = (K, u64, usize)> + '_ {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| Rc::strong_count(&entry.value) == 1)
+            .map(|(key, entry)| (key.clone(), entry.last_used.get(), entry.size))
+    }
+}
+
+/// The environment variable that overrides `AssetOptions::base_path` in native builds.
+#[cfg(not(target_arch = "wasm32"))]
+pub const ASSET_PATH_ENV_VAR: &str = "GL_WRAPPER_ASSET_PATH";
+
+/// Options for loading assets.
+#[derive(Debug, Clone)]
+pub struct AssetOptions {
+    /// A prefix prepended to every asset identifier to get its URL or file path, e.g.
+    /// `"/static/"`, a CDN URL, or a local directory. Assets are still looked up by their
+    /// identifiers, not the full URL.
+    ///
+    /// In native builds, this is overridden by the `GL_WRAPPER_ASSET_PATH` environment variable
+    /// if it's set.
+    pub base_path: String,
+    /// The number of times to retry a failed fetch before giving up. Only used on wasm.
+    pub retries: u32,
+    /// The delay before the first retry, in milliseconds. The delay doubles after each retry.
+    pub retry_delay_ms: u32,
+}
+
+impl Default for AssetOptions {
+    fn default() -> Self {
+        Self { base_path: String::new(), retries: 3, retry_delay_ms: 250 }
+    }
+}
+
+impl AssetOptions {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn with_env_override(mut self) -> Self {
+        if let Ok(base_path) = std::env::var(ASSET_PATH_ENV_VAR) {
+            self.base_path = base_path;
+        }
+        self
+    }
+
+    /// Returns the URL or file path of the asset with the given identifier.
+    pub fn resolve(&self, id: &str) -> String {
+        if self.base_path.is_empty() {
+            id.to_owned()
+        } else {
+            format!("{}/{}", self.base_path.trim_end_matches('/'), id.trim_start_matches('/'))
+        }
+    }
+}
+
+/// An error that occurred while loading an asset.
+#[derive(Debug, Clone)]
+pub enum AssetError {
+    /// The server responded with a non-2xx status code.
+    Http { url: String, status: u16 },
+    /// The request failed without a response, e.g. because the network is unavailable.
+    Network { url: String, message: String },
+    /// The image couldn't be loaded or decoded.
+    Image { url: String },
+    /// The asset hasn't been loaded.
+    NotLoaded { url: String },
+    /// There's no `AssetLoader` registered for the asset's extension and the requested type.
+    NoLoader { url: String },
+    /// An `AssetLoader` failed to decode the asset.
+    Decode { url: String, message: String },
+    /// The file couldn't be read. Only used in native builds.
+    Io { url: String, message: String },
+}
+
+impl AssetError {
+    /// Whether the request that caused this error may succeed if it's retried.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AssetError::Http { status, .. } => *status == 408 || *status == 429 || *status >= 500,
+            AssetError::Network { .. } => true,
+            AssetError::Image { .. } => true,
+            AssetError::NotLoaded { .. } => false,
+            AssetError::NoLoader { .. } => false,
+            AssetError::Decode { .. } => false,
+            AssetError::Io { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Http { url, status } => {
+                write!(f, "Unable to load asset {:?}: HTTP status {}", url, status)
+            }
+            AssetError::Network { url, message } => {
+                write!(f, "Unable to load asset {:?}: {}", url, message)
+            }
+            AssetError::Image { url } => write!(f, "Unable to load image: {:?}", url),
+            AssetError::NotLoaded { url } => write!(f, "Asset {:?} hasn't been loaded", url),
+            AssetError::NoLoader { url } => write!(f, "No asset loader found for {:?}", url),
+            AssetError::Decode { url, message } => {
+                write!(f, "Unable to decode asset {:?}: {}", url, message)
+            }
+            AssetError::Io { url, message } => {
+                write!(f, "Unable to read asset {:?}: {}", url, message)
+            }
+        }
+    }
+}
+
+impl Error for AssetError {}
+
+/// Decodes a loaded asset into a typed value. Loaders are registered with
+/// `Assets::register_loader`, and used by `Assets::get_typed`.
+pub trait AssetLoader {
+    type Asset: 'static;
+
+    /// The file extensions this loader handles, without the leading dot, e.g. `["gltf", "glb"]`.
+    /// If this is empty, the loader is used for any extension.
+    fn extensions(&self) -> &[&str];
+
+    /// Decodes the asset loaded from `url`.
+    fn load(&self, url: &str, bytes: &[u8]) -> Result<Self::Asset, AssetError>;
+}
+
+/// A type-erased `AssetLoader`.
+trait DynAssetLoader {
+    fn asset_type(&self) -> TypeId;
+    fn handles_extension(&self, extension: &str) -> bool;
+    fn load(&self, url: &str, bytes: &[u8]) -> Result<Rc<dyn Any>, AssetError>;
+}
+
+impl<L: AssetLoader> DynAssetLoader for L {
+    fn asset_type(&self) -> TypeId {
+        TypeId::of::<L::Asset>()
+    }
+
+    fn handles_extension(&self, extension: &str) -> bool {
+        let extensions = self.extensions();
+        extensions.is_empty() || extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extension))
+    }
+
+    fn load(&self, url: &str, bytes: &[u8]) -> Result<Rc<dyn Any>, AssetError> {
+        Ok(Rc::new(AssetLoader::load(self, url, bytes)?))
+    }
+}
+
+/// Resolves `path`, which is relative to the file at `url`, into an asset identifier.
+pub(crate) fn relative_url(url: &str, path: &str) -> String {
+    match url.rfind('/') {
+        Some(i) => format!("{}/{}", &url[..i], path),
+        None => path.to_owned(),
+    }
+}
+
+/// Returns the extension of the file at the given URL, ignoring any query string or fragment.
+fn url_extension(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap();
+    let file_name = path.rsplit('/').next().unwrap();
+    match file_name.rfind('.') {
+        Some(i) => &file_name[i + 1..],
+        None => "",
+    }
+}
+
+enum CacheKey {
+    Asset(String),
+    Image(String),
+    Texture((String, TextureSettings)),
+    Typed((String, TypeId)),
+}
+
 /// Stores assets that have been loaded. Currently, a URL can be loaded as a `Vec<u8>` or
-/// an `HtmlImageElement`/`DynamicImage` (depending on platform).
+/// an `HtmlImageElement`/`DynamicImage` (depending on platform). Images can also be turned
+/// into `Texture2d`s with `get_texture`.
+///
+/// Assets are identified by the URLs they were loaded with, relative to the base path in the
+/// `AssetOptions`.
+///
+/// This is intended to be long-lived. Assets are reference-counted, so the `get_shared` family
+/// of methods return handles that share the loaded data rather than copying it. If a memory
+/// budget is set with `set_memory_budget`, `evict_unused` frees the least recently used assets
+/// that aren't referenced outside of `Assets` until the budget is met.
 pub struct Assets {
-    assets: HashMap<String, Vec<u8>>,
-    #[cfg(target_arch = "wasm32")]
-    images: HashMap<String, HtmlImageElement>,
-    #[cfg(not(target_arch = "wasm32"))]
-    images: HashMap<String, image::DynamicImage>,
+    assets: AssetCache<String, Vec<u8>>,
+    images: AssetCache<String, Image>,
+    textures: AssetCache<(String, TextureSettings), Texture2d>,
+    typed: AssetCache<(String, TypeId), dyn Any>,
+    loaders: Vec<Box<dyn DynAssetLoader>>,
+    memory_budget: Option<usize>,
+    time: Cell<u64>,
+    options: AssetOptions,
 }
 
 impl Assets {
+    fn from_loaded(
+        options: AssetOptions,
+        assets: HashMap<String, Vec<u8>>,
+        images: HashMap<String, Image>,
+    ) -> Self {
+        let mut res = Self {
+            options,
+            assets: AssetCache::new(),
+            images: AssetCache::new(),
+            textures: AssetCache::new(),
+            typed: AssetCache::new(),
+            loaders: vec![],
+            memory_budget: None,
+            time: Cell::new(0),
+        };
+        for (url, asset) in assets {
+            let size = asset.len();
+            res.assets.insert(url, Rc::new(asset), size, 0);
+        }
+        for (url, image) in images {
+            let size = image_size(&image);
+            res.images.insert(url, Rc::new(image), size, 0);
+        }
+        res
+    }
+
+    /// Returns a new timestamp for the least-recently-used tracking.
+    fn tick(&self) -> u64 {
+        let time = self.time.get() + 1;
+        self.time.set(time);
+        time
+    }
+
+    /// Returns the options the assets were loaded with.
+    pub fn options(&self) -> &AssetOptions {
+        &self.options
+    }
+
     // TODO: make this version async
     #[cfg(not(target_arch = "wasm32"))]
     pub fn load(asset_paths: Vec<String>, image_paths: Vec<String>) -> Self {
-        Self {
-            assets: asset_paths
-                .into_iter()
-                .map(|asset_path| {
-                    let mut asset = vec![];
-                    File::open(&asset_path).unwrap().read_to_end(&mut asset).unwrap();
-                    (asset_path, asset)
-                })
-                .collect(),
-            images: image_paths
-                .into_iter()
-                .map(|image_path| {
-                    let image = image::open(&image_path).unwrap();
-                    (image_path, image)
-                })
-                .collect(),
-        }
+        Self::load_with_options(AssetOptions::default(), asset_paths, image_paths)
+    }
+
+    /// Like `load`, but uses the given options.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_with_options(
+        options: AssetOptions,
+        asset_paths: Vec<String>,
+        image_paths: Vec<String>,
+    ) -> Self {
+        let options = options.with_env_override();
+        let assets = asset_paths
+            .into_iter()
+            .map(|asset_path| {
+                let mut asset = vec![];
+                File::open(options.resolve(&asset_path)).unwrap().read_to_end(&mut asset).unwrap();
+                (asset_path, asset)
+            })
+            .collect();
+        let images = image_paths
+            .into_iter()
+            .map(|image_path| {
+                let image = image::open(options.resolve(&image_path)).unwrap();
+                (image_path, image)
+            })
+            .collect();
+        Self::from_loaded(options, assets, images)
     }
 
     /// Asynchronously loads one or more assets from URLs.
@@ -71,130 +354,627 @@ impl Assets {
     /// Panics if any asset can't be loaded.
     #[cfg(target_arch = "wasm32")]
     pub async fn load(asset_urls: Vec<String>, image_urls: Vec<String>) -> Self {
-        let loaded_assets: Rc<RefCell<HashMap<String, Vec<u8>>>> =
-            Rc::new(RefCell::new(Default::default()));
-        let loaded_images: Rc<RefCell<HashMap<String, HtmlImageElement>>> =
-            Rc::new(RefCell::new(Default::default()));
-
-        let loaded_assets2 = loaded_assets.clone();
-        let loaded_images2 = loaded_images.clone();
-
-        let mut futures_to_block_on = vec![];
-
-        for asset_url in asset_urls {
-            let loaded_assets = loaded_assets.clone();
-            let future = async move {
-                let asset_url2 = asset_url.clone();
+        Self::load_with_options(AssetOptions::default(), asset_urls, image_urls).await
+    }
 
-                let mut request_init = RequestInit::new();
-                request_init.method("GET");
-                request_init.mode(RequestMode::Cors);
+    /// Like `load`, but uses the given options.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn load_with_options(
+        options: AssetOptions,
+        asset_urls: Vec<String>,
+        image_urls: Vec<String>,
+    ) -> Self {
+        Self::try_load_with_options(options, asset_urls, image_urls)
+            .await
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
 
-                let request = Request::new_with_str_and_init(&asset_url, &request_init).unwrap();
-                let request_promise = window().unwrap().fetch_with_request(&request);
+    /// Like `load_with_options`, but returns an error instead of panicking if an asset can't
+    /// be loaded. Failed fetches are retried according to the options first.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn try_load_with_options(
+        options: AssetOptions,
+        asset_urls: Vec<String>,
+        image_urls: Vec<String>,
+    ) -> Result<Self, AssetError> {
+        let asset_futures = asset_urls.into_iter().map(|asset_url| {
+            let options = &options;
+            async move {
+                let asset =
+                    with_retries(options, || fetch_asset(options.resolve(&asset_url))).await?;
+                Ok((asset_url, asset))
+            }
+        });
+        let image_futures = image_urls.into_iter().map(|image_url| {
+            let options = &options;
+            async move {
+                let image =
+                    with_retries(options, || fetch_image(options.resolve(&image_url))).await?;
+                Ok((image_url, image))
+            }
+        });
 
-                let response = JsFuture::from(request_promise).await.unwrap();
-                let response: Response = response.dyn_into().unwrap();
-                if !response.ok() {
-                    panic!("Unable to load asset: {:?}", asset_url2);
-                }
-                let array_buffer = JsFuture::from(response.array_buffer().unwrap()).await.unwrap();
-                let array_buffer: ArrayBuffer = array_buffer.into();
-                let array: Uint8Array = Uint8Array::new(&array_buffer);
-                let mut dst = vec![0; array_buffer.byte_length() as usize];
-                array.copy_to(&mut dst);
-                loaded_assets.borrow_mut().insert(asset_url.clone(), dst);
-            };
-            futures_to_block_on.push(Either::Left(future));
-        }
-
-        for image_url in image_urls {
-            let loaded_images = loaded_images.clone();
-            let future = async move {
-                let image_element = window()
-                    .unwrap()
-                    .document()
-                    .unwrap()
-                    .create_element("img")
-                    .unwrap()
-                    .dyn_into::<HtmlImageElement>()
-                    .unwrap();
-
-                let promise = Promise::new(&mut |resolve, _reject| {
-                    let image_url2 = image_url.clone();
-                    let image_url3 = image_url.clone();
-                    let image_element2 = image_element.clone();
-                    let loaded_images = loaded_images.clone();
-                    let onload_handler = Rc::new(RefCell::new(None));
-                    let onload_handler2 = onload_handler.clone();
-                    *onload_handler.borrow_mut() = Some(Closure::wrap(Box::new(move || {
-                        loaded_images
-                            .borrow_mut()
-                            .insert(image_url2.clone(), image_element2.clone());
-                        resolve.call0(&resolve).unwrap();
-                        onload_handler2.borrow_mut().take();
-                    })
-                        as Box<dyn FnMut()>));
-                    image_element.set_onload(Some(
-                        onload_handler.borrow().as_ref().unwrap().as_ref().unchecked_ref(),
-                    ));
-
-                    let onerror_handler = Rc::new(RefCell::new(None));
-                    let onerror_handler2 = onerror_handler.clone();
-                    *onerror_handler.borrow_mut() = Some(Closure::wrap(Box::new(move || {
-                        onerror_handler2.borrow_mut().take();
-                        panic!("Unable to load image: {:?}", image_url3);
-                        // TODO: reject here instead of panicking?
-                    })
-                        as Box<dyn FnMut()>));
-                    image_element.set_onerror(Some(
-                        onerror_handler.borrow().as_ref().unwrap().as_ref().unchecked_ref(),
-                    ));
-                });
+        let (assets, images) = join(join_all(asset_futures), join_all(image_futures)).await;
+        let assets = assets.into_iter().collect::<Result<HashMap<_, _>, AssetError>>()?;
+        let images = images.into_iter().collect::<Result<HashMap<_, _>, AssetError>>()?;
+        Ok(Assets::from_loaded(options, assets, images))
+    }
 
-                image_element.set_src(&image_url);
+    /// Returns a reference to the given asset.
+    pub fn get(&self, url: &str) -> Option<&[u8]> {
+        self.assets.get(&url.to_owned(), self.tick()).map(|x| x.as_slice())
+    }
 
-                JsFuture::from(promise).await.unwrap();
-            };
-            futures_to_block_on.push(Either::Right(future));
-        }
+    /// Returns a handle to the given asset, which shares the loaded data.
+    pub fn get_shared(&self, url: &str) -> Option<Rc<Vec<u8>>> {
+        self.assets.get(&url.to_owned(), self.tick()).cloned()
+    }
 
-        join_all(futures_to_block_on).await;
+    /// Parses the given asset as JSON.
+    pub fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, AssetError> {
+        let bytes = self.get(url).ok_or_else(|| AssetError::NotLoaded { url: url.to_owned() })?;
+        serde_json::from_slice(bytes)
+            .map_err(|err| AssetError::Decode { url: url.to_owned(), message: err.to_string() })
+    }
 
-        let assets: HashMap<String, Vec<u8>> =
-            mem::replace(&mut loaded_assets2.borrow_mut(), Default::default());
-        let images: HashMap<String, HtmlImageElement> =
-            mem::replace(&mut loaded_images2.borrow_mut(), Default::default());
-        Assets { assets, images }
+    /// Parses the given asset as TOML.
+    pub fn get_toml<T: DeserializeOwned>(&self, url: &str) -> Result<T, AssetError> {
+        let bytes = self.get(url).ok_or_else(|| AssetError::NotLoaded { url: url.to_owned() })?;
+        toml::from_slice(bytes)
+            .map_err(|err| AssetError::Decode { url: url.to_owned(), message: err.to_string() })
     }
 
-    /// Returns a reference to the given asset.
-    pub fn get(&self, url: &str) -> Option<&[u8]> {
-        self.assets.get(url).map(|x| x.as_slice())
+    /// Returns a handle to the given asset, loading it from disk if it isn't already loaded
+    /// (for instance, because it was evicted).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_or_load(&mut self, path: &str) -> std::io::Result<Rc<Vec<u8>>> {
+        if let Some(asset) = self.get_shared(path) {
+            return Ok(asset);
+        }
+        let mut asset = vec![];
+        File::open(self.options.resolve(path))?.read_to_end(&mut asset)?;
+        let size = asset.len();
+        let asset = Rc::new(asset);
+        self.assets.insert(path.to_owned(), asset.clone(), size, self.tick());
+        Ok(asset)
     }
 
     /// Removes the given asset and returns it. If an asset is only needed in one place, this may
     /// reduce the number of required clones.
     pub fn remove(&mut self, url: &str) -> Option<Vec<u8>> {
-        self.assets.remove(url)
+        let asset = self.assets.remove(&url.to_owned())?;
+        Some(Rc::try_unwrap(asset).unwrap_or_else(|asset| (*asset).clone()))
     }
 
     /// Returns the given image.
     #[cfg(target_arch = "wasm32")]
     pub fn get_image(&self, url: &str) -> Option<&HtmlImageElement> {
-        self.images.get(url)
+        self.images.get(&url.to_owned(), self.tick()).map(|x| &**x)
     }
 
     /// Returns the given image.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn get_image(&self, url: &str) -> Option<&image::DynamicImage> {
-        self.images.get(url)
+        self.images.get(&url.to_owned(), self.tick()).map(|x| &**x)
+    }
+
+    /// Returns a handle to the given image, which shares the loaded data.
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_image_shared(&self, url: &str) -> Option<Rc<HtmlImageElement>> {
+        self.images.get(&url.to_owned(), self.tick()).cloned()
+    }
+
+    /// Returns a handle to the given image, which shares the loaded data.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_image_shared(&self, url: &str) -> Option<Rc<image::DynamicImage>> {
+        self.images.get(&url.to_owned(), self.tick()).cloned()
+    }
+
+    /// Returns a handle to the given image, loading it from disk if it isn't already loaded
+    /// (for instance, because it was evicted).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_or_load_image(&mut self, path: &str) -> image::ImageResult<Rc<image::DynamicImage>> {
+        if let Some(image) = self.get_image_shared(path) {
+            return Ok(image);
+        }
+        let image = image::open(self.options.resolve(path))?;
+        let size = image_size(&image);
+        let image = Rc::new(image);
+        self.images.insert(path.to_owned(), image.clone(), size, self.tick());
+        Ok(image)
     }
 
     /// Removes the given image and returns it. If an asset is only needed in one place, this may
     /// reduce the number of required clones.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn remove_image(&mut self, url: &str) -> Option<image::DynamicImage> {
-        self.images.remove(url)
+        let image = self.images.remove(&url.to_owned())?;
+        Some(Rc::try_unwrap(image).unwrap_or_else(|image| (*image).clone()))
+    }
+
+    /// Returns a `Texture2d` created from the given image. The image must have been loaded as
+    /// an image, not as a regular asset.
+    ///
+    /// Textures are cached, so calling this again with the same URL and settings returns the
+    /// same texture.
+    pub fn get_texture(
+        &mut self,
+        context: &GlContext,
+        url: &str,
+        settings: TextureSettings,
+    ) -> Option<Rc<Texture2d>> {
+        let key = (url.to_owned(), settings);
+        if let Some(texture) = self.textures.get(&key, self.tick()) {
+            return Some(texture.clone());
+        }
+        let image = self.images.get(&url.to_owned(), self.tick())?;
+        let texture = texture_from_image(context, image, settings);
+        let size =
+            texture.size.x as usize * texture.size.y as usize * settings.format.bytes_per_pixel();
+        let texture = Rc::new(texture);
+        self.textures.insert(key, texture.clone(), size, self.tick());
+        Some(texture)
+    }
+
+    /// Registers a loader used by `get_typed`. Loaders registered later take priority over
+    /// earlier ones.
+    pub fn register_loader(&mut self, loader: impl AssetLoader + 'static) {
+        self.loaders.push(Box::new(loader));
+    }
+
+    /// Returns the given asset decoded as a `T`, using the most recently registered loader that
+    /// produces a `T` and handles the asset's extension. The asset must have been loaded as a
+    /// regular asset.
+    ///
+    /// Decoded assets are cached, so calling this again with the same URL and type returns the
+    /// same value.
+    pub fn get_typed<T: 'static>(&mut self, url: &str) -> Result<Rc<T>, AssetError> {
+        let key = (url.to_owned(), TypeId::of::<T>());
+        let asset = match self.typed.get(&key, self.tick()) {
+            Some(asset) => asset.clone(),
+            None => {
+                let bytes = self
+                    .get_shared(url)
+                    .ok_or_else(|| AssetError::NotLoaded { url: url.to_owned() })?;
+                let extension = url_extension(url);
+                let loader = self
+                    .loaders
+                    .iter()
+                    .rev()
+                    .find(|loader| {
+                        loader.asset_type() == TypeId::of::<T>()
+                            && loader.handles_extension(extension)
+                    })
+                    .ok_or_else(|| AssetError::NoLoader { url: url.to_owned() })?;
+                let asset = loader.load(url, &bytes)?;
+                // The decoded size isn't known, so this uses the size of the encoded asset as an
+                // estimate.
+                self.typed.insert(key, asset.clone(), bytes.len(), self.tick());
+                asset
+            }
+        };
+        Ok(asset.downcast().unwrap())
+    }
+
+    /// Returns the OBJ model at `url`, with the materials from the MTL files it references.
+    /// Texture paths in the materials are turned into asset identifiers, which can be passed to
+    /// `get_texture`.
+    ///
+    /// The OBJ and MTL files must have been loaded as regular assets; `load_obj` loads them
+    /// along with their textures.
+    pub fn get_obj(&self, url: &str) -> Result<ObjModel, AssetError> {
+        let mut model = self.parse_obj(url)?;
+        for library in &model.material_libraries {
+            let library_url = relative_url(url, library);
+            let bytes = self
+                .get(&library_url)
+                .ok_or_else(|| AssetError::NotLoaded { url: library_url.clone() })?;
+            let source = std::str::from_utf8(bytes).map_err(|err| AssetError::Decode {
+                url: library_url.clone(),
+                message: err.to_string(),
+            })?;
+            let materials = ObjMaterial::parse_mtl(source)
+                .map_err(|message| AssetError::Decode { url: library_url.clone(), message })?;
+            for mut material in materials {
+                for texture in [
+                    &mut material.diffuse_texture,
+                    &mut material.specular_texture,
+                    &mut material.normal_texture,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    *texture = relative_url(&library_url, texture);
+                }
+                model.materials.push(material);
+            }
+        }
+        Ok(model)
+    }
+
+    /// Parses the OBJ file at `url`, without its materials.
+    fn parse_obj(&self, url: &str) -> Result<ObjModel, AssetError> {
+        let bytes = self.get(url).ok_or_else(|| AssetError::NotLoaded { url: url.to_owned() })?;
+        let source = std::str::from_utf8(bytes)
+            .map_err(|err| AssetError::Decode { url: url.to_owned(), message: err.to_string() })?;
+        ObjModel::parse(source)
+            .map_err(|message| AssetError::Decode { url: url.to_owned(), message })
+    }
+
+    /// Like `get_obj`, but first loads the OBJ file, its MTL files and their textures from disk
+    /// if they aren't already loaded. Textures are loaded as images.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_obj(&mut self, url: &str) -> Result<ObjModel, AssetError> {
+        let io_error = |url: &str, err: std::io::Error| AssetError::Io {
+            url: url.to_owned(),
+            message: err.to_string(),
+        };
+        self.get_or_load(url).map_err(|err| io_error(url, err))?;
+        for library in self.parse_obj(url)?.material_libraries {
+            let library_url = relative_url(url, &library);
+            self.get_or_load(&library_url).map_err(|err| io_error(&library_url, err))?;
+        }
+        let model = self.get_obj(url)?;
+        for texture in model.materials.iter().flat_map(|material| material.textures()) {
+            self.get_or_load_image(texture)
+                .map_err(|_| AssetError::Image { url: texture.to_owned() })?;
+        }
+        Ok(model)
+    }
+
+    /// Like `get_obj`, but first fetches the OBJ file, its MTL files and their textures if they
+    /// aren't already loaded. Textures are loaded as images.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn load_obj(&mut self, url: &str) -> Result<ObjModel, AssetError> {
+        self.fetch_if_needed(url).await?;
+        for library in self.parse_obj(url)?.material_libraries {
+            self.fetch_if_needed(&relative_url(url, &library)).await?;
+        }
+        let model = self.get_obj(url)?;
+        for texture in model.materials.iter().flat_map(|material| material.textures()) {
+            if self.get_image(texture).is_none() {
+                let options = self.options.clone();
+                let image =
+                    with_retries(&options, || fetch_image(options.resolve(texture))).await?;
+                let size = image_size(&image);
+                self.images.insert(texture.to_owned(), Rc::new(image), size, self.tick());
+            }
+        }
+        Ok(model)
+    }
+
+    /// Fetches the given asset if it isn't already loaded.
+    #[cfg(target_arch = "wasm32")]
+    async fn fetch_if_needed(&mut self, url: &str) -> Result<(), AssetError> {
+        if self.get(url).is_none() {
+            let options = self.options.clone();
+            let asset = with_retries(&options, || fetch_asset(options.resolve(url))).await?;
+            let size = asset.len();
+            self.assets.insert(url.to_owned(), Rc::new(asset), size, self.tick());
+        }
+        Ok(())
+    }
+
+    /// Returns the glTF scene at `url`. Requires the `gltf` feature.
+    ///
+    /// The glTF file and any external buffers it uses must have been loaded as regular assets;
+    /// `load_gltf` loads them along with the scene's images.
+    #[cfg(feature = "gltf")]
+    pub fn get_gltf(&self, url: &str) -> Result<GltfScene, AssetError> {
+        let bytes = self.get(url).ok_or_else(|| AssetError::NotLoaded { url: url.to_owned() })?;
+        GltfScene::parse(url, bytes, |id| {
+            self.get(id)
+                .map(|x| x.to_vec())
+                .ok_or_else(|| AssetError::NotLoaded { url: id.to_owned() })
+        })
+    }
+
+    /// Like `get_gltf`, but first loads the glTF file, its external buffers and its images from
+    /// disk if they aren't already loaded. Embedded images are decoded and stored as images too,
+    /// so every texture can be created with `get_texture`.
+    #[cfg(all(feature = "gltf", not(target_arch = "wasm32")))]
+    pub fn load_gltf(&mut self, url: &str) -> Result<GltfScene, AssetError> {
+        let io_error = |url: &str, err: std::io::Error| AssetError::Io {
+            url: url.to_owned(),
+            message: err.to_string(),
+        };
+        let bytes = self.get_or_load(url).map_err(|err| io_error(url, err))?;
+        for buffer in GltfScene::external_buffers(url, &bytes)? {
+            self.get_or_load(&buffer).map_err(|err| io_error(&buffer, err))?;
+        }
+        let scene = self.get_gltf(url)?;
+        for gltf_image in &scene.images {
+            let image_error = |_| AssetError::Image { url: gltf_image.id.clone() };
+            match &gltf_image.embedded {
+                None => {
+                    self.get_or_load_image(&gltf_image.id).map_err(image_error)?;
+                }
+                Some((data, _)) if self.get_image(&gltf_image.id).is_none() => {
+                    let image = image::load_from_memory(data).map_err(image_error)?;
+                    let size = image_size(&image);
+                    self.images.insert(gltf_image.id.clone(), Rc::new(image), size, self.tick());
+                }
+                Some(_) => (),
+            }
+        }
+        Ok(scene)
+    }
+
+    /// Like `get_gltf`, but first fetches the glTF file, its external buffers and its images if
+    /// they aren't already loaded. Embedded images are decoded and stored as images too, so
+    /// every texture can be created with `get_texture`.
+    #[cfg(all(feature = "gltf", target_arch = "wasm32"))]
+    pub async fn load_gltf(&mut self, url: &str) -> Result<GltfScene, AssetError> {
+        self.fetch_if_needed(url).await?;
+        for buffer in GltfScene::external_buffers(url, self.get(url).unwrap())? {
+            self.fetch_if_needed(&buffer).await?;
+        }
+        let scene = self.get_gltf(url)?;
+        for gltf_image in &scene.images {
+            if self.get_image(&gltf_image.id).is_some() {
+                continue;
+            }
+            let options = self.options.clone();
+            let image = match &gltf_image.embedded {
+                None => {
+                    with_retries(&options, || fetch_image(options.resolve(&gltf_image.id))).await?
+                }
+                Some((data, mime_type)) => {
+                    fetch_embedded_image(&gltf_image.id, data, mime_type).await?
+                }
+            };
+            let size = image_size(&image);
+            self.images.insert(gltf_image.id.clone(), Rc::new(image), size, self.tick());
+        }
+        Ok(scene)
+    }
+
+    /// Sets the maximum estimated memory, in bytes, that `evict_unused` should keep. `None`
+    /// means there's no limit. This includes both CPU-side data and textures.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    /// Returns the estimated memory used by all cached assets, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.assets.size() + self.images.size() + self.textures.size() + self.typed.size()
     }
+
+    /// Evicts the least recently used assets until the memory usage is within the budget set by
+    /// `set_memory_budget`. Assets with handles outside of `Assets` are never evicted.
+    ///
+    /// This should typically be called periodically, e.g. after loading a new level.
+    pub fn evict_unused(&mut self) {
+        let budget = match self.memory_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let mut usage = self.memory_usage();
+        if usage <= budget {
+            return;
+        }
+
+        let mut candidates: Vec<(CacheKey, u64, usize)> = self
+            .assets
+            .unused_entries()
+            .map(|(key, time, size)| (CacheKey::Asset(key), time, size))
+            .chain(
+                self.images
+                    .unused_entries()
+                    .map(|(key, time, size)| (CacheKey::Image(key), time, size)),
+            )
+            .chain(
+                self.textures
+                    .unused_entries()
+                    .map(|(key, time, size)| (CacheKey::Texture(key), time, size)),
+            )
+            .chain(
+                self.typed
+                    .unused_entries()
+                    .map(|(key, time, size)| (CacheKey::Typed(key), time, size)),
+            )
+            .collect();
+        candidates.sort_by_key(|&(_, time, _)| time);
+
+        for (key, _, size) in candidates {
+            if usage <= budget {
+                break;
+            }
+            match key {
+                CacheKey::Asset(key) => {
+                    self.assets.remove(&key);
+                }
+                CacheKey::Image(key) => {
+                    self.images.remove(&key);
+                }
+                CacheKey::Texture(key) => {
+                    self.textures.remove(&key);
+                }
+                CacheKey::Typed(key) => {
+                    self.typed.remove(&key);
+                }
+            }
+            usage -= size;
+        }
+    }
+}
+
+/// Calls `f` until it succeeds, it fails with an error that isn't retryable, or it's been
+/// retried `options.retries` times, waiting longer before each retry.
+#[cfg(target_arch = "wasm32")]
+async fn with_retries<T, F: Future<Output = Result<T, AssetError>>>(
+    options: &AssetOptions,
+    mut f: impl FnMut() -> F,
+) -> Result<T, AssetError> {
+    let mut delay_ms = options.retry_delay_ms;
+    let mut retries_left = options.retries;
+    loop {
+        match f().await {
+            Err(err) if err.is_retryable() && retries_left > 0 => {
+                warn!("{}; retrying in {}ms", err, delay_ms);
+                sleep(delay_ms).await;
+                delay_ms = delay_ms.saturating_mul(2);
+                retries_left -= 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(ms: u32) {
+    let promise = Promise::new(&mut |resolve, _reject| {
+        window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms as i32)
+            .unwrap();
+    });
+    JsFuture::from(promise).await.unwrap();
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch_asset(url: String) -> Result<Vec<u8>, AssetError> {
+    let mut request_init = RequestInit::new();
+    request_init.method("GET");
+    request_init.mode(RequestMode::Cors);
+
+    let request = Request::new_with_str_and_init(&url, &request_init).unwrap();
+    let request_promise = window().unwrap().fetch_with_request(&request);
+
+    let response = JsFuture::from(request_promise)
+        .await
+        .map_err(|err| AssetError::Network { url: url.clone(), message: format!("{:?}", err) })?;
+    let response: Response = response.dyn_into().unwrap();
+    if !response.ok() {
+        return Err(AssetError::Http { url, status: response.status() });
+    }
+    let array_buffer = JsFuture::from(response.array_buffer().unwrap())
+        .await
+        .map_err(|err| AssetError::Network { url: url.clone(), message: format!("{:?}", err) })?;
+    let array_buffer: ArrayBuffer = array_buffer.into();
+    let array: Uint8Array = Uint8Array::new(&array_buffer);
+    let mut dst = vec![0; array_buffer.byte_length() as usize];
+    array.copy_to(&mut dst);
+    Ok(dst)
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch_image(url: String) -> Result<HtmlImageElement, AssetError> {
+    let image_element = window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .create_element("img")
+        .unwrap()
+        .dyn_into::<HtmlImageElement>()
+        .unwrap();
+
+    // The handlers must be kept alive until one of them is called.
+    let mut onload_handler = None;
+    let mut onerror_handler = None;
+    let promise = Promise::new(&mut |resolve, reject| {
+        let handler = Closure::wrap(Box::new(move || {
+            resolve.call0(&JsValue::NULL).unwrap();
+        }) as Box<dyn FnMut()>);
+        image_element.set_onload(Some(handler.as_ref().unchecked_ref()));
+        onload_handler = Some(handler);
+
+        let handler = Closure::wrap(Box::new(move || {
+            reject.call0(&JsValue::NULL).unwrap();
+        }) as Box<dyn FnMut()>);
+        image_element.set_onerror(Some(handler.as_ref().unchecked_ref()));
+        onerror_handler = Some(handler);
+    });
+
+    image_element.set_src(&url);
+    let res = JsFuture::from(promise).await;
+
+    image_element.set_onload(None);
+    image_element.set_onerror(None);
+    drop((onload_handler, onerror_handler));
+
+    match res {
+        Ok(_) => Ok(image_element),
+        Err(_) => Err(AssetError::Image { url }),
+    }
+}
+
+/// Decodes an image that's already in memory, through an object URL.
+#[cfg(all(feature = "gltf", target_arch = "wasm32"))]
+async fn fetch_embedded_image(
+    id: &str,
+    data: &[u8],
+    mime_type: &str,
+) -> Result<HtmlImageElement, AssetError> {
+    let image_error = || AssetError::Image { url: id.to_owned() };
+    let parts = Array::of1(&Uint8Array::from(data));
+    let mut properties = BlobPropertyBag::new();
+    properties.type_(mime_type);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &properties)
+        .map_err(|_| image_error())?;
+    let object_url = Url::create_object_url_with_blob(&blob).map_err(|_| image_error())?;
+    let res = fetch_image(object_url.clone()).await;
+    Url::revoke_object_url(&object_url).unwrap();
+    res.map_err(|_| image_error())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn image_size(image: &HtmlImageElement) -> usize {
+    image.width() as usize * image.height() as usize * 4
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn image_size(image: &image::DynamicImage) -> usize {
+    image.as_bytes().len()
+}
+
+/// The format to load an image as. Images only have 8 bits per channel, so there's no point in
+/// loading them as `RGBA16F`, which the image data couldn't be uploaded as anyway.
+fn image_texture_format(format: TextureFormat) -> TextureFormat {
+    match format {
+        TextureFormat::RGBA16F => TextureFormat::RGBA,
+        format => format,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn texture_from_image(
+    context: &GlContext,
+    image: &HtmlImageElement,
+    settings: TextureSettings,
+) -> Texture2d {
+    Texture2d::from_image(
+        context,
+        image,
+        image_texture_format(settings.format),
+        settings.min_filter,
+        settings.mag_filter,
+        settings.wrap_mode,
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn texture_from_image(
+    context: &GlContext,
+    image: &image::DynamicImage,
+    settings: TextureSettings,
+) -> Texture2d {
+    let (width, height) = image.dimensions();
+    let format = image_texture_format(settings.format);
+    let data = match format {
+        TextureFormat::Red => image.to_luma8().into_raw(),
+        TextureFormat::RGB | TextureFormat::SRGB => image.to_rgb8().into_raw(),
+        TextureFormat::RGBA | TextureFormat::SRGBA | TextureFormat::RGBA16F => {
+            image.to_rgba8().into_raw()
+        }
+    };
+    Texture2d::from_data(
+        context,
+        vec2(width, height),
+        &data,
+        format,
+        settings.min_filter,
+        settings.mag_filter,
+        settings.wrap_mode,
+    )
 }
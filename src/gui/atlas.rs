@@ -0,0 +1,157 @@
+use cgmath::*;
+use fxhash::FxHashMap;
+use std::cmp::Reverse;
+use std::rc::Rc;
+
+use crate::gl::*;
+
+/// The location of an image within a `TextureAtlas`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// The region in pixels, from the top-left corner of the atlas.
+    pub rect: Rect<i32>,
+    /// The region in texture coordinates.
+    pub uv: Rect<f32>,
+}
+
+impl AtlasRegion {
+    /// The size of the image, in pixels.
+    pub fn size(&self) -> Vector2<i32> {
+        self.rect.size()
+    }
+}
+
+/// Many images packed into a single texture, so they can be drawn with one draw call; see
+/// `Draw2d::sprite_batch`. Create one with a `TextureAtlasBuilder`.
+pub struct TextureAtlas {
+    texture: Rc<Texture2d>,
+    regions: FxHashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    pub fn texture(&self) -> &Rc<Texture2d> {
+        &self.texture
+    }
+
+    /// Returns the region of the image with the given name. When drawing the same image many
+    /// times, look up its region once and reuse it.
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    /// Returns the names and regions of all images in the atlas.
+    pub fn regions(&self) -> impl Iterator<Item = (&str, AtlasRegion)> {
+        self.regions.iter().map(|(name, region)| (name.as_str(), *region))
+    }
+}
+
+struct AtlasImage {
+    name: String,
+    size: Vector2<u32>,
+    pixels: Vec<u8>,
+}
+
+/// Packs images into a `TextureAtlas`.
+pub struct TextureAtlasBuilder {
+    images: Vec<AtlasImage>,
+    padding: u32,
+}
+
+impl TextureAtlasBuilder {
+    pub fn new() -> Self {
+        Self { images: vec![], padding: 1 }
+    }
+
+    /// Sets the number of transparent pixels between images, which keeps filtering from
+    /// blending neighboring images together. Defaults to 1.
+    pub fn set_padding(&mut self, padding: u32) {
+        self.padding = padding;
+    }
+
+    /// Adds an image, given as RGBA pixels starting from the top row. If an image with the same
+    /// name was already added, it's replaced.
+    pub fn add(&mut self, name: impl Into<String>, size: Vector2<u32>, pixels: Vec<u8>) {
+        assert_eq!(pixels.len(), size.x as usize * size.y as usize * 4, "Wrong number of pixels");
+        let name = name.into();
+        self.images.retain(|image| image.name != name);
+        self.images.push(AtlasImage { name, size, pixels });
+    }
+
+    /// Adds an image loaded with the `image` crate.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn add_image(&mut self, name: impl Into<String>, image: &image::DynamicImage) {
+        let image = image.to_rgba8();
+        self.add(name, vec2(image.width(), image.height()), image.into_raw());
+    }
+
+    /// Packs the images into a texture. The format in `settings` must be `RGBA` or `SRGBA`.
+    pub fn build(&self, context: &GlContext, settings: TextureSettings) -> TextureAtlas {
+        assert!(
+            matches!(settings.format, TextureFormat::RGBA | TextureFormat::SRGBA),
+            "Texture atlases must be RGBA or SRGBA"
+        );
+
+        // Images are placed in rows ("shelves"), tallest first, in a texture that's roughly
+        // square.
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| Reverse(self.images[i].size.y));
+        let area: u64 = self
+            .images
+            .iter()
+            .map(|image| {
+                (image.size.x + self.padding) as u64 * (image.size.y + self.padding) as u64
+            })
+            .sum();
+        let max_width = self.images.iter().map(|image| image.size.x).max().unwrap_or(1);
+        let width = ((area as f64).sqrt().ceil() as u32).max(max_width).next_power_of_two();
+
+        let mut positions = vec![point2(0, 0); self.images.len()];
+        let mut pos = point2(0, 0);
+        let mut shelf_height = 0;
+        for &i in &order {
+            let size = self.images[i].size;
+            if pos.x > 0 && pos.x + size.x > width {
+                pos = point2(0, pos.y + shelf_height + self.padding);
+                shelf_height = 0;
+            }
+            positions[i] = pos;
+            pos.x += size.x + self.padding;
+            shelf_height = shelf_height.max(size.y);
+        }
+        let size = vec2(width, (pos.y + shelf_height).max(1));
+
+        let mut pixels = vec![0; size.x as usize * size.y as usize * 4];
+        let mut regions = FxHashMap::default();
+        for (image, &pos) in self.images.iter().zip(&positions) {
+            let row_bytes = image.size.x as usize * 4;
+            for y in 0..image.size.y as usize {
+                let start = ((pos.y as usize + y) * size.x as usize + pos.x as usize) * 4;
+                pixels[start..start + row_bytes]
+                    .copy_from_slice(&image.pixels[y * row_bytes..(y + 1) * row_bytes]);
+            }
+            let rect = Rect::new(pos, pos + image.size).cast::<i32>().unwrap();
+            let uv = Rect::new(
+                point2(rect.start.x as f32 / size.x as f32, rect.start.y as f32 / size.y as f32),
+                point2(rect.end.x as f32 / size.x as f32, rect.end.y as f32 / size.y as f32),
+            );
+            regions.insert(image.name.clone(), AtlasRegion { rect, uv });
+        }
+
+        let texture = Texture2d::from_data(
+            context,
+            size,
+            &pixels,
+            settings.format,
+            settings.min_filter,
+            settings.mag_filter,
+            settings.wrap_mode,
+        );
+        TextureAtlas { texture: Rc::new(texture), regions }
+    }
+}
+
+impl Default for TextureAtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
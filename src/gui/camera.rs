@@ -0,0 +1,304 @@
+use cgmath::*;
+use std::f32::consts::FRAC_PI_2;
+
+use super::event::*;
+use super::main_loop::*;
+
+// Keeps the pitch away from straight up or down, where the view direction would be parallel to
+// the up vector.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+/// The height of a line, for converting scrolling in pixels to lines.
+const SCROLL_LINE_HEIGHT: f64 = 16.0;
+
+/// A camera that produces view and projection matrices for 3D rendering.
+pub trait Camera {
+    fn view_matrix(&self) -> Matrix4<f32>;
+
+    fn projection_matrix(&self) -> Matrix4<f32>;
+
+    /// Moves the camera to `eye`, looking at `target`.
+    fn look_at(&mut self, eye: Point3<f32>, target: Point3<f32>);
+
+    /// Sets the aspect ratio from the size of the surface being rendered to.
+    fn set_viewport_size(&mut self, size: Vector2<u32>);
+
+    fn view_projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// Updates the aspect ratio when the window is resized.
+    fn handle_event(&mut self, event: &Event) {
+        if let Event::WindowResized(size) = event {
+            self.set_viewport_size(*size);
+        }
+    }
+}
+
+fn aspect_ratio(size: Vector2<u32>) -> f32 {
+    size.x.max(1) as f32 / size.y.max(1) as f32
+}
+
+/// A camera with a perspective projection.
+#[derive(Clone, Debug)]
+pub struct PerspectiveCamera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    /// The vertical field of view.
+    pub fov: Rad<f32>,
+    /// Width divided by height.
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl PerspectiveCamera {
+    /// Creates a camera at the origin looking along -Z, with +Y up.
+    pub fn new(fov: impl Into<Rad<f32>>, viewport_size: Vector2<u32>, near: f32, far: f32) -> Self {
+        Self {
+            eye: Point3::origin(),
+            target: point3(0.0, 0.0, -1.0),
+            up: Vector3::unit_y(),
+            fov: fov.into(),
+            aspect_ratio: aspect_ratio(viewport_size),
+            near,
+            far,
+        }
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        perspective(self.fov, self.aspect_ratio, self.near, self.far)
+    }
+
+    fn look_at(&mut self, eye: Point3<f32>, target: Point3<f32>) {
+        self.eye = eye;
+        self.target = target;
+    }
+
+    fn set_viewport_size(&mut self, size: Vector2<u32>) {
+        self.aspect_ratio = aspect_ratio(size);
+    }
+}
+
+/// A camera with an orthographic projection, centered on the view direction.
+#[derive(Clone, Debug)]
+pub struct OrthographicCamera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    /// The height of the visible area, in world units. The width depends on the aspect ratio.
+    pub height: f32,
+    /// Width divided by height.
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl OrthographicCamera {
+    /// Creates a camera at the origin looking along -Z, with +Y up.
+    pub fn new(height: f32, viewport_size: Vector2<u32>, near: f32, far: f32) -> Self {
+        Self {
+            eye: Point3::origin(),
+            target: point3(0.0, 0.0, -1.0),
+            up: Vector3::unit_y(),
+            height,
+            aspect_ratio: aspect_ratio(viewport_size),
+            near,
+            far,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        let half_height = self.height / 2.0;
+        let half_width = half_height * self.aspect_ratio;
+        ortho(-half_width, half_width, -half_height, half_height, self.near, self.far)
+    }
+
+    fn look_at(&mut self, eye: Point3<f32>, target: Point3<f32>) {
+        self.eye = eye;
+        self.target = target;
+    }
+
+    fn set_viewport_size(&mut self, size: Vector2<u32>) {
+        self.aspect_ratio = aspect_ratio(size);
+    }
+}
+
+// The direction a camera with the given yaw and pitch looks in; a yaw of 0 looks along -Z.
+fn direction(yaw: Rad<f32>, pitch: Rad<f32>) -> Vector3<f32> {
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    vec3(-sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+}
+
+/// Rotates a camera around a target point by dragging with the mouse, and zooms by scrolling.
+/// Assumes +Y is up.
+///
+/// Pass every event to `handle_event`, then call `apply` before rendering.
+#[derive(Clone, Debug)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    /// The rotation around the Y axis; 0 puts the camera on the +Z side of the target.
+    pub yaw: Rad<f32>,
+    /// Positive values put the camera above the target.
+    pub pitch: Rad<f32>,
+    /// The button that rotates the camera when dragging.
+    pub button: MouseButton,
+    /// The rotation per pixel of mouse movement.
+    pub rotate_speed: Rad<f32>,
+    /// The distance is multiplied by this for each notch of the mouse wheel scrolled away from
+    /// the target. When scrolling by pixels, 16 pixels count as a line.
+    pub zoom_factor: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            button: MouseButton::Left,
+            rotate_speed: Rad(0.005),
+            zoom_factor: 1.1,
+            min_distance: 0.01,
+            max_distance: f32::INFINITY,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event, event_state: &EventState) {
+        match event {
+            Event::MouseMove { movement, .. }
+                if event_state.pressed_mouse_buttons.contains(&self.button) =>
+            {
+                self.yaw -= self.rotate_speed * movement.x as f32;
+                self.pitch += self.rotate_speed * movement.y as f32;
+                self.pitch = Rad(self.pitch.0.clamp(-MAX_PITCH, MAX_PITCH));
+            }
+            Event::Scroll { delta, unit } => {
+                let notches = unit.to_lines(*delta, SCROLL_LINE_HEIGHT).y / LINES_PER_NOTCH;
+                self.distance *= self.zoom_factor.powf(notches as f32);
+                self.distance = self.distance.clamp(self.min_distance, self.max_distance);
+            }
+            _ => (),
+        }
+    }
+
+    /// The position of the camera.
+    pub fn eye(&self) -> Point3<f32> {
+        self.target - direction(self.yaw, self.pitch) * self.distance
+    }
+
+    /// Moves `camera` to the controller's position.
+    pub fn apply(&self, camera: &mut impl Camera) {
+        camera.look_at(self.eye(), self.target);
+    }
+}
+
+/// Moves a camera freely, looking around with the mouse and moving with WASD, Space (up) and
+/// Shift (down). Assumes +Y is up.
+///
+/// Pass every event to `handle_event`, call `update` once per frame, then call `apply` before
+/// rendering.
+#[derive(Clone, Debug)]
+pub struct FlyController {
+    pub position: Point3<f32>,
+    /// The rotation around the Y axis; 0 looks along -Z.
+    pub yaw: Rad<f32>,
+    /// Positive values look up.
+    pub pitch: Rad<f32>,
+    /// The button that must be held to look around, or `None` to look around only while the
+    /// pointer is locked. While the pointer is locked, `Event::RawMouseMotion` is used, so mouse
+    /// acceleration doesn't affect looking around.
+    pub look_button: Option<MouseButton>,
+    /// The rotation per pixel of mouse movement.
+    pub look_speed: Rad<f32>,
+    /// The movement speed, in units per second.
+    pub move_speed: f32,
+}
+
+impl FlyController {
+    pub fn new(position: Point3<f32>) -> Self {
+        Self {
+            position,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            look_button: Some(MouseButton::Right),
+            look_speed: Rad(0.003),
+            move_speed: 5.0,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event, event_state: &EventState) {
+        let movement = match (event, self.look_button) {
+            (Event::MouseMove { movement, .. }, Some(button))
+                if event_state.pressed_mouse_buttons.contains(&button) =>
+            {
+                movement.cast::<f32>().unwrap()
+            }
+            (Event::RawMouseMotion(movement), None) if event_state.pointer_locked => {
+                movement.cast::<f32>().unwrap()
+            }
+            _ => return,
+        };
+        self.yaw -= self.look_speed * movement.x;
+        self.pitch -= self.look_speed * movement.y;
+        self.pitch = Rad(self.pitch.0.clamp(-MAX_PITCH, MAX_PITCH));
+    }
+
+    /// Moves the camera based on the keys that are pressed. `dt` is the time since the last
+    /// update, in seconds.
+    pub fn update(&mut self, event_state: &EventState, dt: f64) {
+        let pressed = |code: &str| event_state.pressed_keys.contains(code);
+        let forward = self.direction();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+
+        let mut movement = Vector3::zero();
+        if pressed("KeyW") {
+            movement += forward;
+        }
+        if pressed("KeyS") {
+            movement -= forward;
+        }
+        if pressed("KeyD") {
+            movement += right;
+        }
+        if pressed("KeyA") {
+            movement -= right;
+        }
+        if pressed("Space") {
+            movement += Vector3::unit_y();
+        }
+        if pressed("Shift") {
+            movement -= Vector3::unit_y();
+        }
+        if movement != Vector3::zero() {
+            self.position += movement.normalize() * self.move_speed * dt as f32;
+        }
+    }
+
+    /// The direction the camera is looking in.
+    pub fn direction(&self) -> Vector3<f32> {
+        direction(self.yaw, self.pitch)
+    }
+
+    /// Moves `camera` to the controller's position.
+    pub fn apply(&self, camera: &mut impl Camera) {
+        camera.look_at(self.position, self.position + self.direction());
+    }
+}
@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::ffi::{CStr, CString};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::{JsCast, JsValue};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    // Text read from the clipboard, which the main loop sends as `Event::Paste` events
+    static PENDING_PASTES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    // Sends text read from the clipboard as an `Event::Paste`; set by `setup_event_callbacks`
+    static PASTE_HANDLER: RefCell<Option<Box<dyn Fn(String)>>> = const { RefCell::new(None) };
+}
+
+/// The system clipboard. It's available from `EventState::clipboard`, but since it doesn't hold
+/// any state, widgets can also create one when they need it.
+///
+/// On wasm this uses the async Clipboard API, which browsers only allow in response to user
+/// input, such as while handling a key press. Reading the clipboard may also ask the user for
+/// permission, so the text is passed to a callback rather than returned.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Clipboard;
+
+impl Clipboard {
+    /// Copies text to the clipboard.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_text(&self, text: &str) {
+        // C strings can't contain null bytes.
+        let text = CString::new(text.replace('\0', "")).unwrap();
+        // Since GLFW 3.3 the window is ignored, so it doesn't need to be passed around.
+        unsafe {
+            glfw::ffi::glfwSetClipboardString(std::ptr::null_mut(), text.as_ptr());
+        }
+    }
+
+    /// Copies text to the clipboard.
+    #[cfg(target_arch = "wasm32")]
+    pub fn set_text(&self, text: &str) {
+        if let Some((clipboard, write_text)) = clipboard_method("writeText") {
+            // The promise is ignored; if the browser refuses, there's nothing to do about it.
+            let _ = write_text.call1(&clipboard, &JsValue::from_str(text));
+        }
+    }
+
+    /// Reads the clipboard and calls `f` with its text. Natively this happens immediately; on
+    /// wasm it happens once the browser returns the text. `f` isn't called if the clipboard
+    /// doesn't contain text or the user doesn't allow reading it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_text(&self, f: impl FnOnce(String) + 'static) {
+        let text = unsafe {
+            let text = glfw::ffi::glfwGetClipboardString(std::ptr::null_mut());
+            if text.is_null() {
+                return;
+            }
+            CStr::from_ptr(text).to_string_lossy().into_owned()
+        };
+        f(text);
+    }
+
+    /// Reads the clipboard and calls `f` with its text. Natively this happens immediately; on
+    /// wasm it happens once the browser returns the text. `f` isn't called if the clipboard
+    /// doesn't contain text or the user doesn't allow reading it.
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_text(&self, f: impl FnOnce(String) + 'static) {
+        let (clipboard, read_text) = match clipboard_method("readText") {
+            Some(method) => method,
+            None => return,
+        };
+        let promise = match read_text.call0(&clipboard) {
+            Ok(promise) => js_sys::Promise::from(promise),
+            Err(_) => return,
+        };
+        spawn_local(async move {
+            if let Some(text) = JsFuture::from(promise).await.ok().and_then(|text| text.as_string())
+            {
+                f(text);
+            }
+        });
+    }
+
+    /// Reads the clipboard, and sends its text as an `Event::Paste` so that it goes to the
+    /// active widget. Natively, the event is sent with the next frame's events.
+    pub fn request_paste(&self) {
+        self.get_text(|text| {
+            #[cfg(not(target_arch = "wasm32"))]
+            PENDING_PASTES.with(|pastes| pastes.borrow_mut().push(text));
+            #[cfg(target_arch = "wasm32")]
+            PASTE_HANDLER.with(|handler| {
+                if let Some(handler) = &*handler.borrow() {
+                    handler(text);
+                }
+            });
+        });
+    }
+}
+
+/// Returns `navigator.clipboard` and one of its methods. The Clipboard API isn't in web-sys
+/// without unstable features, so it's accessed dynamically.
+#[cfg(target_arch = "wasm32")]
+fn clipboard_method(name: &str) -> Option<(JsValue, js_sys::Function)> {
+    let navigator = web_sys::window()?.navigator();
+    let clipboard = js_sys::Reflect::get(&navigator, &JsValue::from_str("clipboard")).ok()?;
+    if clipboard.is_undefined() {
+        return None;
+    }
+    let method = js_sys::Reflect::get(&clipboard, &JsValue::from_str(name)).ok()?;
+    Some((clipboard, method.dyn_into().ok()?))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn take_pending_pastes() -> Vec<String> {
+    PENDING_PASTES.with(|pastes| std::mem::take(&mut *pastes.borrow_mut()))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn set_paste_handler(handler: Box<dyn Fn(String)>) {
+    PASTE_HANDLER.with(|paste_handler| *paste_handler.borrow_mut() = Some(handler));
+}
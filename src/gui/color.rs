@@ -1,5 +1,6 @@
 use crate::gl::uniforms::*;
 use crate::gl::*;
+use bytemuck::{Pod, Zeroable};
 use serde::*;
 use std::ops::*;
 
@@ -15,6 +16,9 @@ pub struct Color4 {
     pub a: f32,
 }
 
+unsafe impl Zeroable for Color4 {}
+unsafe impl Pod for Color4 {}
+
 impl Color4 {
     pub const BLACK: Color4 = Color4 { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
     pub const WHITE: Color4 = Color4 { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
@@ -1,17 +1,29 @@
 use crate::gl::uniforms::*;
 use crate::gl::*;
+use bytemuck::{Pod, Zeroable};
 use cgmath::*;
-use std::ops::Neg;
+use serde::{Deserialize, Serialize};
+use std::mem;
+use std::ops::{Neg, Range};
+use std::rc::Rc;
 
+use super::atlas::*;
 use super::color::*;
 use super::shader_header::*;
+use super::text::*;
+use super::triangulate::*;
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub struct PlainVert {
     pub pos: Point2<f32>,
     pub color: Color4,
 }
 
+// `Point2<f32>` is `#[repr(C)]` and only contains `f32`s, so these are safe.
+unsafe impl Zeroable for PlainVert {}
+unsafe impl Pod for PlainVert {}
+
 impl VertexData for PlainVert {
     const ATTRIBUTES: Attributes = &[("pos", 2), ("color", 4)];
 }
@@ -51,12 +63,16 @@ impl GlUniforms for PlainUniformsGl {
 }
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub struct ImageVert {
     pub pos: Point2<f32>,
     pub uv: Point2<f32>,
     pub color: Color4,
 }
 
+unsafe impl Zeroable for ImageVert {}
+unsafe impl Pod for ImageVert {}
+
 impl VertexData for ImageVert {
     const ATTRIBUTES: Attributes = &[("pos", 2), ("uv", 2), ("color", 4)];
 }
@@ -107,6 +123,7 @@ pub struct Draw2dPrograms {
     pub plain_program: GlProgram<PlainVert, PlainUniformsGl>,
     pub image_program_srgb: GlProgram<ImageVert, ImageUniformsGl>,
     pub image_program_linear: GlProgram<ImageVert, ImageUniformsGl>,
+    pub text_program: GlProgram<ImageVert, ImageUniformsGl>,
 }
 
 impl Draw2dPrograms {
@@ -130,23 +147,68 @@ impl Draw2dPrograms {
                 include_str!("shaders/image_frag.glsl"),
                 false,
             );
-        Self { plain_program, image_program_srgb, image_program_linear }
+        let text_program: GlProgram<ImageVert, ImageUniformsGl> = GlProgram::new_with_header(
+            context,
+            include_str!("shaders/image_vert.glsl"),
+            include_str!("shaders/text_frag.glsl"),
+            false,
+        );
+        Self { plain_program, image_program_srgb, image_program_linear, text_program }
+    }
+}
+
+/// What a batch of queued drawing is rendered with.
+enum BatchKind {
+    /// Untextured shapes, stored in `triangle_mesh`.
+    Shapes,
+    /// An image, stored in `batch_mesh`.
+    Image(Rc<Texture2d>),
+    /// Text, stored in `batch_mesh`.
+    Text(Font),
+}
+
+impl BatchKind {
+    fn can_merge(&self, other: &BatchKind) -> bool {
+        match (self, other) {
+            (BatchKind::Shapes, BatchKind::Shapes) => true,
+            (BatchKind::Image(a), BatchKind::Image(b)) => Rc::ptr_eq(a, b),
+            (BatchKind::Text(a), BatchKind::Text(b)) => a.ptr_eq(b),
+            _ => false,
+        }
     }
 }
 
-/// A struct for drawing simple 2D shapes.
+/// Consecutive queued drawing that can be rendered with a single draw call.
+struct Batch {
+    kind: BatchKind,
+    clip: Option<Rect<i32>>,
+    layer: i32,
+    indices: Range<usize>,
+}
+
+/// A struct for drawing simple 2D shapes, images, and text.
 ///
 /// All distance units are pixels, from the top-left corner of the screen, unless
 /// `render_queued_custom_matrix` is used.
 ///
+/// Queued drawing is rendered in order of layer (see `set_layer`), and in the order it was
+/// queued within each layer, using one draw call for each run of drawing that uses the same
+/// program, texture, and clip rectangle.
+///
 // TODO: this struct may not be needed; many of the methods here could be in the impl for
 // `MeshBuilder<PlainVert, Triangles>`
 pub struct Draw2d {
+    context: GlContext,
     triangle_mesh_builder: MeshBuilder<PlainVert, Triangles>,
     triangle_mesh: Mesh<PlainVert, PlainUniformsGl, Triangles>,
-    image_mesh_builder: MeshBuilder<ImageVert, Triangles>,
-    image_mesh_srgb: Mesh<ImageVert, ImageUniformsGl, Triangles>,
-    image_mesh_linear: Mesh<ImageVert, ImageUniformsGl, Triangles>,
+    batch_mesh_builder: MeshBuilder<ImageVert, Triangles>,
+    batch_mesh: Mesh<ImageVert, ImageUniformsGl, Triangles>,
+    batches: Vec<Batch>,
+    clip_stack: Vec<Rect<i32>>,
+    layer: i32,
+    image_program_srgb: GlProgram<ImageVert, ImageUniformsGl>,
+    image_program_linear: GlProgram<ImageVert, ImageUniformsGl>,
+    text_program: GlProgram<ImageVert, ImageUniformsGl>,
 }
 
 pub fn compute_ortho_matrix(surface: &(impl Surface + ?Sized)) -> Matrix4<f32> {
@@ -159,28 +221,35 @@ impl Draw2d {
     /// Creates an object that can render a few types of basic geometric shapes.
     pub fn new(context: &GlContext, programs: &Draw2dPrograms) -> Self {
         let triangle_mesh_builder = MeshBuilder::new();
-        let triangle_mesh = Mesh::new(context, &programs.plain_program, DrawMode::Draw2D);
-        let image_mesh_builder = MeshBuilder::new();
-        let image_mesh_srgb = Mesh::new(context, &programs.image_program_srgb, DrawMode::Draw2D);
-        let image_mesh_linear =
-            Mesh::new(context, &programs.image_program_linear, DrawMode::Draw2D);
+        let mut triangle_mesh = Mesh::new(context, &programs.plain_program, DrawMode::Draw2D);
+        // Both meshes are rebuilt once per frame.
+        triangle_mesh.set_buffering(MeshBuffering::Rotate(3));
+        let batch_mesh_builder = MeshBuilder::new();
+        let mut batch_mesh = Mesh::new(context, &programs.image_program_srgb, DrawMode::Draw2D);
+        batch_mesh.set_buffering(MeshBuffering::Rotate(3));
         Self {
+            context: context.clone(),
             triangle_mesh_builder,
             triangle_mesh,
-            image_mesh_builder,
-            image_mesh_srgb,
-            image_mesh_linear,
+            batch_mesh_builder,
+            batch_mesh,
+            batches: vec![],
+            clip_stack: vec![],
+            layer: 0,
+            image_program_srgb: programs.image_program_srgb.clone(),
+            image_program_linear: programs.image_program_linear.clone(),
+            text_program: programs.text_program.clone(),
         }
     }
 
-    /// Render all queued shapes. Until this is called nothing is actually rendered.
+    /// Render all queued drawing. Until this is called nothing is actually rendered.
     ///
     /// This should typically be called once per frame to minimize the number of draw calls.
     pub fn render_queued(&mut self, surface: &(impl Surface + ?Sized)) {
         self.render_queued_custom_matrix(surface, compute_ortho_matrix(surface));
     }
 
-    /// Render all queued shapes. Until this is called nothing is actually rendered.
+    /// Render all queued drawing. Until this is called nothing is actually rendered.
     ///
     /// This allows a matrix to be specified which will be used instead of a standard orthographic
     /// projection.
@@ -191,37 +260,177 @@ impl Draw2d {
         surface: &(impl Surface + ?Sized),
         matrix: Matrix4<f32>,
     ) {
+        self.end_batch();
         self.triangle_mesh.build_from(&self.triangle_mesh_builder, MeshUsage::StreamDraw);
-        self.triangle_mesh.draw(surface, &PlainUniforms { matrix, color: Color4::WHITE });
+        self.batch_mesh.build_from(&self.batch_mesh_builder, MeshUsage::StreamDraw);
+
+        // The sort is stable, so drawing within each layer stays in the order it was queued.
+        self.batches.sort_by_key(|batch| batch.layer);
+        let surface_height = surface.size().y as i32;
+        for batch in self.batches.drain(..) {
+            // Clip rectangles have their origin at the top left, but scissor rectangles have it
+            // at the bottom left.
+            let scissor = batch.clip.map(|clip| {
+                Rect::new(
+                    point2(clip.start.x, surface_height - clip.end.y),
+                    point2(clip.end.x, surface_height - clip.start.y),
+                )
+            });
+            let pipeline_state = PipelineState { scissor, ..DrawMode::Draw2D.pipeline_state() };
+            self.triangle_mesh.set_pipeline_state(pipeline_state);
+            self.batch_mesh.set_pipeline_state(pipeline_state);
+            match batch.kind {
+                BatchKind::Shapes => self.triangle_mesh.draw_range(
+                    surface,
+                    &PlainUniforms { matrix, color: Color4::WHITE },
+                    batch.indices,
+                ),
+                BatchKind::Image(tex) => {
+                    let program = if tex.is_srgb() {
+                        &self.image_program_srgb
+                    } else {
+                        &self.image_program_linear
+                    };
+                    self.batch_mesh.draw_range_with_program(
+                        program,
+                        surface,
+                        &ImageUniforms { matrix, color: Color4::WHITE, tex: &tex },
+                        batch.indices,
+                    );
+                }
+                BatchKind::Text(font) => font.with_texture(|tex| {
+                    self.batch_mesh.draw_range_with_program(
+                        &self.text_program,
+                        surface,
+                        &ImageUniforms { matrix, color: Color4::WHITE, tex },
+                        batch.indices,
+                    )
+                }),
+            }
+        }
+        // The last batch's clip would otherwise stay in effect for whatever's drawn next.
+        self.context.set_scissor(None);
 
         self.triangle_mesh_builder.clear();
+        self.batch_mesh_builder.clear();
+    }
+
+    /// Restricts drawing to `rect` until the matching call to `pop_clip`. Clips can be nested,
+    /// in which case drawing is restricted to the area inside all of them.
+    ///
+    /// Clip rectangles are in pixels from the top-left corner of the surface, even if
+    /// `render_queued_custom_matrix` is used.
+    pub fn push_clip(&mut self, rect: Rect<i32>) {
+        let rect = match self.clip_stack.last() {
+            Some(clip) => clip.intersection(&rect),
+            None => rect,
+        };
+        self.clip_stack.push(rect);
+    }
+
+    /// Removes the most recently pushed clip rectangle.
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop().expect("pop_clip called without a matching push_clip");
+    }
+
+    /// The area drawing is currently restricted to, or `None` if it isn't restricted.
+    pub fn clip(&self) -> Option<Rect<i32>> {
+        self.clip_stack.last().copied()
+    }
+
+    /// Sets the layer that drawing is queued on. Layers are rendered from lowest to highest, so
+    /// drawing on a higher layer always appears on top of drawing on a lower one, regardless of
+    /// which was queued first. The default layer is 0.
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    /// Runs `f` with drawing queued on the given layer, then restores the previous layer. This
+    /// is useful for overlays such as tooltips.
+    pub fn with_layer<R>(&mut self, layer: i32, f: impl FnOnce(&mut Self) -> R) -> R {
+        let old_layer = mem::replace(&mut self.layer, layer);
+        let res = f(self);
+        self.layer = old_layer;
+        res
+    }
+
+    /// Makes `kind` the current batch. A new batch is only started if `kind` can't be drawn with
+    /// the previous one, or if the clip rectangle or layer has changed.
+    fn start_batch(&mut self, kind: BatchKind) {
+        let (clip, layer) = (self.clip(), self.layer);
+        if let Some(batch) = self.batches.last() {
+            if batch.clip == clip && batch.layer == layer && batch.kind.can_merge(&kind) {
+                return;
+            }
+        }
+        self.end_batch();
+        let start = self.num_indices(&kind);
+        self.batches.push(Batch { kind, clip, layer, indices: start..start });
+    }
+
+    /// Records where the current batch ends.
+    fn end_batch(&mut self) {
+        if let Some(batch) = self.batches.last() {
+            let end = self.num_indices(&batch.kind);
+            self.batches.last_mut().unwrap().indices.end = end;
+        }
+    }
+
+    fn num_indices(&self, kind: &BatchKind) -> usize {
+        match kind {
+            BatchKind::Shapes => self.triangle_mesh_builder.num_indices(),
+            BatchKind::Image(_) | BatchKind::Text(_) => self.batch_mesh_builder.num_indices(),
+        }
     }
 
     /// Draws a filled convex polygon.
     pub fn fill_poly(&mut self, verts: &[Point2<f32>], color: Color4) {
         assert!(verts.len() >= 3);
+        self.start_batch(BatchKind::Shapes);
         let mesh_builder = &mut self.triangle_mesh_builder;
-        let a = mesh_builder.vert(PlainVert { pos: verts[0], color });
-        let mut b = mesh_builder.vert(PlainVert { pos: verts[1], color });
+        let a = mesh_builder.vert_pod(PlainVert { pos: verts[0], color });
+        let mut b = mesh_builder.vert_pod(PlainVert { pos: verts[1], color });
         for c in verts.iter().skip(2) {
-            let c = mesh_builder.vert(PlainVert { pos: *c, color });
+            let c = mesh_builder.vert_pod(PlainVert { pos: *c, color });
             mesh_builder.triangle(a, b, c);
             b = c;
         }
     }
 
+    /// Draws a filled polygon that may be concave and may have holes; see
+    /// `triangulate_polygon` for the restrictions on its shape. This is slower than `fill_poly`,
+    /// so prefer that for convex polygons.
+    pub fn fill_path(&mut self, outline: &[Point2<f32>], holes: &[&[Point2<f32>]], color: Color4) {
+        let (verts, triangles) = triangulate_polygon(outline, holes);
+        if triangles.is_empty() {
+            return;
+        }
+        self.start_batch(BatchKind::Shapes);
+        let mesh_builder = &mut self.triangle_mesh_builder;
+        let indices: Vec<_> =
+            verts.iter().map(|&pos| mesh_builder.vert_pod(PlainVert { pos, color })).collect();
+        for [a, b, c] in triangles {
+            mesh_builder.triangle(indices[a], indices[b], indices[c]);
+        }
+    }
+
     /// Draws a line strip.
     // TODO: change all coords to i32, and ensure that all verts are aligned to pixels?
     pub fn draw_line_strip(&mut self, verts: &[Point2<f32>], color: Color4, width: f32) {
         assert!(verts.len() >= 2);
+        self.start_batch(BatchKind::Shapes);
         let mesh_builder = &mut self.triangle_mesh_builder;
         let half_width = width * 0.5;
         for (a, b) in verts.iter().zip(verts.iter().skip(1)) {
             let perp = ccw_perp(*b - *a).normalize();
-            let vert_a = mesh_builder.vert(PlainVert { pos: *a + perp * half_width, color });
-            let vert_b = mesh_builder.vert(PlainVert { pos: *a - perp * half_width, color });
-            let vert_c = mesh_builder.vert(PlainVert { pos: *b + perp * half_width, color });
-            let vert_d = mesh_builder.vert(PlainVert { pos: *b - perp * half_width, color });
+            let vert_a = mesh_builder.vert_pod(PlainVert { pos: *a + perp * half_width, color });
+            let vert_b = mesh_builder.vert_pod(PlainVert { pos: *a - perp * half_width, color });
+            let vert_c = mesh_builder.vert_pod(PlainVert { pos: *b + perp * half_width, color });
+            let vert_d = mesh_builder.vert_pod(PlainVert { pos: *b - perp * half_width, color });
             mesh_builder.triangle(vert_a, vert_b, vert_c);
             mesh_builder.triangle(vert_b, vert_c, vert_d);
         }
@@ -231,6 +440,50 @@ impl Draw2d {
         self.draw_line_strip(&[a, b], color, width);
     }
 
+    /// Draws a quadratic Bézier curve from `a` to `c` with control point `b`.
+    pub fn draw_quadratic_bezier(
+        &mut self,
+        a: Point2<f32>,
+        b: Point2<f32>,
+        c: Point2<f32>,
+        color: Color4,
+        width: f32,
+    ) {
+        // A quadratic curve is a cubic curve with control points 2/3 of the way to `b`.
+        let b1 = a + (b - a) * (2.0 / 3.0);
+        let b2 = c + (b - c) * (2.0 / 3.0);
+        self.draw_cubic_bezier(a, b1, b2, c, color, width);
+    }
+
+    /// Draws a cubic Bézier curve from `a` to `d` with control points `b` and `c`.
+    pub fn draw_cubic_bezier(
+        &mut self,
+        a: Point2<f32>,
+        b: Point2<f32>,
+        c: Point2<f32>,
+        d: Point2<f32>,
+        color: Color4,
+        width: f32,
+    ) {
+        let mut verts = vec![a];
+        flatten_cubic_bezier([a, b, c, d], &mut verts);
+        self.draw_line_strip(&verts, color, width);
+    }
+
+    /// Draws a smooth curve that passes through all of the given points, using a Catmull-Rom
+    /// spline.
+    pub fn draw_catmull_rom(&mut self, points: &[Point2<f32>], color: Color4, width: f32) {
+        assert!(points.len() >= 2);
+        let mut verts = vec![points[0]];
+        for i in 0..points.len() - 1 {
+            let prev = points[i.saturating_sub(1)];
+            let (a, b) = (points[i], points[i + 1]);
+            let next = points[(i + 2).min(points.len() - 1)];
+            flatten_cubic_bezier([a, a + (b - prev) / 6.0, b - (next - a) / 6.0, b], &mut verts);
+        }
+        self.draw_line_strip(&verts, color, width);
+    }
+
     pub fn fill_rect(&mut self, rect: Rect<i32>, color: Color4) {
         let rect = rect.cast().unwrap();
         self.fill_poly(
@@ -286,94 +539,306 @@ impl Draw2d {
         );
     }
 
-    /// Draws an image. Unlike most other functions on `Draw2d`, this draws the image immediately.
-    pub fn draw_image(
+    /// Draws a filled rectangle with rounded corners. The corner radius is limited to half of
+    /// the rectangle's width or height.
+    pub fn fill_rounded_rect(&mut self, rect: Rect<i32>, corner_radius: f32, color: Color4) {
+        let verts = rounded_rect_verts(rect.cast().unwrap(), corner_radius);
+        self.fill_poly(&verts, color);
+    }
+
+    pub fn outline_rounded_rect(
         &mut self,
-        surface: &(impl Surface + ?Sized),
-        tex: &Texture2d,
-        pos: Point2<f32>,
-        scale: f32,
+        rect: Rect<i32>,
+        corner_radius: f32,
+        color: Color4,
+        width: f32,
     ) {
-        let matrix =
-            compute_ortho_matrix(surface) * Matrix4::from_nonuniform_scale(scale, scale, 1.0);
+        let mut verts = rounded_rect_verts(rect.cast().unwrap(), corner_radius);
+        for vert in &mut verts {
+            *vert += vec2(0.5, 0.5);
+        }
+        verts.push(verts[0]);
+        self.draw_line_strip(&verts, color, width);
+    }
 
-        let a = self.image_mesh_builder.vert(ImageVert {
-            pos,
-            uv: point2(0.0, 0.0),
-            color: Color4::WHITE,
-        });
-        let b = self.image_mesh_builder.vert(ImageVert {
-            pos: pos + vec2(tex.size().x as f32, 0.0),
-            uv: point2(1.0, 0.0),
-            color: Color4::WHITE,
-        });
-        let c = self.image_mesh_builder.vert(ImageVert {
-            pos: pos + vec2(0.0, tex.size().y as f32),
-            uv: point2(0.0, 1.0),
-            color: Color4::WHITE,
-        });
-        let d = self.image_mesh_builder.vert(ImageVert {
-            pos: pos + vec2(tex.size().x as f32, tex.size().y as f32),
-            uv: point2(1.0, 1.0),
-            color: Color4::WHITE,
+    /// Queues a string to be drawn.
+    pub fn draw_string(
+        &mut self,
+        context: &GlContext,
+        font: &Font,
+        str: &str,
+        loc: Point2<i32>,
+        color: Color4,
+    ) {
+        self.start_batch(BatchKind::Text(font.clone()));
+        let mesh_builder = &mut self.batch_mesh_builder;
+        font.glyph_quads(context, str, loc.cast().unwrap(), |quad| {
+            let [a, b, c, d] = quad.map(|(pos, uv)| {
+                mesh_builder.vert_pod(ImageVert {
+                    pos: Point2::from_vec(pos),
+                    uv: Point2::from_vec(uv),
+                    color,
+                })
+            });
+            mesh_builder.triangle(a, b, c);
+            mesh_builder.triangle(b, c, d);
         });
-        self.image_mesh_builder.triangle(a, b, c);
-        self.image_mesh_builder.triangle(b, c, d);
+    }
+
+    /// Queues an overlay showing the `FrameStats` of the previous frame, with its top left corner
+    /// at `loc`. `GlContext::end_frame` must be called each frame for the stats to be updated.
+    pub fn draw_frame_stats(&mut self, context: &GlContext, font: &Font, loc: Point2<i32>) {
+        self.draw_text_overlay(context, font, &context.frame_stats().to_string(), loc);
+    }
+
+    /// Queues an overlay showing the average time of each of the `GpuProfiler`'s scopes, with its
+    /// top left corner at `loc`.
+    pub fn draw_gpu_profiler(
+        &mut self,
+        context: &GlContext,
+        font: &Font,
+        profiler: &GpuProfiler,
+        loc: Point2<i32>,
+    ) {
+        self.draw_text_overlay(context, font, &profiler.to_string(), loc);
+    }
 
-        let image_mesh =
-            if tex.is_srgb() { &mut self.image_mesh_srgb } else { &mut self.image_mesh_linear };
-        image_mesh.build_from(&self.image_mesh_builder, MeshUsage::StreamDraw);
-        image_mesh.draw(surface, &ImageUniforms { matrix, color: Color4::WHITE, tex });
+    /// Draws lines of text on a translucent background.
+    fn draw_text_overlay(
+        &mut self,
+        context: &GlContext,
+        font: &Font,
+        text: &str,
+        loc: Point2<i32>,
+    ) {
+        let line_height = font.advance_y();
+        let width = text.lines().map(|line| font.string_width(context, line)).fold(0.0, f32::max);
+        let height = line_height * text.lines().count() as i32;
+
+        let padding = 4;
+        self.fill_rect(
+            Rect::new(loc, loc + vec2(width.ceil() as i32 + padding * 2, height + padding * 2)),
+            Color4::from_srgba(0.0, 0.0, 0.0, 0.6),
+        );
+        for (i, line) in text.lines().enumerate() {
+            let line_loc = loc + vec2(padding, padding + line_height * i as i32);
+            self.draw_string(context, font, line, line_loc, Color4::WHITE);
+        }
+    }
 
-        self.image_mesh_builder.clear();
+    /// Queues an image to be drawn at `pos`, scaled by `scale`. Both the position and size are
+    /// scaled.
+    pub fn draw_image(&mut self, tex: &Rc<Texture2d>, pos: Point2<f32>, scale: f32) {
+        let size: Vector2<f32> = tex.size().cast().unwrap();
+        let pos = pos * scale;
+        self.queue_image_quad(tex, pos, pos + size * scale, point2(0.0, 0.0), point2(1.0, 1.0));
     }
 
-    /// Draws part of an image. Unlike most other functions on `Draw2d`, this draws the image immediately.
+    /// Queues part of an image to be drawn. `start` and `end` are in pixels within the image, and
+    /// the part is stretched to cover `start_pos` to `end_pos`.
     pub fn draw_part_of_image(
         &mut self,
-        surface: &(impl Surface + ?Sized),
-        tex: &Texture2d,
+        tex: &Rc<Texture2d>,
         start: Point2<i32>,
         end: Point2<i32>,
         start_pos: Point2<f32>,
         end_pos: Point2<f32>,
-        matrix: Matrix4<f32>,
     ) {
-        let start: Point2<f32> = start.cast().unwrap();
-        let end: Point2<f32> = end.cast().unwrap();
-        let start2 = point2(start.x / tex.size().x as f32, start.y / tex.size().y as f32);
-        let end2 = point2(end.x / tex.size().x as f32, end.y / tex.size().y as f32);
-
-        let a = self.image_mesh_builder.vert(ImageVert {
-            pos: start_pos,
-            uv: start2,
-            color: Color4::WHITE,
-        });
-        let b = self.image_mesh_builder.vert(ImageVert {
+        let size: Vector2<f32> = tex.size().cast().unwrap();
+        let start_uv = point2(start.x as f32 / size.x, start.y as f32 / size.y);
+        let end_uv = point2(end.x as f32 / size.x, end.y as f32 / size.y);
+        self.queue_image_quad(tex, start_pos, end_pos, start_uv, end_uv);
+    }
+
+    /// Queues a nine-patch image to be drawn covering `dest`, multiplied by `color`. The corners
+    /// are drawn at their original size, the edges are stretched along their length, and the
+    /// center is stretched to fill the rest, so borders don't get distorted when the image is
+    /// resized. If `dest` is smaller than the margins, the margins are shrunk to fit.
+    pub fn draw_nine_patch(
+        &mut self,
+        tex: &Rc<Texture2d>,
+        margins: NinePatchMargins,
+        dest: Rect<f32>,
+        color: Color4,
+    ) {
+        let tex_size: Vector2<f32> = tex.size().cast().unwrap();
+        let dest_size = dest.size();
+        let (left, right) = (margins.left as f32, margins.right as f32);
+        let (top, bottom) = (margins.top as f32, margins.bottom as f32);
+        let scale_x = (dest_size.x / (left + right)).min(1.0);
+        let scale_y = (dest_size.y / (top + bottom)).min(1.0);
+
+        let xs =
+            [dest.start.x, dest.start.x + left * scale_x, dest.end.x - right * scale_x, dest.end.x];
+        let ys =
+            [dest.start.y, dest.start.y + top * scale_y, dest.end.y - bottom * scale_y, dest.end.y];
+        let us = [0.0, left / tex_size.x, 1.0 - right / tex_size.x, 1.0];
+        let vs = [0.0, top / tex_size.y, 1.0 - bottom / tex_size.y, 1.0];
+
+        self.start_batch(BatchKind::Image(tex.clone()));
+        for y in 0..3 {
+            for x in 0..3 {
+                self.push_image_quad(
+                    point2(xs[x], ys[y]),
+                    point2(xs[x + 1], ys[y + 1]),
+                    point2(us[x], vs[y]),
+                    point2(us[x + 1], vs[y + 1]),
+                    color,
+                );
+            }
+        }
+    }
+
+    /// Starts queuing sprites from `atlas`. All of the sprites are rendered with a single draw
+    /// call, along with other queued drawing.
+    pub fn sprite_batch(&mut self, atlas: &TextureAtlas) -> SpriteBatch<'_> {
+        self.start_batch(BatchKind::Image(atlas.texture().clone()));
+        SpriteBatch { draw_2d: self }
+    }
+
+    fn queue_image_quad(
+        &mut self,
+        tex: &Rc<Texture2d>,
+        start_pos: Point2<f32>,
+        end_pos: Point2<f32>,
+        start_uv: Point2<f32>,
+        end_uv: Point2<f32>,
+    ) {
+        self.start_batch(BatchKind::Image(tex.clone()));
+        self.push_image_quad(start_pos, end_pos, start_uv, end_uv, Color4::WHITE);
+    }
+
+    /// Adds a quad to the current batch, which must be an image batch.
+    fn push_image_quad(
+        &mut self,
+        start_pos: Point2<f32>,
+        end_pos: Point2<f32>,
+        start_uv: Point2<f32>,
+        end_uv: Point2<f32>,
+        color: Color4,
+    ) {
+        let mesh_builder = &mut self.batch_mesh_builder;
+        let a = mesh_builder.vert_pod(ImageVert { pos: start_pos, uv: start_uv, color });
+        let b = mesh_builder.vert_pod(ImageVert {
             pos: point2(end_pos.x, start_pos.y),
-            uv: point2(end2.x, start2.y),
-            color: Color4::WHITE,
+            uv: point2(end_uv.x, start_uv.y),
+            color,
         });
-        let c = self.image_mesh_builder.vert(ImageVert {
+        let c = mesh_builder.vert_pod(ImageVert {
             pos: point2(start_pos.x, end_pos.y),
-            uv: point2(start2.x, end2.y),
-            color: Color4::WHITE,
-        });
-        let d = self.image_mesh_builder.vert(ImageVert {
-            pos: end_pos,
-            uv: end2,
-            color: Color4::WHITE,
+            uv: point2(start_uv.x, end_uv.y),
+            color,
         });
-        self.image_mesh_builder.triangle(a, b, c);
-        self.image_mesh_builder.triangle(b, c, d);
+        let d = mesh_builder.vert_pod(ImageVert { pos: end_pos, uv: end_uv, color });
+        mesh_builder.triangle(a, b, c);
+        mesh_builder.triangle(b, c, d);
+    }
+}
+
+/// The sizes of a nine-patch image's borders, in pixels; see `Draw2d::draw_nine_patch`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NinePatchMargins {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+impl NinePatchMargins {
+    /// Margins that are the same size on every side.
+    pub fn uniform(size: i32) -> Self {
+        Self { left: size, right: size, top: size, bottom: size }
+    }
+}
 
-        let image_mesh =
-            if tex.is_srgb() { &mut self.image_mesh_srgb } else { &mut self.image_mesh_linear };
-        image_mesh.build_from(&self.image_mesh_builder, MeshUsage::StreamDraw);
-        image_mesh.draw(surface, &ImageUniforms { matrix, color: Color4::WHITE, tex });
+/// A texture along with the margins to draw it with as a nine-patch.
+#[derive(Clone)]
+pub struct NinePatch {
+    pub texture: Rc<Texture2d>,
+    pub margins: NinePatchMargins,
+}
 
-        self.image_mesh_builder.clear();
+impl NinePatch {
+    pub fn draw(&self, draw_2d: &mut Draw2d, dest: Rect<f32>, color: Color4) {
+        draw_2d.draw_nine_patch(&self.texture, self.margins, dest, color);
+    }
+}
+
+/// Queues sprites from a `TextureAtlas`; created with `Draw2d::sprite_batch`.
+pub struct SpriteBatch<'a> {
+    draw_2d: &'a mut Draw2d,
+}
+
+impl<'a> SpriteBatch<'a> {
+    /// Queues a region of the atlas to be drawn covering `dest`, multiplied by `color`. The
+    /// region must come from the atlas the batch was created with.
+    pub fn draw(&mut self, region: AtlasRegion, dest: Rect<f32>, color: Color4) {
+        self.draw_2d.push_image_quad(dest.start, dest.end, region.uv.start, region.uv.end, color);
+    }
+
+    /// Queues a region of the atlas to be drawn at its original size, with its top left corner
+    /// at `pos`.
+    pub fn draw_at(&mut self, region: AtlasRegion, pos: Point2<f32>, color: Color4) {
+        let size: Vector2<f32> = region.size().cast().unwrap();
+        self.draw(region, Rect::new(pos, pos + size), color);
+    }
+}
+
+/// The maximum distance, in pixels, between a curve and the line segments it's drawn with.
+const CURVE_TOLERANCE: f32 = 0.2;
+
+/// Adds points along a cubic Bézier curve to `out`, excluding its first point. The number of
+/// points is chosen so that the curve is within `CURVE_TOLERANCE` of the line segments between
+/// them, using Wang's formula.
+fn flatten_cubic_bezier(points: [Point2<f32>; 4], out: &mut Vec<Point2<f32>>) {
+    let [a, b, c, d] = points;
+    let max_second_difference =
+        (a - b * 2.0 + c.to_vec()).magnitude().max((b - c * 2.0 + d.to_vec()).magnitude());
+    let segments =
+        (0.75 * max_second_difference / CURVE_TOLERANCE).sqrt().ceil().clamp(1.0, 1024.0);
+    let segments = segments as usize;
+    for i in 1..=segments {
+        let t = i as f32 / segments as f32;
+        let u = 1.0 - t;
+        let pos = a.to_vec() * (u * u * u)
+            + b.to_vec() * (3.0 * u * u * t)
+            + c.to_vec() * (3.0 * u * t * t)
+            + d.to_vec() * (t * t * t);
+        out.push(Point2::from_vec(pos));
+    }
+}
+
+/// Returns the outline of a rectangle with rounded corners, clockwise from the top left corner.
+fn rounded_rect_verts(rect: Rect<f32>, corner_radius: f32) -> Vec<Point2<f32>> {
+    let size = rect.size();
+    let radius = corner_radius.min(size.x * 0.5).min(size.y * 0.5);
+    if radius <= 0.0 {
+        return vec![
+            rect.start,
+            point2(rect.end.x, rect.start.y),
+            rect.end,
+            point2(rect.start.x, rect.end.y),
+        ];
+    }
+
+    // More segments for larger corners, so they stay smooth
+    let segments = (radius.sqrt() * 2.0).ceil().clamp(2.0, 16.0) as usize;
+    let centers = [
+        point2(rect.start.x + radius, rect.start.y + radius),
+        point2(rect.end.x - radius, rect.start.y + radius),
+        point2(rect.end.x - radius, rect.end.y - radius),
+        point2(rect.start.x + radius, rect.end.y - radius),
+    ];
+    let mut verts = Vec::with_capacity(centers.len() * (segments + 1));
+    for (i, center) in centers.iter().enumerate() {
+        // The y axis points down, so increasing angles go clockwise.
+        let start_angle = Rad::turn_div_2() + Rad::turn_div_4() * i as f32;
+        for j in 0..=segments {
+            let angle = start_angle + Rad::turn_div_4() * (j as f32 / segments as f32);
+            verts.push(center + vec2(angle.cos(), angle.sin()) * radius);
+        }
     }
+    verts
 }
 
 /// Returns the vector 90 degrees counterclockwise from the given vector.
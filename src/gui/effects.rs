@@ -0,0 +1,180 @@
+use crate::gl::*;
+use cgmath::*;
+
+use super::post_process::*;
+
+/// The largest radius, in texels, that `BlurEffect` supports.
+pub const MAX_BLUR_RADIUS: f32 = 32.0;
+
+// Intermediate results use a floating point format where possible, so effects work on HDR
+// images.
+fn create_target(context: &GlContext, size: Vector2<u32>) -> Framebuffer<Texture2d> {
+    let format = if context.is_webgl1() { TextureFormat::RGBA } else { TextureFormat::RGBA16F };
+    Framebuffer::new_with_texture(
+        context,
+        size,
+        format,
+        MinFilter::Linear,
+        MagFilter::Linear,
+        WrapMode::ClampToEdge,
+    )
+}
+
+/// A Gaussian blur, done as a horizontal pass followed by a vertical pass.
+///
+/// It can be rendered directly or added to a `PostProcessChain`.
+pub struct BlurEffect {
+    horizontal: PostProcessPass,
+    vertical: PostProcessPass,
+    target: Option<Framebuffer<Texture2d>>,
+    context: GlContext,
+    /// The blur radius in texels, up to `MAX_BLUR_RADIUS`.
+    pub radius: f32,
+}
+
+impl BlurEffect {
+    pub fn new(context: &GlContext, radius: f32, convert_to_srgb: bool) -> Self {
+        let source = include_str!("shaders/blur_frag.glsl");
+        let horizontal = PostProcessPass::new(context, source, false);
+        let vertical = horizontal.new_sharing_mesh(context, source, convert_to_srgb);
+        Self { horizontal, vertical, target: None, context: context.clone(), radius }
+    }
+
+    /// Blurs `input`, covering all of `output`.
+    pub fn render(&mut self, input: &Texture2d, output: &(impl Surface + ?Sized)) {
+        let size = input.size();
+        if !matches!(&self.target, Some(target) if target.size() == size) {
+            self.target = Some(create_target(&self.context, size));
+        }
+        let target = self.target.as_ref().unwrap();
+
+        self.horizontal.params = [1.0, 0.0, self.radius, 0.0];
+        self.horizontal.render(&[input], target);
+        self.vertical.params = [0.0, 1.0, self.radius, 0.0];
+        self.vertical.render(&[&target.attachment], output);
+    }
+}
+
+impl PostProcessEffect for BlurEffect {
+    fn render(&mut self, input: &Texture2d, output: &dyn Surface) {
+        BlurEffect::render(self, input, output);
+    }
+}
+
+struct BloomLevel {
+    down: Framebuffer<Texture2d>,
+    up: Framebuffer<Texture2d>,
+}
+
+/// Makes bright parts of an image glow.
+///
+/// The parts brighter than `threshold` are extracted at half resolution, then repeatedly
+/// downsampled. The levels are upsampled and added together, and the result is added to the
+/// original image. This works best on HDR images, such as the framebuffer of an `HdrPipeline`,
+/// before tone mapping.
+///
+/// It can be rendered directly or added to a `PostProcessChain`.
+pub struct BloomEffect {
+    prefilter: PostProcessPass,
+    downsample: PostProcessPass,
+    upsample: PostProcessPass,
+    composite: PostProcessPass,
+    levels: Vec<BloomLevel>,
+    context: GlContext,
+    /// Only colors with a component brighter than this contribute to the bloom.
+    pub threshold: f32,
+    /// How gradually colors near the threshold start to contribute, from 0 (a hard cutoff) to 1.
+    pub soft_knee: f32,
+    /// How much of the bloom is added to the image.
+    pub intensity: f32,
+    /// The maximum number of downsampled levels, which determines how far the bloom spreads.
+    pub num_levels: usize,
+}
+
+impl BloomEffect {
+    pub fn new(context: &GlContext, convert_to_srgb: bool) -> Self {
+        let prefilter =
+            PostProcessPass::new(context, include_str!("shaders/bloom_prefilter_frag.glsl"), false);
+        let downsample = prefilter.new_sharing_mesh(
+            context,
+            include_str!("shaders/bloom_downsample_frag.glsl"),
+            false,
+        );
+        let upsample = prefilter.new_sharing_mesh(
+            context,
+            include_str!("shaders/bloom_upsample_frag.glsl"),
+            false,
+        );
+        let composite = prefilter.new_sharing_mesh(
+            context,
+            include_str!("shaders/bloom_composite_frag.glsl"),
+            convert_to_srgb,
+        );
+        Self {
+            prefilter,
+            downsample,
+            upsample,
+            composite,
+            levels: vec![],
+            context: context.clone(),
+            threshold: 1.0,
+            soft_knee: 0.5,
+            intensity: 0.5,
+            num_levels: 6,
+        }
+    }
+
+    /// Adds bloom to `input`, covering all of `output`.
+    pub fn render(&mut self, input: &Texture2d, output: &(impl Surface + ?Sized)) {
+        self.update_levels(input.size());
+        let levels = &self.levels;
+
+        self.prefilter.params = [self.threshold, self.soft_knee, 0.0, 0.0];
+        self.prefilter.render(&[input], &levels[0].down);
+        for pair in levels.windows(2) {
+            self.downsample.render(&[&pair[0].down.attachment], &pair[1].down);
+        }
+
+        // The smallest level doesn't have anything to add, so it's used directly.
+        let (smallest, rest) = levels.split_last().unwrap();
+        let mut bloom = &smallest.down.attachment;
+        for level in rest.iter().rev() {
+            self.upsample.render(&[bloom, &level.down.attachment], &level.up);
+            bloom = &level.up.attachment;
+        }
+
+        self.composite.params = [self.intensity, 0.0, 0.0, 0.0];
+        self.composite.render(&[input, bloom], output);
+    }
+
+    // Creates the levels for the given input size, if they don't exist yet.
+    fn update_levels(&mut self, input_size: Vector2<u32>) {
+        let mut sizes = vec![];
+        let mut size = input_size / 2;
+        while sizes.len() < self.num_levels.max(1) && size.x >= 1 && size.y >= 1 {
+            sizes.push(size);
+            size /= 2;
+        }
+        if sizes.is_empty() {
+            sizes.push(vec2(1, 1));
+        }
+
+        let up_to_date = self.levels.len() == sizes.len()
+            && self.levels.iter().zip(&sizes).all(|(level, &size)| level.down.size() == size);
+        if !up_to_date {
+            self.levels = sizes
+                .into_iter()
+                .map(|size| BloomLevel {
+                    down: create_target(&self.context, size),
+                    up: create_target(&self.context, size),
+                })
+                .collect();
+        }
+    }
+}
+
+impl PostProcessEffect for BloomEffect {
+    fn render(&mut self, input: &Texture2d, output: &dyn Surface) {
+        BloomEffect::render(self, input, output);
+    }
+}
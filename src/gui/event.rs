@@ -1,30 +1,128 @@
 use cgmath::*;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
 #[cfg(target_arch = "wasm32")]
-use web_sys::{window, KeyboardEvent, MouseEvent};
+use web_sys::{window, HtmlCanvasElement, KeyboardEvent, MouseEvent, WheelEvent};
 
 // TODO: can Clone be removed for these types?
 /// An event.
+///
+/// Mouse positions and movements are in physical pixels, the same units as the size of the
+/// screen surface. Divide them by `EventState::scale_factor` to get logical pixels.
 #[derive(Clone, Debug)]
 pub enum Event {
     KeyDown(Key),
     KeyUp(Key),
     CharEntered(char),
+    /// Text read from the clipboard, in response to `Clipboard::request_paste`.
+    Paste(String),
+    /// An input method editor (IME) started composing text, such as Japanese or Chinese text
+    /// built from several key presses.
+    ///
+    /// Composition events are only sent on wasm, and browsers only send them while an editable
+    /// element has focus. Natively, GLFW doesn't report the text being composed; the finished
+    /// text is sent as `CharEntered` events.
+    CompositionStart,
+    /// The text being composed changed. It's shown where it will be inserted, but isn't part of
+    /// the text until the composition ends.
+    CompositionUpdate(String),
+    /// The composition finished, and the given text should be inserted. It's empty if the
+    /// composition was cancelled.
+    CompositionEnd(String),
     MouseDown(MouseButton, Point2<i32>),
     MouseUp(MouseButton, Point2<i32>),
     MouseMove {
         pos: Point2<i32>,
         movement: Vector2<i32>,
     },
+    /// The second click of a double click, sent after its `MouseDown`. See
+    /// `Gui::set_double_click_interval`.
+    DoubleClick(MouseButton, Point2<i32>),
+    /// The cursor entered the window. Components are also sent this when the cursor moves onto
+    /// them.
     MouseEnter,
+    /// The cursor left the window. Components are also sent this when the cursor moves off of
+    /// them.
     MouseLeave,
     FocusGained,
     FocusLost,
     /// When this is received, apps should call something like `self.screen_surface.set_size(&self.context, new_size);`
+    ///
+    /// On wasm, this is sent when the canvas's CSS size or the device pixel ratio changes, and the
+    /// canvas's drawing buffer has already been resized to match.
     // TODO: do this automatically
     WindowResized(Vector2<u32>),
+    /// The content scale (the ratio between physical pixels and logical units) changed, e.g.
+    /// because the window moved to a monitor with a different DPI. Apps with a GUI should pass
+    /// it to `Theme::set_scale_factor`. The initial scale factor is in `EventState`.
+    ScaleFactorChanged(f32),
+    /// The window was minimized. Only sent on desktop.
+    Minimized,
+    /// The window was maximized. Only sent on desktop.
+    Maximized,
+    /// The window was restored after being minimized or maximized. Only sent on desktop.
+    Restored,
+    /// The window was moved to the given position, in screen coordinates. Only sent on desktop.
+    WindowMoved(Point2<i32>),
+    /// A monitor was connected or disconnected; `Monitor::all` returns the new monitors. Their
+    /// indices may have changed. Only sent on desktop.
+    MonitorsChanged,
     PointerLocked,
     PointerUnlocked,
-    Scroll(f64),
+    /// How far the mouse moved while the pointer is locked, for things like first-person camera
+    /// controls. It's sent along with `MouseMove`, but it isn't accelerated if the platform
+    /// supports that, and it's in screen coordinates (CSS pixels on wasm) rather than physical
+    /// pixels. Natively, it's also sent when `MouseMove` events are discarded because the window
+    /// was resized.
+    RawMouseMotion(Vector2<f64>),
+    /// The mouse wheel or trackpad scrolled. Positive deltas scroll down and to the right, i.e.
+    /// towards the end of the content. Trackpads can send many small, fractional deltas.
+    Scroll {
+        delta: Vector2<f64>,
+        unit: ScrollUnit,
+    },
+    /// A file was dragged onto the window and dropped. If several files are dropped at once,
+    /// one event is sent for each.
+    #[cfg(not(target_arch = "wasm32"))]
+    FileDropped(PathBuf),
+    /// A file was dragged onto the canvas and dropped. If several files are dropped at once,
+    /// one event is sent for each. Browsers don't give pages the file's path, so its contents
+    /// are read instead; the event is sent once they've been read.
+    #[cfg(target_arch = "wasm32")]
+    FileDropped {
+        name: String,
+        bytes: Vec<u8>,
+    },
+}
+
+/// The number of lines each notch of a mouse wheel scrolls by.
+pub const LINES_PER_NOTCH: f64 = 3.0;
+
+/// The unit of an `Event::Scroll`'s delta.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ScrollUnit {
+    /// Lines of text, as from a mouse wheel; each notch scrolls `LINES_PER_NOTCH` lines.
+    Lines,
+    /// Physical pixels, as from a trackpad or a browser that scrolls smoothly.
+    Pixels,
+}
+
+impl ScrollUnit {
+    /// Converts a delta in this unit to pixels, given the height of a line.
+    pub fn to_pixels(self, delta: Vector2<f64>, line_height: f64) -> Vector2<f64> {
+        match self {
+            ScrollUnit::Lines => delta * line_height,
+            ScrollUnit::Pixels => delta,
+        }
+    }
+
+    /// Converts a delta in this unit to lines, given the height of a line.
+    pub fn to_lines(self, delta: Vector2<f64>, line_height: f64) -> Vector2<f64> {
+        match self {
+            ScrollUnit::Lines => delta,
+            ScrollUnit::Pixels => delta / line_height,
+        }
+    }
 }
 
 pub type Keycode = String;
@@ -41,12 +139,35 @@ pub struct Key {
     pub ctrl: bool,
     pub alt: bool,
     pub is_modifier: bool,
+    /// True if this is a `KeyDown` sent because the key is being held down, rather than the
+    /// initial press. Always false for `KeyUp`.
+    pub repeat: bool,
+}
+
+/// Which modifier keys are held down. As with `Key::code`, the left and right keys aren't
+/// distinguished.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    /// True if none of the modifier keys are held down.
+    pub fn is_empty(&self) -> bool {
+        !self.shift && !self.ctrl && !self.alt
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
 pub(crate) fn char_from_js(js_key: &KeyboardEvent) -> Option<char> {
     // TODO: find a better way to check if the char is printable
     let key = js_key.key();
+    // Shortcuts such as Ctrl+C don't enter text.
+    if js_key.ctrl_key() || js_key.meta_key() {
+        return None;
+    }
     if key.len() == 1 {
         Some(key.chars().next().unwrap())
     } else {
@@ -55,6 +176,11 @@ pub(crate) fn char_from_js(js_key: &KeyboardEvent) -> Option<char> {
 }
 
 impl Key {
+    /// The modifier keys that were held down when this key was pressed or released.
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers { shift: self.shift, ctrl: self.ctrl, alt: self.alt }
+    }
+
     #[cfg(target_arch = "wasm32")]
     pub(crate) fn from_js(js_key: &KeyboardEvent) -> Self {
         Self {
@@ -70,11 +196,16 @@ impl Key {
             is_modifier: js_key.key() == "Shift"
                 || js_key.key() == "Control"
                 || js_key.key() == "Alt",
+            repeat: js_key.repeat(),
         }
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    pub(crate) fn from_glfw(key: glfw::Key, modifiers: glfw::Modifiers) -> Option<Self> {
+    pub(crate) fn from_glfw(
+        key: glfw::Key,
+        modifiers: glfw::Modifiers,
+        repeat: bool,
+    ) -> Option<Self> {
         use glfw::Key::*;
         let code = match key {
             Space => Some("Space"),
@@ -181,6 +312,7 @@ impl Key {
                 || key == RightShift
                 || key == RightControl
                 || key == RightAlt,
+            repeat,
         })
     }
 }
@@ -218,31 +350,56 @@ impl MouseButton {
     }
 }
 
+/// Converts a length from CSS pixels to physical pixels, to match the canvas's drawing buffer.
 #[cfg(target_arch = "wasm32")]
-fn mouse_pos_from_js(event: MouseEvent) -> Point2<i32> {
-    point2(event.offset_x(), event.offset_y())
+fn css_to_physical(x: i32) -> i32 {
+    (x as f64 * window().unwrap().device_pixel_ratio()).round() as i32
+}
+
+#[cfg(target_arch = "wasm32")]
+fn mouse_pos_from_js(event: &MouseEvent) -> Point2<i32> {
+    point2(css_to_physical(event.offset_x()), css_to_physical(event.offset_y()))
 }
 
 #[cfg(target_arch = "wasm32")]
 pub(crate) fn mouse_down_event_from_js(event: MouseEvent) -> Option<Event> {
     let button = MouseButton::from_js(event.button())?;
-    Some(Event::MouseDown(button, mouse_pos_from_js(event)))
+    Some(Event::MouseDown(button, mouse_pos_from_js(&event)))
 }
 
 #[cfg(target_arch = "wasm32")]
 pub(crate) fn mouse_up_event_from_js(event: MouseEvent) -> Option<Event> {
     let button = MouseButton::from_js(event.button())?;
-    Some(Event::MouseUp(button, mouse_pos_from_js(event)))
+    Some(Event::MouseUp(button, mouse_pos_from_js(&event)))
 }
 
 #[cfg(target_arch = "wasm32")]
 pub(crate) fn mouse_move_event_from_js(event: MouseEvent) -> Option<Event> {
     Some(Event::MouseMove {
-        movement: vec2(event.movement_x(), event.movement_y()),
-        pos: mouse_pos_from_js(event),
+        movement: vec2(css_to_physical(event.movement_x()), css_to_physical(event.movement_y())),
+        pos: mouse_pos_from_js(&event),
     })
 }
 
+/// Converts a wheel event's delta to a scroll event, in lines or physical pixels depending on the
+/// event's `deltaMode`. Pages are converted to pixels using the canvas's size.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn scroll_event_from_js(event: &WheelEvent, canvas: &HtmlCanvasElement) -> Event {
+    let delta = vec2(event.delta_x(), event.delta_y());
+    let scale_factor = window().unwrap().device_pixel_ratio();
+    match event.delta_mode() {
+        WheelEvent::DOM_DELTA_LINE => Event::Scroll { delta, unit: ScrollUnit::Lines },
+        WheelEvent::DOM_DELTA_PAGE => {
+            let page = vec2(canvas.client_width() as f64, canvas.client_height() as f64);
+            Event::Scroll {
+                delta: vec2(delta.x * page.x, delta.y * page.y) * scale_factor,
+                unit: ScrollUnit::Pixels,
+            }
+        }
+        _ => Event::Scroll { delta: delta * scale_factor, unit: ScrollUnit::Pixels },
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 pub fn get_window_size() -> Vector2<u32> {
     let window = window().unwrap();
@@ -252,6 +409,21 @@ pub fn get_window_size() -> Vector2<u32> {
     )
 }
 
+/// Converts a cursor position from screen coordinates to framebuffer pixels. These differ on some
+/// platforms, such as macOS with a high-DPI display.
+#[cfg(not(target_arch = "wasm32"))]
+fn cursor_pos_from_glfw(window: &glfw::Window, x: f64, y: f64) -> Point2<i32> {
+    let (window_width, window_height) = window.get_size();
+    let (framebuffer_width, framebuffer_height) = window.get_framebuffer_size();
+    if window_width == 0 || window_height == 0 {
+        return point2(x as i32, y as i32);
+    }
+    point2(
+        (x * framebuffer_width as f64 / window_width as f64) as i32,
+        (y * framebuffer_height as f64 / window_height as f64) as i32,
+    )
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn event_from_glfw(
     event: &glfw::WindowEvent,
@@ -261,7 +433,7 @@ pub fn event_from_glfw(
     match *event {
         glfw::WindowEvent::MouseButton(button, action, _) => {
             let (cursor_x, cursor_y) = window.get_cursor_pos();
-            let cursor_pos = point2(cursor_x as i32, cursor_y as i32);
+            let cursor_pos = cursor_pos_from_glfw(window, cursor_x, cursor_y);
             if action == glfw::Action::Release {
                 Some(Event::MouseUp(MouseButton::from_glfw(button)?, cursor_pos))
             } else {
@@ -269,7 +441,7 @@ pub fn event_from_glfw(
             }
         }
         glfw::WindowEvent::CursorPos(cursor_x, cursor_y) => {
-            let cursor_pos = point2(cursor_x as i32, cursor_y as i32);
+            let cursor_pos = cursor_pos_from_glfw(window, cursor_x, cursor_y);
             let res = if let Some(prev_cursor_pos) = prev_cursor_pos {
                 let movement = cursor_pos - *prev_cursor_pos;
                 Some(Event::MouseMove { pos: cursor_pos, movement })
@@ -281,7 +453,7 @@ pub fn event_from_glfw(
             res
         }
         glfw::WindowEvent::Key(key, _, action, modifiers) => {
-            let key = Key::from_glfw(key, modifiers)?;
+            let key = Key::from_glfw(key, modifiers, action == glfw::Action::Repeat)?;
             if action == glfw::Action::Release {
                 Some(Event::KeyUp(key))
             } else {
@@ -294,7 +466,17 @@ pub fn event_from_glfw(
         glfw::WindowEvent::FramebufferSize(width, height) => {
             Some(Event::WindowResized(vec2(width as u32, height as u32)))
         }
-        glfw::WindowEvent::Scroll(_x, y) => Some(Event::Scroll(-y.signum())),
+        glfw::WindowEvent::ContentScale(x, _y) => Some(Event::ScaleFactorChanged(x)),
+        glfw::WindowEvent::Iconify(true) => Some(Event::Minimized),
+        glfw::WindowEvent::Maximize(true) => Some(Event::Maximized),
+        glfw::WindowEvent::Iconify(false) | glfw::WindowEvent::Maximize(false) => {
+            Some(Event::Restored)
+        }
+        glfw::WindowEvent::Pos(x, y) => Some(Event::WindowMoved(point2(x, y))),
+        // GLFW's offsets are in notches, and positive offsets scroll up and to the left.
+        glfw::WindowEvent::Scroll(x, y) => {
+            Some(Event::Scroll { delta: vec2(-x, -y) * LINES_PER_NOTCH, unit: ScrollUnit::Lines })
+        }
         glfw::WindowEvent::Focus(true) => Some(Event::FocusGained),
         glfw::WindowEvent::Focus(false) => Some(Event::FocusLost),
         _ => None,
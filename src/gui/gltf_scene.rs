@@ -0,0 +1,384 @@
+#![cfg(feature = "gltf")]
+
+use cgmath::*;
+
+use crate::gl::*;
+
+use super::assets::*;
+
+/// A vertex of a `GltfPrimitive`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GltfVertex {
+    pub pos: Point3<f32>,
+    /// If the primitive doesn't have normals, they're calculated from its triangles.
+    pub normal: Vector3<f32>,
+    /// The first set of texture coordinates, or `(0, 0)` if there aren't any.
+    pub uv: Point2<f32>,
+    /// The first set of vertex colors, or white if there aren't any.
+    pub color: [f32; 4],
+}
+
+/// Part of a `GltfMesh` that uses a single material.
+#[derive(Clone, Debug)]
+pub struct GltfPrimitive {
+    pub vertices: Vec<GltfVertex>,
+    /// Three indices into `vertices` for each triangle.
+    pub indices: Vec<MeshIndex>,
+    /// An index into `GltfScene::materials`.
+    pub material: Option<usize>,
+}
+
+impl GltfPrimitive {
+    /// Creates a `MeshBuilder` containing the primitive. `vert` maps each vertex to a `V`.
+    pub fn mesh_builder<V: Vertex>(
+        &self,
+        mut vert: impl FnMut(&GltfVertex) -> V,
+    ) -> MeshBuilder<V, Triangles> {
+        let mut builder = MeshBuilder::new();
+        for vertex in &self.vertices {
+            builder.vert(vert(vertex));
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            builder.triangle(triangle[0], triangle[1], triangle[2]);
+        }
+        builder
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfMesh {
+    pub name: Option<String>,
+    /// Only triangle primitives are loaded; points and lines are skipped.
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+/// A node in the scene's hierarchy.
+#[derive(Clone, Debug)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// The transform from the node's space to the scene's, including its ancestors' transforms.
+    pub transform: Matrix4<f32>,
+    /// An index into `GltfScene::meshes`.
+    pub mesh: Option<usize>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GltfAlphaMode {
+    Opaque,
+    /// Fragments with an alpha below the cutoff are discarded.
+    Mask(f32),
+    Blend,
+}
+
+/// A metallic-roughness material. Texture fields are indices into `GltfScene::textures`.
+#[derive(Clone, Debug)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness is in the green channel and metalness in the blue channel.
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: GltfAlphaMode,
+    pub double_sided: bool,
+}
+
+/// An image used by a `GltfScene`'s textures.
+#[derive(Clone, Debug)]
+pub struct GltfImage {
+    /// The asset identifier the image is loaded as. Images embedded in the glTF file are
+    /// identified by the file's identifier followed by `#image` and the image's index.
+    pub id: String,
+    /// The encoded image and its MIME type, if it's embedded in the glTF file.
+    pub embedded: Option<(Vec<u8>, String)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfTexture {
+    /// The asset identifier of the texture's image.
+    pub image: String,
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    pub wrap_mode: WrapMode,
+}
+
+impl GltfTexture {
+    /// The settings to pass to `Assets::get_texture`. Base color and emissive textures should
+    /// use `TextureFormat::SRGBA`, and other textures `TextureFormat::RGBA`.
+    pub fn settings(&self, format: TextureFormat) -> TextureSettings {
+        TextureSettings {
+            format,
+            min_filter: self.min_filter,
+            mag_filter: self.mag_filter,
+            wrap_mode: self.wrap_mode,
+        }
+    }
+}
+
+/// A scene loaded from a glTF 2.0 file, which can be either JSON (`.gltf`) or binary (`.glb`).
+/// Requires the `gltf` feature.
+///
+/// Use `Assets::get_gltf` or `Assets::load_gltf` to load a scene. Meshes can be turned into
+/// `MeshBuilder`s with `GltfPrimitive::mesh_builder`, and textures loaded with
+/// `Assets::get_texture`. Animations, skins, cameras and lights aren't supported.
+#[derive(Clone, Debug)]
+pub struct GltfScene {
+    /// The nodes of the default scene, or the first scene if there's no default.
+    pub nodes: Vec<GltfNode>,
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub images: Vec<GltfImage>,
+}
+
+impl GltfScene {
+    /// Parses a glTF file. `url` is the file's asset identifier, which external files are
+    /// relative to. `load_file` is called with the identifier of each external buffer.
+    pub fn parse(
+        url: &str,
+        bytes: &[u8],
+        mut load_file: impl FnMut(&str) -> Result<Vec<u8>, AssetError>,
+    ) -> Result<Self, AssetError> {
+        let decode_error = |message: String| AssetError::Decode { url: url.to_owned(), message };
+        let gltf::Gltf { document, mut blob } =
+            gltf::Gltf::from_slice(bytes).map_err(|err| decode_error(err.to_string()))?;
+
+        let mut buffers = vec![];
+        for buffer in document.buffers() {
+            let data = match buffer.source() {
+                gltf::buffer::Source::Bin => {
+                    blob.take().ok_or_else(|| decode_error("Missing binary chunk".to_owned()))?
+                }
+                gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+                    decode_data_uri(uri)
+                        .ok_or_else(|| decode_error("Invalid data URI".to_owned()))?
+                        .0
+                }
+                gltf::buffer::Source::Uri(uri) => load_file(&relative_url(url, uri))?,
+            };
+            if data.len() < buffer.length() {
+                return Err(decode_error(format!("Buffer {} is too short", buffer.index())));
+            }
+            buffers.push(data);
+        }
+
+        let mut images = vec![];
+        for image in document.images() {
+            let embedded_id = format!("{}#image{}", url, image.index());
+            images.push(match image.source() {
+                gltf::image::Source::Uri { uri, mime_type } if uri.starts_with("data:") => {
+                    let (data, data_mime_type) = decode_data_uri(uri)
+                        .ok_or_else(|| decode_error("Invalid data URI".to_owned()))?;
+                    let mime_type = mime_type.map_or(data_mime_type, |x| x.to_owned());
+                    GltfImage { id: embedded_id, embedded: Some((data, mime_type)) }
+                }
+                gltf::image::Source::Uri { uri, .. } => {
+                    GltfImage { id: relative_url(url, uri), embedded: None }
+                }
+                gltf::image::Source::View { view, mime_type } => {
+                    let start = view.offset();
+                    let data = buffers[view.buffer().index()]
+                        .get(start..start + view.length())
+                        .ok_or_else(|| decode_error("Image is outside its buffer".to_owned()))?;
+                    GltfImage {
+                        id: embedded_id,
+                        embedded: Some((data.to_vec(), mime_type.to_owned())),
+                    }
+                }
+            });
+        }
+
+        let textures = document
+            .textures()
+            .map(|texture| {
+                let sampler = texture.sampler();
+                GltfTexture {
+                    image: images[texture.source().index()].id.clone(),
+                    min_filter: match sampler.min_filter() {
+                        Some(gltf::texture::MinFilter::Nearest) => MinFilter::Nearest,
+                        Some(gltf::texture::MinFilter::Linear) => MinFilter::Linear,
+                        Some(gltf::texture::MinFilter::NearestMipmapNearest) => {
+                            MinFilter::NearestMipmapNearest
+                        }
+                        Some(gltf::texture::MinFilter::NearestMipmapLinear) => {
+                            MinFilter::NearestMipmapLinear
+                        }
+                        Some(gltf::texture::MinFilter::LinearMipmapNearest) => {
+                            MinFilter::LinearMipmapNearest
+                        }
+                        Some(gltf::texture::MinFilter::LinearMipmapLinear) | None => {
+                            MinFilter::LinearMipmapLinear
+                        }
+                    },
+                    mag_filter: match sampler.mag_filter() {
+                        Some(gltf::texture::MagFilter::Nearest) => MagFilter::Nearest,
+                        Some(gltf::texture::MagFilter::Linear) | None => MagFilter::Linear,
+                    },
+                    // Mirrored repeat isn't supported, and the wrap mode can't differ between
+                    // axes.
+                    wrap_mode: match sampler.wrap_s() {
+                        gltf::texture::WrappingMode::ClampToEdge => WrapMode::ClampToEdge,
+                        gltf::texture::WrappingMode::MirroredRepeat
+                        | gltf::texture::WrappingMode::Repeat => WrapMode::Repeat,
+                    },
+                }
+            })
+            .collect();
+
+        let materials = document
+            .materials()
+            .map(|material| {
+                let pbr = material.pbr_metallic_roughness();
+                GltfMaterial {
+                    name: material.name().map(|x| x.to_owned()),
+                    base_color: pbr.base_color_factor(),
+                    base_color_texture: pbr.base_color_texture().map(|x| x.texture().index()),
+                    metallic: pbr.metallic_factor(),
+                    roughness: pbr.roughness_factor(),
+                    metallic_roughness_texture: pbr
+                        .metallic_roughness_texture()
+                        .map(|x| x.texture().index()),
+                    normal_texture: material.normal_texture().map(|x| x.texture().index()),
+                    emissive: material.emissive_factor(),
+                    emissive_texture: material.emissive_texture().map(|x| x.texture().index()),
+                    alpha_mode: match material.alpha_mode() {
+                        gltf::material::AlphaMode::Opaque => GltfAlphaMode::Opaque,
+                        gltf::material::AlphaMode::Mask => {
+                            GltfAlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+                        }
+                        gltf::material::AlphaMode::Blend => GltfAlphaMode::Blend,
+                    },
+                    double_sided: material.double_sided(),
+                }
+            })
+            .collect();
+
+        let meshes = document
+            .meshes()
+            .map(|mesh| GltfMesh {
+                name: mesh.name().map(|x| x.to_owned()),
+                primitives: mesh
+                    .primitives()
+                    .filter(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles)
+                    .filter_map(|primitive| load_primitive(&primitive, &buffers))
+                    .collect(),
+            })
+            .collect();
+
+        let mut nodes = vec![];
+        if let Some(scene) = document.default_scene().or_else(|| document.scenes().next()) {
+            for node in scene.nodes() {
+                add_node(&node, Matrix4::identity(), &mut nodes);
+            }
+        }
+
+        Ok(Self { nodes, meshes, materials, textures, images })
+    }
+
+    /// Returns the identifiers of the external buffers a glTF file uses, which must be loaded
+    /// before it can be parsed.
+    pub(crate) fn external_buffers(url: &str, bytes: &[u8]) -> Result<Vec<String>, AssetError> {
+        let gltf = gltf::Gltf::from_slice(bytes)
+            .map_err(|err| AssetError::Decode { url: url.to_owned(), message: err.to_string() })?;
+        Ok(gltf
+            .document
+            .buffers()
+            .filter_map(|buffer| match buffer.source() {
+                gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => {
+                    Some(relative_url(url, uri))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+// Returns `None` if the primitive doesn't have positions.
+fn load_primitive(primitive: &gltf::Primitive, buffers: &[Vec<u8>]) -> Option<GltfPrimitive> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|x| x.as_slice()));
+    let positions: Vec<[f32; 3]> = reader.read_positions()?.collect();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|x| x.collect());
+    let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|x| x.into_f32().collect());
+    let colors: Option<Vec<[f32; 4]>> = reader.read_colors(0).map(|x| x.into_rgba_f32().collect());
+    let indices: Vec<MeshIndex> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().filter(|&i| (i as usize) < positions.len()).collect(),
+        None => (0..positions.len() as MeshIndex).collect(),
+    };
+
+    let mut vertices: Vec<GltfVertex> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, &pos)| GltfVertex {
+            pos: Point3::from(pos),
+            normal: normals
+                .as_ref()
+                .and_then(|x| x.get(i))
+                .map_or(Vector3::zero(), |&normal| Vector3::from(normal)),
+            uv: uvs.as_ref().and_then(|x| x.get(i)).map_or(point2(0.0, 0.0), |&uv| uv.into()),
+            color: colors.as_ref().and_then(|x| x.get(i)).copied().unwrap_or([1.0; 4]),
+        })
+        .collect();
+
+    if normals.is_none() {
+        // Each vertex gets the sum of the normals of the triangles it's part of, weighted by
+        // area.
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].pos);
+            let normal = (b - a).cross(c - a);
+            for &i in triangle {
+                vertices[i as usize].normal += normal;
+            }
+        }
+        for vertex in &mut vertices {
+            if vertex.normal.magnitude2() > 0.0 {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    }
+
+    Some(GltfPrimitive { vertices, indices, material: primitive.material().index() })
+}
+
+fn add_node(node: &gltf::Node, parent_transform: Matrix4<f32>, nodes: &mut Vec<GltfNode>) {
+    let transform = parent_transform * Matrix4::from(node.transform().matrix());
+    nodes.push(GltfNode {
+        name: node.name().map(|x| x.to_owned()),
+        transform,
+        mesh: node.mesh().map(|mesh| mesh.index()),
+    });
+    for child in node.children() {
+        add_node(&child, transform, nodes);
+    }
+}
+
+// Decodes a base64 `data:` URI into its contents and MIME type.
+fn decode_data_uri(uri: &str) -> Option<(Vec<u8>, String)> {
+    let (header, data) = uri.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+
+    let mut res = Vec::with_capacity(data.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut num_bits = 0;
+    for c in data.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        num_bits += 6;
+        if num_bits >= 8 {
+            num_bits -= 8;
+            res.push((bits >> num_bits) as u8);
+        }
+    }
+    Some((res, mime_type.to_owned()))
+}
@@ -1,12 +1,18 @@
 use crate::gl::*;
 use cgmath::*;
 use fxhash::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::mem;
+use std::rc::Rc;
 use uid::*;
+use wasm_stopwatch::*;
 
+use super::assets::*;
 use super::color::*;
 use super::draw_2d::*;
 use super::event::*;
+use super::style::*;
 use super::text::*;
 
 #[doc(hidden)]
@@ -15,16 +21,387 @@ pub struct WidgetId_(());
 
 pub type WidgetId = Id<WidgetId_>;
 
+#[doc(hidden)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct PopupId_(());
+
+/// Identifies a `Popup`. Unlike most widget IDs, this should be created once and reused each
+/// frame, so that `GuiEventResult::popup_dismissed` can refer to it.
+pub type PopupId = Id<PopupId_>;
+
+/// How far apart, in pixels, two clicks can be on each axis to count as a double click.
+const DOUBLE_CLICK_DISTANCE: i32 = 4;
+
+/// The color modal popups cover the rest of the GUI with.
+const MODAL_DIM_COLOR: Color4 = Color4 { r: 0.0, g: 0.0, b: 0.0, a: 0.5 };
+
 /// Controls the appearance of the GUI.
+///
+/// The fields below are the defaults for every kind of widget; `styles` can change them for
+/// particular kinds of widget and states. Widgets get their final style from `style`.
+///
+/// Sizes in the theme are in logical pixels. The GUI is laid out and drawn in physical pixels,
+/// which are the same as logical pixels unless the theme is given a scale factor with
+/// `set_scale_factor`; that should be done whenever `EventState::scale_factor` changes, so the
+/// GUI stays readable on high-DPI displays.
 pub struct Theme {
     pub font: Font,
+    /// Other fonts, by name, such as "heading", "monospace", "bold" or "italic". Widgets that
+    /// can use them, such as `Label` and `TextBox`, take the name of the font to use.
+    pub fonts: FxHashMap<String, Font>,
+    pub label_color: Color4,
+    pub button_text_color: Color4,
+    pub button_fill_color: Color4,
+    pub button_border_color: Color4,
+    pub button_selected_fill_color: Color4,
+    pub button_active_fill_color: Color4,
+    /// If set, buttons are drawn with this image instead of a filled and outlined rectangle. The
+    /// image is multiplied by the fill color for the button's state, so the fill colors should
+    /// be white or close to it to show the image's own colors.
+    pub button_background: Option<NinePatch>,
+    /// The radius of the corners of buttons and text entries, in pixels. If this is 0, they're
+    /// drawn as plain rectangles.
+    pub corner_radius: f32,
+    pub padding: i32,
+    /// The border color of inputs whose contents aren't valid, such as a `TextEntry` whose
+    /// validator rejects its text.
+    pub invalid_color: Color4,
+    /// The outline color of the active component, for components that don't otherwise show
+    /// that they're active, such as a `Selector`.
+    pub focus_color: Color4,
+    /// Drawn over disabled widgets to grey them out; it should be partly transparent.
+    pub disabled_overlay_color: Color4,
+    /// Changes to the defaults above for particular kinds of widget.
+    pub styles: FxHashMap<WidgetKind, WidgetStyles>,
+    // True while a disabled widget or its children are being drawn
+    drawing_disabled: Cell<bool>,
+    scale_factor: f32,
+}
+
+impl Theme {
+    /// Creates a theme with the given font and a plain light appearance. The other fields can
+    /// be changed afterwards; this is for themes that are built in code rather than loaded from
+    /// a `ThemeConfig`, such as one whose font comes from `include_bytes!`.
+    pub fn new(font: Font) -> Self {
+        Self {
+            font,
+            fonts: FxHashMap::default(),
+            label_color: Color4::BLACK,
+            button_text_color: Color4::BLACK,
+            button_fill_color: Color4::from_grayscale_srgb(0.9),
+            button_border_color: Color4::from_grayscale_srgb(0.5),
+            button_selected_fill_color: Color4::from_grayscale_srgb(0.8),
+            button_active_fill_color: Color4::from_grayscale_srgb(0.7),
+            button_background: None,
+            corner_radius: 0.0,
+            padding: 4,
+            invalid_color: default_invalid_color(),
+            focus_color: default_focus_color(),
+            disabled_overlay_color: default_disabled_overlay_color(),
+            styles: FxHashMap::default(),
+            drawing_disabled: Cell::new(false),
+            scale_factor: 1.0,
+        }
+    }
+
+    /// Creates a theme from a `ThemeConfig`, loading its font and images from `assets`.
+    pub fn from_config(
+        assets: &Assets,
+        context: &GlContext,
+        config: &ThemeConfig,
+    ) -> Result<Self, AssetError> {
+        let button_background = match &config.button_background {
+            Some(background) => Some(background.load(assets, context)?),
+            None => None,
+        };
+        let fonts = config
+            .fonts
+            .iter()
+            .map(|(name, font)| Ok((name.clone(), font.load(assets, context)?)))
+            .collect::<Result<_, AssetError>>()?;
+        let styles = config
+            .styles
+            .iter()
+            .map(|(&kind, styles)| Ok((kind, styles.load(assets, context, &fonts)?)))
+            .collect::<Result<_, AssetError>>()?;
+        Ok(Self {
+            font: config.font.load(assets, context)?,
+            fonts,
+            label_color: config.label_color,
+            button_text_color: config.button_text_color,
+            button_fill_color: config.button_fill_color,
+            button_border_color: config.button_border_color,
+            button_selected_fill_color: config.button_selected_fill_color,
+            button_active_fill_color: config.button_active_fill_color,
+            button_background,
+            corner_radius: config.corner_radius,
+            padding: config.padding,
+            invalid_color: config.invalid_color,
+            focus_color: config.focus_color,
+            disabled_overlay_color: config.disabled_overlay_color,
+            styles,
+            drawing_disabled: Cell::new(false),
+            scale_factor: 1.0,
+        })
+    }
+
+    /// Loads a theme from a `ThemeConfig` in `assets`. It's parsed as JSON if `url` ends with
+    /// `.json`, and as TOML otherwise.
+    pub fn load(assets: &Assets, context: &GlContext, url: &str) -> Result<Self, AssetError> {
+        let config =
+            if url.ends_with(".json") { assets.get_json(url)? } else { assets.get_toml(url)? };
+        Self::from_config(assets, context, &config)
+    }
+
+    /// Creates a theme from a `ThemeConfig` stored as JSON.
+    pub fn from_json(
+        assets: &Assets,
+        context: &GlContext,
+        bytes: &[u8],
+    ) -> Result<Self, AssetError> {
+        let config = serde_json::from_slice(bytes).map_err(theme_decode_error)?;
+        Self::from_config(assets, context, &config)
+    }
+
+    /// Creates a theme from a `ThemeConfig` stored as TOML.
+    pub fn from_toml(
+        assets: &Assets,
+        context: &GlContext,
+        bytes: &[u8],
+    ) -> Result<Self, AssetError> {
+        let config = toml::from_slice(bytes).map_err(theme_decode_error)?;
+        Self::from_config(assets, context, &config)
+    }
+}
+
+impl Theme {
+    /// The ratio between physical and logical pixels that the theme is scaled by. It's 1.0 until
+    /// `set_scale_factor` is called.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Scales the theme for a display with the given ratio between physical and logical pixels,
+    /// such as `EventState::scale_factor`. The fonts are recreated at the new size, so this
+    /// should only be called when the scale factor changes.
+    pub fn set_scale_factor(&mut self, context: &GlContext, scale_factor: f32) {
+        if scale_factor == self.scale_factor {
+            return;
+        }
+        let ratio = scale_factor / self.scale_factor;
+        // Fonts can be shared, such as named fonts that are also used by styles, and should stay
+        // shared once they're resized.
+        let mut resized: Vec<(Font, Font)> = vec![];
+        let mut resize = |font: &mut Font| {
+            let new_font = match resized.iter().find(|(old, _)| old.ptr_eq(font)) {
+                Some((_, new_font)) => new_font.clone(),
+                None => {
+                    let new_font = font.with_size(context, font.size() * ratio);
+                    resized.push((font.clone(), new_font.clone()));
+                    new_font
+                }
+            };
+            *font = new_font;
+        };
+        resize(&mut self.font);
+        for font in self.fonts.values_mut() {
+            resize(font);
+        }
+        for styles in self.styles.values_mut() {
+            for overrides in
+                [&mut styles.normal, &mut styles.hovered, &mut styles.pressed, &mut styles.disabled]
+            {
+                if let Some(font) = &mut overrides.font {
+                    resize(font);
+                }
+            }
+        }
+        self.scale_factor = scale_factor;
+    }
+
+    /// Converts a size in logical pixels to physical pixels. Widgets should use this for any
+    /// fixed sizes they have, so they're scaled along with the theme.
+    pub fn scale(&self, pixels: i32) -> i32 {
+        (pixels as f32 * self.scale_factor).round() as i32
+    }
+
+    /// Returns the font with the given name from `fonts`, or the default font if there isn't
+    /// one with that name.
+    pub fn named_font(&self, name: &str) -> &Font {
+        self.fonts.get(name).unwrap_or(&self.font)
+    }
+
+    /// The style of a kind of widget in the given state: the defaults from the theme's fields,
+    /// changed by the kind's entry in `styles`. While a disabled widget is being drawn, the
+    /// disabled style is always returned.
+    pub fn style(&self, kind: WidgetKind, state: WidgetState) -> WidgetStyle {
+        let state = if self.drawing_disabled.get() { WidgetState::Disabled } else { state };
+        let mut style = self.default_style(kind, state);
+        if let Some(styles) = self.styles.get(&kind) {
+            styles.apply(state, &mut style);
+        }
+        style.border_width *= self.scale_factor;
+        style.corner_radius *= self.scale_factor;
+        style.padding = self.scale(style.padding);
+        style
+    }
+
+    fn default_style(&self, kind: WidgetKind, state: WidgetState) -> WidgetStyle {
+        let mut style = WidgetStyle {
+            font: self.font.clone(),
+            text_color: self.button_text_color,
+            fill_color: match state {
+                WidgetState::Hovered => self.button_selected_fill_color,
+                WidgetState::Pressed => self.button_active_fill_color,
+                WidgetState::Normal | WidgetState::Disabled => self.button_fill_color,
+            },
+            border_color: self.button_border_color,
+            accent_color: self.button_active_fill_color,
+            border_width: 1.0,
+            corner_radius: self.corner_radius,
+            padding: 2,
+        };
+        match kind {
+            WidgetKind::Label => style.text_color = self.label_color,
+            WidgetKind::Selector => {
+                style.text_color = Color4::BLACK;
+                style.fill_color = match state {
+                    WidgetState::Hovered => Color4::WHITE.mul_srgb(0.75),
+                    _ => Color4::WHITE,
+                };
+                style.accent_color = Color4::WHITE.mul_srgb(0.5);
+            }
+            WidgetKind::ScrollBar => {
+                style.fill_color = self.button_fill_color;
+                style.accent_color = self.button_border_color;
+            }
+            WidgetKind::Tabs => style.padding = self.padding,
+            WidgetKind::Table => style.padding = 3,
+            WidgetKind::TextEntry | WidgetKind::TextEditor => {
+                style.fill_color = self.button_fill_color;
+                style.accent_color = self.button_selected_fill_color;
+            }
+            WidgetKind::Button | WidgetKind::Checkbox | WidgetKind::Toggle | WidgetKind::Slider => {
+            }
+        }
+        style
+    }
+
+    /// Calls `f` with styles treating everything as disabled if `disabled` is set.
+    pub(crate) fn with_disabled(&self, disabled: bool, f: impl FnOnce()) {
+        let was_disabled = self.drawing_disabled.replace(self.drawing_disabled.get() || disabled);
+        f();
+        self.drawing_disabled.set(was_disabled);
+    }
+}
+
+pub(crate) fn theme_decode_error(err: impl std::fmt::Display) -> AssetError {
+    AssetError::Decode { url: "<theme>".to_owned(), message: err.to_string() }
+}
+
+/// The serializable form of a `Theme`, so it can be edited without recompiling the app.
+///
+/// Example TOML:
+/// ```toml
+/// padding = 4
+/// corner_radius = 3.0 # Optional
+/// invalid_color = { r = 1.0, g = 0.0, b = 0.0, a = 1.0 } # Optional; red by default
+/// focus_color = { r = 0.0, g = 0.0, b = 1.0, a = 1.0 } # Optional; blue by default
+/// disabled_overlay_color = { r = 0.2, g = 0.2, b = 0.2, a = 0.5 } # Optional
+/// label_color = { r = 1.0, g = 1.0, b = 1.0, a = 1.0 }
+/// # ...the other colors...
+///
+/// [font]
+/// path = "fonts/DejaVuSans.ttf"
+/// size = 16.0
+///
+/// # Optional; any number of named fonts
+/// [fonts.monospace]
+/// path = "fonts/DejaVuSansMono.ttf"
+/// size = 16.0
+///
+/// # Optional; see `WidgetStylesConfig`
+/// [styles.button]
+/// corner_radius = 6.0
+/// font_name = "monospace"
+/// [styles.button.hovered]
+/// fill_color = { r = 0.8, g = 0.8, b = 1.0, a = 1.0 }
+///
+/// # Optional
+/// [button_background]
+/// path = "images/button.png"
+/// margins = { left = 4, right = 4, top = 4, bottom = 4 }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ThemeConfig {
+    pub font: ThemeFontConfig,
+    #[serde(default)]
+    pub fonts: FxHashMap<String, ThemeFontConfig>,
     pub label_color: Color4,
     pub button_text_color: Color4,
     pub button_fill_color: Color4,
     pub button_border_color: Color4,
     pub button_selected_fill_color: Color4,
     pub button_active_fill_color: Color4,
+    #[serde(default)]
+    pub button_background: Option<NinePatchConfig>,
+    #[serde(default)]
+    pub corner_radius: f32,
     pub padding: i32,
+    #[serde(default = "default_invalid_color")]
+    pub invalid_color: Color4,
+    #[serde(default = "default_focus_color")]
+    pub focus_color: Color4,
+    #[serde(default = "default_disabled_overlay_color")]
+    pub disabled_overlay_color: Color4,
+    #[serde(default)]
+    pub styles: FxHashMap<WidgetKind, WidgetStylesConfig>,
+}
+
+fn default_invalid_color() -> Color4 {
+    Color4::RED
+}
+
+fn default_focus_color() -> Color4 {
+    Color4::BLUE
+}
+
+fn default_disabled_overlay_color() -> Color4 {
+    Color4 { r: 0.2, g: 0.2, b: 0.2, a: 0.5 }
+}
+
+/// A font referenced by a `ThemeConfig`. The font must already be loaded by `Assets`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ThemeFontConfig {
+    pub path: String,
+    pub size: f32,
+}
+
+impl ThemeFontConfig {
+    pub(crate) fn load(&self, assets: &Assets, context: &GlContext) -> Result<Font, AssetError> {
+        let font_data = assets
+            .get(&self.path)
+            .ok_or_else(|| AssetError::NotLoaded { url: self.path.clone() })?;
+        Ok(Font::new(context, font_data.to_vec(), self.size))
+    }
+}
+
+/// A nine-patch image referenced by a `ThemeConfig`. The image must already be loaded by
+/// `Assets` as an image.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct NinePatchConfig {
+    pub path: String,
+    pub margins: NinePatchMargins,
+}
+
+impl NinePatchConfig {
+    fn load(&self, assets: &Assets, context: &GlContext) -> Result<NinePatch, AssetError> {
+        let image = assets
+            .get_image(&self.path)
+            .ok_or_else(|| AssetError::NotLoaded { url: self.path.clone() })?;
+        let texture = texture_from_image(context, image, TextureSettings::default());
+        Ok(NinePatch { texture: Rc::new(texture), margins: self.margins })
+    }
 }
 
 /// Components store persistent data about a widget or group of widgets. They
@@ -46,13 +423,45 @@ pub trait Widget {
 
     /// This must return true iff the widget is the root widget of a component.
     ///
-    /// It is undefined behavior if there's a component within another
-    /// component. In the current implementation, the outer component will
-    /// receive the event, but this behavior isn't guaranteed.
+    /// Components can contain other components; events that land on both go to the inner one.
     fn is_component(&self) -> bool {
         false
     }
 
+    /// True if the widget is a component that responds to `Event::Scroll`. Scroll events go to
+    /// the innermost such component under the cursor.
+    fn handles_scroll(&self) -> bool {
+        false
+    }
+
+    /// True if the widget is a component that can be dragged, such as a `Slider`. After a left
+    /// click on it, it gets every `Event::MouseMove` and `Event::MouseUp` until the button is
+    /// released, even once the cursor leaves it, so their positions can be outside its rect.
+    fn captures_pointer(&self) -> bool {
+        false
+    }
+
+    /// False if the widget is disabled. Disabled widgets are greyed out with
+    /// `Theme::disabled_overlay_color`, and neither they nor their children receive events or can
+    /// be tabbed to. Widgets can be disabled with `WidgetExt::enabled`.
+    fn is_enabled(&self) -> bool {
+        true
+    }
+
+    /// False if the widget is hidden. Hidden widgets and their children aren't drawn and don't
+    /// receive events, and they take up no space in the layout. Widgets can be hidden with
+    /// `WidgetExt::visible`.
+    fn is_visible(&self) -> bool {
+        true
+    }
+
+    /// The cursor icon to show when the cursor is at `pos`, relative to the widget's rect. The
+    /// innermost widget under the cursor that returns an icon decides it; if none do, it's
+    /// `CursorIcon::Arrow`. See `GuiResult::cursor_icon`.
+    fn cursor_icon(&self, _theme: &Theme, _pos: Point2<i32>) -> Option<CursorIcon> {
+        None
+    }
+
     /// A widget does *not* need to draw its children. Its children will be automatically drawn
     /// after this widget is drawn.
     fn draw(
@@ -105,7 +514,11 @@ fn compute_widget_min_size(
     for child in widget.children() {
         compute_widget_min_size(child, context, theme, min_sizes, window_size);
     }
-    let min_size = widget.min_size(context, theme, min_sizes, window_size);
+    let min_size = if widget.is_visible() {
+        widget.min_size(context, theme, min_sizes, window_size)
+    } else {
+        Vector2::zero()
+    };
     min_sizes.insert(widget.id(), min_size);
 }
 
@@ -116,9 +529,43 @@ fn widget_handle_event(
     events_out: &mut FxHashMap<WidgetId, Vec<Event>>,
     active_component_id: &mut Option<WidgetId>,
     selectable_components: &FxHashSet<WidgetId>,
+    cursor_pos: Option<Point2<i32>>,
 ) -> bool {
+    let rect = widget_rects[&widget.id()];
+    // Widgets are clipped to their rects, so the parts of their children outside of them can't be
+    // clicked.
+    let event_pos = match *event {
+        Event::MouseDown(_, pos)
+        | Event::MouseUp(_, pos)
+        | Event::MouseMove { pos, .. }
+        | Event::DoubleClick(_, pos) => Some(pos),
+        Event::Scroll { .. } => cursor_pos,
+        _ => None,
+    };
+    if matches!(event_pos, Some(pos) if !rect.contains_point(pos)) || !widget.is_visible() {
+        return false;
+    }
+    if !widget.is_enabled() {
+        // Clicks on disabled widgets are swallowed rather than going to whatever is behind them.
+        return matches!(event, Event::MouseDown(..) | Event::MouseUp(..) | Event::DoubleClick(..));
+    }
+
+    // Children get events first, so components can contain other components.
+    for child in widget.children() {
+        if widget_handle_event(
+            child,
+            event,
+            widget_rects,
+            events_out,
+            active_component_id,
+            selectable_components,
+            cursor_pos,
+        ) {
+            return true;
+        }
+    }
+
     if widget.is_component() {
-        let rect = widget_rects[&widget.id()];
         let is_active = *active_component_id == Some(widget.id());
 
         let event = event.clone();
@@ -137,7 +584,11 @@ fn widget_handle_event(
                     None
                 }
             }
-            Event::CharEntered(_) => {
+            Event::CharEntered(_)
+            | Event::Paste(_)
+            | Event::CompositionStart
+            | Event::CompositionUpdate(_)
+            | Event::CompositionEnd(_) => {
                 if is_active {
                     Some(event)
                 } else {
@@ -168,14 +619,37 @@ fn widget_handle_event(
                     None
                 }
             }
+            Event::DoubleClick(button, pos) => {
+                if rect.contains_point(pos) {
+                    Some(Event::DoubleClick(button, pos - rect.start.to_vec()))
+                } else {
+                    None
+                }
+            }
+            // These are sent to components by `Gui::handle_events` as the cursor crosses them.
             Event::MouseEnter => None,
             Event::MouseLeave => None,
             Event::FocusGained => Some(event),
             Event::FocusLost => Some(event),
             Event::WindowResized(_) => Some(event),
+            Event::ScaleFactorChanged(_) => Some(event),
+            Event::WindowMoved(_) => None,
+            Event::MonitorsChanged => None,
+            Event::Minimized => None,
+            Event::Maximized => None,
+            Event::Restored => None,
             Event::PointerLocked => None,
             Event::PointerUnlocked => None,
-            Event::Scroll(_) => Some(event),
+            Event::RawMouseMotion(_) => None,
+            // Drops aren't sent with a position, so they're left for the app.
+            Event::FileDropped { .. } => None,
+            Event::Scroll { .. } => {
+                if widget.handles_scroll() {
+                    Some(event)
+                } else {
+                    None
+                }
+            }
         };
         if let Some(event2) = event2 {
             let events = events_out.entry(widget.id()).or_insert_with(Vec::new);
@@ -183,19 +657,131 @@ fn widget_handle_event(
             return true;
         }
     }
+    false
+}
+
+/// Returns the widget with the given ID, if it's `widget` or one of its descendants.
+fn find_widget(widget: &dyn Widget, id: WidgetId) -> Option<&dyn Widget> {
+    if widget.id() == id {
+        return Some(widget);
+    }
+    widget.children().into_iter().find_map(|child| find_widget(child, id))
+}
+
+/// Adds the components that can be tabbed to, which are the enabled ones that are at least
+/// partly visible, to `focusable`.
+fn add_focusable_components(
+    widget: &dyn Widget,
+    widget_rects: &FxHashMap<WidgetId, Rect<i32>>,
+    clip: Rect<i32>,
+    focusable: &mut FxHashSet<WidgetId>,
+) {
+    // Widgets are clipped to their rects, so ones scrolled out of view have nothing visible.
+    let clip = clip.intersection(&widget_rects[&widget.id()]);
+    let size = clip.size();
+    if size.x <= 0 || size.y <= 0 {
+        return;
+    }
+    if !widget.is_enabled() || !widget.is_visible() {
+        return;
+    }
+    if widget.is_component() {
+        focusable.insert(widget.id());
+    }
     for child in widget.children() {
-        if widget_handle_event(
-            child,
-            event,
-            widget_rects,
-            events_out,
-            active_component_id,
-            selectable_components,
-        ) {
-            return true;
+        add_focusable_components(child, widget_rects, clip, focusable);
+    }
+}
+
+/// Adds the components under `pos` to `hovered`.
+fn add_hovered_components(
+    widget: &dyn Widget,
+    widget_rects: &FxHashMap<WidgetId, Rect<i32>>,
+    clip: Rect<i32>,
+    pos: Point2<i32>,
+    hovered: &mut FxHashSet<WidgetId>,
+) {
+    let clip = clip.intersection(&widget_rects[&widget.id()]);
+    if !clip.contains_point(pos) || !widget.is_enabled() || !widget.is_visible() {
+        return;
+    }
+    if widget.is_component() {
+        hovered.insert(widget.id());
+    }
+    for child in widget.children() {
+        add_hovered_components(child, widget_rects, clip, pos, hovered);
+    }
+}
+
+/// Returns the cursor icon of the innermost widget under `pos` that has one. Like events, hidden
+/// and disabled widgets are skipped.
+fn widget_cursor_icon(
+    widget: &dyn Widget,
+    theme: &Theme,
+    widget_rects: &FxHashMap<WidgetId, Rect<i32>>,
+    clip: Rect<i32>,
+    pos: Point2<i32>,
+) -> Option<CursorIcon> {
+    let rect = widget_rects.get(&widget.id())?;
+    let clip = clip.intersection(rect);
+    if !clip.contains_point(pos) || !widget.is_enabled() || !widget.is_visible() {
+        return None;
+    }
+    widget
+        .children()
+        .into_iter()
+        .rev()
+        .find_map(|child| widget_cursor_icon(child, theme, widget_rects, clip, pos))
+        .or_else(|| widget.cursor_icon(theme, pos - rect.start.to_vec()))
+}
+
+/// Returns the components under the cursor. Only the tree the cursor is over counts, so
+/// components under popups aren't hovered.
+fn hovered_components(
+    widget: &dyn Widget,
+    widget_rects: &FxHashMap<WidgetId, Rect<i32>>,
+    popups: &[RenderedPopup],
+    cursor_pos: Option<Point2<i32>>,
+) -> FxHashSet<WidgetId> {
+    let mut hovered = Default::default();
+    if let (Some(pos), Some(owner)) = (cursor_pos, cursor_owner(popups, cursor_pos)) {
+        match popups.get(owner) {
+            Some(popup) => {
+                add_hovered_components(
+                    &*popup.widget,
+                    &popup.widget_rects,
+                    popup.rect,
+                    pos,
+                    &mut hovered,
+                );
+            }
+            None => {
+                let rect = widget_rects[&widget.id()];
+                add_hovered_components(widget, widget_rects, rect, pos, &mut hovered);
+            }
         }
     }
-    false
+    hovered
+}
+
+/// Returns the index of the next component after `index` that can be tabbed to, or the previous
+/// one if `reverse` is set. Returns `index` if there aren't any others.
+fn next_focusable_component(
+    ordered_components: &[WidgetId],
+    focusable: &FxHashSet<WidgetId>,
+    index: i32,
+    reverse: bool,
+) -> i32 {
+    let len = ordered_components.len() as i32;
+    let step = if reverse { len - 1 } else { 1 };
+    let mut i = index;
+    for _ in 0..len {
+        i = (i + step) % len;
+        if focusable.contains(&ordered_components[i as usize]) {
+            return i;
+        }
+    }
+    index
 }
 
 fn draw_widget(
@@ -208,25 +794,130 @@ fn draw_widget(
     cursor_pos: Option<Point2<i32>>,
     active_widget_id: Option<WidgetId>,
 ) {
+    if !widget.is_visible() {
+        return;
+    }
     let rect = widget_rects[&widget.id()];
     let is_active = active_widget_id == Some(widget.id());
-    widget.draw(context, surface, rect, theme, draw_2d, cursor_pos, is_active);
-    for child in widget.children() {
-        draw_widget(
-            child,
-            context,
-            surface,
-            theme,
-            draw_2d,
-            widget_rects,
-            cursor_pos,
-            active_widget_id,
-        );
+    // Outlines are drawn one pixel past the end of the rect, so that's included in the clip.
+    draw_2d.push_clip(Rect::new(rect.start, rect.end + vec2(1, 1)));
+    theme.with_disabled(!widget.is_enabled(), || {
+        widget.draw(context, surface, rect, theme, draw_2d, cursor_pos, is_active);
+        for child in widget.children() {
+            draw_widget(
+                child,
+                context,
+                surface,
+                theme,
+                draw_2d,
+                widget_rects,
+                cursor_pos,
+                active_widget_id,
+            );
+        }
+    });
+    if !widget.is_enabled() {
+        draw_2d.fill_rect(rect, theme.disabled_overlay_color);
+    }
+    draw_2d.pop_clip();
+}
+
+/// Where a `Popup` is shown.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PopupPlacement {
+    /// Below the given rect, or above it if there isn't enough room below.
+    Anchored(Rect<i32>),
+    /// In the center of the surface.
+    Centered,
+}
+
+/// A widget tree shown on top of the rest of the GUI, such as a dropdown, context menu, or
+/// dialog; see `Gui::show_popup`. Popups are given their minimum size.
+pub struct Popup {
+    id: PopupId,
+    widget: Box<dyn Widget>,
+    placement: PopupPlacement,
+    modal: bool,
+}
+
+impl Popup {
+    /// Creates a popup shown next to `anchor`, such as a dropdown or context menu. It's dismissed
+    /// by clicking outside of it or pressing Escape.
+    pub fn anchored(id: PopupId, widget: Box<dyn Widget>, anchor: Rect<i32>) -> Self {
+        Self { id, widget, placement: PopupPlacement::Anchored(anchor), modal: false }
+    }
+
+    /// Creates a dialog in the center of the surface. The rest of the GUI is dimmed and doesn't
+    /// receive any events until the dialog is closed. It's dismissed by pressing Escape.
+    pub fn modal(id: PopupId, widget: Box<dyn Widget>) -> Self {
+        Self { id, widget, placement: PopupPlacement::Centered, modal: true }
+    }
+
+    pub fn id(&self) -> PopupId {
+        self.id
+    }
+
+    pub fn placement(&self) -> PopupPlacement {
+        self.placement
+    }
+
+    pub fn is_modal(&self) -> bool {
+        self.modal
+    }
+
+    fn layout(
+        self,
+        context: &GlContext,
+        theme: &Theme,
+        surface_size: Vector2<i32>,
+    ) -> RenderedPopup {
+        let mut min_sizes = Default::default();
+        let mut widget_rects = Default::default();
+        compute_widget_min_size(&*self.widget, context, theme, &mut min_sizes, surface_size);
+        let min_size = min_sizes[&self.widget.id()];
+        let size = vec2(min_size.x.min(surface_size.x), min_size.y.min(surface_size.y));
+        let start = match self.placement {
+            PopupPlacement::Anchored(anchor) => {
+                let y = if anchor.end.y + size.y > surface_size.y && anchor.start.y >= size.y {
+                    anchor.start.y - size.y
+                } else {
+                    anchor.end.y
+                };
+                point2(anchor.start.x.clamp(0, surface_size.x - size.x), y)
+            }
+            PopupPlacement::Centered => Point2::from_vec((surface_size - size) / 2),
+        };
+        let rect = Rect::new(start, start + size);
+        self.widget.compute_rects(rect, theme, &min_sizes, &mut widget_rects);
+        RenderedPopup { id: self.id, widget: self.widget, widget_rects, rect, modal: self.modal }
     }
 }
 
+struct RenderedPopup {
+    id: PopupId,
+    widget: Box<dyn Widget>,
+    widget_rects: FxHashMap<WidgetId, Rect<i32>>,
+    rect: Rect<i32>,
+    modal: bool,
+}
+
+/// Returns the index of the popup the cursor is over, or `Some(popups.len())` if it's over the
+/// main widget tree, or `None` if it's over neither because a modal popup is in the way.
+fn cursor_owner(popups: &[RenderedPopup], cursor_pos: Option<Point2<i32>>) -> Option<usize> {
+    let cursor_pos = cursor_pos?;
+    for (i, popup) in popups.iter().enumerate().rev() {
+        if popup.rect.contains_point(cursor_pos) {
+            return Some(i);
+        } else if popup.modal {
+            return None;
+        }
+    }
+    Some(popups.len())
+}
+
 pub struct GuiResult {
     rendered_size: Vector2<i32>,
+    cursor_icon: CursorIcon,
 }
 
 pub struct GuiEventResult {
@@ -234,6 +925,7 @@ pub struct GuiEventResult {
     component_events: FxHashMap<WidgetId, Vec<Event>>,
     /// Events not handled by any component
     unhandled_events: Vec<Event>,
+    dismissed_popups: Vec<PopupId>,
 }
 
 impl GuiResult {
@@ -241,6 +933,12 @@ impl GuiResult {
     pub fn rendered_size(&self) -> Vector2<i32> {
         self.rendered_size
     }
+
+    /// The cursor icon the widget under the cursor asks for; see `Widget::cursor_icon`. Apps
+    /// can pass it to `ScreenSurface::set_cursor_icon`.
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.cursor_icon
+    }
 }
 
 impl GuiEventResult {
@@ -258,25 +956,100 @@ impl GuiEventResult {
     pub fn unhandled_events(&mut self) -> Vec<Event> {
         mem::take(&mut self.unhandled_events)
     }
+
+    /// True if the user dismissed the given popup, in which case it should no longer be shown.
+    pub fn popup_dismissed(&self, popup: PopupId) -> bool {
+        self.dismissed_popups.contains(&popup)
+    }
 }
 
 pub struct Gui {
     // None if there are no components
     active_component: Option<(i32, WidgetId)>,
     last_render: Option<RenderedGui>,
+    // The cursor position from the most recent mouse event, used to decide where scroll events go
+    cursor_pos: Option<Point2<i32>>,
+    // Popups to show the next time the GUI is drawn
+    popups: Vec<Popup>,
+    // The components under the cursor, which have been sent `Event::MouseEnter`
+    hovered_components: FxHashSet<WidgetId>,
+    // The component being dragged, which gets the mouse events until the left button is released;
+    // see `Widget::captures_pointer`
+    pointer_capture: Option<WidgetId>,
+    // The time, button and position of the last click that could start a double click
+    last_click: Option<(f64, MouseButton, Point2<i32>)>,
+    double_click_interval: f64,
+    stopwatch: Stopwatch,
 }
 
 struct RenderedGui {
     widget: Box<dyn Widget>,
     widget_rects: FxHashMap<WidgetId, Rect<i32>>,
+    // From bottom to top
+    popups: Vec<RenderedPopup>,
 }
 
 impl Gui {
     pub fn new() -> Self {
-        Self { active_component: None, last_render: None }
+        Self {
+            active_component: None,
+            last_render: None,
+            cursor_pos: None,
+            popups: vec![],
+            hovered_components: Default::default(),
+            pointer_capture: None,
+            last_click: None,
+            double_click_interval: 0.5,
+            stopwatch: Stopwatch::new(),
+        }
+    }
+
+    /// Sets the longest time, in seconds, between two clicks for them to count as a double
+    /// click. The default is 0.5 seconds.
+    pub fn set_double_click_interval(&mut self, interval: f64) {
+        self.double_click_interval = interval;
+    }
+
+    /// True if the cursor was over the component as of the last event handled. Components are
+    /// also sent `Event::MouseEnter` and `Event::MouseLeave` when this changes.
+    pub fn is_hovered(&self, component: WidgetId) -> bool {
+        self.hovered_components.contains(&component)
+    }
+
+    /// True if a click is the second click of a double click. A third click starts a new double
+    /// click rather than finishing another one.
+    fn is_double_click(&mut self, button: MouseButton, pos: Point2<i32>) -> bool {
+        let now = self.stopwatch.get_time();
+        let is_double_click = match self.last_click {
+            Some((time, last_button, last_pos)) => {
+                let offset = pos - last_pos;
+                last_button == button
+                    && now - time <= self.double_click_interval
+                    && offset.x.abs() <= DOUBLE_CLICK_DISTANCE
+                    && offset.y.abs() <= DOUBLE_CLICK_DISTANCE
+            }
+            None => false,
+        };
+        self.last_click = if is_double_click { None } else { Some((now, button, pos)) };
+        is_double_click
     }
 
-    /// Draws the GUI.
+    /// The first layer popups are drawn on; see `Draw2d::set_layer`. Each popup is drawn on the
+    /// layer above the previous one.
+    pub const POPUP_LAYER: i32 = 1000;
+
+    /// Shows a popup on top of the GUI the next time it's drawn. Like the main widget tree,
+    /// popups must be shown again every frame until they're closed. Popups shown later appear
+    /// on top of earlier ones, and receive events before them and before the main widget tree.
+    ///
+    /// Components in popups should be included in the `ordered_components` passed to
+    /// `handle_events`.
+    pub fn show_popup(&mut self, popup: Popup) {
+        self.popups.push(popup);
+    }
+
+    /// Draws the GUI. Each widget's drawing is clipped to its rect, so content that doesn't fit
+    /// is cut off rather than drawn over its neighbors.
     pub fn draw(
         &mut self,
         context: &GlContext,
@@ -297,7 +1070,13 @@ impl Gui {
         );
         let rect = Rect::new(Point2::origin(), Point2::from_vec(surface.size().cast().unwrap()));
         widget.compute_rects(rect, theme, &min_sizes, &mut widget_rects);
+        let popups: Vec<_> = mem::take(&mut self.popups)
+            .into_iter()
+            .map(|popup| popup.layout(context, theme, rect.size()))
+            .collect();
 
+        // Only the tree the cursor is over sees it, so widgets under popups aren't highlighted.
+        let cursor_owner = cursor_owner(&popups, cursor_pos);
         let active_component_id = self.active_component.map(|(_a, b)| b);
         draw_widget(
             &*widget,
@@ -306,12 +1085,40 @@ impl Gui {
             theme,
             draw_2d,
             &widget_rects,
-            cursor_pos,
+            cursor_pos.filter(|_| cursor_owner == Some(popups.len())),
             active_component_id,
         );
+        for (i, popup) in popups.iter().enumerate() {
+            draw_2d.with_layer(Self::POPUP_LAYER + i as i32, |draw_2d| {
+                if popup.modal {
+                    draw_2d.fill_rect(rect, MODAL_DIM_COLOR);
+                }
+                draw_widget(
+                    &*popup.widget,
+                    context,
+                    surface,
+                    theme,
+                    draw_2d,
+                    &popup.widget_rects,
+                    cursor_pos.filter(|_| cursor_owner == Some(i)),
+                    active_component_id,
+                );
+            });
+        }
 
-        let res = GuiResult { rendered_size: widget_rects[&widget.id()].size() };
-        self.last_render = Some(RenderedGui { widget, widget_rects });
+        let cursor_icon = match (cursor_pos, cursor_owner) {
+            (Some(pos), Some(i)) if i < popups.len() => {
+                let popup = &popups[i];
+                widget_cursor_icon(&*popup.widget, theme, &popup.widget_rects, popup.rect, pos)
+            }
+            (Some(pos), Some(_)) => widget_cursor_icon(&*widget, theme, &widget_rects, rect, pos),
+            _ => None,
+        };
+        let res = GuiResult {
+            rendered_size: widget_rects[&widget.id()].size(),
+            cursor_icon: cursor_icon.unwrap_or_default(),
+        };
+        self.last_render = Some(RenderedGui { widget, widget_rects, popups });
         res
     }
 
@@ -324,21 +1131,161 @@ impl Gui {
         events: &[Event],
         ordered_components: &[WidgetId],
     ) -> GuiEventResult {
-        if let Some(RenderedGui { widget, widget_rects }) = &self.last_render {
-            let mut events_out = Default::default();
+        let mut expanded_events = Vec::with_capacity(events.len());
+        for event in events {
+            expanded_events.push(event.clone());
+            if let Event::MouseDown(button, pos) = *event {
+                if self.is_double_click(button, pos) {
+                    expanded_events.push(Event::DoubleClick(button, pos));
+                }
+            }
+        }
+        let events = &expanded_events;
+
+        if let Some(RenderedGui { widget, widget_rects, popups }) = &mut self.last_render {
+            let mut events_out: FxHashMap<WidgetId, Vec<Event>> = Default::default();
             let mut unhandled_events = vec![];
+            let mut dismissed_popups = vec![];
             let mut active_component_id = self.active_component.map(|(_a, b)| b);
+            let selectable_components = ordered_components.iter().copied().collect();
+            // Components that aren't drawn, such as ones in other tabs, aren't in the rects at
+            // all, so they're skipped along with hidden and disabled ones.
+            let mut focusable_components = Default::default();
+            let whole_gui = widget_rects[&widget.id()];
+            add_focusable_components(&**widget, widget_rects, whole_gui, &mut focusable_components);
+            for popup in popups.iter() {
+                add_focusable_components(
+                    &*popup.widget,
+                    &popup.widget_rects,
+                    popup.rect,
+                    &mut focusable_components,
+                );
+            }
 
             for event in events {
-                let old_active_component_id = active_component_id;
-                widget_handle_event(
-                    &**widget,
+                match *event {
+                    Event::MouseDown(_, pos)
+                    | Event::MouseUp(_, pos)
+                    | Event::MouseMove { pos, .. } => self.cursor_pos = Some(pos),
+                    Event::MouseLeave => self.cursor_pos = None,
+                    _ => (),
+                }
+                if matches!(
                     event,
-                    widget_rects,
-                    &mut events_out,
-                    &mut active_component_id,
-                    &ordered_components.iter().copied().collect(),
-                );
+                    Event::MouseDown(..)
+                        | Event::MouseUp(..)
+                        | Event::MouseMove { .. }
+                        | Event::MouseLeave
+                ) {
+                    let hovered =
+                        hovered_components(&**widget, widget_rects, popups, self.cursor_pos);
+                    for &id in self.hovered_components.difference(&hovered) {
+                        events_out.entry(id).or_default().push(Event::MouseLeave);
+                    }
+                    for &id in hovered.difference(&self.hovered_components) {
+                        events_out.entry(id).or_default().push(Event::MouseEnter);
+                    }
+                    self.hovered_components = hovered;
+                }
+                // A component that's being dragged gets the mouse events until the drag ends, even
+                // once the cursor leaves it.
+                if let Some(id) = self.pointer_capture {
+                    if let Event::MouseMove { pos, .. } | Event::MouseUp(MouseButton::Left, pos) =
+                        *event
+                    {
+                        let rect = widget_rects.get(&id).or_else(|| {
+                            popups.iter().find_map(|popup| popup.widget_rects.get(&id))
+                        });
+                        // The component might no longer be shown, in which case the drag is over.
+                        if let Some(rect) = rect {
+                            let pos = pos - rect.start.to_vec();
+                            let event = match *event {
+                                Event::MouseMove { movement, .. } => {
+                                    Event::MouseMove { pos, movement }
+                                }
+                                _ => {
+                                    self.pointer_capture = None;
+                                    Event::MouseUp(MouseButton::Left, pos)
+                                }
+                            };
+                            events_out.entry(id).or_default().push(event);
+                            continue;
+                        }
+                        self.pointer_capture = None;
+                    }
+                }
+                if let Event::FocusLost = event {
+                    self.pointer_capture = None;
+                }
+                let old_active_component_id = active_component_id;
+                // The events from this event alone, to tell which component was clicked.
+                let mut handled = FxHashMap::default();
+
+                // Popups get events before the main widget tree, from the top one down.
+                let mut captured = false;
+                if matches!(event, Event::KeyDown(key) if key.code == "Escape") {
+                    if let Some(popup) = popups.pop() {
+                        dismissed_popups.push(popup.id);
+                        captured = true;
+                    }
+                }
+                while !captured {
+                    let popup = match popups.last() {
+                        Some(popup) => popup,
+                        None => break,
+                    };
+                    if widget_handle_event(
+                        &*popup.widget,
+                        event,
+                        &popup.widget_rects,
+                        &mut handled,
+                        &mut active_component_id,
+                        &selectable_components,
+                        self.cursor_pos,
+                    ) {
+                        captured = true;
+                    } else if popup.modal {
+                        // Modal popups block everything below them.
+                        captured = true;
+                    } else if let Event::MouseDown(_, pos) = *event {
+                        captured = true;
+                        if !popup.rect.contains_point(pos) {
+                            dismissed_popups.push(popup.id);
+                            popups.pop();
+                        }
+                    } else if let Event::MouseUp(_, pos)
+                    | Event::MouseMove { pos, .. }
+                    | Event::DoubleClick(_, pos) = *event
+                    {
+                        captured = popup.rect.contains_point(pos);
+                        break;
+                    } else {
+                        break;
+                    }
+                }
+                if !captured {
+                    widget_handle_event(
+                        &**widget,
+                        event,
+                        widget_rects,
+                        &mut handled,
+                        &mut active_component_id,
+                        &selectable_components,
+                        self.cursor_pos,
+                    );
+                }
+                if let Event::MouseDown(MouseButton::Left, _) = event {
+                    // At most one component gets each event.
+                    self.pointer_capture = handled.keys().copied().find(|&id| {
+                        let widget = find_widget(&**widget, id).or_else(|| {
+                            popups.iter().find_map(|popup| find_widget(&*popup.widget, id))
+                        });
+                        matches!(widget, Some(widget) if widget.captures_pointer())
+                    });
+                }
+                for (id, events) in handled {
+                    events_out.entry(id).or_default().extend(events);
+                }
                 if active_component_id != old_active_component_id {
                     let active_component_id = active_component_id.unwrap();
                     self.active_component = Some((
@@ -352,31 +1299,31 @@ impl Gui {
                     &mut self.active_component
                 {
                     if let Event::KeyDown(key) = event {
-                        if key.code == "Tab" && !key.shift {
-                            *active_component_index =
-                                (*active_component_index + 1) % (ordered_components.len() as i32);
-                            *active_component_id =
-                                ordered_components[*active_component_index as usize];
-                            continue;
-                        } else if key.code == "Tab" && key.shift {
-                            // Workaround for mod_euc not yet being stable
-                            *active_component_index = (*active_component_index - 1
-                                + ordered_components.len() as i32)
-                                % (ordered_components.len() as i32);
+                        // Ctrl+Tab is left for components such as `Tabs`.
+                        if key.code == "Tab" && !key.ctrl {
+                            *active_component_index = next_focusable_component(
+                                ordered_components,
+                                &focusable_components,
+                                *active_component_index,
+                                key.shift,
+                            );
                             *active_component_id =
                                 ordered_components[*active_component_index as usize];
                             continue;
                         }
                     }
                 }
-                unhandled_events.push(event.clone());
+                if !captured {
+                    unhandled_events.push(event.clone());
+                }
             }
 
-            GuiEventResult { component_events: events_out, unhandled_events }
+            GuiEventResult { component_events: events_out, unhandled_events, dismissed_popups }
         } else {
             GuiEventResult {
                 component_events: Default::default(),
                 unhandled_events: events.to_vec(),
+                dismissed_popups: vec![],
             }
         }
     }
@@ -0,0 +1,136 @@
+use crate::gl::uniforms::*;
+use crate::gl::*;
+use cgmath::*;
+
+use super::shader_header::*;
+
+/// How `HdrPipeline` maps HDR colors to the displayable range.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ToneMapping {
+    /// Colors brighter than 1 are clamped.
+    Clamp,
+    Reinhard,
+    /// An approximation of the ACES filmic curve, which keeps more contrast than Reinhard.
+    Aces,
+}
+
+impl ToneMapping {
+    fn as_uniform(self) -> f32 {
+        match self {
+            ToneMapping::Clamp => 0.0,
+            ToneMapping::Reinhard => 1.0,
+            ToneMapping::Aces => 2.0,
+        }
+    }
+}
+
+struct ToneMapUniforms<'a> {
+    tex: &'a Texture2d,
+    tone_mapping: ToneMapping,
+    exposure: f32,
+}
+
+struct ToneMapUniformsGl {
+    tex: TextureUniform,
+    tone_mapping: F32Uniform,
+    exposure: F32Uniform,
+}
+
+impl<'a> Uniforms for ToneMapUniforms<'a> {
+    type GlUniforms = ToneMapUniformsGl;
+
+    fn update(&self, context: &GlContext, gl_uniforms: &Self::GlUniforms) {
+        gl_uniforms.tex.set(context, self.tex, 0);
+        gl_uniforms.tone_mapping.set(context, self.tone_mapping.as_uniform());
+        gl_uniforms.exposure.set(context, self.exposure);
+    }
+}
+
+impl GlUniforms for ToneMapUniformsGl {
+    fn new(context: &GlContext, program: GlProgramId) -> Self {
+        ToneMapUniformsGl {
+            tex: TextureUniform::new("tex0", context, program),
+            tone_mapping: F32Uniform::new("toneMapping", context, program),
+            exposure: F32Uniform::new(EXPOSURE_UNIFORM, context, program),
+        }
+    }
+}
+
+/// Renders a scene in HDR and tone maps it for display.
+///
+/// ```ignore
+/// let framebuffer = hdr.framebuffer(screen_surface.size());
+/// framebuffer.clear(&context, &[ClearBuffer::Color([0.0; 4]), ClearBuffer::Depth(1.0)]);
+/// draw_scene(framebuffer);
+/// hdr.resolve(&screen_surface);
+/// ```
+///
+/// The scene is drawn into an RGBA16F framebuffer with a depth/stencil buffer, so shaders can
+/// write colors brighter than 1. WebGL1 doesn't support rendering to floating point textures, so
+/// there the framebuffer is RGBA and colors are clamped.
+pub struct HdrPipeline {
+    program: GlProgram<FullscreenVert, ToneMapUniformsGl>,
+    quad: FullscreenQuad<ToneMapUniformsGl>,
+    framebuffer: Option<Framebuffer<Texture2d>>,
+    context: GlContext,
+    pub tone_mapping: ToneMapping,
+    /// The exposure in stops; each stop doubles the brightness.
+    pub exposure: f32,
+}
+
+impl HdrPipeline {
+    pub fn new(context: &GlContext, convert_to_srgb: bool) -> Self {
+        let program = GlProgram::new_with_hdr_header(
+            context,
+            include_str!("shaders/post_process_vert.glsl"),
+            include_str!("shaders/tonemap_frag.glsl"),
+            convert_to_srgb,
+        );
+        let quad = FullscreenQuad::new(context, &program);
+        Self {
+            program,
+            quad,
+            framebuffer: None,
+            context: context.clone(),
+            tone_mapping: ToneMapping::Aces,
+            exposure: 0.0,
+        }
+    }
+
+    /// Returns the framebuffer that the scene should be drawn to. It's created the first time
+    /// this is called, and recreated when `size` changes; its contents are undefined until it's
+    /// cleared.
+    pub fn framebuffer(&mut self, size: Vector2<u32>) -> &Framebuffer<Texture2d> {
+        if !matches!(&self.framebuffer, Some(framebuffer) if framebuffer.size() == size) {
+            let format =
+                if self.context.is_webgl1() { TextureFormat::RGBA } else { TextureFormat::RGBA16F };
+            let framebuffer = Framebuffer::new_with_texture(
+                &self.context,
+                size,
+                format,
+                MinFilter::Nearest,
+                MagFilter::Nearest,
+                WrapMode::ClampToEdge,
+            )
+            .with_depth_stencil();
+            self.framebuffer = Some(framebuffer);
+        }
+        self.framebuffer.as_ref().unwrap()
+    }
+
+    /// Tone maps the contents of the framebuffer and writes the result to `output`.
+    ///
+    /// Panics if `framebuffer` hasn't been called yet.
+    pub fn resolve(&self, output: &(impl Surface + ?Sized)) {
+        let framebuffer = self.framebuffer.as_ref().expect("HDR framebuffer hasn't been created");
+        self.quad.draw(
+            &self.program,
+            output,
+            &ToneMapUniforms {
+                tex: &framebuffer.attachment,
+                tone_mapping: self.tone_mapping,
+                exposure: self.exposure,
+            },
+        );
+    }
+}
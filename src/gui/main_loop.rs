@@ -1,5 +1,7 @@
 use cgmath::*;
 use fxhash::*;
+use std::cell::Cell;
+use std::rc::Rc;
 use wasm_stopwatch::*;
 
 use crate::gl::*;
@@ -12,17 +14,21 @@ use std::cell::RefCell;
 #[cfg(target_arch = "wasm32")]
 use std::ops::*;
 #[cfg(target_arch = "wasm32")]
-use std::rc::Rc;
-#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsCast;
 #[cfg(target_arch = "wasm32")]
-use web_sys::{window, KeyboardEvent, MouseEvent, WheelEvent};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+#[cfg(target_arch = "wasm32")]
+use web_sys::{
+    window, CompositionEvent, DragEvent, HtmlCanvasElement, KeyboardEvent, MouseEvent,
+    ResizeObserver, WheelEvent,
+};
 
 #[cfg(not(target_arch = "wasm32"))]
 use glfw::Context;
 
+use super::clipboard::*;
 use super::event::*;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::glfw::*;
@@ -32,14 +38,41 @@ pub struct EventState {
     /// Contains all keys that are currently pressed.
     /// Note that this contains keycodes (`event.code`), not `event.key` values.
     pub pressed_keys: FxHashSet<Keycode>,
+    /// The modifier keys that are currently pressed. Unlike the flags in `Key`, this is updated
+    /// as soon as a modifier key is pressed or released.
+    pub modifiers: Modifiers,
     /// All mouse buttons that are currently pressed.
     pub pressed_mouse_buttons: FxHashSet<MouseButton>,
-    /// The current position of the cursor, if it's within the canvas.
+    /// The current position of the cursor, if it's within the canvas. Like the positions in
+    /// mouse events, it's in physical pixels; see `logical_cursor_pos`.
     pub cursor_pos: Option<Point2<i32>>,
     /// The position of the cursor before the last mouse movement event.
     pub prev_cursor_pos: Option<Point2<i32>>,
     /// True if a pointer lock is active (through the pointer lock API).
     pub pointer_locked: bool,
+    /// The system clipboard.
+    pub clipboard: Clipboard,
+    /// The ratio between physical pixels and logical pixels: the GLFW content scale, or
+    /// `devicePixelRatio` on wasm. It's updated by `Event::ScaleFactorChanged`.
+    pub scale_factor: f32,
+    /// Controls the main loop started by `start_main_loop`.
+    pub main_loop: MainLoopControl,
+}
+
+impl EventState {
+    fn update_modifiers(&mut self) {
+        self.modifiers = Modifiers {
+            shift: self.pressed_keys.contains("Shift"),
+            ctrl: self.pressed_keys.contains("Ctrl"),
+            alt: self.pressed_keys.contains("Alt"),
+        };
+    }
+
+    /// The current position of the cursor in logical pixels, i.e. `cursor_pos` divided by the
+    /// scale factor.
+    pub fn logical_cursor_pos(&self) -> Option<Point2<f32>> {
+        self.cursor_pos.map(|pos| pos.cast::<f32>().unwrap() / self.scale_factor)
+    }
 }
 
 /// The callback will be called every time an event occurs. This function is called by
@@ -56,10 +89,14 @@ pub fn setup_event_callbacks(
 ) -> Rc<RefCell<EventState>> {
     let event_state = Rc::new(RefCell::new(EventState {
         pressed_keys: Default::default(),
+        modifiers: Default::default(),
         pressed_mouse_buttons: Default::default(),
         cursor_pos: None,
         prev_cursor_pos: None,
         pointer_locked: false,
+        clipboard: Clipboard,
+        scale_factor: window().unwrap().device_pixel_ratio() as f32,
+        main_loop: Default::default(),
     }));
     let event_state2 = event_state.clone();
     let event_state3 = event_state.clone();
@@ -69,13 +106,16 @@ pub fn setup_event_callbacks(
         match event {
             Event::KeyDown(ref key) => {
                 event_state.pressed_keys.insert(key.code.clone());
+                event_state.update_modifiers();
             }
             Event::KeyUp(ref key) => {
                 event_state.pressed_keys.remove(&key.code);
+                event_state.update_modifiers();
             }
             Event::FocusLost => {
                 event_state.pressed_keys.clear();
                 event_state.pressed_mouse_buttons.clear();
+                event_state.update_modifiers();
             }
             Event::MouseDown(button, _) => {
                 event_state.pressed_mouse_buttons.insert(button);
@@ -96,6 +136,9 @@ pub fn setup_event_callbacks(
                 event_state.prev_cursor_pos = event_state.cursor_pos;
                 event_state.cursor_pos = Some(pos);
             }
+            Event::ScaleFactorChanged(scale_factor) => {
+                event_state.scale_factor = scale_factor;
+            }
             _ => (),
         }
         callback(event, &event_state);
@@ -112,11 +155,17 @@ pub fn setup_event_callbacks(
     let callback10 = callback.clone();
     let callback11 = callback.clone();
     let callback12 = callback.clone();
+    let callback13 = callback.clone();
+    let callback14 = callback.clone();
+    let callback15 = callback.clone();
+    let callback16 = callback.clone();
 
     let window = window().unwrap();
     let document = window.document().unwrap();
     let document2 = document.clone();
     let canvas = document.get_element_by_id(canvas_id).unwrap();
+    let canvas2 = canvas.clone().dyn_into::<HtmlCanvasElement>().unwrap();
+    let canvas3 = canvas2.clone();
 
     let keydown_handler = Closure::wrap(Box::new(move |e: KeyboardEvent| {
         let key = Key::from_js(&e);
@@ -178,12 +227,17 @@ pub fn setup_event_callbacks(
         .unwrap();
     mouseup_handler.forget();
 
+    let document3 = document.clone();
     let mousemove_handler = Closure::wrap(Box::new(move |e: MouseEvent| {
+        let movement = vec2(e.movement_x() as f64, e.movement_y() as f64);
         if let Some(event) = mouse_move_event_from_js(e) {
             callback7.borrow_mut().deref_mut()(event);
         } else {
             warn!("Invalid mouse event");
         }
+        if document3.pointer_lock_element().is_some() {
+            callback7.borrow_mut().deref_mut()(Event::RawMouseMotion(movement));
+        }
     }) as Box<dyn FnMut(MouseEvent)>);
     canvas
         .add_event_listener_with_callback("mousemove", mousemove_handler.as_ref().unchecked_ref())
@@ -207,8 +261,29 @@ pub fn setup_event_callbacks(
         .unwrap();
     mouseleave_handler.forget();
 
+    // The canvas is resized whenever its CSS size changes, whether that's caused by the window
+    // or by the page's layout. This fires once when the canvas is first observed.
+    let resize_observer_handler = Closure::wrap(Box::new(move || {
+        if let Some(size) = resize_canvas_to_display_size(&canvas2) {
+            (&mut callback13.borrow_mut())(Event::WindowResized(size));
+        }
+    }) as Box<dyn FnMut()>);
+    ResizeObserver::new(resize_observer_handler.as_ref().unchecked_ref()).unwrap().observe(&canvas);
+    resize_observer_handler.forget();
+
+    let mut scale_factor = window.device_pixel_ratio();
     let resize_handler = Closure::wrap(Box::new(move || {
-        (&mut callback10.borrow_mut())(Event::WindowResized(get_window_size()));
+        // The device pixel ratio can change when the page is zoomed or moved to another monitor;
+        // both also trigger a resize event.
+        let new_scale_factor = web_sys::window().unwrap().device_pixel_ratio();
+        if new_scale_factor != scale_factor {
+            scale_factor = new_scale_factor;
+            (&mut callback10.borrow_mut())(Event::ScaleFactorChanged(scale_factor as f32));
+            // The CSS size may not have changed, but the drawing buffer size depends on the ratio.
+            if let Some(size) = resize_canvas_to_display_size(&canvas3) {
+                (&mut callback10.borrow_mut())(Event::WindowResized(size));
+            }
+        }
     }) as Box<dyn FnMut()>);
     window
         .add_event_listener_with_callback("resize", resize_handler.as_ref().unchecked_ref())
@@ -230,20 +305,177 @@ pub fn setup_event_callbacks(
         .unwrap();
     pointer_lock_change_handler.forget();
 
+    let canvas4 = canvas2.clone();
     let wheel_handler = Closure::wrap(Box::new(move |e: WheelEvent| {
-        // Different browsers have different behavior for the "wheel" event, so restrict the scroll
-        // amount to either -1 or 1.
-        // TODO: is there a better solution?
-        callback12.borrow_mut().deref_mut()(Event::Scroll(e.delta_y().signum()));
+        callback12.borrow_mut().deref_mut()(scroll_event_from_js(&e, &canvas4));
     }) as Box<dyn FnMut(WheelEvent)>);
     canvas
         .add_event_listener_with_callback("wheel", wheel_handler.as_ref().unchecked_ref())
         .unwrap();
     wheel_handler.forget();
 
+    let composition_handler = Closure::wrap(Box::new(move |e: CompositionEvent| {
+        let text = e.data().unwrap_or_default();
+        let event = match e.type_().as_ref() {
+            "compositionstart" => Event::CompositionStart,
+            "compositionupdate" => Event::CompositionUpdate(text),
+            _ => Event::CompositionEnd(text),
+        };
+        callback15.borrow_mut().deref_mut()(event);
+    }) as Box<dyn FnMut(CompositionEvent)>);
+    for event in ["compositionstart", "compositionupdate", "compositionend"] {
+        document
+            .add_event_listener_with_callback(event, composition_handler.as_ref().unchecked_ref())
+            .unwrap();
+    }
+    composition_handler.forget();
+
+    // The page must cancel `dragover` for the canvas to accept drops, and cancel `drop` so that
+    // the browser doesn't open the file itself.
+    let dragover_handler = Closure::wrap(Box::new(move |e: DragEvent| {
+        e.prevent_default();
+    }) as Box<dyn FnMut(DragEvent)>);
+    canvas
+        .add_event_listener_with_callback("dragover", dragover_handler.as_ref().unchecked_ref())
+        .unwrap();
+    dragover_handler.forget();
+
+    let drop_handler = Closure::wrap(Box::new(move |e: DragEvent| {
+        e.prevent_default();
+        let files = match e.data_transfer().and_then(|data| data.files()) {
+            Some(files) => files,
+            None => return,
+        };
+        for file in (0..files.length()).filter_map(|i| files.get(i)) {
+            let callback = callback16.clone();
+            spawn_local(async move {
+                if let Ok(buffer) = JsFuture::from(file.array_buffer()).await {
+                    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+                    callback.borrow_mut().deref_mut()(Event::FileDropped {
+                        name: file.name(),
+                        bytes,
+                    });
+                }
+            });
+        }
+    }) as Box<dyn FnMut(DragEvent)>);
+    canvas.add_event_listener_with_callback("drop", drop_handler.as_ref().unchecked_ref()).unwrap();
+    drop_handler.forget();
+
+    set_paste_handler(Box::new(move |text| {
+        callback14.borrow_mut().deref_mut()(Event::Paste(text))
+    }));
+
     event_state3
 }
 
+/// Sets the size of the canvas's drawing buffer to its CSS size times the device pixel ratio, so
+/// it isn't stretched. Returns the new size if it changed.
+///
+/// The canvas's CSS size must not depend on its drawing buffer size, e.g. it could be set with
+/// `width: 100%; height: 100%;`. Otherwise it would grow every time the canvas is resized.
+#[cfg(target_arch = "wasm32")]
+fn resize_canvas_to_display_size(canvas: &HtmlCanvasElement) -> Option<Vector2<u32>> {
+    let scale_factor = window().unwrap().device_pixel_ratio();
+    let size = vec2(
+        (canvas.client_width() as f64 * scale_factor).round() as u32,
+        (canvas.client_height() as f64 * scale_factor).round() as u32,
+    );
+    if size == vec2(canvas.width(), canvas.height()) {
+        return None;
+    }
+    canvas.set_width(size.x);
+    canvas.set_height(size.y);
+    Some(size)
+}
+
+/// A handle for stopping the main loop, available from `EventState::main_loop`. Clones of it
+/// control the same loop.
+#[derive(Clone, Debug, Default)]
+pub struct MainLoopControl {
+    exit_requested: Rc<Cell<bool>>,
+}
+
+impl MainLoopControl {
+    /// Stops the main loop after the current frame. Natively the window is closed; on wasm no
+    /// more frames are requested and events are no longer passed to the app. `App::on_close` is
+    /// called in both cases.
+    ///
+    /// This has no effect if the main loop wasn't started with `start_main_loop` or
+    /// `start_main_loop_with_config`.
+    pub fn request_exit(&self) {
+        self.exit_requested.set(true);
+    }
+
+    /// True if `request_exit` has been called.
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested.get()
+    }
+}
+
+/// How the native main loop paces frames.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FramePacing {
+    /// Sleeps between frames to render at most this many frames per second.
+    TargetFps(f64),
+    /// Waits for the display's vertical sync when presenting each frame.
+    Vsync,
+    /// Renders frames as fast as possible.
+    Uncapped,
+}
+
+/// Options for `start_main_loop_with_config`.
+#[derive(Clone, Debug)]
+pub struct MainLoopConfig {
+    /// How frames are paced. This only applies natively; on wasm, frames are always scheduled
+    /// with `requestAnimationFrame`. The default is 60 FPS.
+    pub pacing: FramePacing,
+    /// If set, `App::fixed_update` is called with this timestep, in seconds, as many times as
+    /// needed to keep up with real time before each frame is rendered. `App::set_interpolation`
+    /// is then told how far the frame is between the last fixed update and the next one.
+    pub fixed_timestep: Option<f64>,
+    /// The most fixed updates to run before a frame. If the app falls further behind than this,
+    /// such as when a frame takes a long time, the time it's missed is skipped rather than
+    /// running ever more updates to catch up.
+    pub max_fixed_updates: u32,
+}
+
+impl Default for MainLoopConfig {
+    fn default() -> Self {
+        Self { pacing: FramePacing::TargetFps(60.0), fixed_timestep: None, max_fixed_updates: 8 }
+    }
+}
+
+/// Runs `App::fixed_update` for `MainLoopConfig::fixed_timestep`.
+struct FixedTimestep {
+    timestep: f64,
+    max_updates: u32,
+    // Time that hasn't been simulated yet, in seconds
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    fn new(config: &MainLoopConfig) -> Option<Self> {
+        let timestep = config.fixed_timestep?;
+        Some(Self { timestep, max_updates: config.max_fixed_updates, accumulator: 0.0 })
+    }
+
+    fn run(&mut self, app: &mut dyn App, event_state: &EventState, dt: f64) {
+        self.accumulator += dt;
+        let mut updates = 0;
+        while self.accumulator >= self.timestep {
+            if updates == self.max_updates {
+                self.accumulator %= self.timestep;
+                break;
+            }
+            app.fixed_update(event_state, self.timestep);
+            self.accumulator -= self.timestep;
+            updates += 1;
+        }
+        app.set_interpolation(self.accumulator / self.timestep);
+    }
+}
+
 /// An app that renders to a WebGL canvas.
 pub trait App {
     /// Called every time an event occurs. Apps may handle events here, or in `render_frame`.
@@ -260,9 +492,27 @@ pub trait App {
         dt: f64,
     );
 
-    /// Called when the web page is being closed.
+    /// Called with a constant `dt`, in seconds, if `MainLoopConfig::fixed_timestep` is set. This
+    /// is called before `render_frame`, zero or more times per frame, and is where things like
+    /// physics that need a fixed timestep should be updated.
+    fn fixed_update(&mut self, _event_state: &EventState, _dt: f64) {}
+
+    /// Called before `render_frame` if `MainLoopConfig::fixed_timestep` is set, with how far
+    /// the frame is between the last fixed update and the next one, from 0 to 1. Rendering can
+    /// interpolate between the last two fixed updates' states by this much so that motion looks
+    /// smooth when the frame rate doesn't match the timestep.
+    fn set_interpolation(&mut self, _alpha: f64) {}
+
+    /// Called when the window or web page is being closed, or after
+    /// `MainLoopControl::request_exit` stops the main loop.
     fn on_close(&mut self) {}
 
+    /// Returns the app's `ShaderWatcher`, if it has one. The main loop polls it before each frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn shader_watcher(&mut self) -> Option<&mut ShaderWatcher> {
+        None
+    }
+
     /// Returns a references to the app's `ScreenSurface`.
     // TODO: remove this if possible
     fn screen_surface(&mut self) -> &mut ScreenSurface;
@@ -276,6 +526,13 @@ pub trait App {
 /// `app` will never be dropped. The `on_close` method can be used as an alternative.
 #[cfg(target_arch = "wasm32")]
 pub fn start_main_loop(canvas_id: &str, app: Box<dyn App>) {
+    start_main_loop_with_config(canvas_id, app, MainLoopConfig::default());
+}
+
+/// Like `start_main_loop`, but with options such as a fixed timestep. `config.pacing` is
+/// ignored, since frames are always scheduled with `requestAnimationFrame`.
+#[cfg(target_arch = "wasm32")]
+pub fn start_main_loop_with_config(canvas_id: &str, app: Box<dyn App>, config: MainLoopConfig) {
     let queued_events = Rc::new(RefCell::new(vec![]));
     let queued_events2 = queued_events.clone();
 
@@ -284,8 +541,12 @@ pub fn start_main_loop(canvas_id: &str, app: Box<dyn App>) {
     let app3 = app.clone();
 
     let mut stopwatch = Stopwatch::new();
+    let mut fixed_timestep = FixedTimestep::new(&config);
 
-    let callback = move |event: Event, _: &EventState| {
+    let callback = move |event: Event, event_state: &EventState| {
+        if event_state.main_loop.exit_requested() {
+            return;
+        }
         app.borrow_mut().handle_event(event.clone());
         queued_events.borrow_mut().push(event);
     };
@@ -307,10 +568,23 @@ pub fn start_main_loop(canvas_id: &str, app: Box<dyn App>) {
         let events = std::mem::take(&mut *queued_events);
         let dt = stopwatch.get_time();
         stopwatch.reset();
-        app3.borrow_mut().render_frame(events, &event_state, dt);
+        let mut app = app3.borrow_mut();
+        if !event_state.main_loop.exit_requested() {
+            if let Some(fixed_timestep) = &mut fixed_timestep {
+                fixed_timestep.run(&mut **app, &event_state, dt);
+            }
+            app.render_frame(events, &event_state, dt);
+        }
 
-        web_sys::window()
-            .unwrap()
+        let window = web_sys::window().unwrap();
+        if event_state.main_loop.exit_requested() {
+            // Not requesting another frame ends the loop. `on_close` has already been called,
+            // so it shouldn't be called again when the page is closed.
+            window.set_onbeforeunload(None);
+            app.on_close();
+            return;
+        }
+        window
             .request_animation_frame(closure2.borrow().as_ref().unwrap().as_ref().unchecked_ref())
             .unwrap();
     }) as Box<dyn FnMut()>));
@@ -320,28 +594,48 @@ pub fn start_main_loop(canvas_id: &str, app: Box<dyn App>) {
         .unwrap();
 }
 
-/// Starts a main loop for an OpenGL app.
+/// Starts a main loop for an OpenGL app, rendering at up to 60 FPS.
 #[cfg(not(target_arch = "wasm32"))]
-pub fn start_main_loop(mut app: Box<dyn App>, event_receiver: EventReceiver) {
-    let fps: i32 = 60;
-    let dt_goal: f64 = 1.0 / fps as f64;
+pub fn start_main_loop(app: Box<dyn App>, event_receiver: EventReceiver) {
+    start_main_loop_with_config(app, event_receiver, MainLoopConfig::default());
+}
 
+/// Like `start_main_loop`, but with options for frame pacing and a fixed timestep.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn start_main_loop_with_config(
+    mut app: Box<dyn App>,
+    event_receiver: EventReceiver,
+    config: MainLoopConfig,
+) {
     // TODO: are both of these needed?
     let mut stopwatch = Stopwatch::new();
     let mut stopwatch2 = Stopwatch::new();
+    let mut fixed_timestep = FixedTimestep::new(&config);
 
     let mut glfw = get_glfw();
+    glfw.set_swap_interval(if config.pacing == FramePacing::Vsync {
+        glfw::SwapInterval::Sync(1)
+    } else {
+        glfw::SwapInterval::None
+    });
 
     let mut event_state = EventState {
         pressed_keys: Default::default(),
+        modifiers: Default::default(),
         pressed_mouse_buttons: Default::default(),
         cursor_pos: None,
         prev_cursor_pos: None,
         pointer_locked: app.screen_surface().grab_cursor,
+        clipboard: Clipboard,
+        scale_factor: app.screen_surface().content_scale(),
+        main_loop: Default::default(),
     }; // TODO
     let mut prev_cursor_pos = None; // TODO: merge with event_state
 
-    while !app.screen_surface().inner.should_close() {
+    // The cursor position in screen coordinates while it's grabbed, for `Event::RawMouseMotion`
+    let mut prev_raw_cursor_pos: Option<Vector2<f64>> = None;
+
+    while !app.screen_surface().inner.should_close() && !event_state.main_loop.exit_requested() {
         let dt = stopwatch2.get_time();
         stopwatch2.reset();
 
@@ -351,19 +645,47 @@ pub fn start_main_loop(mut app: Box<dyn App>, event_receiver: EventReceiver) {
         glfw.poll_events();
         let mut events = Vec::new();
         for (_, event) in glfw::flush_messages(&event_receiver) {
-            if let Some(event) =
-                event_from_glfw(&event, &app.screen_surface().inner, &mut prev_cursor_pos)
-            {
+            let mut converted = vec![];
+            if let glfw::WindowEvent::CursorPos(x, y) = event {
+                // While the cursor is grabbed, GLFW reports raw motion as cursor positions if
+                // it's supported. The cursor jumps when it's grabbed or released, so there's no
+                // motion until the second position after that.
+                let pos = vec2(x, y);
+                if app.screen_surface().grab_cursor {
+                    if let Some(prev_pos) = prev_raw_cursor_pos {
+                        converted.push(Event::RawMouseMotion(pos - prev_pos));
+                    }
+                    prev_raw_cursor_pos = Some(pos);
+                } else {
+                    prev_raw_cursor_pos = None;
+                }
+            }
+            // GLFW reports all of the files dropped at once in one event, but they're sent as
+            // separate events.
+            match event {
+                glfw::WindowEvent::FileDrop(paths) => {
+                    converted.extend(paths.into_iter().map(Event::FileDropped))
+                }
+                _ => converted.extend(event_from_glfw(
+                    &event,
+                    &app.screen_surface().inner,
+                    &mut prev_cursor_pos,
+                )),
+            }
+            for event in converted {
                 match event {
                     Event::KeyDown(ref key) => {
                         event_state.pressed_keys.insert(key.code.clone());
+                        event_state.update_modifiers();
                     }
                     Event::KeyUp(ref key) => {
                         event_state.pressed_keys.remove(&key.code);
+                        event_state.update_modifiers();
                     }
                     Event::FocusLost => {
                         event_state.pressed_keys.clear();
                         event_state.pressed_mouse_buttons.clear();
+                        event_state.update_modifiers();
                     }
                     Event::MouseDown(button, _) => {
                         event_state.pressed_mouse_buttons.insert(button);
@@ -388,6 +710,9 @@ pub fn start_main_loop(mut app: Box<dyn App>, event_receiver: EventReceiver) {
                         event_state.prev_cursor_pos = event_state.cursor_pos;
                         event_state.cursor_pos = Some(pos);
                     }
+                    Event::ScaleFactorChanged(scale_factor) => {
+                        event_state.scale_factor = scale_factor;
+                    }
                     _ => (),
                 }
                 events.push(event.clone());
@@ -395,24 +720,60 @@ pub fn start_main_loop(mut app: Box<dyn App>, event_receiver: EventReceiver) {
             }
         }
 
+        if take_monitors_changed() {
+            let event = Event::MonitorsChanged;
+            events.push(event.clone());
+            app.handle_event(event);
+        }
+
+        for text in take_pending_pastes() {
+            let event = Event::Paste(text);
+            events.push(event.clone());
+            app.handle_event(event);
+        }
+
+        // GLFW doesn't have an event for the cursor being grabbed, so report changes the same way
+        // the pointer lock API does on wasm.
+        let grab_cursor = app.screen_surface().grab_cursor;
+        if grab_cursor != event_state.pointer_locked {
+            event_state.pointer_locked = grab_cursor;
+            prev_raw_cursor_pos = None;
+            let event = if grab_cursor { Event::PointerLocked } else { Event::PointerUnlocked };
+            events.push(event.clone());
+            app.handle_event(event);
+        }
+
         if window_size != app.screen_surface().size() {
             let event = Event::WindowResized(window_size);
             events.push(event.clone());
             app.handle_event(event);
         }
 
+        if let Some(shader_watcher) = app.shader_watcher() {
+            shader_watcher.poll();
+        }
+
+        if let Some(fixed_timestep) = &mut fixed_timestep {
+            fixed_timestep.run(&mut *app, &event_state, dt);
+        }
         app.render_frame(events, &event_state, dt);
 
         app.screen_surface().inner.swap_buffers();
 
-        // We have to do this instead of glfwSwapInterval because that function does busy waiting on
+        // Sleeping is the default rather than vsync because glfwSwapInterval does busy waiting on
         // some platforms, using 100% of a cpu core for no good reason. On some platforms it
         // doesn't work at all. On some systems the omission of this can result in a high pitched
         // noise.
-        // TODO: is this still needed?
-        stopwatch.sleep_until(dt_goal);
-        stopwatch.add_time(-dt_goal);
+        if let FramePacing::TargetFps(fps) = config.pacing {
+            let dt_goal = 1.0 / fps;
+            stopwatch.sleep_until(dt_goal);
+            stopwatch.add_time(-dt_goal);
+        }
     }
 
+    // The window is destroyed when `app` is dropped, but it's hidden now in case `on_close`
+    // takes a while.
+    app.screen_surface().inner.set_should_close(true);
+    app.screen_surface().inner.hide();
     app.on_close();
 }
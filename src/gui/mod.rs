@@ -4,20 +4,43 @@
 //! be moved to separate crates at some point.
 
 mod assets;
+mod atlas;
+mod camera;
+mod clipboard;
 mod color;
 mod draw_2d;
+mod effects;
 mod event;
+mod gltf_scene;
 mod gui;
+mod hdr;
 mod main_loop;
+mod obj;
+mod picking;
+mod post_process;
 mod shader_header;
+mod style;
 mod text;
+mod triangulate;
 pub mod widgets;
 
 pub use self::assets::*;
+pub use self::atlas::*;
+pub use self::camera::*;
+pub use self::clipboard::*;
 pub use self::color::*;
 pub use self::draw_2d::*;
+pub use self::effects::*;
 pub use self::event::*;
+#[cfg(feature = "gltf")]
+pub use self::gltf_scene::*;
 pub use self::gui::*;
+pub use self::hdr::*;
 pub use self::main_loop::*;
+pub use self::obj::*;
+pub use self::picking::*;
+pub use self::post_process::*;
 pub use self::shader_header::*;
+pub use self::style::*;
 pub use self::text::Font;
+pub use self::triangulate::triangulate_polygon;
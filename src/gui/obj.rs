@@ -0,0 +1,319 @@
+use cgmath::*;
+use fxhash::FxHashMap;
+
+use crate::gl::*;
+
+/// A vertex of an `ObjModel`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ObjVertex {
+    pub pos: Point3<f32>,
+    /// Faces without normals get their geometric normal.
+    pub normal: Vector3<f32>,
+    /// Faces without texture coordinates get `(0, 0)`.
+    pub uv: Point2<f32>,
+}
+
+/// A material from an MTL file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// `Ka`
+    pub ambient: [f32; 3],
+    /// `Kd`
+    pub diffuse: [f32; 3],
+    /// `Ks`
+    pub specular: [f32; 3],
+    /// `Ns`
+    pub shininess: f32,
+    /// `d`, or 1 minus `Tr`
+    pub opacity: f32,
+    /// `map_Kd`. When loaded through `Assets`, texture paths are asset identifiers; otherwise
+    /// they're relative to the MTL file.
+    pub diffuse_texture: Option<String>,
+    /// `map_Ks`
+    pub specular_texture: Option<String>,
+    /// `map_Bump`, `bump` or `norm`
+    pub normal_texture: Option<String>,
+}
+
+impl ObjMaterial {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            ambient: [0.0; 3],
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+        }
+    }
+
+    /// The textures this material uses.
+    pub fn textures(&self) -> impl Iterator<Item = &str> {
+        [&self.diffuse_texture, &self.specular_texture, &self.normal_texture]
+            .into_iter()
+            .filter_map(|texture| texture.as_deref())
+    }
+
+    /// Parses an MTL file.
+    pub fn parse_mtl(source: &str) -> Result<Vec<Self>, String> {
+        let mut materials: Vec<ObjMaterial> = vec![];
+        for (line_num, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let (keyword, rest) = match line.split_once(char::is_whitespace) {
+                Some((keyword, rest)) => (keyword, rest.trim()),
+                None => (line, ""),
+            };
+            let error = |message: &str| format!("Line {}: {}", line_num + 1, message);
+            if keyword.is_empty() {
+                continue;
+            }
+            if keyword == "newmtl" {
+                materials.push(ObjMaterial::new(rest.to_owned()));
+                continue;
+            }
+            let material = match materials.last_mut() {
+                Some(material) => material,
+                None => return Err(error("Material property before `newmtl`")),
+            };
+            match keyword {
+                "Ka" => material.ambient = parse_color(rest).ok_or_else(|| error("Invalid Ka"))?,
+                "Kd" => material.diffuse = parse_color(rest).ok_or_else(|| error("Invalid Kd"))?,
+                "Ks" => material.specular = parse_color(rest).ok_or_else(|| error("Invalid Ks"))?,
+                "Ns" => material.shininess = rest.parse().map_err(|_| error("Invalid Ns"))?,
+                "d" => material.opacity = rest.parse().map_err(|_| error("Invalid d"))?,
+                "Tr" => {
+                    material.opacity = 1.0 - rest.parse::<f32>().map_err(|_| error("Invalid Tr"))?
+                }
+                "map_Kd" => material.diffuse_texture = Some(texture_path(rest)),
+                "map_Ks" => material.specular_texture = Some(texture_path(rest)),
+                "map_Bump" | "map_bump" | "bump" | "norm" => {
+                    material.normal_texture = Some(texture_path(rest))
+                }
+                // Other properties, such as illumination models, aren't supported.
+                _ => (),
+            }
+        }
+        Ok(materials)
+    }
+}
+
+fn parse_color(s: &str) -> Option<[f32; 3]> {
+    let mut components = s.split_whitespace().map(|x| x.parse::<f32>());
+    let r = components.next()?.ok()?;
+    // A single value is used for all components.
+    let g = components.next().unwrap_or(Ok(r)).ok()?;
+    let b = components.next().unwrap_or(Ok(r)).ok()?;
+    Some([r, g, b])
+}
+
+// Texture statements can have options such as `-bm 1.0` before the path; the path is assumed to
+// be the last word.
+fn texture_path(s: &str) -> String {
+    s.split_whitespace().last().unwrap_or("").to_owned()
+}
+
+/// The faces of an `ObjModel` that use the same material.
+#[derive(Clone, Debug)]
+pub struct ObjGroup {
+    /// The name given by `usemtl`, if any.
+    pub material: Option<String>,
+    pub vertices: Vec<ObjVertex>,
+    /// Three indices into `vertices` for each triangle.
+    pub indices: Vec<MeshIndex>,
+}
+
+/// A model loaded from a Wavefront OBJ file, with its faces grouped by material.
+///
+/// Polygons are split into triangles. Use `Assets::get_obj` or `Assets::load_obj` to load a
+/// model along with its materials, or `ObjModel::parse` to parse just the OBJ file.
+#[derive(Clone, Debug)]
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+    pub materials: Vec<ObjMaterial>,
+    /// The MTL files named by `mtllib`, relative to the OBJ file.
+    pub material_libraries: Vec<String>,
+}
+
+impl ObjModel {
+    /// Parses an OBJ file. The model's `materials` are empty; they can be parsed from the files
+    /// in `material_libraries` with `ObjMaterial::parse_mtl`.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut positions: Vec<Point3<f32>> = vec![];
+        let mut normals: Vec<Vector3<f32>> = vec![];
+        let mut uvs: Vec<Point2<f32>> = vec![];
+        let mut material_libraries = vec![];
+        let mut groups: Vec<ObjGroup> = vec![];
+        let mut current_group = None;
+        // Maps (position, uv, normal) indices to vertex indices within each group; faces without
+        // normals aren't deduplicated
+        let mut vertex_indices: Vec<FxHashMap<(usize, usize, usize), MeshIndex>> = vec![];
+
+        for (line_num, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let mut words = line.split_whitespace();
+            let keyword = match words.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+            let error = |message: &str| format!("Line {}: {}", line_num + 1, message);
+            let mut floats = || -> Result<Vec<f32>, String> {
+                words
+                    .by_ref()
+                    .map(|x| x.parse().map_err(|_| error(&format!("Invalid number {:?}", x))))
+                    .collect()
+            };
+            match keyword {
+                "v" => match floats()?[..] {
+                    [x, y, z, ..] => positions.push(point3(x, y, z)),
+                    _ => return Err(error("Vertex positions must have 3 components")),
+                },
+                "vn" => match floats()?[..] {
+                    [x, y, z] => normals.push(vec3(x, y, z)),
+                    _ => return Err(error("Normals must have 3 components")),
+                },
+                "vt" => match floats()?[..] {
+                    [u] => uvs.push(point2(u, 0.0)),
+                    [u, v, ..] => uvs.push(point2(u, v)),
+                    _ => return Err(error("Texture coordinates must have 1 to 3 components")),
+                },
+                "usemtl" => {
+                    let name = line[keyword.len()..].trim().to_owned();
+                    current_group = Some(
+                        match groups.iter().position(|group| group.material.as_ref() == Some(&name))
+                        {
+                            Some(index) => index,
+                            None => {
+                                groups.push(ObjGroup {
+                                    material: Some(name),
+                                    vertices: vec![],
+                                    indices: vec![],
+                                });
+                                vertex_indices.push(FxHashMap::default());
+                                groups.len() - 1
+                            }
+                        },
+                    );
+                }
+                "mtllib" => material_libraries.extend(words.map(|x| x.to_owned())),
+                "f" => {
+                    let corners = words
+                        .map(|corner| {
+                            parse_corner(corner, positions.len(), uvs.len(), normals.len())
+                                .ok_or_else(|| error(&format!("Invalid face vertex {:?}", corner)))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if corners.len() < 3 {
+                        return Err(error("Faces must have at least 3 vertices"));
+                    }
+
+                    let group_index = *current_group.get_or_insert_with(|| {
+                        groups.push(ObjGroup { material: None, vertices: vec![], indices: vec![] });
+                        vertex_indices.push(FxHashMap::default());
+                        groups.len() - 1
+                    });
+                    let group = &mut groups[group_index];
+                    let face_normal = {
+                        let [a, b, c] = [0, 1, 2].map(|i| positions[corners[i].0]);
+                        let normal = (b - a).cross(c - a);
+                        if normal.magnitude2() > 0.0 {
+                            normal.normalize()
+                        } else {
+                            Vector3::unit_y()
+                        }
+                    };
+
+                    let mut indices = Vec::with_capacity(corners.len());
+                    for &(pos, uv, normal) in &corners {
+                        let vertex = ObjVertex {
+                            pos: positions[pos],
+                            normal: normal.map_or(face_normal, |normal| normals[normal]),
+                            uv: uv.map_or(point2(0.0, 0.0), |uv| uvs[uv]),
+                        };
+                        let index = match normal {
+                            Some(normal) => {
+                                let key = (pos, uv.unwrap_or(usize::MAX), normal);
+                                *vertex_indices[group_index].entry(key).or_insert_with(|| {
+                                    group.vertices.push(vertex);
+                                    group.vertices.len() as MeshIndex - 1
+                                })
+                            }
+                            None => {
+                                group.vertices.push(vertex);
+                                group.vertices.len() as MeshIndex - 1
+                            }
+                        };
+                        indices.push(index);
+                    }
+                    for i in 1..indices.len() - 1 {
+                        group.indices.extend([indices[0], indices[i], indices[i + 1]]);
+                    }
+                }
+                // Object and group names, smoothing groups, etc. are ignored.
+                _ => (),
+            }
+        }
+
+        groups.retain(|group| !group.indices.is_empty());
+        Ok(Self { groups, materials: vec![], material_libraries })
+    }
+
+    /// Returns the material with the given name.
+    pub fn material(&self, name: &str) -> Option<&ObjMaterial> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+
+    /// Creates a `MeshBuilder` for each group, along with the group's material. `vert` maps
+    /// each vertex's position, normal and UV coordinates to a `V`.
+    pub fn mesh_builders<V: Vertex>(
+        &self,
+        mut vert: impl FnMut(Point3<f32>, Vector3<f32>, Point2<f32>) -> V,
+    ) -> Vec<(Option<&ObjMaterial>, MeshBuilder<V, Triangles>)> {
+        self.groups
+            .iter()
+            .map(|group| {
+                let mut builder = MeshBuilder::new();
+                for vertex in &group.vertices {
+                    builder.vert(vert(vertex.pos, vertex.normal, vertex.uv));
+                }
+                for triangle in group.indices.chunks(3) {
+                    builder.triangle(triangle[0], triangle[1], triangle[2]);
+                }
+                (group.material.as_deref().and_then(|name| self.material(name)), builder)
+            })
+            .collect()
+    }
+}
+
+// Parses a face vertex such as `1`, `1/2`, `1//3` or `1/2/3` into 0-based position, UV and
+// normal indices. Negative indices are relative to the end of each list.
+fn parse_corner(
+    corner: &str,
+    num_positions: usize,
+    num_uvs: usize,
+    num_normals: usize,
+) -> Option<(usize, Option<usize>, Option<usize>)> {
+    let index = |s: &str, len: usize| -> Option<usize> {
+        let i: isize = s.parse().ok()?;
+        let i = if i < 0 { len as isize + i } else { i - 1 };
+        if i >= 0 && (i as usize) < len {
+            Some(i as usize)
+        } else {
+            None
+        }
+    };
+    let mut parts = corner.split('/');
+    let pos = index(parts.next()?, num_positions)?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(s) => Some(index(s, num_uvs)?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(s) => Some(index(s, num_normals)?),
+    };
+    Some((pos, uv, normal))
+}
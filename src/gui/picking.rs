@@ -0,0 +1,290 @@
+use crate::gl::uniforms::*;
+use crate::gl::*;
+use cgmath::*;
+use glow::HasContext;
+
+use super::shader_header::*;
+
+type GlFramebuffer = <glow::Context as HasContext>::Framebuffer;
+type GlRenderbuffer = <glow::Context as HasContext>::Renderbuffer;
+
+/// The largest ID that can be drawn to a `PickingBuffer`. IDs are stored in the RGB channels,
+/// and 0 is reserved for empty pixels.
+pub const MAX_PICKING_ID: u32 = (1 << 24) - 2;
+
+pub struct PickingUniforms {
+    pub matrix: Matrix4<f32>,
+    pub id: u32,
+}
+
+pub struct PickingUniformsGl {
+    matrix: Matrix4Uniform,
+    id: Array4Uniform,
+}
+
+impl Uniforms for PickingUniforms {
+    type GlUniforms = PickingUniformsGl;
+
+    fn update(&self, context: &GlContext, gl_uniforms: &Self::GlUniforms) {
+        assert!(self.id <= MAX_PICKING_ID, "Picking ID too large");
+        let [r, g, b, _] = (self.id + 1).to_le_bytes();
+        gl_uniforms.matrix.set(context, &self.matrix);
+        gl_uniforms.id.set(context, [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0]);
+    }
+}
+
+impl GlUniforms for PickingUniformsGl {
+    fn new(context: &GlContext, program: GlProgramId) -> Self {
+        let matrix = Matrix4Uniform::new("matrix", context, program);
+        let id = Array4Uniform::new("pickingId", context, program);
+        PickingUniformsGl { matrix, id }
+    }
+}
+
+/// A program that draws meshes with vertex type `V` as a flat color encoding an object ID.
+///
+/// The program is generated from `V`'s attributes. The position attribute is transformed by the
+/// matrix passed to `PickingBuffer::draw`; other attributes are ignored.
+pub struct PickingProgram<V: Vertex> {
+    program: GlProgram<V, PickingUniformsGl>,
+}
+
+impl<V: Vertex> PickingProgram<V> {
+    /// `position_attribute` is the name of `V`'s position attribute, which must have 2-4
+    /// components.
+    pub fn new(context: &GlContext, position_attribute: &str) -> Self {
+        let mut vert_shader = String::from("uniform mat4 matrix;\n");
+        let mut position = None;
+        for &(attr, size) in V::ATTRIBUTES {
+            let ty = match size {
+                1 => "float",
+                2 => "vec2",
+                3 => "vec3",
+                4 => "vec4",
+                16 => "mat4",
+                _ => panic!("Unsupported vertex data size"),
+            };
+            vert_shader.push_str(&format!("in {} {};\n", ty, attr));
+            if attr == position_attribute {
+                position = Some(match size {
+                    2 => format!("vec4({}, 0.0, 1.0)", attr),
+                    3 => format!("vec4({}, 1.0)", attr),
+                    4 => attr.to_string(),
+                    _ => panic!("Position attribute must have 2-4 components"),
+                });
+            }
+        }
+        let position = position.expect("Position attribute not found");
+        vert_shader
+            .push_str(&format!("void main() {{\n  gl_Position = matrix * {};\n}}", position));
+
+        let frag_shader = "
+uniform vec4 pickingId;
+
+out vec4 outColor;
+
+void main() {
+  outColor = pickingId;
+}";
+
+        Self { program: GlProgram::new_with_minimal_header(context, &vert_shader, frag_shader) }
+    }
+}
+
+/// An offscreen buffer that objects are drawn to with their IDs, so the object under a point
+/// can be found by reading back a single pixel.
+///
+/// The buffer has a depth attachment, so meshes drawn with depth testing occlude each other as
+/// they do on screen. It isn't resized automatically; create a new one when the screen size
+/// changes.
+pub struct PickingBuffer {
+    framebuffer: GlFramebuffer,
+    color: GlRenderbuffer,
+    depth: GlRenderbuffer,
+    size: Vector2<u32>,
+    viewport: Rect<i32>,
+    id: FramebufferId,
+    context: GlContext,
+}
+
+impl Drop for PickingBuffer {
+    fn drop(&mut self) {
+        let bytes = self.renderbuffer_bytes();
+        self.context.track_deleted(ResourceKind::Framebuffer, 0);
+        self.context.track_deleted(ResourceKind::Renderbuffer, bytes);
+        self.context.track_deleted(ResourceKind::Renderbuffer, bytes);
+        unsafe {
+            self.context.inner().delete_framebuffer(self.framebuffer);
+            self.context.inner().delete_renderbuffer(self.color);
+            self.context.inner().delete_renderbuffer(self.depth);
+        }
+    }
+}
+
+impl PickingBuffer {
+    pub fn new(context: &GlContext, size: Vector2<u32>) -> Self {
+        assert!(!context.is_webgl1(), "Picking isn't supported in WebGL1");
+        unsafe {
+            let gl = context.inner();
+            let create_renderbuffer = |format| {
+                let renderbuffer = gl.create_renderbuffer().unwrap();
+                gl.bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+                // This isn't multisampled, since blending IDs at the edges of objects would
+                // produce invalid IDs.
+                gl.renderbuffer_storage(glow::RENDERBUFFER, format, size.x as i32, size.y as i32);
+                renderbuffer
+            };
+            let color = create_renderbuffer(glow::RGBA8);
+            let depth = create_renderbuffer(glow::DEPTH_COMPONENT24);
+
+            let framebuffer = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::RENDERBUFFER,
+                Some(color),
+            );
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_ATTACHMENT,
+                glow::RENDERBUFFER,
+                Some(depth),
+            );
+            assert_eq!(
+                gl.check_framebuffer_status(glow::FRAMEBUFFER),
+                glow::FRAMEBUFFER_COMPLETE,
+                "Picking framebuffer not complete"
+            );
+            drop(gl);
+            // The framebuffer was bound directly, so the cached bindings are no longer valid.
+            let mut cache = context.cache.borrow_mut();
+            cache.bound_framebuffer = None;
+            cache.bound_read_framebuffer = None;
+            drop(cache);
+
+            let res = Self {
+                framebuffer,
+                color,
+                depth,
+                size,
+                viewport: Rect::new(Point2::origin(), Point2::from_vec(size.cast().unwrap())),
+                id: FramebufferId::new(),
+                context: context.clone(),
+            };
+            let bytes = res.renderbuffer_bytes();
+            context.track_created(ResourceKind::Renderbuffer, bytes);
+            context.track_created(ResourceKind::Renderbuffer, bytes);
+            context.track_created(ResourceKind::Framebuffer, 0);
+            res
+        }
+    }
+
+    // Both renderbuffers use 4 bytes per pixel.
+    fn renderbuffer_bytes(&self) -> usize {
+        self.size.x as usize * self.size.y as usize * 4
+    }
+
+    /// Clears all IDs and depth values. This should be done before drawing each frame's objects.
+    pub fn clear(&self) {
+        Surface::clear(
+            self,
+            &self.context,
+            &[ClearBuffer::Color([0.0; 4]), ClearBuffer::Depth(1.0)],
+        );
+    }
+
+    /// Draws a mesh with the given ID. The mesh's `PipelineState` is used, so it's culled and depth
+    /// tested the same way as when it's drawn normally.
+    pub fn draw<V: Vertex, U: GlUniforms, P: Primitive>(
+        &self,
+        mesh: &Mesh<V, U, P>,
+        program: &PickingProgram<V>,
+        matrix: Matrix4<f32>,
+        id: u32,
+    ) {
+        mesh.draw_range_with_program(
+            &program.program,
+            self,
+            &PickingUniforms { matrix, id },
+            0..mesh.num_indices(),
+        );
+    }
+
+    /// Returns the ID of the object at `pos`, in pixels from the top left corner, or `None` if
+    /// there's no object there.
+    pub fn pick(&self, pos: Point2<i32>) -> Option<u32> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.size.x as i32 || pos.y >= self.size.y as i32 {
+            return None;
+        }
+
+        self.bind_read(&self.context);
+        let mut pixel = [0; 4];
+        unsafe {
+            self.context.inner().read_pixels(
+                pos.x,
+                self.size.y as i32 - 1 - pos.y,
+                1,
+                1,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixel),
+            );
+        }
+        let [r, g, b, _] = pixel;
+        u32::from_le_bytes([r, g, b, 0]).checked_sub(1)
+    }
+
+    /// Like `pick`, but doesn't stall while waiting for the picking buffer to be drawn. The result
+    /// is typically available a frame or two later.
+    pub fn pick_async(&self, pos: Point2<i32>) -> PendingPick {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.size.x as i32 || pos.y >= self.size.y as i32 {
+            return PendingPick { readback: None };
+        }
+        let pos = point2(pos.x, self.size.y as i32 - 1 - pos.y);
+        let readback =
+            self.read_pixels_async(&self.context, Rect::new(pos, pos + vec2(1, 1)), true);
+        PendingPick { readback: Some(readback) }
+    }
+}
+
+/// The result of `PickingBuffer::pick_async`.
+pub struct PendingPick {
+    // `None` if the position was outside the picking buffer
+    readback: Option<PixelReadback>,
+}
+
+impl PendingPick {
+    /// Returns `None` if the result isn't available yet. Otherwise, returns the ID of the object
+    /// at the position, or `None` if there's no object there.
+    pub fn poll(&mut self) -> Option<Option<u32>> {
+        match &mut self.readback {
+            None => Some(None),
+            Some(readback) => {
+                let pixel = readback.poll()?;
+                Some(u32::from_le_bytes([pixel[0], pixel[1], pixel[2], 0]).checked_sub(1))
+            }
+        }
+    }
+}
+
+impl Surface for PickingBuffer {
+    #[doc(hidden)]
+    fn bind(&self, context: &GlContext) {
+        context.bind_draw_framebuffer(self.id, Some(self.framebuffer), &self.viewport);
+    }
+
+    #[doc(hidden)]
+    fn bind_read(&self, context: &GlContext) {
+        context.bind_read_framebuffer(self.id, Some(self.framebuffer));
+    }
+
+    #[doc(hidden)]
+    fn id(&self) -> FramebufferId {
+        self.id
+    }
+
+    fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+}
@@ -0,0 +1,217 @@
+use crate::gl::uniforms::*;
+use crate::gl::*;
+use std::rc::Rc;
+
+use super::shader_header::*;
+
+/// The maximum number of input textures a `PostProcessPass` can use.
+pub const MAX_POST_PROCESS_INPUTS: usize = 4;
+
+// Added before each pass's fragment shader, after the standard header.
+const FRAG_PREAMBLE: &str = "
+in vec2 uv;
+
+uniform sampler2D tex0;
+uniform sampler2D tex1;
+uniform sampler2D tex2;
+uniform sampler2D tex3;
+// The size of one texel of tex0, in UV coordinates
+uniform vec2 texelSize;
+uniform vec4 params;
+";
+
+pub type PostProcessVert = FullscreenVert;
+
+pub struct PostProcessUniforms<'a> {
+    pub inputs: &'a [&'a Texture2d],
+    pub params: [f32; 4],
+}
+
+pub struct PostProcessUniformsGl {
+    inputs: Vec<Option<TextureUniform>>,
+    texel_size: Option<Array2Uniform>,
+    params: Option<Array4Uniform>,
+}
+
+impl<'a> Uniforms for PostProcessUniforms<'a> {
+    type GlUniforms = PostProcessUniformsGl;
+
+    fn update(&self, context: &GlContext, gl_uniforms: &Self::GlUniforms) {
+        assert!(self.inputs.len() <= MAX_POST_PROCESS_INPUTS, "Too many post-processing inputs");
+        for (i, (uniform, tex)) in gl_uniforms.inputs.iter().zip(self.inputs).enumerate() {
+            if let Some(uniform) = uniform {
+                uniform.set(context, tex, i as u32);
+            }
+        }
+        if let (Some(uniform), Some(tex)) = (&gl_uniforms.texel_size, self.inputs.first()) {
+            let size = tex.size();
+            uniform.set(context, [1.0 / size.x as f32, 1.0 / size.y as f32]);
+        }
+        if let Some(uniform) = &gl_uniforms.params {
+            uniform.set(context, self.params);
+        }
+    }
+}
+
+impl GlUniforms for PostProcessUniformsGl {
+    fn new(context: &GlContext, program: GlProgramId) -> Self {
+        // Passes don't have to use every uniform, and unused ones have no location.
+        let inputs = (0..MAX_POST_PROCESS_INPUTS)
+            .map(|i| TextureUniform::try_new(&format!("tex{}", i), context, program))
+            .collect();
+        let texel_size = Array2Uniform::try_new("texelSize", context, program);
+        let params = Array4Uniform::try_new("params", context, program);
+        PostProcessUniformsGl { inputs, texel_size, params }
+    }
+}
+
+/// A full-screen effect, such as a vignette or FXAA, implemented by a fragment shader.
+///
+/// The fragment shader is given the standard header (see `GlProgramWithHeader::new_with_header`)
+/// and these declarations:
+/// ```glsl
+/// in vec2 uv;
+/// uniform sampler2D tex0; // The first input; up to tex3 are available
+/// uniform vec2 texelSize; // The size of one texel of tex0, in UV coordinates
+/// uniform vec4 params;    // `PostProcessPass::params`
+/// ```
+/// It should write its output with `writeColor2D`.
+pub struct PostProcessPass {
+    program: GlProgram<PostProcessVert, PostProcessUniformsGl>,
+    // This can be shared between passes
+    quad: Rc<FullscreenQuad<PostProcessUniformsGl>>,
+    /// Values passed to the shader's `params` uniform.
+    pub params: [f32; 4],
+}
+
+impl PostProcessPass {
+    pub fn new(context: &GlContext, frag_shader_source: &str, convert_to_srgb: bool) -> Self {
+        let program = Self::create_program(context, frag_shader_source, convert_to_srgb);
+        let quad = FullscreenQuad::new(context, &program);
+        Self { program, quad: Rc::new(quad), params: [0.0; 4] }
+    }
+
+    /// Like `new`, but shares this pass's `FullscreenQuad` instead of creating another one.
+    pub fn new_sharing_mesh(
+        &self,
+        context: &GlContext,
+        frag_shader_source: &str,
+        convert_to_srgb: bool,
+    ) -> Self {
+        let program = Self::create_program(context, frag_shader_source, convert_to_srgb);
+        Self { program, quad: self.quad.clone(), params: [0.0; 4] }
+    }
+
+    fn create_program(
+        context: &GlContext,
+        frag_shader_source: &str,
+        convert_to_srgb: bool,
+    ) -> GlProgram<PostProcessVert, PostProcessUniformsGl> {
+        GlProgram::new_with_header(
+            context,
+            include_str!("shaders/post_process_vert.glsl"),
+            &format!("{}{}", FRAG_PREAMBLE, frag_shader_source),
+            convert_to_srgb,
+        )
+    }
+
+    /// Runs the pass, reading from `inputs` and covering all of `output`.
+    pub fn render(&self, inputs: &[&Texture2d], output: &(impl Surface + ?Sized)) {
+        self.quad.draw(&self.program, output, &PostProcessUniforms { inputs, params: self.params });
+    }
+}
+
+/// Something that can be added to a `PostProcessChain`: a `PostProcessPass`, or an effect with
+/// several passes such as `BlurEffect` or `BloomEffect`.
+pub trait PostProcessEffect {
+    /// Reads from `input` and covers all of `output`.
+    fn render(&mut self, input: &Texture2d, output: &dyn Surface);
+
+    /// Used by `PostProcessChain::add_pass` to return the pass it added.
+    #[doc(hidden)]
+    fn as_pass_mut(&mut self) -> Option<&mut PostProcessPass> {
+        None
+    }
+}
+
+impl PostProcessEffect for PostProcessPass {
+    fn render(&mut self, input: &Texture2d, output: &dyn Surface) {
+        PostProcessPass::render(self, &[input], output);
+    }
+
+    fn as_pass_mut(&mut self) -> Option<&mut PostProcessPass> {
+        Some(self)
+    }
+}
+
+/// A sequence of `PostProcessEffect`s, each of which reads the previous effect's output.
+///
+/// Intermediate results are stored in two framebuffers that are created when needed and
+/// recreated when the input size changes.
+pub struct PostProcessChain {
+    pub effects: Vec<Box<dyn PostProcessEffect>>,
+    // Shared by the passes added with `add_pass`
+    quad: Option<Rc<FullscreenQuad<PostProcessUniformsGl>>>,
+    targets: Option<[Framebuffer<Texture2d>; 2]>,
+    context: GlContext,
+}
+
+impl PostProcessChain {
+    pub fn new(context: &GlContext) -> Self {
+        Self { effects: vec![], quad: None, targets: None, context: context.clone() }
+    }
+
+    /// Adds a pass to the end of the chain, sharing the `FullscreenQuad` of the other passes
+    /// added with this method.
+    pub fn add_pass(
+        &mut self,
+        frag_shader_source: &str,
+        convert_to_srgb: bool,
+    ) -> &mut PostProcessPass {
+        let program =
+            PostProcessPass::create_program(&self.context, frag_shader_source, convert_to_srgb);
+        let quad = self
+            .quad
+            .get_or_insert_with(|| Rc::new(FullscreenQuad::new(&self.context, &program)))
+            .clone();
+        self.add(PostProcessPass { program, quad, params: [0.0; 4] });
+        self.effects.last_mut().unwrap().as_pass_mut().unwrap()
+    }
+
+    /// Adds an effect to the end of the chain.
+    pub fn add(&mut self, effect: impl PostProcessEffect + 'static) {
+        self.effects.push(Box::new(effect));
+    }
+
+    /// Runs each effect in order, starting with `input` and writing the last effect's output to
+    /// `output`. The chain must have at least one effect.
+    pub fn render(&mut self, input: &Texture2d, output: &dyn Surface) {
+        assert!(!self.effects.is_empty(), "Post-processing chain has no effects");
+
+        let size = input.size();
+        if self.effects.len() > 1
+            && !matches!(&self.targets, Some(targets) if targets[0].size() == size)
+        {
+            let create_target = || {
+                Framebuffer::new_with_texture(
+                    &self.context,
+                    size,
+                    TextureFormat::RGBA,
+                    MinFilter::Linear,
+                    MagFilter::Linear,
+                    WrapMode::ClampToEdge,
+                )
+            };
+            self.targets = Some([create_target(), create_target()]);
+        }
+
+        let mut input = input;
+        let (last, rest) = self.effects.split_last_mut().unwrap();
+        for (i, effect) in rest.iter_mut().enumerate() {
+            let target = &self.targets.as_ref().unwrap()[i % 2];
+            effect.render(input, target);
+            input = &target.attachment;
+        }
+        last.render(input, output);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::gl::uniforms::*;
 use crate::gl::*;
 
-const COMMON_HEADER: &str = "#version 300 es
+// Added after the `#version` directive, which depends on the context.
+const COMMON_HEADER: &str = "
 precision highp float;
 precision highp sampler2D;
 precision highp samplerCube;
@@ -14,9 +15,8 @@ void writeGlPosition2D(vec4 pos) {
 }
 ";
 
+// The fragment headers are preceded by the declaration of `outColor`; see `frag_output_decl`.
 const FRAG_HEADER_SRGB: &str = "
-out vec4 outColor;
-
 vec4 srgb(vec4 color) {
   return vec4(pow(color.rgb, vec3(1.0 / 2.2)), color.a);
 }
@@ -27,79 +27,304 @@ void writeColor2D(vec4 color) {
 ";
 
 const FRAG_HEADER_NO_SRGB: &str = "
-out vec4 outColor;
-
 void writeColor2D(vec4 color) {
   outColor = color;
 }
 ";
 
-fn get_shader_header(shader_type: ShaderType, convert_to_srgb: bool) -> &'static str {
+/// The name of the uniform that HDR fragment shaders use for exposure; see
+/// `GlProgramWithHeader::new_with_hdr_header`.
+pub const EXPOSURE_UNIFORM: &str = "exposure";
+
+// Added to fragment shaders after the standard header by `new_with_hdr_header`.
+const FRAG_HEADER_HDR: &str = "
+uniform float exposure;
+
+vec3 tonemapReinhard(vec3 color) {
+  return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+vec3 tonemapACES(vec3 color) {
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+vec3 applyExposure(vec3 color) {
+  return color * exp2(exposure);
+}
+";
+
+fn get_shader_header(
+    context: &GlContext,
+    shader_type: ShaderType,
+    convert_to_srgb: bool,
+) -> String {
     match shader_type {
-        ShaderType::Vertex => VERT_HEADER,
+        ShaderType::Vertex => VERT_HEADER.to_owned(),
         ShaderType::Fragment => {
-            if convert_to_srgb {
-                FRAG_HEADER_SRGB
-            } else {
-                FRAG_HEADER_NO_SRGB
-            }
+            let header = if convert_to_srgb { FRAG_HEADER_SRGB } else { FRAG_HEADER_NO_SRGB };
+            format!("{}{}", frag_output_decl(context), header)
         }
+        ShaderType::Compute => String::new(),
+    }
+}
+
+/// Declares `outColor`. It's given an explicit location where that's supported, so shaders can
+/// declare additional outputs for framebuffers with multiple attachments.
+fn frag_output_decl(context: &GlContext) -> &'static str {
+    match context.glsl_version() {
+        "#version 300 es" | "#version 330 core" => "\nlayout(location = 0) out vec4 outColor;\n",
+        _ => "\nout vec4 outColor;\n",
+    }
+}
+
+// WebGL1 only supports GLSL ES 1.00, which doesn't have `#version` or sampler precisions.
+const COMMON_HEADER_WEBGL1: &str = "precision highp float;
+";
+
+fn add_shader_minimal_header(context: &GlContext, shader_type: ShaderType, source: &str) -> String {
+    if context.is_webgl1() {
+        return format!("{}{}", COMMON_HEADER_WEBGL1, to_glsl_100(shader_type, source));
     }
+    format!("{}{}{}", context.glsl_version(), COMMON_HEADER, source)
 }
 
-fn add_shader_minimal_header(source: &str) -> String {
-    let header = COMMON_HEADER;
-    format!("{}{}", header, source)
+fn add_shader_header(
+    context: &GlContext,
+    shader_type: ShaderType,
+    source: &str,
+    convert_to_srgb: bool,
+) -> String {
+    let source = format!(
+        "{}{}{}",
+        context.shader_snippets(shader_type),
+        get_shader_header(context, shader_type, convert_to_srgb),
+        source
+    );
+    add_shader_minimal_header(context, shader_type, &source)
 }
 
-fn add_shader_header(shader_type: ShaderType, source: &str, convert_to_srgb: bool) -> String {
-    let header = format!("{}{}", COMMON_HEADER, get_shader_header(shader_type, convert_to_srgb));
-    format!("{}{}", header, source)
+/// Translates GLSL ES 3.00 to GLSL ES 1.00 for WebGL1. This only handles the differences that
+/// simple shaders run into: `in`/`out` declarations, fragment shader outputs, and `texture()`.
+fn to_glsl_100(shader_type: ShaderType, source: &str) -> String {
+    let mut res = String::new();
+    for line in source.lines() {
+        let mut trimmed = line.trim_start();
+        // GLSL ES 1.00 doesn't have layout qualifiers.
+        if trimmed.starts_with("layout(") {
+            if let Some(end) = trimmed.find(')') {
+                trimmed = trimmed[end + 1..].trim_start();
+            }
+        }
+        if let Some(decl) = trimmed.strip_prefix("in ") {
+            let qualifier = match shader_type {
+                ShaderType::Vertex => "attribute",
+                ShaderType::Fragment => "varying",
+                ShaderType::Compute => unreachable!("Compute shaders aren't supported in WebGL1"),
+            };
+            res.push_str(&format!("{} {}\n", qualifier, decl));
+        } else if let Some(decl) = trimmed.strip_prefix("out ") {
+            match shader_type {
+                ShaderType::Vertex => res.push_str(&format!("varying {}\n", decl)),
+                ShaderType::Fragment => {
+                    // Fragment shaders write to `gl_FragColor` instead of a declared output.
+                    let name = decl.trim_end().trim_end_matches(';').split_whitespace().last();
+                    res.push_str(&format!("#define {} gl_FragColor\n", name.unwrap_or_default()));
+                }
+                ShaderType::Compute => unreachable!("Compute shaders aren't supported in WebGL1"),
+            }
+        } else {
+            res.push_str(&replace_texture_calls(line));
+            res.push('\n');
+        }
+    }
+    res
+}
+
+/// Replaces calls to `texture()` with `texture2D()`.
+fn replace_texture_calls(line: &str) -> String {
+    let mut res = String::new();
+    let mut rest = line;
+    while let Some(i) = rest.find("texture(") {
+        let is_identifier_start = !matches!(
+            rest[..i].chars().next_back(),
+            Some(c) if c.is_alphanumeric() || c == '_'
+        );
+        res.push_str(&rest[..i]);
+        res.push_str(if is_identifier_start { "texture2D(" } else { "texture(" });
+        rest = &rest[i + "texture(".len()..];
+    }
+    res.push_str(rest);
+    res
 }
 
 /// Some additional constructors for `GlProgram` to make it easier to create shaders which share
 /// a common header.
-pub trait GlProgramWithHeader {
+///
+/// Each constructor panics if the program can't be created; the `try_` versions return a
+/// `ShaderError` instead. Line numbers in errors include the header.
+pub trait GlProgramWithHeader: Sized {
     /// Adds a `#version` declaration to each shader and `precision highp` declarations.
     fn new_with_minimal_header(
         context: &GlContext,
         vert_shader_source: &str,
         frag_shader_source: &str,
-    ) -> Self;
+    ) -> Self {
+        Self::try_new_with_minimal_header(context, vert_shader_source, frag_shader_source)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
 
     /// Adds a header to each shader, which includes everything added in
-    /// `new_with_minimal_header` plus sRGB conversion functions.
+    /// `new_with_minimal_header`, snippets registered with `GlContext::add_shader_snippet`, and
+    /// sRGB conversion functions.
     fn new_with_header(
         context: &GlContext,
         vert_shader_source: &str,
         frag_shader_source: &str,
         convert_to_srgb: bool,
-    ) -> Self;
+    ) -> Self {
+        Self::try_new_with_header(context, vert_shader_source, frag_shader_source, convert_to_srgb)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `new_with_header`, but the fragment shader also gets helpers for displaying HDR
+    /// colors:
+    /// ```glsl
+    /// uniform float exposure;             // In stops; set it with `EXPOSURE_UNIFORM`
+    /// vec3 applyExposure(vec3 color);     // Multiplies by 2^exposure
+    /// vec3 tonemapReinhard(vec3 color);
+    /// vec3 tonemapACES(vec3 color);
+    /// ```
+    /// A typical shader ends with
+    /// `writeColor2D(vec4(tonemapACES(applyExposure(color.rgb)), color.a));`.
+    fn new_with_hdr_header(
+        context: &GlContext,
+        vert_shader_source: &str,
+        frag_shader_source: &str,
+        convert_to_srgb: bool,
+    ) -> Self {
+        Self::try_new_with_hdr_header(
+            context,
+            vert_shader_source,
+            frag_shader_source,
+            convert_to_srgb,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn try_new_with_minimal_header(
+        context: &GlContext,
+        vert_shader_source: &str,
+        frag_shader_source: &str,
+    ) -> Result<Self, ShaderError>;
+
+    fn try_new_with_header(
+        context: &GlContext,
+        vert_shader_source: &str,
+        frag_shader_source: &str,
+        convert_to_srgb: bool,
+    ) -> Result<Self, ShaderError>;
+
+    fn try_new_with_hdr_header(
+        context: &GlContext,
+        vert_shader_source: &str,
+        frag_shader_source: &str,
+        convert_to_srgb: bool,
+    ) -> Result<Self, ShaderError>;
 }
 
 impl<V: Vertex, U: GlUniforms> GlProgramWithHeader for GlProgram<V, U> {
-    fn new_with_minimal_header(
+    fn try_new_with_minimal_header(
         context: &GlContext,
         vert_shader_source: &str,
         frag_shader_source: &str,
-    ) -> Self {
-        Self::new(
+    ) -> Result<Self, ShaderError> {
+        Self::try_new(
             context,
-            &add_shader_minimal_header(vert_shader_source),
-            &add_shader_minimal_header(frag_shader_source),
+            &add_shader_minimal_header(context, ShaderType::Vertex, vert_shader_source),
+            &add_shader_minimal_header(context, ShaderType::Fragment, frag_shader_source),
         )
     }
 
-    fn new_with_header(
+    fn try_new_with_header(
         context: &GlContext,
         vert_shader_source: &str,
         frag_shader_source: &str,
         convert_to_srgb: bool,
-    ) -> Self {
-        Self::new(
+    ) -> Result<Self, ShaderError> {
+        Self::try_new(
             context,
-            &add_shader_header(ShaderType::Vertex, vert_shader_source, convert_to_srgb),
-            &add_shader_header(ShaderType::Fragment, frag_shader_source, convert_to_srgb),
+            &add_shader_header(context, ShaderType::Vertex, vert_shader_source, convert_to_srgb),
+            &add_shader_header(context, ShaderType::Fragment, frag_shader_source, convert_to_srgb),
         )
     }
+
+    fn try_new_with_hdr_header(
+        context: &GlContext,
+        vert_shader_source: &str,
+        frag_shader_source: &str,
+        convert_to_srgb: bool,
+    ) -> Result<Self, ShaderError> {
+        Self::try_new_with_header(
+            context,
+            vert_shader_source,
+            &format!("{}{}", FRAG_HEADER_HDR, frag_shader_source),
+            convert_to_srgb,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glsl_100_vertex_shader() {
+        let source = "layout(location = 0) in vec3 pos;\nout vec2 uv;\nvoid main() {}";
+        assert_eq!(
+            to_glsl_100(ShaderType::Vertex, source),
+            "attribute vec3 pos;\nvarying vec2 uv;\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn glsl_100_fragment_shader() {
+        let source = "in vec2 uv;\nlayout(location = 0) out vec4 outColor;\n\
+                      void main() {\n    outColor = texture(tex, uv);\n}";
+        assert_eq!(
+            to_glsl_100(ShaderType::Fragment, source),
+            "varying vec2 uv;\n#define outColor gl_FragColor\n\
+             void main() {\n    outColor = texture2D(tex, uv);\n}\n"
+        );
+    }
+
+    #[test]
+    fn glsl_100_leaves_similar_names() {
+        // `int` and `output` start with "in"/"out" but aren't declarations.
+        let source = "int index = 0;\noutput = 1.0;";
+        assert_eq!(to_glsl_100(ShaderType::Vertex, source), "int index = 0;\noutput = 1.0;\n");
+    }
+
+    #[test]
+    fn texture_calls() {
+        assert_eq!(replace_texture_calls("texture(a, b)"), "texture2D(a, b)");
+        assert_eq!(
+            replace_texture_calls("texture(a, uv) + texture(b, uv)"),
+            "texture2D(a, uv) + texture2D(b, uv)"
+        );
+        assert_eq!(replace_texture_calls("vec4 c=texture(a, b);"), "vec4 c=texture2D(a, b);");
+    }
+
+    #[test]
+    fn texture_calls_ignore_other_identifiers() {
+        assert_eq!(replace_texture_calls("myTexture(a)"), "myTexture(a)");
+        assert_eq!(replace_texture_calls("sample_texture(a)"), "sample_texture(a)");
+        assert_eq!(replace_texture_calls("vec4 texture2D;"), "vec4 texture2D;");
+        assert_eq!(replace_texture_calls("no calls here"), "no calls here");
+    }
 }
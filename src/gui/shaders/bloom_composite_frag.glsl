@@ -0,0 +1,8 @@
+// tex0: the original image
+// tex1: the bloom
+// params.x: the bloom's intensity
+
+void main() {
+  vec4 color = texture(tex0, uv);
+  writeColor2D(vec4(color.rgb + params.x * texture(tex1, uv).rgb, color.a));
+}
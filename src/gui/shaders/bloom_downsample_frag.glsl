@@ -0,0 +1,10 @@
+void main() {
+  // Four bilinear samples average a 4x4 block of texels.
+  vec3 color = 0.25 * (
+    texture(tex0, uv + texelSize * vec2(-1.0, -1.0)).rgb +
+    texture(tex0, uv + texelSize * vec2(1.0, -1.0)).rgb +
+    texture(tex0, uv + texelSize * vec2(-1.0, 1.0)).rgb +
+    texture(tex0, uv + texelSize * vec2(1.0, 1.0)).rgb
+  );
+  writeColor2D(vec4(color, 1.0));
+}
@@ -0,0 +1,19 @@
+// params.x: the brightness threshold
+// params.y: the soft knee, as a fraction of the threshold
+
+void main() {
+  // Four bilinear samples average a 4x4 block of texels.
+  vec3 color = 0.25 * (
+    texture(tex0, uv + texelSize * vec2(-1.0, -1.0)).rgb +
+    texture(tex0, uv + texelSize * vec2(1.0, -1.0)).rgb +
+    texture(tex0, uv + texelSize * vec2(-1.0, 1.0)).rgb +
+    texture(tex0, uv + texelSize * vec2(1.0, 1.0)).rgb
+  );
+
+  float brightness = max(color.r, max(color.g, color.b));
+  float knee = params.x * params.y + 0.00001;
+  float soft = clamp(brightness - params.x + knee, 0.0, 2.0 * knee);
+  soft = soft * soft / (4.0 * knee);
+  float contribution = max(soft, brightness - params.x) / max(brightness, 0.00001);
+  writeColor2D(vec4(color * contribution, 1.0));
+}
@@ -0,0 +1,19 @@
+// tex0: the smaller level, which is upsampled with a tent filter
+// tex1: the level being added to
+
+vec3 tent(vec2 uv) {
+  vec3 color = 4.0 * texture(tex0, uv).rgb;
+  color += 2.0 * texture(tex0, uv + texelSize * vec2(-1.0, 0.0)).rgb;
+  color += 2.0 * texture(tex0, uv + texelSize * vec2(1.0, 0.0)).rgb;
+  color += 2.0 * texture(tex0, uv + texelSize * vec2(0.0, -1.0)).rgb;
+  color += 2.0 * texture(tex0, uv + texelSize * vec2(0.0, 1.0)).rgb;
+  color += texture(tex0, uv + texelSize * vec2(-1.0, -1.0)).rgb;
+  color += texture(tex0, uv + texelSize * vec2(1.0, -1.0)).rgb;
+  color += texture(tex0, uv + texelSize * vec2(-1.0, 1.0)).rgb;
+  color += texture(tex0, uv + texelSize * vec2(1.0, 1.0)).rgb;
+  return color / 16.0;
+}
+
+void main() {
+  writeColor2D(vec4(tent(uv) + texture(tex1, uv).rgb, 1.0));
+}
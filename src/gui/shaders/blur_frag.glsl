@@ -0,0 +1,22 @@
+// params.xy: the direction to blur in, in texels
+// params.z: the blur radius, in texels; at most MAX_BLUR_RADIUS
+const int MAX_BLUR_RADIUS = 32;
+
+void main() {
+  float radius = min(params.z, float(MAX_BLUR_RADIUS));
+  float sigma = max(radius / 2.0, 0.5);
+  vec2 offset = params.xy * texelSize;
+
+  vec4 sum = texture(tex0, uv);
+  float totalWeight = 1.0;
+  for (int i = 1; i <= MAX_BLUR_RADIUS; i++) {
+    float x = float(i);
+    if (x > radius) {
+      break;
+    }
+    float weight = exp(-x * x / (2.0 * sigma * sigma));
+    sum += weight * (texture(tex0, uv + offset * x) + texture(tex0, uv - offset * x));
+    totalWeight += 2.0 * weight;
+  }
+  writeColor2D(sum / totalWeight);
+}
@@ -0,0 +1,8 @@
+in vec2 pos;
+
+out vec2 uv;
+
+void main() {
+  uv = pos * 0.5 + 0.5;
+  writeGlPosition2D(vec4(pos, 0.0, 1.0));
+}
@@ -0,0 +1,12 @@
+in vec2 UV;
+in vec4 Color;
+
+uniform sampler2D tex;
+
+void main() {
+  // The glyph cache only stores coverage, in the red channel
+  vec4 color = vec4(Color.rgb, Color.a * texture(tex, UV).r);
+  // Premultiplied alpha
+  color.rgb *= color.a;
+  writeColor2D(color);
+}
@@ -0,0 +1,16 @@
+in vec2 uv;
+
+uniform sampler2D tex0;
+// 0: clamp, 1: Reinhard, 2: ACES; see `ToneMapping`
+uniform float toneMapping;
+
+void main() {
+  vec4 color = texture(tex0, uv);
+  vec3 rgb = applyExposure(color.rgb);
+  if (toneMapping > 1.5) {
+    rgb = tonemapACES(rgb);
+  } else if (toneMapping > 0.5) {
+    rgb = tonemapReinhard(rgb);
+  }
+  writeColor2D(vec4(clamp(rgb, 0.0, 1.0), color.a));
+}
@@ -0,0 +1,193 @@
+use crate::gl::*;
+use fxhash::*;
+use serde::{Deserialize, Serialize};
+
+use super::assets::*;
+use super::color::*;
+use super::gui::*;
+use super::text::*;
+
+/// The kinds of widget that can be styled separately in a `Theme`. Widgets made of several
+/// parts use the styles of those parts too; for instance, a `Checkbox`'s label uses the `Label`
+/// style.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WidgetKind {
+    Label,
+    Button,
+    Checkbox,
+    Toggle,
+    Slider,
+    Selector,
+    ScrollBar,
+    Tabs,
+    Table,
+    TextEntry,
+    TextEditor,
+}
+
+/// The state of a widget, which its style can depend on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum WidgetState {
+    Normal,
+    /// The cursor is over the widget.
+    Hovered,
+    /// The widget is being pressed, or is the active component.
+    Pressed,
+    /// The widget is disabled; see `Widget::is_enabled`. Disabled widgets are also covered with
+    /// `Theme::disabled_overlay_color`, which can be made transparent if the disabled styles are
+    /// enough.
+    Disabled,
+}
+
+/// How a kind of widget is drawn in a particular state; returned by `Theme::style`.
+///
+/// Not every kind of widget uses every field. Layout always uses the normal state's font and
+/// padding, so the other states shouldn't change them. Sizes are in physical pixels; they've
+/// already been scaled by the theme's scale factor.
+#[derive(Clone)]
+pub struct WidgetStyle {
+    pub font: Font,
+    pub text_color: Color4,
+    pub fill_color: Color4,
+    pub border_color: Color4,
+    /// Highlights such as selected text, the selected row or tab, and checked toggles.
+    pub accent_color: Color4,
+    pub border_width: f32,
+    pub corner_radius: f32,
+    /// The space between the widget's border and its contents.
+    pub padding: i32,
+}
+
+/// Changes to part of a `WidgetStyle`. Fields that are `None` are left unchanged.
+#[derive(Clone, Default)]
+pub struct StyleOverrides {
+    pub font: Option<Font>,
+    pub text_color: Option<Color4>,
+    pub fill_color: Option<Color4>,
+    pub border_color: Option<Color4>,
+    pub accent_color: Option<Color4>,
+    pub border_width: Option<f32>,
+    pub corner_radius: Option<f32>,
+    pub padding: Option<i32>,
+}
+
+impl StyleOverrides {
+    fn apply(&self, style: &mut WidgetStyle) {
+        if let Some(font) = &self.font {
+            style.font = font.clone();
+        }
+        style.text_color = self.text_color.unwrap_or(style.text_color);
+        style.fill_color = self.fill_color.unwrap_or(style.fill_color);
+        style.border_color = self.border_color.unwrap_or(style.border_color);
+        style.accent_color = self.accent_color.unwrap_or(style.accent_color);
+        style.border_width = self.border_width.unwrap_or(style.border_width);
+        style.corner_radius = self.corner_radius.unwrap_or(style.corner_radius);
+        style.padding = self.padding.unwrap_or(style.padding);
+    }
+}
+
+/// The style of one kind of widget, as changes to the theme's defaults. The state-specific
+/// overrides are applied on top of `normal`.
+#[derive(Clone, Default)]
+pub struct WidgetStyles {
+    pub normal: StyleOverrides,
+    pub hovered: StyleOverrides,
+    pub pressed: StyleOverrides,
+    pub disabled: StyleOverrides,
+}
+
+impl WidgetStyles {
+    pub(crate) fn apply(&self, state: WidgetState, style: &mut WidgetStyle) {
+        self.normal.apply(style);
+        match state {
+            WidgetState::Normal => (),
+            WidgetState::Hovered => self.hovered.apply(style),
+            WidgetState::Pressed => self.pressed.apply(style),
+            WidgetState::Disabled => self.disabled.apply(style),
+        }
+    }
+}
+
+/// The serializable form of `StyleOverrides`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct StyleOverridesConfig {
+    pub font: Option<ThemeFontConfig>,
+    /// Uses one of the theme's named fonts (see `ThemeConfig::fonts`) instead of loading a new
+    /// one. It's ignored if `font` is set.
+    pub font_name: Option<String>,
+    pub text_color: Option<Color4>,
+    pub fill_color: Option<Color4>,
+    pub border_color: Option<Color4>,
+    pub accent_color: Option<Color4>,
+    pub border_width: Option<f32>,
+    pub corner_radius: Option<f32>,
+    pub padding: Option<i32>,
+}
+
+impl StyleOverridesConfig {
+    fn load(
+        &self,
+        assets: &Assets,
+        context: &GlContext,
+        fonts: &FxHashMap<String, Font>,
+    ) -> Result<StyleOverrides, AssetError> {
+        let font = match (&self.font, &self.font_name) {
+            (Some(font), _) => Some(font.load(assets, context)?),
+            (None, Some(name)) => Some(
+                fonts
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| theme_decode_error(format!("Unknown font {:?}", name)))?,
+            ),
+            (None, None) => None,
+        };
+        Ok(StyleOverrides {
+            font,
+            text_color: self.text_color,
+            fill_color: self.fill_color,
+            border_color: self.border_color,
+            accent_color: self.accent_color,
+            border_width: self.border_width,
+            corner_radius: self.corner_radius,
+            padding: self.padding,
+        })
+    }
+}
+
+/// The serializable form of `WidgetStyles`. The normal style's fields are written directly in
+/// the widget kind's table, with a nested table for each state:
+/// ```toml
+/// [styles.button]
+/// fill_color = { r = 0.2, g = 0.2, b = 0.2, a = 1.0 }
+/// border_width = 2.0
+///
+/// [styles.button.hovered]
+/// fill_color = { r = 0.3, g = 0.3, b = 0.3, a = 1.0 }
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WidgetStylesConfig {
+    #[serde(flatten)]
+    pub normal: StyleOverridesConfig,
+    pub hovered: StyleOverridesConfig,
+    pub pressed: StyleOverridesConfig,
+    pub disabled: StyleOverridesConfig,
+}
+
+impl WidgetStylesConfig {
+    pub(crate) fn load(
+        &self,
+        assets: &Assets,
+        context: &GlContext,
+        fonts: &FxHashMap<String, Font>,
+    ) -> Result<WidgetStyles, AssetError> {
+        Ok(WidgetStyles {
+            normal: self.normal.load(assets, context, fonts)?,
+            hovered: self.hovered.load(assets, context, fonts)?,
+            pressed: self.pressed.load(assets, context, fonts)?,
+            disabled: self.disabled.load(assets, context, fonts)?,
+        })
+    }
+}
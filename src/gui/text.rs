@@ -1,12 +1,14 @@
 use crate::gl::uniforms::*;
 use crate::gl::*;
 use ab_glyph::ScaleFont;
+use bytemuck::{Pod, Zeroable};
 use cgmath::*;
 use fxhash::*;
 use std::cell::RefCell;
 use std::collections::hash_map::*;
 use std::iter;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use super::color::*;
 use super::shader_header::*;
@@ -29,12 +31,17 @@ impl VertexData for TextCacheVert {
 }
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 struct TextRenderVert {
     pos: Vector2<f32>,
     uv: Vector2<f32>,
     color: Color4,
 }
 
+// `Vector2<f32>` is `#[repr(C)]` and only contains `f32`s, so these are safe.
+unsafe impl Zeroable for TextRenderVert {}
+unsafe impl Pod for TextRenderVert {}
+
 impl VertexComponent for TextRenderVert {
     fn add_to_mesh(&self, f: &mut dyn FnMut(f32)) {
         self.pos.add_to_mesh(f);
@@ -162,7 +169,7 @@ void main() {
 }";
 
 struct FontInner {
-    font: ab_glyph::PxScaleFont<ab_glyph::FontVec>,
+    rasterizer: GlyphRasterizer,
     advance_y: i32,
     ascent: f32,
     glyphs: FxHashMap<char, CachedGlyph>,
@@ -177,15 +184,59 @@ struct FontInner {
     scale: f32,
 }
 
-/// A glyph that has been generated but not yet added to the cache.
-struct PendingGlyph {
+/// Rasterizes glyphs for a `Font`. Unlike `Font`, this can be sent to other threads, so glyphs
+/// can be rasterized in the background and added to the font with `Font::add_glyphs`.
+#[derive(Clone)]
+pub struct GlyphRasterizer {
+    font: Arc<ab_glyph::FontVec>,
+    scale: f32,
+}
+
+impl GlyphRasterizer {
+    fn scaled(&self) -> ab_glyph::PxScaleFont<&ab_glyph::FontVec> {
+        ab_glyph::Font::as_scaled(&*self.font, self.scale)
+    }
+
+    /// Rasterizes a glyph.
+    pub fn rasterize(&self, c: char) -> RasterizedGlyph {
+        let font = self.scaled();
+        let glyph_id = font.glyph_id(c);
+        let glyph = glyph_id.with_scale(self.scale);
+
+        let advance_x = font.h_advance(glyph_id);
+
+        let display = font.outline_glyph(glyph).map(|outlined_glyph| {
+            let mut bitmap = vec![];
+            outlined_glyph.draw(|_x, _y, pixel| {
+                bitmap.push((pixel * 255.0) as u8);
+            });
+            let bounding_box = outlined_glyph.px_bounds();
+            RasterizedGlyphDisplay {
+                bitmap,
+                size: vec2(
+                    (bounding_box.max.x - bounding_box.min.x) as u32,
+                    (bounding_box.max.y - bounding_box.min.y) as u32,
+                ),
+                left: bounding_box.min.x as i32,
+                top: bounding_box.min.y as i32,
+            }
+        });
+
+        RasterizedGlyph { c, display, advance_x }
+    }
+}
+
+/// A glyph that has been rasterized but not yet added to the cache.
+pub struct RasterizedGlyph {
+    c: char,
     // None for whitespace
-    display: Option<PendingGlyphDisplay>,
+    display: Option<RasterizedGlyphDisplay>,
     advance_x: f32,
 }
 
-struct PendingGlyphDisplay {
-    texture: Texture2d,
+struct RasterizedGlyphDisplay {
+    bitmap: Vec<u8>,
+    size: Vector2<u32>,
     left: i32,
     top: i32,
 }
@@ -206,9 +257,9 @@ struct CachedGlyphDisplay {
 }
 
 impl FontInner {
-    pub fn new(context: &GlContext, data: Vec<u8>, size: f32) -> Self {
-        use ab_glyph::Font;
-        let font = ab_glyph::FontVec::try_from_vec(data).unwrap().into_scaled(size);
+    pub fn new(context: &GlContext, font: Arc<ab_glyph::FontVec>, size: f32) -> Self {
+        let rasterizer = GlyphRasterizer { font, scale: size };
+        let font = rasterizer.scaled();
         let descent = font.descent();
         let ascent = font.ascent();
         let advance_y = ascent - descent;
@@ -231,10 +282,11 @@ impl FontInner {
         let cache_mesh_builder = MeshBuilder::new();
         let render_mesh_builder = MeshBuilder::new();
         let cache_mesh = Mesh::new(context, &cache_program, DrawMode::Draw2D);
-        let render_mesh = Mesh::new(context, &render_program, DrawMode::Draw2D);
+        let mut render_mesh = Mesh::new(context, &render_program, DrawMode::Draw2D);
+        render_mesh.set_buffering(MeshBuffering::Orphan);
 
         Self {
-            font,
+            rasterizer,
             advance_y: advance_y as i32,
             ascent,
             glyphs: FxHashMap::default(),
@@ -253,60 +305,43 @@ impl FontInner {
     fn get_kerning(&mut self, a: char, b: char) -> f32 {
         match self.kerning.entry((a, b)) {
             Entry::Vacant(entry) => {
-                let kerning = self.font.kern(self.font.glyph_id(a), self.font.glyph_id(b));
+                let font = self.rasterizer.scaled();
+                let kerning = font.kern(font.glyph_id(a), font.glyph_id(b));
                 *entry.insert(kerning)
             }
             Entry::Occupied(entry) => *entry.get(),
         }
     }
 
-    // Renders a glyph and returns a glyph to be added to the cache.
-    fn load_glyph(&self, context: &GlContext, c: char) -> PendingGlyph {
-        let glyph_id = self.font.glyph_id(c);
-        let glyph = glyph_id.with_scale(self.scale);
+    fn cache_glyph(&mut self, context: &GlContext, c: char) {
+        if self.glyphs.contains_key(&c) {
+            return;
+        }
 
-        let advance_x = self.font.h_advance(glyph_id);
+        let glyph = self.rasterizer.rasterize(c);
+        self.add_glyph(context, glyph);
+    }
 
-        let display = if let Some(outlined_glyph) = self.font.outline_glyph(glyph) {
-            let mut bitmap = vec![];
-            outlined_glyph.draw(|_x, _y, pixel| {
-                bitmap.push((pixel * 255.0) as u8);
-            });
-            let bounding_box = outlined_glyph.px_bounds();
-            let left = bounding_box.min.x;
-            let top = bounding_box.min.y;
+    // Adds a rasterized glyph to the cache.
+    fn add_glyph(&mut self, context: &GlContext, glyph: RasterizedGlyph) {
+        if self.glyphs.contains_key(&glyph.c) {
+            return;
+        }
 
+        let display = if let Some(display) = glyph.display {
             // TODO: consider using glBufferSubData here
             let texture = Texture2d::from_data(
                 context,
-                vec2(
-                    (bounding_box.max.x - bounding_box.min.x) as u32,
-                    (bounding_box.max.y - bounding_box.min.y) as u32,
-                ),
-                &bitmap,
+                display.size,
+                &display.bitmap,
                 TextureFormat::Red,
                 MinFilter::Nearest,
                 MagFilter::Nearest,
                 WrapMode::ClampToEdge,
             );
 
-            Some(PendingGlyphDisplay { texture, left: left as i32, top: top as i32 })
-        } else {
-            None
-        };
-
-        PendingGlyph { display, advance_x }
-    }
-
-    fn cache_glyph(&mut self, context: &GlContext, c: char) {
-        if self.glyphs.contains_key(&c) {
-            return;
-        }
-
-        let glyph = self.load_glyph(context, c);
-        let display = if let Some(display) = glyph.display {
             let framebuffer_size = self.framebuffer.attachment.size();
-            let glyph_texture_size = display.texture.size();
+            let glyph_texture_size = texture.size();
             let line_out_of_space = self.cur_x + glyph_texture_size.x >= framebuffer_size.x;
             let (x, y) = if line_out_of_space {
                 // Note: 1 was added to Y to try to avoid overlap between chars
@@ -351,7 +386,7 @@ impl FontInner {
                         0.0,
                         1.0,
                     ),
-                    tex: &display.texture,
+                    tex: &texture,
                 },
             );
 
@@ -365,7 +400,7 @@ impl FontInner {
             None
         };
 
-        self.glyphs.insert(c, CachedGlyph { display, advance_x: glyph.advance_x });
+        self.glyphs.insert(glyph.c, CachedGlyph { display, advance_x: glyph.advance_x });
     }
 
     fn get_cached_glyph(&self, c: char) -> &CachedGlyph {
@@ -404,6 +439,19 @@ impl FontInner {
         loc: Point2<f32>,
         color: Color4,
         matrix: Matrix4<f32>,
+    ) {
+        self.layout_string(context, str, loc, |font, c, loc| {
+            font.draw_char(context, c, loc, color, matrix);
+        });
+    }
+
+    /// Calls `f` with each char in the string and the position it should be drawn at.
+    fn layout_string(
+        &mut self,
+        context: &GlContext,
+        str: &str,
+        loc: Point2<f32>,
+        mut f: impl FnMut(&mut Self, char, Point2<f32>),
     ) {
         for c in str.chars() {
             self.cache_glyph(context, c);
@@ -411,7 +459,7 @@ impl FontInner {
 
         let mut x_pos = 0;
         for (a, b) in str.chars().zip(str.chars().skip(1).map(Some).chain(iter::once(None))) {
-            self.draw_char(context, a, loc + vec2(x_pos as f32, 0.0), color, matrix);
+            f(self, a, loc + vec2(x_pos as f32, 0.0));
             if let Some(b) = b {
                 // TODO: remove cast, or floor/round
                 x_pos += self.horiz_advance_between(a, b) as i32;
@@ -427,60 +475,48 @@ impl FontInner {
         color: Color4,
         matrix: Matrix4<f32>,
     ) {
-        self.cache_glyph(context, c);
-        let glyph = self.get_cached_glyph(c);
-        if let Some(display) = &glyph.display {
-            let loc = vec2(loc.x as f32, loc.y as f32 + self.ascent as f32);
-            let framebuffer_size = self.framebuffer.attachment.size();
-            let tex_start = display.loc;
-            let tex_end = tex_start + display.size;
-            let tex_start_x = (tex_start.x as f32) / framebuffer_size.x as f32;
-            let tex_start_y = (tex_start.y as f32) / framebuffer_size.y as f32;
-            let tex_end_x = (tex_end.x as f32) / framebuffer_size.x as f32;
-            let tex_end_y = (tex_end.y as f32) / framebuffer_size.y as f32;
-            let left = display.left as f32;
-            let top = display.top as f32;
-            let size: Vector2<f32> = display.size.cast().unwrap();
-
+        if let Some(quad) = self.glyph_quad(context, c, loc, matrix) {
             let mesh_builder = &mut self.render_mesh_builder;
-
-            let vert_a = mesh_builder.vert(TextRenderVert {
-                pos: point3_to_vec2(matrix.transform_point(point3(loc.x + left, loc.y + top, 0.0))),
-                uv: vec2(tex_start_x, tex_start_y),
-                color,
-            });
-            let vert_b = mesh_builder.vert(TextRenderVert {
-                pos: point3_to_vec2(matrix.transform_point(point3(
-                    loc.x + left + size.x,
-                    loc.y + top,
-                    0.0,
-                ))),
-                uv: vec2(tex_end_x, tex_start_y),
-                color,
-            });
-            let vert_c = mesh_builder.vert(TextRenderVert {
-                pos: point3_to_vec2(matrix.transform_point(point3(
-                    loc.x + left,
-                    loc.y + top + size.y,
-                    0.0,
-                ))),
-                uv: vec2(tex_start_x, tex_end_y),
-                color,
-            });
-            let vert_d = mesh_builder.vert(TextRenderVert {
-                pos: point3_to_vec2(matrix.transform_point(point3(
-                    loc.x + left + size.x,
-                    loc.y + top + size.y,
-                    0.0,
-                ))),
-                uv: vec2(tex_end_x, tex_end_y),
-                color,
-            });
-            mesh_builder.triangle(vert_a, vert_b, vert_c);
-            mesh_builder.triangle(vert_b, vert_c, vert_d);
+            let [a, b, c, d] =
+                quad.map(|(pos, uv)| mesh_builder.vert_pod(TextRenderVert { pos, uv, color }));
+            mesh_builder.triangle(a, b, c);
+            mesh_builder.triangle(b, c, d);
         }
     }
 
+    /// Returns the quad used to draw a char from the cache, as (position, UV) pairs for the top
+    /// left, top right, bottom left, and bottom right corners. Returns `None` for whitespace.
+    fn glyph_quad(
+        &mut self,
+        context: &GlContext,
+        c: char,
+        loc: Point2<f32>,
+        matrix: Matrix4<f32>,
+    ) -> Option<[(Vector2<f32>, Vector2<f32>); 4]> {
+        self.cache_glyph(context, c);
+        let glyph = self.get_cached_glyph(c);
+        let display = glyph.display.as_ref()?;
+        let loc = vec2(loc.x, loc.y + self.ascent);
+        let framebuffer_size = self.framebuffer.attachment.size();
+        let tex_start = display.loc;
+        let tex_end = tex_start + display.size;
+        let tex_start_x = (tex_start.x as f32) / framebuffer_size.x as f32;
+        let tex_start_y = (tex_start.y as f32) / framebuffer_size.y as f32;
+        let tex_end_x = (tex_end.x as f32) / framebuffer_size.x as f32;
+        let tex_end_y = (tex_end.y as f32) / framebuffer_size.y as f32;
+        let left = display.left as f32;
+        let top = display.top as f32;
+        let size: Vector2<f32> = display.size.cast().unwrap();
+
+        let corner = |x: f32, y: f32| point3_to_vec2(matrix.transform_point(point3(x, y, 0.0)));
+        Some([
+            (corner(loc.x + left, loc.y + top), vec2(tex_start_x, tex_start_y)),
+            (corner(loc.x + left + size.x, loc.y + top), vec2(tex_end_x, tex_start_y)),
+            (corner(loc.x + left, loc.y + top + size.y), vec2(tex_start_x, tex_end_y)),
+            (corner(loc.x + left + size.x, loc.y + top + size.y), vec2(tex_end_x, tex_end_y)),
+        ])
+    }
+
     // Note: this requires the chars to already be cached
     fn horiz_advance_between(&mut self, a: char, b: char) -> f32 {
         let kerning = self.get_kerning(a, b);
@@ -520,6 +556,24 @@ impl FontInner {
         width
     }
 
+    pub fn char_offsets(&mut self, context: &GlContext, str: &str) -> Vec<f32> {
+        for c in str.chars() {
+            self.cache_glyph(context, c);
+        }
+        let mut offsets = Vec::with_capacity(str.len() + 1);
+        let mut x = 0.0;
+        offsets.push(x);
+        let mut chars = str.chars().peekable();
+        while let Some(a) = chars.next() {
+            x += match chars.peek() {
+                Some(&b) => self.horiz_advance_between(a, b),
+                None => self.horiz_advance_after(a),
+            };
+            offsets.push(x);
+        }
+        offsets
+    }
+
     // TODO: change this to return Vec2<f32>, or change string_width to return i32
     pub fn string_size(&mut self, context: &GlContext, str: &str) -> Vector2<i32> {
         vec2(self.string_width(context, str) as i32, self.advance_y)
@@ -542,7 +596,15 @@ pub struct Font {
 impl Font {
     /// Creates a new `Font` from a `Vec` containing the contents of a `ttf` file.
     pub fn new(context: &GlContext, data: Vec<u8>, size: f32) -> Self {
-        Self { inner: Rc::new(RefCell::new(FontInner::new(context, data, size))) }
+        let font = Arc::new(ab_glyph::FontVec::try_from_vec(data).unwrap());
+        Self { inner: Rc::new(RefCell::new(FontInner::new(context, font, size))) }
+    }
+
+    /// Creates a new `Font` from the same `ttf` file as this one, but with a different size. The
+    /// file isn't parsed again, but the new font has its own glyph cache.
+    pub fn with_size(&self, context: &GlContext, size: f32) -> Self {
+        let font = self.inner.borrow().rasterizer.font.clone();
+        Self { inner: Rc::new(RefCell::new(FontInner::new(context, font, size))) }
     }
 
     /// Renders all characters that have been drawn with `draw_string` or `draw_char`.
@@ -609,11 +671,63 @@ impl Font {
         self.inner.borrow_mut().draw_char(context, c, loc, color, matrix);
     }
 
+    /// Calls `f` with the quad for each glyph in the string, as (position, UV) pairs for the top
+    /// left, top right, bottom left, and bottom right corners. The UVs refer to the texture
+    /// passed to `with_texture`.
+    pub(crate) fn glyph_quads(
+        &self,
+        context: &GlContext,
+        str: &str,
+        loc: Point2<f32>,
+        mut f: impl FnMut([(Vector2<f32>, Vector2<f32>); 4]),
+    ) {
+        self.inner.borrow_mut().layout_string(context, str, loc, |font, c, loc| {
+            if let Some(quad) = font.glyph_quad(context, c, loc, Matrix4::identity()) {
+                f(quad);
+            }
+        });
+    }
+
+    /// Calls `f` with the texture that caches this font's glyphs. The texture only contains
+    /// single-channel coverage values.
+    pub(crate) fn with_texture<T>(&self, f: impl FnOnce(&Texture2d) -> T) -> T {
+        f(&self.inner.borrow().framebuffer.attachment)
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &Font) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Returns a `GlyphRasterizer` for this font, which can be used to rasterize glyphs on
+    /// another thread.
+    pub fn rasterizer(&self) -> GlyphRasterizer {
+        self.inner.borrow().rasterizer.clone()
+    }
+
+    /// Adds glyphs that were rasterized with `rasterizer` to the glyph cache. Glyphs that are
+    /// already in the cache are ignored.
+    pub fn add_glyphs(
+        &self,
+        context: &GlContext,
+        glyphs: impl IntoIterator<Item = RasterizedGlyph>,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        for glyph in glyphs {
+            inner.add_glyph(context, glyph);
+        }
+    }
+
     /// Returns the width of a rendered string in pixels.
     pub fn string_width(&self, context: &GlContext, str: &str) -> f32 {
         self.inner.borrow_mut().string_width(context, str)
     }
 
+    /// Returns the x position of each char boundary in a rendered string, including the start
+    /// and end of the string, so the result has one more element than the string has chars.
+    pub fn char_offsets(&self, context: &GlContext, str: &str) -> Vec<f32> {
+        self.inner.borrow_mut().char_offsets(context, str)
+    }
+
     /// Returns the size of a rendered string in pixels.
     pub fn string_size(&self, context: &GlContext, str: &str) -> Vector2<i32> {
         self.inner.borrow_mut().string_size(context, str)
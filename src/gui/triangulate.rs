@@ -0,0 +1,199 @@
+use cgmath::*;
+
+/// Splits a simple polygon, which may be concave and may have holes, into triangles. Returns the
+/// polygon's vertices along with the indices of each triangle's vertices into them; the triangles
+/// all have the same winding as the outline.
+///
+/// The outline and holes can be in either winding order, and repeated consecutive points (such
+/// as a closing point that's the same as the first one) are ignored. The holes must be inside
+/// the outline and mustn't overlap each other or the outline. Self-intersecting outlines aren't
+/// supported; they're still triangulated, but some of their area might be missing or doubled.
+pub fn triangulate_polygon(
+    outline: &[Point2<f32>],
+    holes: &[&[Point2<f32>]],
+) -> (Vec<Point2<f32>>, Vec<[usize; 3]>) {
+    let mut outline = dedup_points(outline);
+    if outline.len() < 3 {
+        return (outline, vec![]);
+    }
+    let flipped = signed_area(&outline) < 0.0;
+    if flipped {
+        outline.reverse();
+    }
+
+    // Holes have the opposite winding to the outline, so that they can be spliced into it.
+    let mut holes: Vec<Vec<Point2<f32>>> = holes
+        .iter()
+        .map(|hole| dedup_points(hole))
+        .filter(|hole| hole.len() >= 3)
+        .map(|mut hole| {
+            if signed_area(&hole) > 0.0 {
+                hole.reverse();
+            }
+            hole
+        })
+        .collect();
+    // Each hole is connected to the polygon with a bridge to the right of its rightmost point.
+    // Doing the rightmost holes first means that a bridge can only cross holes that are
+    // already part of the polygon.
+    holes.sort_by(|a, b| max_x(b).partial_cmp(&max_x(a)).unwrap());
+
+    let mut verts = outline;
+    for hole in &holes {
+        splice_hole(&mut verts, hole);
+    }
+
+    let mut triangles = clip_ears(&verts);
+    if flipped {
+        for triangle in &mut triangles {
+            triangle.swap(1, 2);
+        }
+    }
+    (verts, triangles)
+}
+
+fn dedup_points(points: &[Point2<f32>]) -> Vec<Point2<f32>> {
+    let mut res: Vec<Point2<f32>> = points.to_vec();
+    res.dedup();
+    while res.len() > 1 && res.first() == res.last() {
+        res.pop();
+    }
+    res
+}
+
+/// Twice the polygon's signed area; positive if the vertices go counterclockwise when the y axis
+/// points up.
+fn signed_area(points: &[Point2<f32>]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area
+}
+
+fn max_x(points: &[Point2<f32>]) -> f32 {
+    points.iter().map(|point| point.x).fold(f32::NEG_INFINITY, f32::max)
+}
+
+/// Positive if `a`, `b`, `c` turn left (counterclockwise when the y axis points up).
+fn orient(a: Point2<f32>, b: Point2<f32>, c: Point2<f32>) -> f32 {
+    (b - a).perp_dot(c - b)
+}
+
+fn point_in_triangle(p: Point2<f32>, a: Point2<f32>, b: Point2<f32>, c: Point2<f32>) -> bool {
+    orient(a, b, p) >= 0.0 && orient(b, c, p) >= 0.0 && orient(c, a, p) >= 0.0
+}
+
+/// Connects a hole to the polygon, turning them into a single polygon with a zero-width gap
+/// between the hole and the rest of the polygon.
+fn splice_hole(verts: &mut Vec<Point2<f32>>, hole: &[Point2<f32>]) {
+    let (hole_start, &m) =
+        hole.iter().enumerate().max_by(|(_, a), (_, b)| a.x.partial_cmp(&b.x).unwrap()).unwrap();
+    let bridge = find_bridge(verts, m);
+    let spliced = hole[hole_start..]
+        .iter()
+        .chain(&hole[..hole_start])
+        .chain(std::iter::once(&m))
+        .chain(std::iter::once(&verts[bridge]))
+        .copied()
+        .collect::<Vec<_>>();
+    verts.splice(bridge + 1..bridge + 1, spliced);
+}
+
+/// Finds a vertex of the polygon that can be connected to `m` without crossing any edges, by
+/// casting a ray from `m` in the +x direction.
+fn find_bridge(verts: &[Point2<f32>], m: Point2<f32>) -> usize {
+    let n = verts.len();
+    let mut nearest_x = f32::INFINITY;
+    let mut candidate = None;
+    for i in 0..n {
+        let (p, q) = (verts[i], verts[(i + 1) % n]);
+        if p.y == q.y || (m.y < p.y.min(q.y)) || (m.y > p.y.max(q.y)) {
+            continue;
+        }
+        let x = p.x + (m.y - p.y) * (q.x - p.x) / (q.y - p.y);
+        if x >= m.x && x < nearest_x {
+            nearest_x = x;
+            candidate = Some(if p.x > q.x { i } else { (i + 1) % n });
+            if x == m.x {
+                return candidate.unwrap();
+            }
+        }
+    }
+    let candidate = candidate.expect("Hole isn't inside the polygon");
+
+    // Another vertex might be inside the triangle formed by `m`, the ray's intersection with the
+    // edge, and the candidate, blocking the bridge. If so, the one closest in angle to the ray
+    // is used instead.
+    let hit = point2(nearest_x, m.y);
+    let c = verts[candidate];
+    let (a, b) = if c.y < m.y { (hit, m) } else { (m, hit) };
+    let mut best = candidate;
+    let mut best_tan = f32::INFINITY;
+    for (i, &p) in verts.iter().enumerate() {
+        if i == candidate || p.x < m.x || p == c || !point_in_triangle(p, a, b, c) {
+            continue;
+        }
+        let tan = (m.y - p.y).abs() / (p.x - m.x);
+        if (tan < best_tan || (tan == best_tan && p.x < verts[best].x))
+            && locally_inside(verts, i, m)
+        {
+            best = i;
+            best_tan = tan;
+        }
+    }
+    best
+}
+
+/// True if a line from vertex `i` towards `p` starts inside the polygon.
+fn locally_inside(verts: &[Point2<f32>], i: usize, p: Point2<f32>) -> bool {
+    let n = verts.len();
+    let (prev, a, next) = (verts[(i + n - 1) % n], verts[i], verts[(i + 1) % n]);
+    if orient(prev, a, next) >= 0.0 {
+        orient(prev, a, p) >= 0.0 && orient(a, next, p) >= 0.0
+    } else {
+        orient(prev, a, p) >= 0.0 || orient(a, next, p) >= 0.0
+    }
+}
+
+/// Triangulates a counterclockwise simple polygon by repeatedly cutting off ears: triangles
+/// formed by three consecutive vertices that don't contain any other vertex.
+fn clip_ears(verts: &[Point2<f32>]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..verts.len()).collect();
+    let mut triangles = Vec::with_capacity(verts.len().saturating_sub(2));
+    let mut i = 0;
+    let mut since_last_ear = 0;
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        i %= len;
+        let (a, b, c) = (remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]);
+        // If there are no ears, the polygon is degenerate or self-intersecting. Clipping a vertex
+        // anyway guarantees that this terminates.
+        if since_last_ear > len || is_ear(verts, &remaining, a, b, c) {
+            triangles.push([a, b, c]);
+            remaining.remove(i);
+            since_last_ear = 0;
+        } else {
+            i += 1;
+            since_last_ear += 1;
+        }
+    }
+    if let [a, b, c] = remaining[..] {
+        triangles.push([a, b, c]);
+    }
+    triangles
+}
+
+fn is_ear(verts: &[Point2<f32>], remaining: &[usize], a: usize, b: usize, c: usize) -> bool {
+    let (pa, pb, pc) = (verts[a], verts[b], verts[c]);
+    if orient(pa, pb, pc) <= 0.0 {
+        return false;
+    }
+    // Vertices at the same position as the triangle's are skipped, since holes' bridges
+    // duplicate vertices.
+    !remaining.iter().any(|&i| {
+        let p = verts[i];
+        p != pa && p != pb && p != pc && point_in_triangle(p, pa, pb, pc)
+    })
+}
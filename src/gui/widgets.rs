@@ -1,22 +1,56 @@
 use crate::gl::*;
 use cgmath::*;
 use fxhash::*;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
 use wasm_stopwatch::*;
 
+use super::clipboard::*;
 use super::color::*;
 use super::draw_2d::*;
 use super::event::*;
 use super::gui::*;
+use super::style::*;
+use super::text::*;
+
+/// The state to style a widget in: hovered if the cursor is over `rect`, or otherwise pressed if
+/// it's the active component.
+fn widget_state(rect: Rect<i32>, cursor_pos: Option<Point2<i32>>, is_active: bool) -> WidgetState {
+    if matches!(cursor_pos, Some(pos) if rect.contains_point(pos)) {
+        WidgetState::Hovered
+    } else if is_active {
+        WidgetState::Pressed
+    } else {
+        WidgetState::Normal
+    }
+}
 
 pub struct Label {
     id: WidgetId,
     text: String,
+    font: Option<String>,
 }
 
 impl Label {
     pub fn new(text: &str) -> Box<Self> {
-        Box::new(Label { id: WidgetId::new(), text: text.to_owned() })
+        Box::new(Label { id: WidgetId::new(), text: text.to_owned(), font: None })
+    }
+
+    /// Draws the label with one of the theme's named fonts; see `Theme::fonts`.
+    pub fn font(mut self: Box<Self>, name: &str) -> Box<Self> {
+        self.font = Some(name.to_owned());
+        self
+    }
+}
+
+/// The font a widget that can use the theme's named fonts should use.
+fn chosen_font(theme: &Theme, name: &Option<String>, style: WidgetStyle) -> Font {
+    match name {
+        Some(name) => theme.named_font(name).clone(),
+        None => style.font,
     }
 }
 
@@ -31,11 +65,14 @@ impl Widget for Label {
         _surface: &dyn Surface,
         rect: Rect<i32>,
         theme: &Theme,
-        _draw_2d: &mut Draw2d,
+        draw_2d: &mut Draw2d,
         _cursor_pos: Option<Point2<i32>>,
         _is_active: bool,
     ) {
-        theme.font.draw_string(context, &self.text, rect.start, theme.label_color);
+        let style = theme.style(WidgetKind::Label, WidgetState::Normal);
+        let color = style.text_color;
+        let font = chosen_font(theme, &self.font, style);
+        draw_2d.draw_string(context, &font, &self.text, rect.start, color);
     }
 
     fn min_size(
@@ -45,7 +82,8 @@ impl Widget for Label {
         _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        theme.font.string_size(context, &self.text)
+        let style = theme.style(WidgetKind::Label, WidgetState::Normal);
+        chosen_font(theme, &self.font, style).string_size(context, &self.text)
     }
 }
 
@@ -87,7 +125,7 @@ impl Component for Button {
                     pressed = true;
                     break;
                 }
-                Event::KeyDown(key) => {
+                Event::KeyDown(key) if !key.repeat => {
                     if key.code == "Enter" || key.code == "space" {
                         pressed = true;
                         break;
@@ -110,6 +148,10 @@ impl Widget for Button {
         true
     }
 
+    fn cursor_icon(&self, _theme: &Theme, _pos: Point2<i32>) -> Option<CursorIcon> {
+        Some(CursorIcon::Hand)
+    }
+
     fn draw(
         &self,
         context: &GlContext,
@@ -120,21 +162,25 @@ impl Widget for Button {
         cursor_pos: Option<Point2<i32>>,
         is_active: bool,
     ) {
-        let fill_color =
-            if cursor_pos.is_some() && rect.contains_point(cursor_pos.unwrap().cast().unwrap()) {
-                theme.button_selected_fill_color
-            } else if is_active {
-                theme.button_active_fill_color
-            } else {
-                theme.button_fill_color
-            };
-        draw_2d.fill_rect(rect, fill_color);
-        draw_2d.outline_rect(rect, theme.button_border_color, 1.0);
-        theme.font.draw_string(
+        let style = theme.style(WidgetKind::Button, widget_state(rect, cursor_pos, is_active));
+        match &theme.button_background {
+            Some(background) => background.draw(draw_2d, rect.cast().unwrap(), style.fill_color),
+            None => {
+                draw_2d.fill_rounded_rect(rect, style.corner_radius, style.fill_color);
+                draw_2d.outline_rounded_rect(
+                    rect,
+                    style.corner_radius,
+                    style.border_color,
+                    style.border_width,
+                );
+            }
+        }
+        draw_2d.draw_string(
             context,
+            &style.font,
             &self.text,
-            rect.start + vec2(2, 1),
-            theme.button_text_color,
+            rect.start + vec2(style.padding, style.padding / 2),
+            style.text_color,
         );
     }
 
@@ -145,365 +191,501 @@ impl Widget for Button {
         _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        theme.font.string_size(context, &self.text) + vec2(4, 2)
+        let style = theme.style(WidgetKind::Button, WidgetState::Normal);
+        style.font.string_size(context, &self.text) + vec2(style.padding * 2, style.padding)
     }
 }
 
-/// A widget that makes its child its minimum possible size rather than filling the whole
-/// window.
-pub struct NoFill {
-    id: WidgetId,
-    child: Box<dyn Widget>,
-}
+/// The gap between a checkbox, toggle, or slider and its label.
+const CHECK_LABEL_GAP: i32 = 4;
 
-impl NoFill {
-    pub fn new(child: Box<dyn Widget>) -> Box<Self> {
-        Box::new(NoFill { id: WidgetId::new(), child })
-    }
+pub struct CheckboxResult {
+    changed: bool,
+    checked: bool,
 }
 
-impl Widget for NoFill {
-    fn id(&self) -> WidgetId {
-        self.id
+impl CheckboxResult {
+    /// True if the user changed the state since the last update.
+    pub fn changed(&self) -> bool {
+        self.changed
     }
 
-    fn draw(
-        &self,
-        _context: &GlContext,
-        _surface: &dyn Surface,
-        _rect: Rect<i32>,
-        _theme: &Theme,
-        _draw_2d: &mut Draw2d,
-        _cursor_pos: Option<Point2<i32>>,
-        _is_active: bool,
-    ) {
+    pub fn checked(&self) -> bool {
+        self.checked
     }
+}
 
-    fn min_size(
-        &self,
-        _context: &GlContext,
-        _theme: &Theme,
-        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
-        _window_size: Vector2<i32>,
-    ) -> Vector2<i32> {
-        min_sizes[&self.child.id()]
+/// Flips `checked` for each click or press of Space in `events`. Holding Space down doesn't
+/// keep flipping it.
+fn update_checked(checked: &mut bool, events: Vec<Event>) -> CheckboxResult {
+    let old_checked = *checked;
+    for event in events {
+        match event {
+            Event::MouseDown(MouseButton::Left, _) => *checked = !*checked,
+            Event::KeyDown(key) if key.code == "Space" && !key.repeat => *checked = !*checked,
+            _ => (),
+        }
     }
+    CheckboxResult { changed: *checked != old_checked, checked: *checked }
+}
 
-    fn children(&self) -> Vec<&dyn Widget> {
-        vec![&*self.child]
+/// The size of a checkbox or toggle with the given control size and label.
+fn check_widget_size(
+    context: &GlContext,
+    theme: &Theme,
+    control_size: Vector2<i32>,
+    text: &str,
+) -> Vector2<i32> {
+    let text_size =
+        theme.style(WidgetKind::Label, WidgetState::Normal).font.string_size(context, text);
+    let height = control_size.y.max(text_size.y);
+    if text.is_empty() {
+        vec2(control_size.x, height)
+    } else {
+        vec2(control_size.x + theme.scale(CHECK_LABEL_GAP) + text_size.x, height)
     }
+}
 
-    fn compute_rects(
-        &self,
-        rect: Rect<i32>,
-        theme: &Theme,
-        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
-        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
-    ) {
-        let min_size = min_sizes[&self.id()];
-        widget_rects.insert(self.id(), Rect::new(rect.start, rect.start + min_size));
-        self.child.compute_rects(
-            Rect::new(rect.start, rect.start + min_size),
-            theme,
-            min_sizes,
-            widget_rects,
-        );
-    }
+/// Draws the label of a checkbox, toggle, or slider.
+fn draw_check_label(
+    context: &GlContext,
+    theme: &Theme,
+    draw_2d: &mut Draw2d,
+    text: &str,
+    pos: Point2<i32>,
+) {
+    let style = theme.style(WidgetKind::Label, WidgetState::Normal);
+    draw_2d.draw_string(context, &style.font, text, pos, style.text_color);
 }
 
-pub struct Col {
+/// A box that can be checked and unchecked by clicking it or pressing Space, with an optional
+/// label.
+#[derive(Clone)]
+pub struct Checkbox {
     id: WidgetId,
-    children: Vec<(Box<dyn Widget>, f32)>,
+    text: String,
+    checked: bool,
 }
 
-impl Col {
-    pub fn new() -> Box<Self> {
-        Box::new(Col { id: WidgetId::new(), children: vec![] })
+impl Checkbox {
+    pub fn new(text: &str, checked: bool) -> Box<Self> {
+        Box::new(Checkbox { id: WidgetId::new(), text: text.to_owned(), checked })
     }
 
-    /// Flex controls how to distribute unused space.
-    pub fn child(mut self: Box<Self>, flex: f32, child: Box<dyn Widget>) -> Box<Self> {
-        self.children.push((child, flex));
-        self
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_owned();
     }
 
-    pub fn children(mut self: Box<Self>, children: Vec<(f32, Box<dyn Widget>)>) -> Box<Self> {
-        self.children.extend(children.into_iter().map(|(a, b)| (b, a)));
-        self
+    pub fn checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
     }
 }
 
-impl Widget for Col {
+impl Component for Checkbox {
+    type Res = CheckboxResult;
+
+    fn update(&mut self, _theme: &Theme, events: Vec<Event>) -> CheckboxResult {
+        update_checked(&mut self.checked, events)
+    }
+}
+
+impl Widget for Checkbox {
     fn id(&self) -> WidgetId {
         self.id
     }
 
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn cursor_icon(&self, _theme: &Theme, _pos: Point2<i32>) -> Option<CursorIcon> {
+        Some(CursorIcon::Hand)
+    }
+
     fn draw(
         &self,
-        _context: &GlContext,
+        context: &GlContext,
         _surface: &dyn Surface,
-        _rect: Rect<i32>,
-        _theme: &Theme,
-        _draw_2d: &mut Draw2d,
-        _cursor_pos: Option<Point2<i32>>,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
         _is_active: bool,
     ) {
+        let style = theme.style(WidgetKind::Checkbox, widget_state(rect, cursor_pos, false));
+        let size = style.font.advance_y();
+        let box_rect = Rect::new(rect.start, rect.start + vec2(size, size));
+        draw_2d.fill_rounded_rect(box_rect, style.corner_radius, style.fill_color);
+        draw_2d.outline_rounded_rect(
+            box_rect,
+            style.corner_radius,
+            style.border_color,
+            style.border_width,
+        );
+        if self.checked {
+            let start: Point2<f32> = box_rect.start.cast().unwrap();
+            let size = size as f32;
+            draw_2d.draw_line_strip(
+                &[
+                    start + vec2(0.2, 0.5) * size,
+                    start + vec2(0.42, 0.75) * size,
+                    start + vec2(0.8, 0.25) * size,
+                ],
+                style.text_color,
+                2.0,
+            );
+        }
+        let label_pos = rect.start + vec2(size + theme.scale(CHECK_LABEL_GAP), 0);
+        draw_check_label(context, theme, draw_2d, &self.text, label_pos);
     }
 
     fn min_size(
         &self,
-        _context: &GlContext,
-        _theme: &Theme,
-        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        context: &GlContext,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        let mut min_size: Vector2<i32> = Vector2::zero();
-        for &(ref child, _flex) in &self.children {
-            let child_min_size = min_sizes[&child.id()];
-            min_size.x = min_size.x.max(child_min_size.x);
-            min_size.y += child_min_size.y;
-        }
-        min_size
-    }
-
-    fn children(&self) -> Vec<&dyn Widget> {
-        self.children.iter().map(|(child, _)| &**child as &dyn Widget).collect()
-    }
-
-    fn compute_rects(
-        &self,
-        rect: Rect<i32>,
-        theme: &Theme,
-        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
-        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
-    ) {
-        let total_flex = self.children.iter().map(|&(ref _child, flex)| flex).sum();
-        let min_size = min_sizes[&self.id()];
-        let own_rect = if total_flex == 0.0 {
-            Rect::new(rect.start, rect.start + vec2(rect.size().x, min_size.y))
-        } else {
-            Rect::new(rect.start, rect.start + vec2(rect.size().x, rect.size().y))
-        };
-        widget_rects.insert(self.id(), own_rect);
-        let mut next_pos = rect.start;
-        let total_flex = if total_flex == 0.0 { 1.0 } else { total_flex };
-        let extra_space = rect.size().y - min_size.y;
-        for &(ref child, flex) in &self.children {
-            let child_min_size = min_sizes[&child.id()];
-            let widget_extra_space = (extra_space as f32 * flex / total_flex) as i32;
-            let widget_height = child_min_size.y + widget_extra_space;
-            let widget_rect = Rect::new(next_pos, next_pos + vec2(rect.size().x, widget_height));
-            next_pos.y += widget_height;
-            child.compute_rects(widget_rect, theme, min_sizes, widget_rects);
-        }
+        let size = theme.style(WidgetKind::Checkbox, WidgetState::Normal).font.advance_y();
+        check_widget_size(context, theme, vec2(size, size), &self.text)
     }
 }
 
-pub struct Row {
+/// A switch that can be turned on and off by clicking it or pressing Space, with an optional
+/// label. It works like a `Checkbox`, but is drawn as a sliding switch.
+#[derive(Clone)]
+pub struct Toggle {
     id: WidgetId,
-    children: Vec<(Box<dyn Widget>, f32)>,
+    text: String,
+    checked: bool,
 }
 
-impl Row {
-    pub fn new() -> Box<Self> {
-        Box::new(Row { id: WidgetId::new(), children: vec![] })
+impl Toggle {
+    pub fn new(text: &str, checked: bool) -> Box<Self> {
+        Box::new(Toggle { id: WidgetId::new(), text: text.to_owned(), checked })
     }
 
-    /// Flex controls how to distribute unused space.
-    pub fn child(mut self: Box<Self>, flex: f32, child: Box<dyn Widget>) -> Box<Self> {
-        self.children.push((child, flex));
-        self
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_owned();
     }
 
-    pub fn children(mut self: Box<Self>, children: Vec<(f32, Box<dyn Widget>)>) -> Box<Self> {
-        self.children.extend(children.into_iter().map(|(a, b)| (b, a)));
-        self
+    pub fn checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
     }
 }
 
-impl Widget for Row {
+impl Component for Toggle {
+    type Res = CheckboxResult;
+
+    fn update(&mut self, _theme: &Theme, events: Vec<Event>) -> CheckboxResult {
+        update_checked(&mut self.checked, events)
+    }
+}
+
+impl Widget for Toggle {
     fn id(&self) -> WidgetId {
         self.id
     }
 
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn cursor_icon(&self, _theme: &Theme, _pos: Point2<i32>) -> Option<CursorIcon> {
+        Some(CursorIcon::Hand)
+    }
+
     fn draw(
         &self,
-        _context: &GlContext,
+        context: &GlContext,
         _surface: &dyn Surface,
-        _rect: Rect<i32>,
-        _theme: &Theme,
-        _draw_2d: &mut Draw2d,
-        _cursor_pos: Option<Point2<i32>>,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
         _is_active: bool,
     ) {
+        let style = theme.style(WidgetKind::Toggle, widget_state(rect, cursor_pos, false));
+        let height = style.font.advance_y();
+        let track = Rect::new(rect.start, rect.start + vec2(height * 2, height));
+        let radius = height as f32 * 0.5;
+        let track_color = if self.checked { style.accent_color } else { style.fill_color };
+        draw_2d.fill_rounded_rect(track, radius, track_color);
+        draw_2d.outline_rounded_rect(track, radius, style.border_color, style.border_width);
+
+        let knob_start = track.start + vec2(if self.checked { height } else { 0 }, 0);
+        let knob = Rect::new(knob_start + vec2(2, 2), knob_start + vec2(height - 2, height - 2));
+        draw_2d.fill_rounded_rect(knob, radius, style.text_color);
+
+        let label_pos = rect.start + vec2(height * 2 + theme.scale(CHECK_LABEL_GAP), 0);
+        draw_check_label(context, theme, draw_2d, &self.text, label_pos);
     }
 
     fn min_size(
         &self,
-        _context: &GlContext,
-        _theme: &Theme,
-        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        context: &GlContext,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        let mut min_size: Vector2<i32> = Vector2::zero();
-        for &(ref child, _flex) in &self.children {
-            let child_min_size = min_sizes[&child.id()];
-            min_size.y = min_size.y.max(child_min_size.y);
-            min_size.x += child_min_size.x;
-        }
-        min_size
-    }
-
-    fn children(&self) -> Vec<&dyn Widget> {
-        self.children.iter().map(|(child, _)| &**child as &dyn Widget).collect()
+        let height = theme.style(WidgetKind::Toggle, WidgetState::Normal).font.advance_y();
+        check_widget_size(context, theme, vec2(height * 2, height), &self.text)
     }
+}
 
-    fn compute_rects(
-        &self,
-        rect: Rect<i32>,
-        theme: &Theme,
-        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
-        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
-    ) {
-        let total_flex = self.children.iter().map(|&(ref _child, flex)| flex).sum();
-        let min_size = min_sizes[&self.id()];
-        let own_rect = if total_flex == 0.0 {
-            Rect::new(rect.start, rect.start + vec2(min_size.x, rect.size().y))
-        } else {
-            Rect::new(rect.start, rect.start + vec2(rect.size().x, rect.size().y))
-        };
-        widget_rects.insert(self.id(), own_rect);
-        let mut next_pos = rect.start;
-        let total_flex = if total_flex == 0.0 { 1.0 } else { total_flex };
-        let extra_space = rect.size().x - min_size.x;
-        for &(ref child, flex) in &self.children {
-            let child_min_size = min_sizes[&child.id()];
-            let widget_extra_space = (extra_space as f32 * flex / total_flex) as i32;
-            let widget_width = child_min_size.x + widget_extra_space;
-            let widget_rect = Rect::new(next_pos, next_pos + vec2(widget_width, rect.size().y));
-            next_pos.x += widget_width;
-            child.compute_rects(widget_rect, theme, min_sizes, widget_rects);
-        }
-    }
+pub struct SliderResult {
+    pub value: f32,
+    /// True if the user changed the value since the last update.
+    pub changed: bool,
 }
 
+/// Lets the user pick a number in a range by dragging a knob along a track, or with the arrow
+/// keys when it's active. The value can optionally be shown next to the slider.
 #[derive(Clone)]
-pub struct TextBox {
-    text: String,
-    lines: Vec<String>,
-    text_color: Color4,
+pub struct Slider {
     id: WidgetId,
+    value: f32,
+    min: f32,
+    max: f32,
+    step: Option<f32>,
+    vertical: bool,
+    show_value: bool,
+    length: i32,
+    dragging: bool,
+    // The start and length of the track along the slider's axis, relative to its rect. This is
+    // recorded when the slider is drawn, since it depends on the size of the value label.
+    track: Rc<Cell<(i32, i32)>>,
 }
 
-impl TextBox {
-    pub fn new(text: &str) -> Box<Self> {
-        let mut res = Box::new(TextBox {
-            text: text.to_owned(),
-            lines: vec![],
-            text_color: Color4::BLACK,
+impl Slider {
+    /// Creates a horizontal slider. The value is clamped to the range.
+    pub fn new(value: f32, min: f32, max: f32) -> Box<Self> {
+        assert!(min < max);
+        Box::new(Slider {
             id: WidgetId::new(),
-        });
-        res.update_lines();
-        res
+            value: value.clamp(min, max),
+            min,
+            max,
+            step: None,
+            vertical: false,
+            show_value: false,
+            length: 100,
+            dragging: false,
+            track: Rc::new(Cell::new((0, 0))),
+        })
     }
 
-    pub fn text_color(mut self: Box<Self>, color: Color4) -> Box<Self> {
-        self.text_color = color;
+    /// Makes the value snap to multiples of `step` above `min`.
+    pub fn step(mut self: Box<Self>, step: f32) -> Box<Self> {
+        assert!(step > 0.0);
+        self.step = Some(step);
+        self.value = self.snap(self.value);
         self
     }
 
-    fn update_lines(&mut self) {
-        self.lines = self.text.split('\n').map(|x| x.to_owned()).collect();
+    /// Makes the slider vertical, with the minimum at the bottom.
+    pub fn vertical(mut self: Box<Self>, vertical: bool) -> Box<Self> {
+        self.vertical = vertical;
+        self
     }
-}
 
-impl Widget for TextBox {
-    fn id(&self) -> WidgetId {
-        self.id
+    /// Shows the value after the slider.
+    pub fn show_value(mut self: Box<Self>, show_value: bool) -> Box<Self> {
+        self.show_value = show_value;
+        self
     }
 
-    fn draw(
-        &self,
-        context: &GlContext,
-        _surface: &dyn Surface,
-        rect: Rect<i32>,
-        theme: &Theme,
-        _draw_2d: &mut Draw2d,
-        _cursor_pos: Option<Point2<i32>>,
-        _is_active: bool,
-    ) {
-        let advance_y = theme.font.advance_y();
-        for (i, line) in self.lines.iter().enumerate() {
-            theme.font.draw_string(
-                context,
-                line,
-                rect.start.cast().unwrap() + vec2(0, advance_y * i as i32),
-                self.text_color,
-            );
+    /// The minimum length of the track, in pixels. Defaults to 100.
+    pub fn length(mut self: Box<Self>, length: i32) -> Box<Self> {
+        self.length = length;
+        self
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn set_value(&mut self, value: f32) {
+        self.value = self.snap(value);
+    }
+
+    fn snap(&self, value: f32) -> f32 {
+        let value = match self.step {
+            Some(step) => self.min + ((value - self.min) / step).round() * step,
+            None => value,
+        };
+        value.clamp(self.min, self.max)
+    }
+
+    /// The amount the arrow keys change the value by.
+    fn key_step(&self) -> f32 {
+        self.step.unwrap_or((self.max - self.min) / 100.0)
+    }
+
+    fn axis(&self) -> usize {
+        if self.vertical {
+            1
+        } else {
+            0
         }
     }
 
-    fn min_size(
-        &self,
-        context: &GlContext,
-        theme: &Theme,
-        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
-        _window_size: Vector2<i32>,
-    ) -> Vector2<i32> {
-        let max_width = self.lines.iter().map(|x| theme.font.string_width(context, x) as i32).max();
-        if let Some(max_width) = max_width {
-            vec2(max_width as i32, theme.font.advance_y() as i32 * self.lines.len() as i32)
+    /// The value's position along the track, from 0 to 1.
+    fn fraction(&self) -> f32 {
+        (self.value - self.min) / (self.max - self.min)
+    }
+
+    fn format_value(&self, value: f32) -> String {
+        // Enough decimal places to show the step, or 2 if there isn't one
+        let decimals = match self.step {
+            Some(step) => (-step.log10()).ceil().max(0.0) as usize,
+            None => 2,
+        };
+        format!("{:.*}", decimals, value)
+    }
+
+    /// The length of the value label along the slider's axis, including the gap before it.
+    fn label_length(&self, context: &GlContext, theme: &Theme) -> i32 {
+        let font = theme.style(WidgetKind::Label, WidgetState::Normal).font;
+        if !self.show_value {
+            0
+        } else if self.vertical {
+            font.advance_y() + theme.scale(CHECK_LABEL_GAP)
         } else {
-            vec2(0, 0)
+            let width = [self.min, self.max]
+                .iter()
+                .map(|&value| font.string_width(context, &self.format_value(value)) as i32)
+                .max()
+                .unwrap();
+            width + theme.scale(CHECK_LABEL_GAP)
         }
     }
-}
 
-// This is intended to be persistent, which is tricky since widgets have to own their child widgets,
-// but it can be cloned.
-#[derive(Clone)]
-pub struct MessageBox {
-    lines: Vec<(String, Color4)>,
-    max_lines: usize,
-    id: WidgetId,
+    /// Sets the value from a cursor position along the slider's axis, relative to its rect.
+    fn set_value_from_pos(&mut self, pos: i32) {
+        let (start, len) = self.track.get();
+        if len <= 0 {
+            return;
+        }
+        let mut fraction = ((pos - start) as f32 / len as f32).clamp(0.0, 1.0);
+        if self.vertical {
+            fraction = 1.0 - fraction;
+        }
+        self.value = self.snap(self.min + fraction * (self.max - self.min));
+    }
 }
 
-impl MessageBox {
-    pub fn new(max_lines: usize) -> Box<Self> {
-        Box::new(MessageBox { lines: vec![], max_lines, id: WidgetId::new() })
-    }
+impl Component for Slider {
+    type Res = SliderResult;
 
-    pub fn add_line(&mut self, color: Color4, line: String) {
-        self.lines.push((line, color));
-        if self.lines.len() > self.max_lines {
-            self.lines.remove(0);
+    fn update(&mut self, _theme: &Theme, events: Vec<Event>) -> SliderResult {
+        let old_value = self.value;
+        let axis = self.axis();
+        for event in events {
+            match event {
+                Event::MouseDown(MouseButton::Left, pos) => {
+                    self.dragging = true;
+                    self.set_value_from_pos(pos[axis]);
+                }
+                Event::MouseMove { pos, .. } if self.dragging => self.set_value_from_pos(pos[axis]),
+                Event::MouseUp(MouseButton::Left, _) | Event::FocusLost => self.dragging = false,
+                Event::KeyDown(key) => match key.code.as_ref() {
+                    "ArrowLeft" | "ArrowDown" => {
+                        self.value = self.snap(self.value - self.key_step())
+                    }
+                    "ArrowRight" | "ArrowUp" => {
+                        self.value = self.snap(self.value + self.key_step())
+                    }
+                    "Home" => self.value = self.min,
+                    "End" => self.value = self.max,
+                    _ => (),
+                },
+                _ => (),
+            }
         }
+        SliderResult { value: self.value, changed: self.value != old_value }
     }
 }
 
-impl Widget for MessageBox {
+impl Widget for Slider {
     fn id(&self) -> WidgetId {
         self.id
     }
 
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn captures_pointer(&self) -> bool {
+        true
+    }
+
     fn draw(
         &self,
         context: &GlContext,
         _surface: &dyn Surface,
         rect: Rect<i32>,
         theme: &Theme,
-        _draw_2d: &mut Draw2d,
-        _cursor_pos: Option<Point2<i32>>,
-        _is_active: bool,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        is_active: bool,
     ) {
-        let advance_y = theme.font.advance_y();
-        for (i, &(ref line, color)) in self.lines.iter().enumerate() {
-            theme.font.draw_string(
-                context,
-                line,
-                rect.start.cast().unwrap() + vec2(0, advance_y * i as i32),
-                color,
-            );
+        let axis = self.axis();
+        let style = theme.style(WidgetKind::Slider, WidgetState::Normal);
+        let thickness = style.font.advance_y();
+        let length = rect.size()[axis] - self.label_length(context, theme);
+        // The knob's center stays inside the track, so the knob stays inside the rect.
+        self.track.set((thickness / 2, length - thickness));
+
+        let mut fraction = self.fraction();
+        if self.vertical {
+            fraction = 1.0 - fraction;
+        }
+        let knob_center = thickness / 2 + ((length - thickness) as f32 * fraction).round() as i32;
+
+        let (track, knob_start) = if self.vertical {
+            let x = rect.start.x + thickness / 2;
+            (
+                Rect::new(point2(x - 2, rect.start.y), point2(x + 2, rect.start.y + length)),
+                rect.start + vec2(0, knob_center - thickness / 2),
+            )
+        } else {
+            let y = rect.start.y + thickness / 2;
+            (
+                Rect::new(point2(rect.start.x, y - 2), point2(rect.start.x + length, y + 2)),
+                rect.start + vec2(knob_center - thickness / 2, 0),
+            )
+        };
+        draw_2d.fill_rounded_rect(track, 2.0, style.fill_color);
+        draw_2d.outline_rounded_rect(track, 2.0, style.border_color, style.border_width);
+
+        let knob = Rect::new(knob_start, knob_start + vec2(thickness, thickness));
+        let knob_style = if self.dragging || is_active {
+            theme.style(WidgetKind::Slider, WidgetState::Pressed)
+        } else {
+            theme.style(WidgetKind::Slider, widget_state(knob, cursor_pos, false))
+        };
+        let radius = thickness as f32 * 0.5;
+        draw_2d.fill_rounded_rect(knob, radius, knob_style.fill_color);
+        draw_2d.outline_rounded_rect(
+            knob,
+            radius,
+            knob_style.border_color,
+            knob_style.border_width,
+        );
+
+        if self.show_value {
+            let mut label_pos = rect.start;
+            label_pos[axis] += length + theme.scale(CHECK_LABEL_GAP);
+            draw_check_label(context, theme, draw_2d, &self.format_value(self.value), label_pos);
         }
     }
 
@@ -514,73 +696,121 @@ impl Widget for MessageBox {
         _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        let max_width =
-            self.lines.iter().map(|x| theme.font.string_width(context, &x.0) as i32).max();
-        if let Some(max_width) = max_width {
-            vec2(max_width as i32, theme.font.advance_y() as i32 * self.lines.len() as i32)
+        let thickness = theme.style(WidgetKind::Slider, WidgetState::Normal).font.advance_y();
+        let length = self.length.max(thickness) + self.label_length(context, theme);
+        if self.vertical {
+            let label_width = if self.show_value {
+                let font = theme.style(WidgetKind::Label, WidgetState::Normal).font;
+                font.string_width(context, &self.format_value(self.max)) as i32
+            } else {
+                0
+            };
+            vec2(thickness.max(label_width), length)
         } else {
-            vec2(0, 0)
+            vec2(length, thickness)
         }
     }
 }
 
-/// Allows overlapping several widgets on top of one another.
-pub struct Overlap {
-    id: WidgetId,
-    children: Vec<Box<dyn Widget>>,
+/// Disables or hides a widget; see `WidgetExt`.
+pub struct WidgetFlags {
+    widget: Box<dyn Widget>,
+    enabled: bool,
+    visible: bool,
 }
 
-impl Overlap {
-    pub fn new() -> Box<Self> {
-        Box::new(Overlap { id: WidgetId::new(), children: vec![] })
+impl WidgetFlags {
+    pub fn enabled(mut self: Box<Self>, enabled: bool) -> Box<Self> {
+        self.enabled = enabled;
+        self
     }
 
-    pub fn child(mut self: Box<Self>, child: Box<dyn Widget>) -> Box<Self> {
-        self.children.push(child);
+    pub fn visible(mut self: Box<Self>, visible: bool) -> Box<Self> {
+        self.visible = visible;
         self
     }
+}
 
-    pub fn children(mut self: Box<Self>, children: Vec<Box<dyn Widget>>) -> Box<Self> {
-        self.children.extend(children.into_iter());
-        self
+/// Lets any widget be disabled or hidden, e.g. `Button::new(...).enabled(false)`. See
+/// `Widget::is_enabled` and `Widget::is_visible`.
+///
+/// The widget keeps its ID, so a disabled component can still be updated as usual; it just
+/// won't receive any events.
+pub trait WidgetExt {
+    fn enabled(self, enabled: bool) -> Box<WidgetFlags>;
+    fn visible(self, visible: bool) -> Box<WidgetFlags>;
+}
+
+impl<W: Widget + 'static> WidgetExt for Box<W> {
+    fn enabled(self, enabled: bool) -> Box<WidgetFlags> {
+        Box::new(WidgetFlags { widget: self, enabled, visible: true })
+    }
+
+    fn visible(self, visible: bool) -> Box<WidgetFlags> {
+        Box::new(WidgetFlags { widget: self, enabled: true, visible })
     }
 }
 
-impl Widget for Overlap {
+impl WidgetExt for Box<dyn Widget> {
+    fn enabled(self, enabled: bool) -> Box<WidgetFlags> {
+        Box::new(WidgetFlags { widget: self, enabled, visible: true })
+    }
+
+    fn visible(self, visible: bool) -> Box<WidgetFlags> {
+        Box::new(WidgetFlags { widget: self, enabled: true, visible })
+    }
+}
+
+impl Widget for WidgetFlags {
     fn id(&self) -> WidgetId {
-        self.id
+        self.widget.id()
+    }
+
+    fn is_component(&self) -> bool {
+        self.widget.is_component()
+    }
+
+    fn handles_scroll(&self) -> bool {
+        self.widget.handles_scroll()
+    }
+
+    fn cursor_icon(&self, theme: &Theme, pos: Point2<i32>) -> Option<CursorIcon> {
+        self.widget.cursor_icon(theme, pos)
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled && self.widget.is_enabled()
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible && self.widget.is_visible()
     }
 
     fn draw(
         &self,
-        _context: &GlContext,
-        _surface: &dyn Surface,
-        _rect: Rect<i32>,
-        _theme: &Theme,
-        _draw_2d: &mut Draw2d,
-        _cursor_pos: Option<Point2<i32>>,
-        _is_active: bool,
+        context: &GlContext,
+        surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        is_active: bool,
     ) {
+        self.widget.draw(context, surface, rect, theme, draw_2d, cursor_pos, is_active);
     }
 
     fn min_size(
         &self,
-        _context: &GlContext,
-        _theme: &Theme,
+        context: &GlContext,
+        theme: &Theme,
         min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
-        _window_size: Vector2<i32>,
+        window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        let mut min_size: Vector2<i32> = Vector2::zero();
-        for child in &self.children {
-            let child_min_size = min_sizes[&child.id()];
-            min_size.x = min_size.x.max(child_min_size.x);
-            min_size.y = min_size.y.max(child_min_size.y);
-        }
-        min_size
+        self.widget.min_size(context, theme, min_sizes, window_size)
     }
 
     fn children(&self) -> Vec<&dyn Widget> {
-        self.children.iter().map(|child| &**child as &dyn Widget).collect()
+        self.widget.children()
     }
 
     fn compute_rects(
@@ -590,31 +820,24 @@ impl Widget for Overlap {
         min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
     ) {
-        let own_rect = rect;
-        widget_rects.insert(self.id(), own_rect);
-        for child in &self.children {
-            child.compute_rects(own_rect, theme, min_sizes, widget_rects);
-        }
+        self.widget.compute_rects(rect, theme, min_sizes, widget_rects);
     }
 }
 
-#[derive(Clone)]
-pub struct EmptyWidget {
+/// A widget that makes its child its minimum possible size rather than filling the whole
+/// window.
+pub struct NoFill {
     id: WidgetId,
-    size: Vector2<i32>,
+    child: Box<dyn Widget>,
 }
 
-impl EmptyWidget {
-    pub fn new() -> Box<Self> {
-        Self::with_size(Vector2::zero())
-    }
-
-    pub fn with_size(size: Vector2<i32>) -> Box<Self> {
-        Box::new(EmptyWidget { id: WidgetId::new(), size })
+impl NoFill {
+    pub fn new(child: Box<dyn Widget>) -> Box<Self> {
+        Box::new(NoFill { id: WidgetId::new(), child })
     }
 }
 
-impl Widget for EmptyWidget {
+impl Widget for NoFill {
     fn id(&self) -> WidgetId {
         self.id
     }
@@ -635,24 +858,66 @@ impl Widget for EmptyWidget {
         &self,
         _context: &GlContext,
         _theme: &Theme,
-        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        self.size
+        min_sizes[&self.child.id()]
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        vec![&*self.child]
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        let min_size = min_sizes[&self.id()];
+        widget_rects.insert(self.id(), Rect::new(rect.start, rect.start + min_size));
+        self.child.compute_rects(
+            Rect::new(rect.start, rect.start + min_size),
+            theme,
+            min_sizes,
+            widget_rects,
+        );
     }
 }
 
-pub struct Padding {
+/// Hidden children of rows and columns don't get any of the unused space.
+fn visible_flex(child: &dyn Widget, flex: f32) -> f32 {
+    if child.is_visible() {
+        flex
+    } else {
+        0.0
+    }
+}
+
+pub struct Col {
     id: WidgetId,
+    children: Vec<(Box<dyn Widget>, f32)>,
 }
 
-impl Padding {
+impl Col {
     pub fn new() -> Box<Self> {
-        Box::new(Padding { id: WidgetId::new() })
+        Box::new(Col { id: WidgetId::new(), children: vec![] })
+    }
+
+    /// Flex controls how to distribute unused space.
+    pub fn child(mut self: Box<Self>, flex: f32, child: Box<dyn Widget>) -> Box<Self> {
+        self.children.push((child, flex));
+        self
+    }
+
+    pub fn children(mut self: Box<Self>, children: Vec<(f32, Box<dyn Widget>)>) -> Box<Self> {
+        self.children.extend(children.into_iter().map(|(a, b)| (b, a)));
+        self
     }
 }
 
-impl Widget for Padding {
+impl Widget for Col {
     fn id(&self) -> WidgetId {
         self.id
     }
@@ -672,26 +937,77 @@ impl Widget for Padding {
     fn min_size(
         &self,
         _context: &GlContext,
-        theme: &Theme,
-        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        vec2(theme.padding, theme.padding)
-    }
-}
-
-pub struct Inset {
-    id: WidgetId,
-    child: Box<dyn Widget>,
+        let mut min_size: Vector2<i32> = Vector2::zero();
+        for &(ref child, _flex) in &self.children {
+            let child_min_size = min_sizes[&child.id()];
+            min_size.x = min_size.x.max(child_min_size.x);
+            min_size.y += child_min_size.y;
+        }
+        min_size
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        self.children.iter().map(|(child, _)| &**child as &dyn Widget).collect()
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        let total_flex =
+            self.children.iter().map(|(child, flex)| visible_flex(&**child, *flex)).sum();
+        let min_size = min_sizes[&self.id()];
+        let own_rect = if total_flex == 0.0 {
+            Rect::new(rect.start, rect.start + vec2(rect.size().x, min_size.y))
+        } else {
+            Rect::new(rect.start, rect.start + vec2(rect.size().x, rect.size().y))
+        };
+        widget_rects.insert(self.id(), own_rect);
+        let mut next_pos = rect.start;
+        let total_flex = if total_flex == 0.0 { 1.0 } else { total_flex };
+        let extra_space = rect.size().y - min_size.y;
+        for &(ref child, flex) in &self.children {
+            let child_min_size = min_sizes[&child.id()];
+            let widget_extra_space =
+                (extra_space as f32 * visible_flex(&**child, flex) / total_flex) as i32;
+            let widget_height = child_min_size.y + widget_extra_space;
+            let widget_rect = Rect::new(next_pos, next_pos + vec2(rect.size().x, widget_height));
+            next_pos.y += widget_height;
+            child.compute_rects(widget_rect, theme, min_sizes, widget_rects);
+        }
+    }
 }
 
-impl Inset {
-    pub fn new(child: Box<dyn Widget>) -> Box<Self> {
-        Box::new(Inset { id: WidgetId::new(), child })
+pub struct Row {
+    id: WidgetId,
+    children: Vec<(Box<dyn Widget>, f32)>,
+}
+
+impl Row {
+    pub fn new() -> Box<Self> {
+        Box::new(Row { id: WidgetId::new(), children: vec![] })
+    }
+
+    /// Flex controls how to distribute unused space.
+    pub fn child(mut self: Box<Self>, flex: f32, child: Box<dyn Widget>) -> Box<Self> {
+        self.children.push((child, flex));
+        self
+    }
+
+    pub fn children(mut self: Box<Self>, children: Vec<(f32, Box<dyn Widget>)>) -> Box<Self> {
+        self.children.extend(children.into_iter().map(|(a, b)| (b, a)));
+        self
     }
 }
 
-impl Widget for Inset {
+impl Widget for Row {
     fn id(&self) -> WidgetId {
         self.id
     }
@@ -711,15 +1027,21 @@ impl Widget for Inset {
     fn min_size(
         &self,
         _context: &GlContext,
-        theme: &Theme,
+        _theme: &Theme,
         min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        min_sizes[&self.child.id()] + vec2(theme.padding * 2, theme.padding * 2)
+        let mut min_size: Vector2<i32> = Vector2::zero();
+        for &(ref child, _flex) in &self.children {
+            let child_min_size = min_sizes[&child.id()];
+            min_size.y = min_size.y.max(child_min_size.y);
+            min_size.x += child_min_size.x;
+        }
+        min_size
     }
 
     fn children(&self) -> Vec<&dyn Widget> {
-        vec![&*self.child]
+        self.children.iter().map(|(child, _)| &**child as &dyn Widget).collect()
     }
 
     fn compute_rects(
@@ -729,67 +1051,73 @@ impl Widget for Inset {
         min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
     ) {
-        widget_rects.insert(
-            self.id(),
-            Rect::new(rect.start, rect.end + vec2(theme.padding * 2, theme.padding * 2)),
-        );
-        self.child.compute_rects(
-            Rect::new(
-                rect.start + vec2(theme.padding, theme.padding),
-                rect.end - vec2(theme.padding, theme.padding),
-            ),
-            theme,
-            min_sizes,
-            widget_rects,
-        );
+        let total_flex =
+            self.children.iter().map(|(child, flex)| visible_flex(&**child, *flex)).sum();
+        let min_size = min_sizes[&self.id()];
+        let own_rect = if total_flex == 0.0 {
+            Rect::new(rect.start, rect.start + vec2(min_size.x, rect.size().y))
+        } else {
+            Rect::new(rect.start, rect.start + vec2(rect.size().x, rect.size().y))
+        };
+        widget_rects.insert(self.id(), own_rect);
+        let mut next_pos = rect.start;
+        let total_flex = if total_flex == 0.0 { 1.0 } else { total_flex };
+        let extra_space = rect.size().x - min_size.x;
+        for &(ref child, flex) in &self.children {
+            let child_min_size = min_sizes[&child.id()];
+            let widget_extra_space =
+                (extra_space as f32 * visible_flex(&**child, flex) / total_flex) as i32;
+            let widget_width = child_min_size.x + widget_extra_space;
+            let widget_rect = Rect::new(next_pos, next_pos + vec2(widget_width, rect.size().y));
+            next_pos.x += widget_width;
+            child.compute_rects(widget_rect, theme, min_sizes, widget_rects);
+        }
     }
 }
 
-/// Lets the user select one of several options, which are all shown at once.
 #[derive(Clone)]
-pub struct Selector<T: Copy + PartialEq> {
-    options: Vec<(String, T)>,
-    selected_option: Option<usize>,
+pub struct TextBox {
+    text: String,
+    lines: Vec<String>,
+    text_color: Color4,
+    font: Option<String>,
     id: WidgetId,
 }
 
-impl<T: Copy + PartialEq> Selector<T> {
-    pub fn new(options: Vec<(String, T)>, selected_option: Option<usize>) -> Box<Self> {
-        if let Some(selected_option) = selected_option {
-            assert!(selected_option < options.len());
-        }
-        Box::new(Self { selected_option, options, id: WidgetId::new() })
+impl TextBox {
+    pub fn new(text: &str) -> Box<Self> {
+        let mut res = Box::new(TextBox {
+            text: text.to_owned(),
+            lines: vec![],
+            text_color: Color4::BLACK,
+            font: None,
+            id: WidgetId::new(),
+        });
+        res.update_lines();
+        res
     }
 
-    pub fn selected_option(&self) -> Option<T> {
-        self.selected_option.map(|selected_option| self.options[selected_option].1)
+    pub fn text_color(mut self: Box<Self>, color: Color4) -> Box<Self> {
+        self.text_color = color;
+        self
     }
 
-    pub fn add_option(&mut self, option: (String, T)) {
-        self.options.push(option);
+    /// Draws the text with one of the theme's named fonts; see `Theme::fonts`.
+    pub fn font(mut self: Box<Self>, name: &str) -> Box<Self> {
+        self.font = Some(name.to_owned());
+        self
     }
 
-    pub fn remove_option(&mut self, i: usize) {
-        self.options.remove(i);
-        if let Some(selected_option) = &mut self.selected_option {
-            if *selected_option == i {
-                self.selected_option = None;
-            } else if *selected_option > i {
-                *selected_option -= 1;
-            }
-        }
+    fn update_lines(&mut self) {
+        self.lines = self.text.split('\n').map(|x| x.to_owned()).collect();
     }
 }
 
-impl<T: Copy + PartialEq> Widget for Selector<T> {
+impl Widget for TextBox {
     fn id(&self) -> WidgetId {
         self.id
     }
 
-    fn is_component(&self) -> bool {
-        true
-    }
-
     fn draw(
         &self,
         context: &GlContext,
@@ -797,23 +1125,20 @@ impl<T: Copy + PartialEq> Widget for Selector<T> {
         rect: Rect<i32>,
         theme: &Theme,
         draw_2d: &mut Draw2d,
-        cursor_pos: Option<Point2<i32>>,
+        _cursor_pos: Option<Point2<i32>>,
         _is_active: bool,
     ) {
-        for (i, (line, _)) in self.options.iter().enumerate() {
-            let pos = rect.start.cast().unwrap() + vec2(0, theme.font.advance_y() * i as i32);
-            let rect = Rect::new(pos, pos + theme.font.string_size(context, line));
-            let background_color = if Some(i) == self.selected_option {
-                Color4::WHITE.mul_srgb(0.5)
-            } else if cursor_pos.is_some()
-                && rect.contains_point(cursor_pos.unwrap().cast().unwrap())
-            {
-                Color4::WHITE.mul_srgb(0.75)
-            } else {
-                Color4::WHITE
-            };
-            draw_2d.fill_rect(rect, background_color);
-            theme.font.draw_string(context, line, pos, Color4::BLACK);
+        let font =
+            chosen_font(theme, &self.font, theme.style(WidgetKind::Label, WidgetState::Normal));
+        let advance_y = font.advance_y();
+        for (i, line) in self.lines.iter().enumerate() {
+            draw_2d.draw_string(
+                context,
+                &font,
+                line,
+                rect.start.cast().unwrap() + vec2(0, advance_y * i as i32),
+                self.text_color,
+            );
         }
     }
 
@@ -824,63 +1149,146 @@ impl<T: Copy + PartialEq> Widget for Selector<T> {
         _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        let max_width =
-            self.options.iter().map(|(x, _)| theme.font.string_width(context, x) as i32).max();
+        let font =
+            chosen_font(theme, &self.font, theme.style(WidgetKind::Label, WidgetState::Normal));
+        let max_width = self.lines.iter().map(|x| font.string_width(context, x) as i32).max();
         if let Some(max_width) = max_width {
-            vec2(max_width as i32, theme.font.advance_y() as i32 * self.options.len() as i32)
+            vec2(max_width, font.advance_y() * self.lines.len() as i32)
         } else {
             vec2(0, 0)
         }
     }
 }
 
-pub struct SelectorResult<T: Copy + PartialEq> {
-    pub selected: Option<(String, T)>,
-    pub just_selected: bool,
+// This is intended to be persistent, which is tricky since widgets have to own their child widgets,
+// but it can be cloned.
+#[derive(Clone)]
+pub struct MessageBox {
+    lines: Vec<MessageLine>,
+    max_lines: usize,
+    /// The color used for timestamps.
+    pub timestamp_color: Color4,
+    id: WidgetId,
 }
 
-impl<T: Copy + PartialEq> Component for Selector<T> {
-    type Res = SelectorResult<T>;
+#[derive(Clone)]
+struct MessageLine {
+    timestamp: Option<String>,
+    segments: Vec<(String, Color4)>,
+}
 
-    fn update(&mut self, theme: &Theme, events: Vec<Event>) -> Self::Res {
-        let mut just_selected = false;
-        for event in events {
-            if let Event::MouseDown(MouseButton::Left, pos) = event {
-                let entry = pos.y / theme.font.advance_y() as i32;
-                assert!(
-                    entry >= 0 && (entry as usize) < self.options.len(),
-                    "entry {} out of range (max={})",
-                    entry,
-                    self.options.len()
+impl MessageLine {
+    /// Returns the text to draw for each part of the line, including the timestamp.
+    fn parts(&self, timestamp_color: Color4) -> impl Iterator<Item = (&str, Color4)> {
+        let timestamp = self.timestamp.iter().map(move |x| (x.as_str(), timestamp_color));
+        timestamp.chain(self.segments.iter().map(|(text, color)| (text.as_str(), *color)))
+    }
+}
+
+impl MessageBox {
+    pub fn new(max_lines: usize) -> Box<Self> {
+        Box::new(MessageBox {
+            lines: vec![],
+            max_lines,
+            timestamp_color: Color4::from_grayscale_srgb(0.6),
+            id: WidgetId::new(),
+        })
+    }
+
+    pub fn add_line(&mut self, color: Color4, line: String) {
+        self.add_segments(None, vec![(line, color)]);
+    }
+
+    /// Adds a line made of several differently colored segments, such as a player's name followed
+    /// by a chat message. If `timestamp` is given, it's drawn before the segments in
+    /// `timestamp_color`, separated by a space.
+    pub fn add_segments(&mut self, timestamp: Option<String>, segments: Vec<(String, Color4)>) {
+        let timestamp = timestamp.map(|timestamp| timestamp + " ");
+        self.lines.push(MessageLine { timestamp, segments });
+        if self.lines.len() > self.max_lines {
+            self.lines.remove(0);
+        }
+    }
+
+    fn line_width(&self, context: &GlContext, font: &Font, line: &MessageLine) -> i32 {
+        line.parts(self.timestamp_color)
+            .map(|(text, _)| font.string_width(context, text))
+            .sum::<f32>() as i32
+    }
+}
+
+impl Widget for MessageBox {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        context: &GlContext,
+        _surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+        let font = theme.style(WidgetKind::Label, WidgetState::Normal).font;
+        let advance_y = font.advance_y();
+        for (i, line) in self.lines.iter().enumerate() {
+            let mut x = 0.0;
+            for (text, color) in line.parts(self.timestamp_color) {
+                draw_2d.draw_string(
+                    context,
+                    &font,
+                    text,
+                    rect.start.cast().unwrap() + vec2(x as i32, advance_y * i as i32),
+                    color,
                 );
-                self.selected_option = Some(entry as usize);
-                just_selected = true;
+                x += font.string_width(context, text);
             }
         }
+    }
 
-        SelectorResult {
-            selected: self
-                .selected_option
-                .map(|selected_option| self.options[selected_option].clone()),
-            just_selected,
+    fn min_size(
+        &self,
+        context: &GlContext,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let font = theme.style(WidgetKind::Label, WidgetState::Normal).font;
+        let max_width = self.lines.iter().map(|x| self.line_width(context, &font, x)).max();
+        if let Some(max_width) = max_width {
+            vec2(max_width, font.advance_y() * self.lines.len() as i32)
+        } else {
+            vec2(0, 0)
         }
     }
 }
 
-/// A widget that's filled with a background color.
-pub struct Fill {
+/// Allows overlapping several widgets on top of one another.
+pub struct Overlap {
     id: WidgetId,
-    child: Box<dyn Widget>,
-    fill_color: Color4,
+    children: Vec<Box<dyn Widget>>,
 }
 
-impl Fill {
-    pub fn new(fill_color: Color4, child: Box<dyn Widget>) -> Box<Self> {
-        Box::new(Fill { id: WidgetId::new(), child, fill_color })
+impl Overlap {
+    pub fn new() -> Box<Self> {
+        Box::new(Overlap { id: WidgetId::new(), children: vec![] })
+    }
+
+    pub fn child(mut self: Box<Self>, child: Box<dyn Widget>) -> Box<Self> {
+        self.children.push(child);
+        self
+    }
+
+    pub fn children(mut self: Box<Self>, children: Vec<Box<dyn Widget>>) -> Box<Self> {
+        self.children.extend(children.into_iter());
+        self
     }
 }
 
-impl Widget for Fill {
+impl Widget for Overlap {
     fn id(&self) -> WidgetId {
         self.id
     }
@@ -889,13 +1297,12 @@ impl Widget for Fill {
         &self,
         _context: &GlContext,
         _surface: &dyn Surface,
-        rect: Rect<i32>,
+        _rect: Rect<i32>,
         _theme: &Theme,
-        draw_2d: &mut Draw2d,
+        _draw_2d: &mut Draw2d,
         _cursor_pos: Option<Point2<i32>>,
         _is_active: bool,
     ) {
-        draw_2d.fill_rect(Rect::new(rect.start, rect.end), self.fill_color);
     }
 
     fn min_size(
@@ -905,154 +1312,3152 @@ impl Widget for Fill {
         min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        min_sizes[&self.child.id()]
-    }
+        let mut min_size: Vector2<i32> = Vector2::zero();
+        for child in &self.children {
+            let child_min_size = min_sizes[&child.id()];
+            min_size.x = min_size.x.max(child_min_size.x);
+            min_size.y = min_size.y.max(child_min_size.y);
+        }
+        min_size
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        self.children.iter().map(|child| &**child as &dyn Widget).collect()
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        let own_rect = rect;
+        widget_rects.insert(self.id(), own_rect);
+        for child in &self.children {
+            child.compute_rects(own_rect, theme, min_sizes, widget_rects);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EmptyWidget {
+    id: WidgetId,
+    size: Vector2<i32>,
+}
+
+impl EmptyWidget {
+    pub fn new() -> Box<Self> {
+        Self::with_size(Vector2::zero())
+    }
+
+    pub fn with_size(size: Vector2<i32>) -> Box<Self> {
+        Box::new(EmptyWidget { id: WidgetId::new(), size })
+    }
+}
+
+impl Widget for EmptyWidget {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        _rect: Rect<i32>,
+        _theme: &Theme,
+        _draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        _theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        self.size
+    }
+}
+
+pub struct Padding {
+    id: WidgetId,
+}
+
+impl Padding {
+    pub fn new() -> Box<Self> {
+        Box::new(Padding { id: WidgetId::new() })
+    }
+}
+
+impl Widget for Padding {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        _rect: Rect<i32>,
+        _theme: &Theme,
+        _draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let padding = theme.scale(theme.padding);
+        vec2(padding, padding)
+    }
+}
+
+pub struct Inset {
+    id: WidgetId,
+    child: Box<dyn Widget>,
+}
+
+impl Inset {
+    pub fn new(child: Box<dyn Widget>) -> Box<Self> {
+        Box::new(Inset { id: WidgetId::new(), child })
+    }
+}
+
+impl Widget for Inset {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        _rect: Rect<i32>,
+        _theme: &Theme,
+        _draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let padding = theme.scale(theme.padding);
+        min_sizes[&self.child.id()] + vec2(padding * 2, padding * 2)
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        vec![&*self.child]
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        let padding = theme.scale(theme.padding);
+        widget_rects
+            .insert(self.id(), Rect::new(rect.start, rect.end + vec2(padding * 2, padding * 2)));
+        self.child.compute_rects(
+            Rect::new(rect.start + vec2(padding, padding), rect.end - vec2(padding, padding)),
+            theme,
+            min_sizes,
+            widget_rects,
+        );
+    }
+}
+
+/// Lets the user select one of several options. All options are shown at once unless
+/// `max_visible_options` is used, in which case the list scrolls.
+///
+/// When it's active, the selection can be moved with the up and down arrow keys, and Enter
+/// reports the current selection again. It's outlined with `Theme::focus_color` while it's active.
+#[derive(Clone)]
+pub struct Selector<T: Copy + PartialEq> {
+    options: Vec<(String, T)>,
+    selected_option: Option<usize>,
+    max_visible_options: Option<usize>,
+    // The index of the first visible option
+    scroll: usize,
+    // Scrolling that didn't add up to a whole option yet
+    scroll_remainder: f64,
+    id: WidgetId,
+}
+
+impl<T: Copy + PartialEq> Selector<T> {
+    pub fn new(options: Vec<(String, T)>, selected_option: Option<usize>) -> Box<Self> {
+        if let Some(selected_option) = selected_option {
+            assert!(selected_option < options.len());
+        }
+        Box::new(Self {
+            selected_option,
+            options,
+            max_visible_options: None,
+            scroll: 0,
+            scroll_remainder: 0.0,
+            id: WidgetId::new(),
+        })
+    }
+
+    /// Limits the number of options shown at once. The rest can be reached by scrolling or with
+    /// the arrow keys.
+    pub fn max_visible_options(mut self: Box<Self>, max_visible_options: usize) -> Box<Self> {
+        assert!(max_visible_options > 0);
+        self.max_visible_options = Some(max_visible_options);
+        self.scroll_to_selection();
+        self
+    }
+
+    fn num_visible_options(&self) -> usize {
+        let len = self.options.len();
+        self.max_visible_options.map_or(len, |max| max.min(len))
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.options.len() - self.num_visible_options()
+    }
+
+    /// Scrolls so the selected option is visible.
+    fn scroll_to_selection(&mut self) {
+        if let Some(selected_option) = self.selected_option {
+            let num_visible = self.num_visible_options();
+            if selected_option < self.scroll {
+                self.scroll = selected_option;
+            } else if selected_option >= self.scroll + num_visible {
+                self.scroll = selected_option + 1 - num_visible;
+            }
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    pub fn selected_option(&self) -> Option<T> {
+        self.selected_option.map(|selected_option| self.options[selected_option].1)
+    }
+
+    pub fn add_option(&mut self, option: (String, T)) {
+        self.options.push(option);
+    }
+
+    pub fn remove_option(&mut self, i: usize) {
+        self.options.remove(i);
+        if let Some(selected_option) = &mut self.selected_option {
+            if *selected_option == i {
+                self.selected_option = None;
+            } else if *selected_option > i {
+                *selected_option -= 1;
+            }
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+}
+
+impl<T: Copy + PartialEq> Widget for Selector<T> {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn handles_scroll(&self) -> bool {
+        true
+    }
+
+    fn draw(
+        &self,
+        context: &GlContext,
+        _surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        is_active: bool,
+    ) {
+        let visible_options =
+            self.options.iter().enumerate().skip(self.scroll).take(self.num_visible_options());
+        let font = theme.style(WidgetKind::Selector, WidgetState::Normal).font;
+        for (row, (i, (line, _))) in visible_options.enumerate() {
+            let pos = rect.start.cast().unwrap() + vec2(0, font.advance_y() * row as i32);
+            let rect = Rect::new(pos, pos + font.string_size(context, line));
+            let style = theme.style(WidgetKind::Selector, widget_state(rect, cursor_pos, false));
+            let background_color =
+                if Some(i) == self.selected_option { style.accent_color } else { style.fill_color };
+            draw_2d.fill_rect(rect, background_color);
+            draw_2d.draw_string(context, &style.font, line, pos, style.text_color);
+        }
+        if is_active {
+            draw_2d.outline_rect(rect, theme.focus_color, 1.0);
+        }
+    }
+
+    fn min_size(
+        &self,
+        context: &GlContext,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let font = theme.style(WidgetKind::Selector, WidgetState::Normal).font;
+        let max_width =
+            self.options.iter().map(|(x, _)| font.string_width(context, x) as i32).max();
+        if let Some(max_width) = max_width {
+            let num_rows = self.num_visible_options() as i32;
+            vec2(max_width, font.advance_y() * num_rows)
+        } else {
+            vec2(0, 0)
+        }
+    }
+}
+
+pub struct SelectorResult<T: Copy + PartialEq> {
+    pub selected: Option<(String, T)>,
+    pub just_selected: bool,
+}
+
+impl<T: Copy + PartialEq> Component for Selector<T> {
+    type Res = SelectorResult<T>;
+
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) -> Self::Res {
+        let mut just_selected = false;
+        for event in events {
+            match event {
+                Event::MouseDown(MouseButton::Left, pos) => {
+                    // Clicks can land below the last option, e.g. when the widget is given more
+                    // space than it needs.
+                    let font = theme.style(WidgetKind::Selector, WidgetState::Normal).font;
+                    let row = pos.y / font.advance_y();
+                    if row >= 0 && (row as usize) < self.num_visible_options() {
+                        self.selected_option = Some(self.scroll + row as usize);
+                        just_selected = true;
+                    }
+                }
+                Event::KeyDown(key) if !self.options.is_empty() => {
+                    let last = self.options.len() - 1;
+                    match key.code.as_ref() {
+                        "ArrowUp" => {
+                            self.selected_option =
+                                Some(self.selected_option.map_or(last, |x| x.saturating_sub(1)));
+                            just_selected = true;
+                        }
+                        "ArrowDown" => {
+                            self.selected_option =
+                                Some(self.selected_option.map_or(0, |x| (x + 1).min(last)));
+                            just_selected = true;
+                        }
+                        "Enter" => just_selected = self.selected_option.is_some(),
+                        _ => (),
+                    }
+                    self.scroll_to_selection();
+                }
+                Event::Scroll { delta, unit } => {
+                    let font = theme.style(WidgetKind::Selector, WidgetState::Normal).font;
+                    let lines = unit.to_lines(delta, font.advance_y() as f64).y;
+                    let rows = whole_lines(&mut self.scroll_remainder, lines);
+                    self.scroll =
+                        (self.scroll as i64 + rows).clamp(0, self.max_scroll() as i64) as usize;
+                }
+                _ => (),
+            }
+        }
+
+        SelectorResult {
+            selected: self
+                .selected_option
+                .map(|selected_option| self.options[selected_option].clone()),
+            just_selected,
+        }
+    }
+}
+
+const SCROLL_BAR_WIDTH: i32 = 8;
+
+/// Adds `lines` of scrolling to `remainder` and returns the whole number of lines to scroll by,
+/// for widgets that can only scroll by whole lines or rows. The rest is kept in `remainder`, so
+/// that small trackpad movements add up.
+fn whole_lines(remainder: &mut f64, lines: f64) -> i64 {
+    *remainder += lines;
+    let whole = remainder.trunc();
+    *remainder -= whole;
+    whole as i64
+}
+
+#[derive(Copy, Clone)]
+struct ScrollState {
+    offset: Vector2<i32>,
+    content_size: Vector2<i32>,
+    view_size: Vector2<i32>,
+    // The width of the scroll bars, scaled by the theme's scale factor
+    bar_width: i32,
+    // The axis of the scroll bar being dragged, along with the cursor's position along that axis
+    // and the offset when the drag started
+    drag: Option<(usize, i32, i32)>,
+}
+
+impl ScrollState {
+    fn max_offset(&self) -> Vector2<i32> {
+        vec2(
+            (self.content_size.x - self.view_size.x).max(0),
+            (self.content_size.y - self.view_size.y).max(0),
+        )
+    }
+
+    fn clamp_offset(&mut self) {
+        let max_offset = self.max_offset();
+        self.offset =
+            vec2(self.offset.x.clamp(0, max_offset.x), self.offset.y.clamp(0, max_offset.y));
+    }
+
+    /// Returns the start and length of the scroll bar's thumb along the given axis, relative to
+    /// the start of the scroll bar.
+    fn thumb(&self, axis: usize) -> (i32, i32) {
+        let track = self.view_size[axis];
+        let content = self.content_size[axis].max(track).max(1);
+        let len = (track * track / content).max(self.bar_width).min(track);
+        let max_offset = self.max_offset()[axis];
+        let start =
+            if max_offset == 0 { 0 } else { (track - len) * self.offset[axis] / max_offset };
+        (start, len)
+    }
+
+    /// Converts a distance along a scroll bar to a distance in the content.
+    fn track_to_offset(&self, axis: usize, distance: i32) -> i32 {
+        let (_, len) = self.thumb(axis);
+        let free_space = self.view_size[axis] - len;
+        if free_space <= 0 {
+            0
+        } else {
+            distance * self.max_offset()[axis] / free_space
+        }
+    }
+}
+
+/// Shows part of a child that can be larger than the view. It scrolls with the mouse wheel, or
+/// by dragging or clicking its scroll bars.
+///
+/// The scroll position is kept between frames, so keep one `ScrollView` around, and each frame
+/// put the widget returned by `with_child` in the GUI and pass the original to
+/// `GuiEventResult::update_component`.
+///
+/// On the axes it scrolls along, the view doesn't need to be as large as the child. Its minimum
+/// size on those axes is set by `min_view_size`, so it's typically given extra space with flex.
+pub struct ScrollView {
+    id: WidgetId,
+    horizontal: bool,
+    vertical: bool,
+    min_view_size: Vector2<i32>,
+    state: Rc<Cell<ScrollState>>,
+    content: Option<ScrollContent>,
+}
+
+impl ScrollView {
+    /// Creates a `ScrollView` that scrolls vertically.
+    pub fn new() -> Box<Self> {
+        let state = ScrollState {
+            offset: vec2(0, 0),
+            content_size: vec2(0, 0),
+            view_size: vec2(0, 0),
+            bar_width: SCROLL_BAR_WIDTH,
+            drag: None,
+        };
+        Box::new(ScrollView {
+            id: WidgetId::new(),
+            horizontal: false,
+            vertical: true,
+            min_view_size: vec2(0, 0),
+            state: Rc::new(Cell::new(state)),
+            content: None,
+        })
+    }
+
+    pub fn horizontal(mut self: Box<Self>, horizontal: bool) -> Box<Self> {
+        self.horizontal = horizontal;
+        self
+    }
+
+    pub fn vertical(mut self: Box<Self>, vertical: bool) -> Box<Self> {
+        self.vertical = vertical;
+        self
+    }
+
+    /// The minimum size of the visible area on the axes the view scrolls along.
+    pub fn min_view_size(mut self: Box<Self>, min_view_size: Vector2<i32>) -> Box<Self> {
+        self.min_view_size = min_view_size;
+        self
+    }
+
+    /// Returns a widget that shows `child` with this view's scroll position.
+    pub fn with_child(&self, child: Box<dyn Widget>) -> Box<Self> {
+        Box::new(ScrollView {
+            id: self.id,
+            horizontal: self.horizontal,
+            vertical: self.vertical,
+            min_view_size: self.min_view_size,
+            state: self.state.clone(),
+            content: Some(ScrollContent { id: WidgetId::new(), child, state: self.state.clone() }),
+        })
+    }
+
+    /// The distance the child is scrolled by, in pixels.
+    pub fn offset(&self) -> Vector2<i32> {
+        self.state.get().offset
+    }
+
+    /// Scrolls to the given offset. It's limited to the size of the child the next time the
+    /// view is laid out.
+    pub fn set_offset(&mut self, offset: Vector2<i32>) {
+        let mut state = self.state.get();
+        state.offset = offset;
+        self.state.set(state);
+    }
+
+    fn scroll_bar_size(&self, theme: &Theme) -> Vector2<i32> {
+        let width = theme.scale(SCROLL_BAR_WIDTH);
+        vec2(if self.vertical { width } else { 0 }, if self.horizontal { width } else { 0 })
+    }
+
+    fn axes(&self) -> impl Iterator<Item = usize> {
+        let axes = [(0, self.horizontal), (1, self.vertical)];
+        axes.into_iter().filter(|(_, enabled)| *enabled).map(|(axis, _)| axis)
+    }
+
+    /// Returns the scroll bar's rect for the given axis, relative to the view's start.
+    fn scroll_bar_rect(axis: usize, state: &ScrollState) -> Rect<i32> {
+        let (view_size, width) = (state.view_size, state.bar_width);
+        if axis == 0 {
+            Rect::new(point2(0, view_size.y), point2(view_size.x, view_size.y + width))
+        } else {
+            Rect::new(point2(view_size.x, 0), point2(view_size.x + width, view_size.y))
+        }
+    }
+}
+
+impl Component for ScrollView {
+    type Res = ();
+
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) {
+        let mut state = self.state.get();
+        for event in events {
+            match event {
+                Event::Scroll { mut delta, unit } => {
+                    // Mouse wheels only scroll vertically, so they scroll horizontal-only views
+                    // horizontally.
+                    if !self.vertical && delta.x == 0.0 {
+                        delta = vec2(delta.y, 0.0);
+                    }
+                    let delta = unit.to_pixels(delta, theme.font.advance_y() as f64);
+                    for axis in self.axes() {
+                        state.offset[axis] += delta[axis].round() as i32;
+                    }
+                }
+                Event::MouseDown(MouseButton::Left, pos) => {
+                    let axis = self
+                        .axes()
+                        .find(|&axis| Self::scroll_bar_rect(axis, &state).contains_point(pos));
+                    if let Some(axis) = axis {
+                        let (start, len) = state.thumb(axis);
+                        // Clicking outside of the thumb moves its center to the cursor.
+                        if pos[axis] < start || pos[axis] >= start + len {
+                            state.offset[axis] = state.track_to_offset(axis, pos[axis] - len / 2);
+                            state.clamp_offset();
+                        }
+                        state.drag = Some((axis, pos[axis], state.offset[axis]));
+                    }
+                }
+                Event::MouseMove { pos, .. } => {
+                    if let Some((axis, start_pos, start_offset)) = state.drag {
+                        state.offset[axis] =
+                            start_offset + state.track_to_offset(axis, pos[axis] - start_pos);
+                    }
+                }
+                Event::MouseUp(MouseButton::Left, _) | Event::FocusLost => state.drag = None,
+                _ => (),
+            }
+        }
+        state.clamp_offset();
+        self.state.set(state);
+    }
+}
+
+impl Widget for ScrollView {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn captures_pointer(&self) -> bool {
+        true
+    }
+
+    fn handles_scroll(&self) -> bool {
+        true
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+        let state = self.state.get();
+        let style = theme.style(WidgetKind::ScrollBar, WidgetState::Normal);
+        for axis in self.axes() {
+            let bar = Self::scroll_bar_rect(axis, &state);
+            let bar = Rect::new(rect.start + bar.start.to_vec(), rect.start + bar.end.to_vec());
+            draw_2d.fill_rect(bar, style.fill_color);
+
+            let (start, len) = state.thumb(axis);
+            let mut thumb = bar;
+            thumb.start[axis] += start;
+            thumb.end[axis] = thumb.start[axis] + len;
+            draw_2d.fill_rounded_rect(thumb, style.corner_radius, style.accent_color);
+        }
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let child_size = match &self.content {
+            Some(content) => min_sizes[&content.id],
+            None => vec2(0, 0),
+        };
+        let view_size = vec2(
+            if self.horizontal { self.min_view_size.x } else { child_size.x },
+            if self.vertical { self.min_view_size.y } else { child_size.y },
+        );
+        view_size + self.scroll_bar_size(theme)
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        match &self.content {
+            Some(content) => vec![content],
+            None => vec![],
+        }
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        widget_rects.insert(self.id(), rect);
+        let view_rect = Rect::new(rect.start, rect.end - self.scroll_bar_size(theme));
+        let view_size = view_rect.size();
+        let child_size = match &self.content {
+            Some(content) => min_sizes[&content.id],
+            None => vec2(0, 0),
+        };
+
+        let mut state = self.state.get();
+        state.view_size = view_size;
+        state.bar_width = theme.scale(SCROLL_BAR_WIDTH);
+        state.content_size = vec2(
+            if self.horizontal { child_size.x.max(view_size.x) } else { view_size.x },
+            if self.vertical { child_size.y.max(view_size.y) } else { view_size.y },
+        );
+        state.clamp_offset();
+        self.state.set(state);
+
+        if let Some(content) = &self.content {
+            content.compute_rects(view_rect, theme, min_sizes, widget_rects);
+        }
+    }
+}
+
+/// The visible area of a `ScrollView`, which positions the child according to the scroll offset.
+/// This is a separate widget so that the child is clipped to the area next to the scroll bars.
+struct ScrollContent {
+    id: WidgetId,
+    child: Box<dyn Widget>,
+    state: Rc<Cell<ScrollState>>,
+}
+
+impl Widget for ScrollContent {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        _rect: Rect<i32>,
+        _theme: &Theme,
+        _draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        _theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        min_sizes[&self.child.id()]
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        vec![&*self.child]
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        widget_rects.insert(self.id(), rect);
+        let state = self.state.get();
+        let start = rect.start - state.offset;
+        self.child.compute_rects(
+            Rect::new(start, start + state.content_size),
+            theme,
+            min_sizes,
+            widget_rects,
+        );
+    }
+}
+
+/// A vertical list of rows that only builds, lays out, and draws the rows that are visible, so it
+/// can hold a very large number of them. Every row has the same height, and is as wide as the
+/// list. The rows are created each frame by a callback, so they don't need to be kept around.
+///
+/// Like a `ScrollView`, keep one `ListView` around, and each frame put the widget returned by
+/// `with_items` in the GUI and pass the original to `GuiEventResult::update_component`. The rows
+/// are chosen using the scroll position and view size from the last update and layout.
+pub struct ListView {
+    row_height: i32,
+    view: Box<ScrollView>,
+}
+
+impl ListView {
+    pub fn new(row_height: i32) -> Box<Self> {
+        Box::new(ListView { row_height, view: ScrollView::new() })
+    }
+
+    /// The minimum size of the visible area.
+    pub fn min_view_size(mut self: Box<Self>, min_view_size: Vector2<i32>) -> Box<Self> {
+        self.view = self.view.min_view_size(min_view_size);
+        self
+    }
+
+    /// Returns a widget that shows `count` rows, calling `items` with the index of each row that
+    /// needs to be shown.
+    pub fn with_items(&self, count: usize, items: impl Fn(usize) -> Box<dyn Widget>) -> Box<Self> {
+        let state = self.view.state.get();
+        let view_height =
+            if state.view_size.y > 0 { state.view_size.y } else { self.view.min_view_size.y };
+        let row_height = self.row_height.max(1);
+        let first = (state.offset.y / row_height) as usize;
+        let last = ((state.offset.y + view_height) / row_height + 1) as usize;
+        let rows = (first.min(count)..last.min(count)).map(|i| (i, items(i))).collect();
+        let content = Box::new(ListContent { id: WidgetId::new(), row_height, count, rows });
+        Box::new(ListView { row_height, view: self.view.with_child(content) })
+    }
+
+    /// The distance the rows are scrolled by, in pixels.
+    pub fn offset(&self) -> i32 {
+        self.view.offset().y
+    }
+
+    pub fn set_offset(&mut self, offset: i32) {
+        self.view.set_offset(vec2(0, offset));
+    }
+
+    /// Scrolls by as little as possible to make the given row visible.
+    pub fn scroll_to(&mut self, index: usize) {
+        let state = self.view.state.get();
+        let start = index as i32 * self.row_height;
+        let end = start + self.row_height;
+        if start < state.offset.y {
+            self.set_offset(start);
+        } else if end > state.offset.y + state.view_size.y {
+            self.set_offset(end - state.view_size.y);
+        }
+    }
+}
+
+impl Component for ListView {
+    type Res = ();
+
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) {
+        self.view.update(theme, events)
+    }
+}
+
+impl Widget for ListView {
+    fn id(&self) -> WidgetId {
+        self.view.id()
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn handles_scroll(&self) -> bool {
+        true
+    }
+
+    fn draw(
+        &self,
+        context: &GlContext,
+        surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        is_active: bool,
+    ) {
+        self.view.draw(context, surface, rect, theme, draw_2d, cursor_pos, is_active);
+    }
+
+    fn min_size(
+        &self,
+        context: &GlContext,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        self.view.min_size(context, theme, min_sizes, window_size)
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        self.view.children()
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        self.view.compute_rects(rect, theme, min_sizes, widget_rects);
+    }
+}
+
+/// The rows of a `ListView`. It's as tall as all of the rows together, but only contains the
+/// visible ones.
+struct ListContent {
+    id: WidgetId,
+    row_height: i32,
+    count: usize,
+    rows: Vec<(usize, Box<dyn Widget>)>,
+}
+
+impl Widget for ListContent {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        _rect: Rect<i32>,
+        _theme: &Theme,
+        _draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        _theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let width = self.rows.iter().map(|(_, row)| min_sizes[&row.id()].x).max().unwrap_or(0);
+        vec2(width, self.row_height * self.count as i32)
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        self.rows.iter().map(|(_, row)| &**row).collect()
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        widget_rects.insert(self.id(), rect);
+        for (i, row) in &self.rows {
+            let start = point2(rect.start.x, rect.start.y + *i as i32 * self.row_height);
+            let end = point2(rect.end.x, start.y + self.row_height);
+            row.compute_rects(Rect::new(start, end), theme, min_sizes, widget_rects);
+        }
+    }
+}
+
+pub struct TabsResult {
+    /// The index of the selected tab.
+    pub selected: usize,
+    /// True if the user switched tabs since the last update, including by closing the selected
+    /// tab.
+    pub changed: bool,
+    /// The index the tab had if the user closed one. The tab has already been removed, so the
+    /// app should remove its page too.
+    pub closed: Option<usize>,
+}
+
+/// A row of tab headers above a page. Clicking a header selects its tab, and Ctrl+Tab and
+/// Ctrl+Shift+Tab cycle through the tabs when this is active. Tabs can optionally have close
+/// buttons.
+///
+/// Only the selected tab's page is shown, so only it needs to be built. Keep one `Tabs` around,
+/// and each frame put the widget returned by `with_page` in the GUI and pass the original to
+/// `GuiEventResult::update_component`.
+pub struct Tabs {
+    id: WidgetId,
+    titles: Vec<String>,
+    selected: usize,
+    closable: bool,
+    page: Option<TabPage>,
+    // The horizontal extent of each tab's header, relative to the widget's rect. This is
+    // recorded when the tabs are drawn, since it depends on the size of the titles.
+    headers: Rc<RefCell<Vec<(i32, i32)>>>,
+}
+
+impl Tabs {
+    pub fn new(titles: Vec<String>) -> Box<Self> {
+        Box::new(Tabs {
+            id: WidgetId::new(),
+            titles,
+            selected: 0,
+            closable: false,
+            page: None,
+            headers: Rc::new(RefCell::new(vec![])),
+        })
+    }
+
+    /// Adds a close button to each tab.
+    pub fn closable(mut self: Box<Self>, closable: bool) -> Box<Self> {
+        self.closable = closable;
+        self
+    }
+
+    /// Returns a widget that shows the tabs with `page` below them. `page` should be the
+    /// selected tab's page.
+    pub fn with_page(&self, page: Box<dyn Widget>) -> Box<Self> {
+        Box::new(Tabs {
+            id: self.id,
+            titles: self.titles.clone(),
+            selected: self.selected,
+            closable: self.closable,
+            page: Some(TabPage { id: WidgetId::new(), child: page }),
+            headers: self.headers.clone(),
+        })
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: usize) {
+        assert!(index < self.titles.len());
+        self.selected = index;
+    }
+
+    pub fn titles(&self) -> &[String] {
+        &self.titles
+    }
+
+    /// Adds a tab after the existing ones.
+    pub fn add_tab(&mut self, title: &str) {
+        self.titles.push(title.to_owned());
+    }
+
+    pub fn remove_tab(&mut self, index: usize) {
+        self.titles.remove(index);
+        if self.selected > index || self.selected == self.titles.len() {
+            self.selected = self.selected.saturating_sub(1);
+        }
+    }
+
+    fn header_height(&self, theme: &Theme) -> i32 {
+        let style = theme.style(WidgetKind::Tabs, WidgetState::Normal);
+        style.font.advance_y() + style.padding * 2
+    }
+
+    fn close_button_size(&self, theme: &Theme) -> i32 {
+        if self.closable {
+            theme.style(WidgetKind::Tabs, WidgetState::Normal).font.advance_y() / 2
+        } else {
+            0
+        }
+    }
+
+    fn header_widths(&self, context: &GlContext, theme: &Theme) -> Vec<i32> {
+        let style = theme.style(WidgetKind::Tabs, WidgetState::Normal);
+        let close_button_width =
+            if self.closable { self.close_button_size(theme) + style.padding } else { 0 };
+        self.titles
+            .iter()
+            .map(|title| {
+                style.font.string_width(context, title) as i32
+                    + style.padding * 2
+                    + close_button_width
+            })
+            .collect()
+    }
+
+    /// Returns the close button's rect for a header with the given extent, relative to the
+    /// widget's rect.
+    fn close_button_rect(&self, theme: &Theme, header: (i32, i32)) -> Rect<i32> {
+        let size = self.close_button_size(theme);
+        let padding = theme.style(WidgetKind::Tabs, WidgetState::Normal).padding;
+        let start = point2(header.1 - padding - size, (self.header_height(theme) - size) / 2);
+        Rect::new(start, start + vec2(size, size))
+    }
+}
+
+impl Component for Tabs {
+    type Res = TabsResult;
+
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) -> TabsResult {
+        let old_selected = self.selected;
+        let mut closed = None;
+        for event in events {
+            if self.titles.is_empty() {
+                break;
+            }
+            match event {
+                Event::MouseDown(MouseButton::Left, pos) if pos.y < self.header_height(theme) => {
+                    let headers = self.headers.borrow().clone();
+                    let clicked =
+                        headers.iter().position(|&(start, end)| pos.x >= start && pos.x < end);
+                    if let Some(i) = clicked.filter(|&i| i < self.titles.len()) {
+                        if self.closable
+                            && self.close_button_rect(theme, headers[i]).contains_point(pos)
+                        {
+                            self.remove_tab(i);
+                            closed = Some(i);
+                            // The remaining headers have moved.
+                            self.headers.borrow_mut().clear();
+                        } else {
+                            self.selected = i;
+                        }
+                    }
+                }
+                Event::KeyDown(key) if key.code == "Tab" && key.ctrl => {
+                    let len = self.titles.len();
+                    self.selected = if key.shift {
+                        (self.selected + len - 1) % len
+                    } else {
+                        (self.selected + 1) % len
+                    };
+                }
+                _ => (),
+            }
+        }
+        TabsResult {
+            selected: self.selected,
+            changed: self.selected != old_selected || closed == Some(old_selected),
+            closed,
+        }
+    }
+}
+
+impl Widget for Tabs {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn cursor_icon(&self, theme: &Theme, pos: Point2<i32>) -> Option<CursorIcon> {
+        if pos.y < self.header_height(theme) {
+            Some(CursorIcon::Hand)
+        } else {
+            None
+        }
+    }
+
+    fn draw(
+        &self,
+        context: &GlContext,
+        _surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+        let header_height = self.header_height(theme);
+        let mut headers = self.headers.borrow_mut();
+        headers.clear();
+        let mut x = 0;
+        for (i, (title, width)) in
+            self.titles.iter().zip(self.header_widths(context, theme)).enumerate()
+        {
+            let header = (x, x + width);
+            headers.push(header);
+            x += width;
+
+            let header_rect = Rect::new(
+                rect.start + vec2(header.0, 0),
+                rect.start + vec2(header.1, header_height),
+            );
+            let style = theme.style(WidgetKind::Tabs, widget_state(header_rect, cursor_pos, false));
+            let fill_color = if i == self.selected { style.accent_color } else { style.fill_color };
+            draw_2d.fill_rect(header_rect, fill_color);
+            draw_2d.outline_rect(header_rect, style.border_color, style.border_width);
+            draw_2d.draw_string(
+                context,
+                &style.font,
+                title,
+                header_rect.start + vec2(style.padding, style.padding),
+                style.text_color,
+            );
+
+            if self.closable {
+                let close = self.close_button_rect(theme, header);
+                let start: Point2<f32> = (rect.start + close.start.to_vec()).cast().unwrap();
+                let end: Point2<f32> = (rect.start + close.end.to_vec()).cast().unwrap();
+                draw_2d.draw_line(start, end, style.text_color, 1.5);
+                draw_2d.draw_line(
+                    point2(start.x, end.y),
+                    point2(end.x, start.y),
+                    style.text_color,
+                    1.5,
+                );
+            }
+        }
+
+        let style = theme.style(WidgetKind::Tabs, WidgetState::Normal);
+        let page_rect = Rect::new(rect.start + vec2(0, header_height), rect.end);
+        draw_2d.outline_rect(page_rect, style.border_color, style.border_width);
+    }
+
+    fn min_size(
+        &self,
+        context: &GlContext,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let headers_width: i32 = self.header_widths(context, theme).iter().sum();
+        let page_size = match &self.page {
+            Some(page) => min_sizes[&page.id],
+            None => vec2(0, 0),
+        };
+        vec2(headers_width.max(page_size.x), self.header_height(theme) + page_size.y)
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        match &self.page {
+            Some(page) => vec![page],
+            None => vec![],
+        }
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        widget_rects.insert(self.id(), rect);
+        if let Some(page) = &self.page {
+            let page_rect = Rect::new(rect.start + vec2(0, self.header_height(theme)), rect.end);
+            page.compute_rects(page_rect, theme, min_sizes, widget_rects);
+        }
+    }
+}
+
+/// The area below a `Tabs` widget's headers. This is a separate widget so that the page is
+/// clipped to it.
+struct TabPage {
+    id: WidgetId,
+    child: Box<dyn Widget>,
+}
+
+impl Widget for TabPage {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        _rect: Rect<i32>,
+        _theme: &Theme,
+        _draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        _theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        min_sizes[&self.child.id()]
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        vec![&*self.child]
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        widget_rects.insert(self.id(), rect);
+        self.child.compute_rects(rect, theme, min_sizes, widget_rects);
+    }
+}
+
+/// The function a `Canvas` draws with. It's given the canvas's rect, in pixels from the top left
+/// of the surface.
+pub type CanvasPainter = dyn Fn(&GlContext, &dyn Surface, Rect<i32>, &mut Draw2d);
+
+/// A widget whose content is drawn by the app, such as a chart, minimap, or game view. Drawing is
+/// clipped to the canvas's rect. Drawing directly to the surface rather than through `Draw2d`
+/// isn't clipped, and happens before any queued `Draw2d` drawing is rendered.
+///
+/// The canvas is a component whose result is the mouse and keyboard events it received, with
+/// mouse positions relative to its top left corner. Keep one `Canvas` around, and each frame put
+/// the widget returned by `with_painter` in the GUI and pass the original to
+/// `GuiEventResult::update_component`.
+pub struct Canvas {
+    id: WidgetId,
+    min_size: Vector2<i32>,
+    painter: Option<Box<CanvasPainter>>,
+}
+
+impl Canvas {
+    pub fn new(min_size: Vector2<i32>) -> Box<Self> {
+        Box::new(Canvas { id: WidgetId::new(), min_size, painter: None })
+    }
+
+    pub fn set_min_size(&mut self, min_size: Vector2<i32>) {
+        self.min_size = min_size;
+    }
+
+    /// Returns a widget that draws with `painter`.
+    pub fn with_painter(
+        &self,
+        painter: impl Fn(&GlContext, &dyn Surface, Rect<i32>, &mut Draw2d) + 'static,
+    ) -> Box<Self> {
+        Box::new(Canvas { id: self.id, min_size: self.min_size, painter: Some(Box::new(painter)) })
+    }
+}
+
+impl Component for Canvas {
+    type Res = Vec<Event>;
+
+    fn update(&mut self, _theme: &Theme, events: Vec<Event>) -> Vec<Event> {
+        events
+    }
+}
+
+impl Widget for Canvas {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn handles_scroll(&self) -> bool {
+        true
+    }
+
+    fn draw(
+        &self,
+        context: &GlContext,
+        surface: &dyn Surface,
+        rect: Rect<i32>,
+        _theme: &Theme,
+        draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+        if let Some(painter) = &self.painter {
+            // The GUI's clip includes a pixel past the end of the rect for outlines, which
+            // arbitrary content shouldn't draw into.
+            draw_2d.push_clip(rect);
+            painter(context, surface, rect, draw_2d);
+            draw_2d.pop_clip();
+        }
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        _theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        self.min_size
+    }
+}
+
+/// How close to the edge between two column headers the cursor must be to resize a column.
+const TABLE_RESIZE_HANDLE_WIDTH: i32 = 4;
+const TABLE_MIN_COLUMN_WIDTH: i32 = 16;
+
+/// A column in a `Table`.
+#[derive(Clone, Debug)]
+pub struct TableColumn {
+    pub title: String,
+    /// The column's width in pixels. Users can change it by dragging the edge of its header.
+    pub width: i32,
+    /// Whether clicking the column's header sorts the table by it.
+    pub sortable: bool,
+}
+
+impl TableColumn {
+    pub fn new(title: &str, width: i32) -> Self {
+        Self { title: title.to_owned(), width, sortable: true }
+    }
+
+    pub fn sortable(mut self, sortable: bool) -> Self {
+        self.sortable = sortable;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+pub struct TableResult {
+    /// The index of the selected row, if any.
+    pub selected: Option<usize>,
+    /// True if the user changed the selection since the last update.
+    pub selection_changed: bool,
+    /// The column the table is sorted by, if any.
+    pub sort: Option<(usize, SortOrder)>,
+    /// True if the user changed the sort column or order since the last update, in which case
+    /// the rows should be sorted again.
+    pub sort_changed: bool,
+    /// The row the user double-clicked since the last update, if any, such as to open it.
+    pub double_clicked: Option<usize>,
+}
+
+/// Returns the text of the cell at the given row and column of a `Table`.
+pub type TableRowProvider = dyn Fn(usize, usize) -> String;
+
+#[derive(Copy, Clone)]
+struct TableState {
+    // The first visible row
+    scroll: usize,
+    // Scrolling that didn't add up to a whole row yet
+    scroll_remainder: f64,
+    // The number of rows that fit in the table's rect
+    visible_rows: usize,
+    num_rows: usize,
+}
+
+/// Shows rows of text in columns, with headers that can be clicked to sort the table and dragged
+/// to resize the columns. Rows can be selected by clicking them or with the arrow keys, and the
+/// rows scroll with the mouse wheel.
+///
+/// The cells' text comes from a callback, and only the visible rows are drawn, so large tables
+/// don't need a widget per cell. The table doesn't sort the rows itself; the callback should
+/// return them in the order given by `TableResult::sort`.
+///
+/// Keep one `Table` around, and each frame put the widget returned by `with_rows` in the GUI and
+/// pass the original to `GuiEventResult::update_component`.
+pub struct Table {
+    id: WidgetId,
+    columns: Vec<TableColumn>,
+    selected: Option<usize>,
+    sort: Option<(usize, SortOrder)>,
+    min_visible_rows: usize,
+    // The column being resized, along with the cursor's x position and the column's width when
+    // the drag started
+    resize_drag: Option<(usize, i32, i32)>,
+    state: Rc<Cell<TableState>>,
+    rows: Option<Box<TableRowProvider>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<TableColumn>) -> Box<Self> {
+        Box::new(Table {
+            id: WidgetId::new(),
+            columns,
+            selected: None,
+            sort: None,
+            min_visible_rows: 5,
+            resize_drag: None,
+            state: Rc::new(Cell::new(TableState {
+                scroll: 0,
+                scroll_remainder: 0.0,
+                visible_rows: 0,
+                num_rows: 0,
+            })),
+            rows: None,
+        })
+    }
+
+    /// The number of rows the table's minimum height has room for. Defaults to 5.
+    pub fn min_visible_rows(mut self: Box<Self>, min_visible_rows: usize) -> Box<Self> {
+        self.min_visible_rows = min_visible_rows;
+        self
+    }
+
+    /// Returns a widget that shows `num_rows` rows, getting each cell's text from `rows`.
+    pub fn with_rows(
+        &self,
+        num_rows: usize,
+        rows: impl Fn(usize, usize) -> String + 'static,
+    ) -> Box<Self> {
+        let mut state = self.state.get();
+        state.num_rows = num_rows;
+        self.state.set(state);
+        Box::new(Table {
+            id: self.id,
+            columns: self.columns.clone(),
+            selected: self.selected,
+            sort: self.sort,
+            min_visible_rows: self.min_visible_rows,
+            resize_drag: self.resize_drag,
+            state: self.state.clone(),
+            rows: Some(Box::new(rows)),
+        })
+    }
+
+    pub fn columns(&self) -> &[TableColumn] {
+        &self.columns
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Selects a row, and scrolls so that it's visible.
+    pub fn select(&mut self, row: Option<usize>) {
+        self.selected = row;
+        self.scroll_to_selection();
+    }
+
+    pub fn sort(&self) -> Option<(usize, SortOrder)> {
+        self.sort
+    }
+
+    pub fn set_sort(&mut self, sort: Option<(usize, SortOrder)>) {
+        self.sort = sort;
+    }
+
+    fn row_height(theme: &Theme) -> i32 {
+        let style = theme.style(WidgetKind::Table, WidgetState::Normal);
+        style.font.advance_y() + style.padding * 2
+    }
+
+    /// Returns the horizontal extent of each column, relative to the table's rect.
+    fn column_extents(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.columns.iter().scan(0, |x, column| {
+            let start = *x;
+            *x += column.width;
+            Some((start, *x))
+        })
+    }
+
+    fn scroll_to_selection(&mut self) {
+        let mut state = self.state.get();
+        if let Some(selected) = self.selected {
+            if selected < state.scroll {
+                state.scroll = selected;
+            } else if state.visible_rows > 0 && selected >= state.scroll + state.visible_rows {
+                state.scroll = selected + 1 - state.visible_rows;
+            }
+        }
+        self.state.set(state);
+    }
+}
+
+impl TableState {
+    fn max_scroll(&self) -> usize {
+        self.num_rows.saturating_sub(self.visible_rows)
+    }
+}
+
+impl Component for Table {
+    type Res = TableResult;
+
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) -> TableResult {
+        let old_selected = self.selected;
+        let old_sort = self.sort;
+        let mut double_clicked = None;
+        let row_height = Self::row_height(theme);
+        for event in events {
+            let mut state = self.state.get();
+            match event {
+                Event::MouseDown(MouseButton::Left, pos) if pos.y < row_height => {
+                    let handle_width = theme.scale(TABLE_RESIZE_HANDLE_WIDTH);
+                    let column = self.column_extents().enumerate().find(|(_, (start, end))| {
+                        pos.x >= start - handle_width / 2 && pos.x < end + handle_width / 2
+                    });
+                    let on_edge = |end: i32| (pos.x - end).abs() <= handle_width / 2;
+                    // The resize handle takes priority over the next column's header.
+                    let resized = self.column_extents().position(|(_, end)| on_edge(end));
+                    if let Some(i) = resized {
+                        self.resize_drag = Some((i, pos.x, self.columns[i].width));
+                    } else if let Some((i, _)) = column {
+                        if self.columns[i].sortable {
+                            self.sort = match self.sort {
+                                Some((column, SortOrder::Ascending)) if column == i => {
+                                    Some((i, SortOrder::Descending))
+                                }
+                                _ => Some((i, SortOrder::Ascending)),
+                            };
+                        }
+                    }
+                }
+                Event::MouseDown(MouseButton::Left, pos) => {
+                    let row = state.scroll + ((pos.y - row_height) / row_height) as usize;
+                    if row < state.num_rows {
+                        self.selected = Some(row);
+                    }
+                }
+                Event::DoubleClick(MouseButton::Left, pos) if pos.y >= row_height => {
+                    let row = state.scroll + ((pos.y - row_height) / row_height) as usize;
+                    if row < state.num_rows {
+                        double_clicked = Some(row);
+                    }
+                }
+                Event::MouseMove { pos, .. } => {
+                    if let Some((i, start_x, start_width)) = self.resize_drag {
+                        self.columns[i].width = (start_width + pos.x - start_x)
+                            .max(theme.scale(TABLE_MIN_COLUMN_WIDTH));
+                    }
+                }
+                Event::MouseUp(MouseButton::Left, _) | Event::FocusLost => self.resize_drag = None,
+                Event::Scroll { delta, unit } => {
+                    let lines = unit.to_lines(delta, row_height as f64).y;
+                    let rows = whole_lines(&mut state.scroll_remainder, lines);
+                    state.scroll =
+                        (state.scroll as i64 + rows).clamp(0, state.max_scroll() as i64) as usize;
+                    self.state.set(state);
+                }
+                Event::KeyDown(key) if state.num_rows > 0 => {
+                    let last = state.num_rows - 1;
+                    let page = state.visible_rows.max(1);
+                    let selected = match key.code.as_ref() {
+                        "ArrowUp" => Some(self.selected.map_or(last, |x| x.saturating_sub(1))),
+                        "ArrowDown" => Some(self.selected.map_or(0, |x| (x + 1).min(last))),
+                        "PageUp" => Some(self.selected.map_or(0, |x| x.saturating_sub(page))),
+                        "PageDown" => Some(self.selected.map_or(0, |x| (x + page).min(last))),
+                        "Home" => Some(0),
+                        "End" => Some(last),
+                        _ => None,
+                    };
+                    if selected.is_some() {
+                        self.select(selected);
+                    }
+                }
+                _ => (),
+            }
+        }
+        if matches!(self.selected, Some(selected) if selected >= self.state.get().num_rows) {
+            self.selected = None;
+        }
+        TableResult {
+            selected: self.selected,
+            selection_changed: self.selected != old_selected,
+            sort: self.sort,
+            sort_changed: self.sort != old_sort,
+            double_clicked,
+        }
+    }
+}
+
+impl Widget for Table {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn captures_pointer(&self) -> bool {
+        true
+    }
+
+    fn cursor_icon(&self, theme: &Theme, pos: Point2<i32>) -> Option<CursorIcon> {
+        let handle_width = theme.scale(TABLE_RESIZE_HANDLE_WIDTH);
+        let on_edge = self.column_extents().any(|(_, end)| (pos.x - end).abs() <= handle_width / 2);
+        if self.resize_drag.is_some() || (pos.y < Self::row_height(theme) && on_edge) {
+            Some(CursorIcon::ResizeHorizontal)
+        } else {
+            None
+        }
+    }
+
+    fn handles_scroll(&self) -> bool {
+        true
+    }
+
+    fn draw(
+        &self,
+        context: &GlContext,
+        _surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+        let row_height = Self::row_height(theme);
+        let state = self.state.get();
+        let style = theme.style(WidgetKind::Table, WidgetState::Normal);
+        let text_offset = vec2(style.padding, style.padding);
+
+        // Headers
+        for (column, (start, end)) in self.columns.iter().zip(self.column_extents()) {
+            let header = Rect::new(rect.start + vec2(start, 0), rect.start + vec2(end, row_height));
+            let header_style =
+                theme.style(WidgetKind::Table, widget_state(header, cursor_pos, false));
+            draw_2d.fill_rect(header, header_style.fill_color);
+            draw_2d.outline_rect(header, header_style.border_color, header_style.border_width);
+            draw_2d.push_clip(header);
+            draw_2d.draw_string(
+                context,
+                &style.font,
+                &column.title,
+                header.start + text_offset,
+                header_style.text_color,
+            );
+            draw_2d.pop_clip();
+        }
+        if let Some((i, order)) = self.sort {
+            if let Some((_, end)) = self.column_extents().nth(i) {
+                // A small triangle at the right of the header, pointing up for ascending order
+                let size = (row_height / 3) as f32;
+                let center: Point2<f32> =
+                    (rect.start + vec2(end - row_height / 2, row_height / 2)).cast().unwrap();
+                let dir = if order == SortOrder::Ascending { -1.0 } else { 1.0 };
+                draw_2d.fill_poly(
+                    &[
+                        center + vec2(-size * 0.5, -dir * size * 0.5),
+                        center + vec2(size * 0.5, -dir * size * 0.5),
+                        center + vec2(0.0, dir * size * 0.5),
+                    ],
+                    style.text_color,
+                );
+            }
+        }
+
+        // Rows
+        let rows_rect = Rect::new(rect.start + vec2(0, row_height), rect.end);
+        draw_2d.outline_rect(rows_rect, style.border_color, style.border_width);
+        let rows = match &self.rows {
+            Some(rows) => rows,
+            None => return,
+        };
+        // One more row than fits is drawn, since the last one can be partly visible.
+        let visible = state.scroll..(state.scroll + state.visible_rows + 1).min(state.num_rows);
+        let row_y = |row: usize| rows_rect.start.y + (row - state.scroll) as i32 * row_height;
+        draw_2d.push_clip(rows_rect);
+        if let Some(selected) = self.selected.filter(|selected| visible.contains(selected)) {
+            let y = row_y(selected);
+            draw_2d.fill_rect(
+                Rect::new(point2(rows_rect.start.x, y), point2(rows_rect.end.x, y + row_height)),
+                style.accent_color,
+            );
+        }
+        // Cells are drawn like labels, in the table's font.
+        let cell_color = theme.style(WidgetKind::Label, WidgetState::Normal).text_color;
+        // Each column is clipped separately, so long text doesn't overlap the next column.
+        for (column, (start, end)) in self.column_extents().enumerate() {
+            let column_rect = Rect::new(
+                point2(rect.start.x + start, rows_rect.start.y),
+                point2(rect.start.x + end, rows_rect.end.y),
+            );
+            draw_2d.push_clip(column_rect);
+            for row in visible.clone() {
+                let pos = point2(column_rect.start.x, row_y(row)) + text_offset;
+                draw_2d.draw_string(context, &style.font, &rows(row, column), pos, cell_color);
+            }
+            draw_2d.pop_clip();
+        }
+
+        // A scroll indicator, if not all rows fit
+        if state.num_rows > state.visible_rows && state.visible_rows > 0 {
+            let height = rows_rect.size().y;
+            let thumb_height = (height * state.visible_rows as i32 / state.num_rows as i32).max(4);
+            let thumb_y = (height - thumb_height) * state.scroll as i32 / state.max_scroll() as i32;
+            let start = point2(rows_rect.end.x - 4, rows_rect.start.y + thumb_y);
+            draw_2d.fill_rect(
+                Rect::new(start, start + vec2(4, thumb_height)),
+                theme.style(WidgetKind::ScrollBar, WidgetState::Normal).accent_color,
+            );
+        }
+        draw_2d.pop_clip();
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let width = self.columns.iter().map(|column| column.width).sum();
+        vec2(width, Self::row_height(theme) * (self.min_visible_rows as i32 + 1))
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        widget_rects.insert(self.id(), rect);
+        let row_height = Self::row_height(theme);
+        let mut state = self.state.get();
+        state.visible_rows = ((rect.size().y - row_height) / row_height).max(0) as usize;
+        state.scroll = state.scroll.min(state.max_scroll());
+        self.state.set(state);
+    }
+}
+
+/// A widget that's filled with a background color.
+pub struct Fill {
+    id: WidgetId,
+    child: Box<dyn Widget>,
+    fill_color: Color4,
+}
+
+impl Fill {
+    pub fn new(fill_color: Color4, child: Box<dyn Widget>) -> Box<Self> {
+        Box::new(Fill { id: WidgetId::new(), child, fill_color })
+    }
+}
+
+impl Widget for Fill {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn draw(
+        &self,
+        _context: &GlContext,
+        _surface: &dyn Surface,
+        rect: Rect<i32>,
+        _theme: &Theme,
+        draw_2d: &mut Draw2d,
+        _cursor_pos: Option<Point2<i32>>,
+        _is_active: bool,
+    ) {
+        draw_2d.fill_rect(Rect::new(rect.start, rect.end), self.fill_color);
+    }
+
+    fn min_size(
+        &self,
+        _context: &GlContext,
+        _theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        min_sizes[&self.child.id()]
+    }
+
+    fn children(&self) -> Vec<&dyn Widget> {
+        vec![&*self.child]
+    }
+
+    fn compute_rects(
+        &self,
+        rect: Rect<i32>,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+    ) {
+        widget_rects.insert(self.id(), Rect::new(rect.start, rect.end));
+        self.child.compute_rects(Rect::new(rect.start, rect.end), theme, min_sizes, widget_rects);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum TextEntryEvent {
+    AddChar(char),
+    Backspace,
+    CaretLeft,
+    CaretRight,
+    EnterPressed,
+}
+
+pub struct TextEntryResult {
+    pub text: Option<String>,
+}
+
+impl TextEntryResult {
+    pub fn text(&self) -> Option<&str> {
+        // See https://stackoverflow.com/questions/31233938/converting-from-optionstring-to-optionstr
+        self.text.as_ref().map(|x| x.as_ref())
+    }
+}
+
+/// The undo and redo history of a text widget. Each step stores the text and caret position from
+/// before an edit; consecutive typed chars are merged into a single step.
+#[derive(Clone, Default)]
+struct TextHistory {
+    undo: Vec<(String, usize)>,
+    redo: Vec<(String, usize)>,
+    // The caret position after the last edit, if it was typing, so that typing more from there
+    // can be merged with it
+    typing_caret: Option<usize>,
+}
+
+impl TextHistory {
+    const MAX_STEPS: usize = 100;
+
+    /// Records an edit, given the text and caret position from before it and the caret position
+    /// after it.
+    fn record(&mut self, text: String, caret: usize, new_caret: usize, typing: bool) {
+        if !(typing && self.typing_caret == Some(caret)) {
+            if self.undo.len() == Self::MAX_STEPS {
+                self.undo.remove(0);
+            }
+            self.undo.push((text, caret));
+        }
+        self.redo.clear();
+        self.typing_caret = if typing { Some(new_caret) } else { None };
+    }
+
+    /// Undoes the last edit, if there is one, replacing `text` and `caret`. Returns false if
+    /// there's nothing to undo.
+    fn undo(&mut self, text: &mut String, caret: &mut usize) -> bool {
+        self.typing_caret = None;
+        Self::step(&mut self.undo, &mut self.redo, text, caret)
+    }
+
+    /// Redoes the last undone edit, if there is one.
+    fn redo(&mut self, text: &mut String, caret: &mut usize) -> bool {
+        self.typing_caret = None;
+        Self::step(&mut self.redo, &mut self.undo, text, caret)
+    }
+
+    fn step(
+        from: &mut Vec<(String, usize)>,
+        to: &mut Vec<(String, usize)>,
+        text: &mut String,
+        caret: &mut usize,
+    ) -> bool {
+        match from.pop() {
+            Some((new_text, new_caret)) => {
+                to.push((mem::replace(text, new_text), mem::replace(caret, new_caret)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Returns the undo (Ctrl+Z) or redo (Ctrl+Shift+Z or Ctrl+Y) shortcut that `event` is, if any.
+/// The result is true for redo.
+fn undo_shortcut(event: &Event) -> Option<bool> {
+    match event {
+        Event::KeyDown(key) if key.ctrl && key.code == "KeyZ" => Some(key.shift),
+        Event::KeyDown(key) if key.ctrl && key.code == "KeyY" => Some(true),
+        _ => None,
+    }
+}
+
+/// Decides whether a char can be entered in a `TextEntry`.
+pub type TextFilter = dyn Fn(char) -> bool;
+
+/// Decides whether the text of a `TextEntry` is valid.
+pub type TextValidator = dyn Fn(&str) -> bool;
+
+const CARET_BLINK_RATE: f64 = 1.0;
+/// The char drawn in place of each char of a masked `TextEntry`.
+const MASK_CHAR: char = '\u{2022}';
+
+#[derive(Clone)]
+pub struct TextEntry {
+    id: WidgetId,
+    pub text: String,
+    placeholder_text: String,
+    text_color: Color4,
+    caret_pos: usize,
+    // The other end of the selection
+    selection_anchor: Option<usize>,
+    dragging: bool,
+    // TODO: support specifying the max length in pixels
+    max_len: usize,
+    stopwatch: Stopwatch,
+    use_placeholder_text_if_empty: bool,
+    continuous_updates: bool,
+    masked: bool,
+    reveal_toggle: bool,
+    revealed: bool,
+    filter: Option<Rc<TextFilter>>,
+    validator: Option<Rc<TextValidator>>,
+    history: TextHistory,
+    // The text being composed by an input method editor, if any
+    composition: Option<String>,
+    // The x position of each char boundary in the text when it was last drawn, for clicking
+    char_offsets: Rc<RefCell<Vec<f32>>>,
+    // The widget's size when it was last drawn, for clicking the reveal toggle
+    size: Rc<Cell<Vector2<i32>>>,
+}
+
+impl TextEntry {
+    /// Creates a new `TextEntry`.
+    ///
+    /// If `continuous_updates` is enabled, the widget sends an update each time the text is
+    /// changed, and isn't cleared when enter is pressed.
+    pub fn new(
+        start_text: &str,
+        placeholder_text: &str,
+        use_placeholder_text_if_empty: bool,
+        max_len: usize,
+        continuous_updates: bool,
+    ) -> Box<Self> {
+        assert!(placeholder_text.len() <= max_len);
+        Box::new(TextEntry {
+            id: WidgetId::new(),
+            text: start_text.to_string(),
+            placeholder_text: placeholder_text.to_string(),
+            text_color: Color4::BLACK,
+            caret_pos: 0,
+            selection_anchor: None,
+            dragging: false,
+            max_len,
+            stopwatch: Stopwatch::new(),
+            use_placeholder_text_if_empty,
+            continuous_updates,
+            masked: false,
+            reveal_toggle: false,
+            revealed: false,
+            filter: None,
+            validator: None,
+            history: TextHistory::default(),
+            composition: None,
+            char_offsets: Rc::new(RefCell::new(vec![])),
+            size: Rc::new(Cell::new(vec2(0, 0))),
+        })
+    }
+
+    /// Draws a bullet for each char instead of the text, for passwords. While the text is
+    /// hidden it can't be copied, and word navigation treats it as a single word.
+    pub fn masked(mut self: Box<Self>, masked: bool) -> Box<Self> {
+        self.masked = masked;
+        self
+    }
+
+    /// Shows a button at the end of a masked `TextEntry` that toggles whether the text is
+    /// revealed.
+    pub fn reveal_toggle(mut self: Box<Self>, reveal_toggle: bool) -> Box<Self> {
+        self.reveal_toggle = reveal_toggle;
+        self
+    }
+
+    /// Only allows chars for which `filter` returns true to be typed or pasted.
+    pub fn with_filter(mut self: Box<Self>, filter: impl Fn(char) -> bool + 'static) -> Box<Self> {
+        self.filter = Some(Rc::new(filter));
+        self
+    }
+
+    /// Marks the text as invalid, which draws the border with `Theme::invalid_color`, when
+    /// `validator` returns false for it. This doesn't prevent any text from being entered.
+    pub fn with_validator(
+        mut self: Box<Self>,
+        validator: impl Fn(&str) -> bool + 'static,
+    ) -> Box<Self> {
+        self.validator = Some(Rc::new(validator));
+        self
+    }
+
+    /// False if there's a validator and it rejects the current text.
+    pub fn is_valid(&self) -> bool {
+        match &self.validator {
+            Some(validator) => validator(&self.text),
+            None => true,
+        }
+    }
+
+    /// Replaces the text, moving the caret to the end and clearing the selection. The text
+    /// isn't filtered or limited to the maximum length.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_owned();
+        self.move_caret(self.text.len(), false);
+    }
+
+    /// True if the text is masked and currently hidden.
+    pub fn is_hidden(&self) -> bool {
+        self.masked && !self.revealed
+    }
+
+    /// Shows or hides the text of a masked `TextEntry`.
+    pub fn set_revealed(&mut self, revealed: bool) {
+        self.revealed = revealed;
+    }
+
+    pub fn text_color(mut self: Box<Self>, color: Color4) -> Box<Self> {
+        self.text_color = color;
+        self
+    }
+
+    pub fn cur_text(&self) -> &str {
+        if self.text.is_empty() && self.use_placeholder_text_if_empty {
+            &self.placeholder_text
+        } else {
+            &self.text
+        }
+    }
+
+    /// Returns the current contents of the TextEntry, and clears the contents unless
+    /// `continuous_updates` is enabled.
+    fn take_cur_text(&mut self) -> String {
+        if self.text.is_empty() && self.use_placeholder_text_if_empty {
+            self.placeholder_text.clone()
+        } else if self.continuous_updates {
+            self.text.clone()
+        } else {
+            self.history.clear();
+            mem::take(&mut self.text)
+        }
+    }
+
+    /// Undoes the last edit. Returns false if there's nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let res = self.history.undo(&mut self.text, &mut self.caret_pos);
+        self.selection_anchor = None;
+        res
+    }
+
+    /// Redoes the last undone edit. Returns false if there's nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let res = self.history.redo(&mut self.text, &mut self.caret_pos);
+        self.selection_anchor = None;
+        res
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// The text as it's drawn: the placeholder text if the text is empty, bullets if the text is
+    /// hidden, and otherwise the text with the text being composed inserted at the caret.
+    fn displayed_text(&self) -> Cow<'_, str> {
+        if let Some(composition) = self.shown_composition() {
+            let (before, after) = self.text.split_at(self.caret_pos);
+            Cow::Owned(format!("{}{}{}", before, composition, after))
+        } else if self.text.is_empty() {
+            Cow::Borrowed(&self.placeholder_text)
+        } else if self.is_hidden() {
+            Cow::Owned(MASK_CHAR.to_string().repeat(self.text.chars().count()))
+        } else {
+            Cow::Borrowed(&self.text)
+        }
+    }
+
+    /// The text being composed, unless it's empty or the text is hidden.
+    fn shown_composition(&self) -> Option<&str> {
+        self.composition
+            .as_deref()
+            .filter(|composition| !composition.is_empty() && !self.is_hidden())
+    }
+
+    /// Returns the index of the char in the displayed text that's at a position in the text.
+    /// The caret is after the text being composed.
+    fn displayed_char_index(&self, pos: usize) -> usize {
+        let index = self.text[..pos].chars().count();
+        match self.shown_composition() {
+            Some(composition) if pos >= self.caret_pos => index + composition.chars().count(),
+            _ => index,
+        }
+    }
+
+    fn has_reveal_toggle(&self) -> bool {
+        self.masked && self.reveal_toggle
+    }
+
+    /// The selected part of the text, if any.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.selection_anchor
+            .filter(|&anchor| anchor != self.caret_pos)
+            .map(|anchor| anchor.min(self.caret_pos)..anchor.max(self.caret_pos))
+    }
+
+    /// Moves the caret, extending the selection from the old position if `extend` is true.
+    fn move_caret(&mut self, pos: usize, extend: bool) {
+        if extend {
+            self.selection_anchor.get_or_insert(self.caret_pos);
+        } else {
+            self.selection_anchor = None;
+        }
+        self.caret_pos = pos.min(self.text.len());
+        self.stopwatch = Stopwatch::new();
+    }
+
+    fn delete(&mut self, range: Range<usize>) {
+        self.text.replace_range(range.clone(), "");
+        self.move_caret(range.start, false);
+    }
+
+    /// Replaces the selection, if any, with `text`, removing chars rejected by the filter and
+    /// truncating it to fit in `max_len`.
+    fn insert(&mut self, text: &str) {
+        let text = match &self.filter {
+            Some(filter) => text.chars().filter(|&c| filter(c)).collect(),
+            None => text.to_owned(),
+        };
+        if text.is_empty() {
+            return;
+        }
+        if let Some(selection) = self.selection() {
+            self.delete(selection);
+        }
+        let mut len = text.len().min(self.max_len.saturating_sub(self.text.len()));
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.text.insert_str(self.caret_pos, &text[..len]);
+        self.move_caret(self.caret_pos + len, false);
+    }
+
+    /// Returns the start of the word before the caret. Hidden text is treated as one word, so
+    /// that it doesn't reveal where the spaces are.
+    fn prev_word(&self) -> usize {
+        if self.is_hidden() {
+            0
+        } else {
+            prev_word_boundary(&self.text, self.caret_pos)
+        }
+    }
+
+    /// Returns the end of the word after the caret.
+    fn next_word(&self) -> usize {
+        if self.is_hidden() {
+            self.text.len()
+        } else {
+            next_word_boundary(&self.text, self.caret_pos)
+        }
+    }
+
+    /// Returns the char boundary closest to an x position relative to the widget.
+    fn position_at_x(&self, theme: &Theme, x: i32) -> usize {
+        let offsets = self.char_offsets.borrow();
+        let x = (x - theme.style(WidgetKind::TextEntry, WidgetState::Normal).padding) as f32;
+        let closest = (0..offsets.len())
+            .min_by(|&a, &b| (offsets[a] - x).abs().total_cmp(&(offsets[b] - x).abs()));
+        match closest {
+            Some(i) => {
+                self.text.char_indices().map(|(pos, _)| pos).nth(i).unwrap_or(self.text.len())
+            }
+            None => self.caret_pos,
+        }
+    }
+}
+
+fn prev_char_boundary(text: &str, pos: usize) -> usize {
+    text[..pos].char_indices().next_back().map_or(0, |(i, _)| i)
+}
+
+fn next_char_boundary(text: &str, pos: usize) -> usize {
+    pos + text[pos..].chars().next().map_or(0, char::len_utf8)
+}
+
+/// Returns the start of the word before `pos`.
+fn prev_word_boundary(text: &str, pos: usize) -> usize {
+    let before = text[..pos].trim_end();
+    before.rfind(char::is_whitespace).map_or(0, |i| next_char_boundary(before, i))
+}
+
+/// Returns the end of the word after `pos`.
+fn next_word_boundary(text: &str, pos: usize) -> usize {
+    let after = &text[pos..];
+    let word_start = after.len() - after.trim_start().len();
+    let word_len =
+        after[word_start..].find(char::is_whitespace).unwrap_or(after.len() - word_start);
+    pos + word_start + word_len
+}
+
+impl Component for TextEntry {
+    type Res = TextEntryResult;
+
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) -> TextEntryResult {
+        let mut res = None;
+        for event in events {
+            match undo_shortcut(&event) {
+                Some(false) => {
+                    self.undo();
+                    continue;
+                }
+                Some(true) => {
+                    self.redo();
+                    continue;
+                }
+                None => (),
+            }
+            let typing = matches!(event, Event::CharEntered(_));
+            let mut before = match event {
+                Event::KeyDown(_)
+                | Event::CharEntered(_)
+                | Event::Paste(_)
+                | Event::CompositionEnd(_) => Some((self.text.clone(), self.caret_pos)),
+                _ => None,
+            };
+            match event {
+                // The input method editor handles key presses while it's composing.
+                Event::KeyDown(_) if self.composition.is_some() => (),
+                Event::KeyDown(key) => {
+                    let extend = key.shift;
+                    let selection = self.selection();
+                    match key.code.as_ref() {
+                        "Backspace" => {
+                            let range = selection.unwrap_or_else(|| {
+                                prev_char_boundary(&self.text, self.caret_pos)..self.caret_pos
+                            });
+                            self.delete(range);
+                        }
+                        "Delete" => {
+                            let range = selection.unwrap_or_else(|| {
+                                self.caret_pos..next_char_boundary(&self.text, self.caret_pos)
+                            });
+                            self.delete(range);
+                        }
+                        "ArrowLeft" => match selection {
+                            Some(selection) if !extend && !key.ctrl => {
+                                self.move_caret(selection.start, false)
+                            }
+                            _ => {
+                                let pos = if key.ctrl {
+                                    self.prev_word()
+                                } else {
+                                    prev_char_boundary(&self.text, self.caret_pos)
+                                };
+                                self.move_caret(pos, extend);
+                            }
+                        },
+                        "ArrowRight" => match selection {
+                            Some(selection) if !extend && !key.ctrl => {
+                                self.move_caret(selection.end, false)
+                            }
+                            _ => {
+                                let pos = if key.ctrl {
+                                    self.next_word()
+                                } else {
+                                    next_char_boundary(&self.text, self.caret_pos)
+                                };
+                                self.move_caret(pos, extend);
+                            }
+                        },
+                        "Home" => self.move_caret(0, extend),
+                        "End" => self.move_caret(self.text.len(), extend),
+                        "KeyA" if key.ctrl => {
+                            self.move_caret(0, false);
+                            self.move_caret(self.text.len(), true);
+                        }
+                        "KeyC" | "KeyX" if key.ctrl && !self.is_hidden() => {
+                            if let Some(selection) = selection {
+                                Clipboard.set_text(&self.text[selection.clone()]);
+                                if key.code == "KeyX" {
+                                    self.delete(selection);
+                                }
+                            }
+                        }
+                        "KeyV" if key.ctrl => Clipboard.request_paste(),
+                        "Enter" => {
+                            res = Some(self.take_cur_text());
+                            self.move_caret(0, false);
+                            // Clearing the text after it's submitted isn't an edit.
+                            before = None;
+                        }
+                        _ => (),
+                    }
+                }
+                Event::CharEntered(c) => self.insert(c.encode_utf8(&mut [0; 4])),
+                // A single line can't contain newlines.
+                Event::Paste(text) => self.insert(&text.replace(['\r', '\n'], " ")),
+                Event::CompositionStart => self.composition = Some(String::new()),
+                Event::CompositionUpdate(text) => self.composition = Some(text),
+                Event::CompositionEnd(text) => {
+                    self.composition = None;
+                    self.insert(&text);
+                }
+                Event::MouseDown(MouseButton::Left, pos)
+                    if self.has_reveal_toggle()
+                        && pos.x >= self.size.get().x - self.size.get().y =>
+                {
+                    self.revealed = !self.revealed;
+                }
+                Event::MouseDown(MouseButton::Left, pos) => {
+                    self.move_caret(self.position_at_x(theme, pos.x), false);
+                    self.selection_anchor = Some(self.caret_pos);
+                    self.dragging = true;
+                }
+                Event::MouseMove { pos, .. } if self.dragging => {
+                    self.move_caret(self.position_at_x(theme, pos.x), true);
+                }
+                Event::MouseUp(MouseButton::Left, _) | Event::FocusLost => self.dragging = false,
+                _ => (),
+            }
+            if let Some((text, caret)) = before {
+                if text != self.text {
+                    self.history.record(text, caret, self.caret_pos, typing);
+                }
+            }
+        }
+        if self.continuous_updates {
+            res = Some(self.cur_text().to_owned());
+        }
+        TextEntryResult { text: res }
+    }
+}
+
+impl Widget for TextEntry {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn captures_pointer(&self) -> bool {
+        true
+    }
+
+    fn cursor_icon(&self, _theme: &Theme, _pos: Point2<i32>) -> Option<CursorIcon> {
+        Some(CursorIcon::Text)
+    }
+
+    fn draw(
+        &self,
+        context: &GlContext,
+        _surface: &dyn Surface,
+        rect: Rect<i32>,
+        theme: &Theme,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        is_active: bool,
+    ) {
+        self.size.set(rect.size());
+        let style = theme.style(WidgetKind::TextEntry, widget_state(rect, cursor_pos, is_active));
+        let padding = style.padding;
+        let drawn_text = self.displayed_text();
+        let drawn_text_color = if self.text.is_empty() && self.shown_composition().is_none() {
+            style.text_color * 0.8
+        } else {
+            style.text_color
+        };
+        let border_color = if self.is_valid() { style.border_color } else { theme.invalid_color };
+        draw_2d.fill_rounded_rect(rect, style.corner_radius, style.fill_color);
+        draw_2d.outline_rounded_rect(rect, style.corner_radius, border_color, style.border_width);
+        // The offsets are of the drawn text, but they're indexed by char, so they still line up
+        // with the text when it's masked.
+        let offsets = style.font.char_offsets(context, &drawn_text);
+        let x_at_index =
+            |i: usize| (rect.start.x + padding) as f32 + offsets[i.min(offsets.len() - 1)];
+        let x = |pos: usize| x_at_index(self.displayed_char_index(pos));
+        if let Some(composition) = self.shown_composition() {
+            // The text being composed is underlined.
+            let end = self.displayed_char_index(self.caret_pos);
+            let start = end - composition.chars().count();
+            let y = rect.end.y as f32 - 3.0;
+            draw_2d.draw_line(
+                point2(x_at_index(start), y),
+                point2(x_at_index(end), y),
+                drawn_text_color,
+                1.0,
+            );
+        }
+        if let Some(selection) = self.selection() {
+            let x = |pos: usize| x(pos) as i32;
+            draw_2d.fill_rect(
+                Rect::new(
+                    point2(x(selection.start), rect.start.y + padding),
+                    point2(x(selection.end), rect.end.y - padding),
+                ),
+                style.accent_color,
+            );
+        }
+        draw_2d.draw_string(
+            context,
+            &style.font,
+            &drawn_text,
+            rect.start + vec2(padding, padding / 2),
+            drawn_text_color,
+        );
+        if self.stopwatch.get_time().rem_euclid(CARET_BLINK_RATE) < CARET_BLINK_RATE * 0.5
+            && is_active
+        {
+            let caret_x = x(self.caret_pos);
+            draw_2d.draw_line(
+                point2(caret_x, (rect.start.y + padding) as f32),
+                point2(caret_x, (rect.end.y - padding) as f32),
+                style.text_color,
+                1.0,
+            );
+        }
+        *self.char_offsets.borrow_mut() = offsets;
+
+        if self.has_reveal_toggle() {
+            let size = rect.size().y;
+            let toggle = Rect::new(point2(rect.end.x - size, rect.start.y), rect.end);
+            draw_reveal_toggle(draw_2d, toggle, self.revealed, style.text_color);
+        }
+    }
+
+    fn min_size(
+        &self,
+        context: &GlContext,
+        theme: &Theme,
+        _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        _window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let style = theme.style(WidgetKind::TextEntry, WidgetState::Normal);
+        let size = style.font.string_size(context, &self.displayed_text())
+            + vec2(style.padding * 2, style.padding);
+        if self.has_reveal_toggle() {
+            size + vec2(size.y, 0)
+        } else {
+            size
+        }
+    }
+}
+
+/// Draws an eye, which is crossed out if the text isn't revealed.
+fn draw_reveal_toggle(draw_2d: &mut Draw2d, rect: Rect<i32>, revealed: bool, color: Color4) {
+    let size = rect.size().y;
+    let center = rect.start + vec2(size / 2, size / 2);
+    let eye_size = vec2(size * 2 / 3, size / 3);
+    let eye = Rect::new(center - eye_size / 2, center + eye_size / 2);
+    draw_2d.outline_rounded_rect(eye, (eye_size.y / 2) as f32, color, 1.0);
+    let pupil = Rect::new(center - vec2(2, 2), center + vec2(2, 2));
+    draw_2d.fill_rounded_rect(pupil, 2.0, color);
+    if !revealed {
+        let (start, end): (Point2<f32>, Point2<f32>) =
+            (eye.start.cast().unwrap(), eye.end.cast().unwrap());
+        draw_2d.draw_line(point2(start.x, end.y + 2.0), point2(end.x, start.y - 2.0), color, 1.0);
+    }
+}
+
+pub struct NumberEntryResult {
+    pub value: f64,
+    /// True if the user changed the value since the last update.
+    pub changed: bool,
+}
+
+/// An entry for a number between a minimum and maximum, with buttons to step it down and up. It
+/// can also be stepped with the up and down arrow keys or the mouse wheel.
+///
+/// Typed text that isn't a number in range is shown as invalid and doesn't change the value. The
+/// text is replaced with the current value when enter is pressed.
+#[derive(Clone)]
+pub struct NumberEntry {
+    id: WidgetId,
+    value: f64,
+    min: f64,
+    max: f64,
+    step: f64,
+    decimals: usize,
+    entry: Box<TextEntry>,
+    // The widget's size when it was last drawn, for clicking the step buttons
+    size: Rc<Cell<Vector2<i32>>>,
+    // Scrolling that didn't add up to a whole step yet
+    scroll_remainder: f64,
+}
+
+impl NumberEntry {
+    /// Creates an entry for an integer with no minimum or maximum.
+    pub fn new(value: f64) -> Box<Self> {
+        let mut res = Box::new(NumberEntry {
+            id: WidgetId::new(),
+            value,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+            step: 1.0,
+            decimals: 0,
+            entry: TextEntry::new("", "", false, 32, true),
+            size: Rc::new(Cell::new(vec2(0, 0))),
+            scroll_remainder: 0.0,
+        });
+        res.update_entry();
+        res
+    }
+
+    pub fn range(mut self: Box<Self>, min: f64, max: f64) -> Box<Self> {
+        assert!(min <= max);
+        self.min = min;
+        self.max = max;
+        self.update_entry();
+        self
+    }
+
+    /// The amount the step buttons, arrow keys, and mouse wheel change the value by.
+    pub fn step(mut self: Box<Self>, step: f64) -> Box<Self> {
+        self.step = step;
+        self
+    }
 
-    fn children(&self) -> Vec<&dyn Widget> {
-        vec![&*self.child]
+    /// The number of digits after the decimal point. If this is 0, which is the default, the
+    /// value is an integer.
+    pub fn decimals(mut self: Box<Self>, decimals: usize) -> Box<Self> {
+        self.decimals = decimals;
+        self.update_entry();
+        self
     }
 
-    fn compute_rects(
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Sets the value, limiting it to the range and rounding it to the number of decimals.
+    pub fn set_value(&mut self, value: f64) {
+        let scale = 10f64.powi(self.decimals as i32);
+        self.value = ((value * scale).round() / scale).clamp(self.min, self.max);
+        self.entry.set_text(&format!("{:.*}", self.decimals, self.value));
+    }
+
+    /// Updates the entry's text, filter, and validator after the value's constraints change.
+    fn update_entry(&mut self) {
+        let (min, max, decimals) = (self.min, self.max, self.decimals);
+        let allow_negative = min < 0.0;
+        self.entry.filter = Some(Rc::new(move |c| {
+            c.is_ascii_digit() || (c == '-' && allow_negative) || (c == '.' && decimals > 0)
+        }));
+        self.entry.validator = Some(Rc::new(move |text| parse_number(text, min, max).is_some()));
+        self.set_value(self.value);
+    }
+
+    /// Returns the rects of the step down and step up buttons, relative to the widget.
+    fn button_rects(size: Vector2<i32>) -> [Rect<i32>; 2] {
+        let button_size = size.y;
+        let down_start = point2(size.x - button_size * 2, 0);
+        let up_start = point2(size.x - button_size, 0);
+        [
+            Rect::new(down_start, down_start + vec2(button_size, button_size)),
+            Rect::new(up_start, up_start + vec2(button_size, button_size)),
+        ]
+    }
+}
+
+fn parse_number(text: &str, min: f64, max: f64) -> Option<f64> {
+    text.trim().parse::<f64>().ok().filter(|&value| value >= min && value <= max)
+}
+
+impl Component for NumberEntry {
+    type Res = NumberEntryResult;
+
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) -> NumberEntryResult {
+        let old_value = self.value;
+        let [down_button, up_button] = Self::button_rects(self.size.get());
+        let mut entry_events = vec![];
+        for event in events {
+            match event {
+                Event::MouseDown(MouseButton::Left, pos) if down_button.contains_point(pos) => {
+                    self.set_value(self.value - self.step)
+                }
+                Event::MouseDown(MouseButton::Left, pos) if up_button.contains_point(pos) => {
+                    self.set_value(self.value + self.step)
+                }
+                Event::Scroll { delta, unit } => {
+                    // Each notch of the mouse wheel is one step.
+                    let font = theme.style(WidgetKind::TextEntry, WidgetState::Normal).font;
+                    let lines = unit.to_lines(delta, font.advance_y() as f64).y;
+                    let steps = whole_lines(&mut self.scroll_remainder, lines / LINES_PER_NOTCH);
+                    self.set_value(self.value - steps as f64 * self.step);
+                }
+                Event::KeyDown(ref key) => match key.code.as_ref() {
+                    "ArrowUp" => self.set_value(self.value + self.step),
+                    "ArrowDown" => self.set_value(self.value - self.step),
+                    "Enter" => self.set_value(self.value),
+                    _ => entry_events.push(event),
+                },
+                _ => entry_events.push(event),
+            }
+        }
+        let res = self.entry.update(theme, entry_events);
+        if let Some(value) = res.text().and_then(|text| parse_number(text, self.min, self.max)) {
+            self.value = value;
+        }
+        NumberEntryResult { value: self.value, changed: self.value != old_value }
+    }
+}
+
+impl Widget for NumberEntry {
+    fn id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn is_component(&self) -> bool {
+        true
+    }
+
+    fn cursor_icon(&self, _theme: &Theme, pos: Point2<i32>) -> Option<CursorIcon> {
+        let buttons = Self::button_rects(self.size.get());
+        if buttons.iter().any(|button| button.contains_point(pos)) {
+            Some(CursorIcon::Hand)
+        } else {
+            Some(CursorIcon::Text)
+        }
+    }
+
+    fn handles_scroll(&self) -> bool {
+        true
+    }
+
+    fn draw(
         &self,
+        context: &GlContext,
+        surface: &dyn Surface,
         rect: Rect<i32>,
         theme: &Theme,
-        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
-        widget_rects: &mut FxHashMap<WidgetId, Rect<i32>>,
+        draw_2d: &mut Draw2d,
+        cursor_pos: Option<Point2<i32>>,
+        is_active: bool,
     ) {
-        widget_rects.insert(self.id(), Rect::new(rect.start, rect.end));
-        self.child.compute_rects(Rect::new(rect.start, rect.end), theme, min_sizes, widget_rects);
+        self.size.set(rect.size());
+        let buttons = Self::button_rects(rect.size());
+        let entry_rect =
+            Rect::new(rect.start, point2(rect.start.x + buttons[0].start.x, rect.end.y));
+        self.entry.draw(context, surface, entry_rect, theme, draw_2d, cursor_pos, is_active);
+
+        for (i, button) in buttons.iter().enumerate() {
+            let button =
+                Rect::new(rect.start + button.start.to_vec(), rect.start + button.end.to_vec());
+            let style = theme.style(WidgetKind::Button, widget_state(button, cursor_pos, false));
+            draw_2d.fill_rounded_rect(button, style.corner_radius, style.fill_color);
+            draw_2d.outline_rounded_rect(
+                button,
+                style.corner_radius,
+                style.border_color,
+                style.border_width,
+            );
+
+            // A minus sign, with a vertical line added to make the plus sign
+            let center: Point2<f32> =
+                button.start.cast().unwrap() + button.size().cast().unwrap() / 2.0;
+            let half_len = (button.size().y / 4) as f32;
+            let color = style.text_color;
+            draw_2d.draw_line(
+                center - vec2(half_len, 0.0),
+                center + vec2(half_len, 0.0),
+                color,
+                1.0,
+            );
+            if i == 1 {
+                draw_2d.draw_line(
+                    center - vec2(0.0, half_len),
+                    center + vec2(0.0, half_len),
+                    color,
+                    1.0,
+                );
+            }
+        }
+    }
+
+    fn min_size(
+        &self,
+        context: &GlContext,
+        theme: &Theme,
+        min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
+        window_size: Vector2<i32>,
+    ) -> Vector2<i32> {
+        let entry_size = self.entry.min_size(context, theme, min_sizes, window_size);
+        entry_size + vec2(entry_size.y * 2, 0)
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub enum TextEntryEvent {
-    AddChar(char),
-    Backspace,
-    CaretLeft,
-    CaretRight,
-    EnterPressed,
+/// A change made to a `TextEditor`'s text. Positions are byte offsets into the text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TextEdit {
+    /// `text` was inserted at `pos`.
+    Insert { pos: usize, text: String },
+    /// The text in `start..end` was deleted.
+    Delete { start: usize, end: usize },
 }
 
-pub struct TextEntryResult {
-    pub text: Option<String>,
+pub struct TextEditorResult {
+    /// The changes made to the text since the last update, in the order they were made.
+    pub edits: Vec<TextEdit>,
 }
 
-impl TextEntryResult {
-    pub fn text(&self) -> Option<&str> {
-        // See https://stackoverflow.com/questions/31233938/converting-from-optionstring-to-optionstr
-        self.text.as_ref().map(|x| x.as_ref())
+impl TextEditorResult {
+    pub fn changed(&self) -> bool {
+        !self.edits.is_empty()
     }
 }
 
-const CARET_BLINK_RATE: f64 = 1.0;
+/// A line of a `TextEditor`'s text, after wrapping.
+struct EditorLine {
+    start: usize,
+    /// The end of the line, not including the newline after it if there is one.
+    end: usize,
+    /// The byte offset of each char boundary in the line, including its start and end.
+    boundaries: Vec<usize>,
+    /// The x position of each char boundary, relative to the start of the line.
+    offsets: Vec<f32>,
+    /// True if the line was wrapped, rather than ending at a newline or the end of the text.
+    soft_wrapped: bool,
+}
+
+impl EditorLine {
+    fn x(&self, pos: usize) -> f32 {
+        match self.boundaries.binary_search(&pos) {
+            Ok(i) => self.offsets[i],
+            Err(_) => *self.offsets.last().unwrap(),
+        }
+    }
+
+    /// Returns the char boundary closest to `x`. The end of a wrapped line is skipped, since it's
+    /// the same position as the start of the next line.
+    fn position_at_x(&self, x: f32) -> usize {
+        let len = if self.soft_wrapped { self.offsets.len() - 1 } else { self.offsets.len() };
+        let closest = (0..len)
+            .min_by(|&a, &b| (self.offsets[a] - x).abs().total_cmp(&(self.offsets[b] - x).abs()))
+            .unwrap();
+        self.boundaries[closest]
+    }
+}
+
+/// Returns the range of `old` that was replaced to turn it into `new`, and the range of `new` that
+/// replaced it, ignoring the parts at the start and end that are the same.
+fn text_difference(old: &str, new: &str) -> (Range<usize>, Range<usize>) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, _), _)| i);
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    (prefix..old.len() - suffix, prefix..new.len() - suffix)
+}
+
+/// Splits text into lines no wider than `width`, breaking after spaces where possible.
+fn wrap_text(context: &GlContext, font: &Font, text: &str, width: f32) -> Vec<EditorLine> {
+    let mut lines = vec![];
+    let mut paragraph_start = 0;
+    for paragraph in text.split('\n') {
+        let chars: Vec<char> = paragraph.chars().collect();
+        let offsets = font.char_offsets(context, paragraph);
+        let boundaries: Vec<usize> = paragraph
+            .char_indices()
+            .map(|(i, _)| paragraph_start + i)
+            .chain(std::iter::once(paragraph_start + paragraph.len()))
+            .collect();
+        let mut line_start = 0;
+        loop {
+            let mut end = line_start;
+            while end < chars.len() && offsets[end + 1] - offsets[line_start] <= width {
+                end += 1;
+            }
+            let soft_wrapped = end < chars.len();
+            if soft_wrapped {
+                // A space that doesn't fit can still end the line, since it's invisible. Each line
+                // has at least one char, even if it doesn't fit.
+                end = match (line_start..=end).rev().find(|&i| chars[i] == ' ') {
+                    Some(space) => space + 1,
+                    None => end.max(line_start + 1),
+                };
+            }
+            lines.push(EditorLine {
+                start: boundaries[line_start],
+                end: boundaries[end],
+                boundaries: boundaries[line_start..=end].to_vec(),
+                offsets: offsets[line_start..=end]
+                    .iter()
+                    .map(|x| x - offsets[line_start])
+                    .collect(),
+                soft_wrapped,
+            });
+            if !soft_wrapped {
+                break;
+            }
+            line_start = end;
+        }
+        paragraph_start += paragraph.len() + 1;
+    }
+    lines
+}
+
+/// Returns the index of the line that `pos` is shown on.
+fn editor_line_index(lines: &[EditorLine], pos: usize) -> usize {
+    lines.iter().rposition(|line| line.start <= pos).unwrap_or(0)
+}
+
+#[derive(Default)]
+struct EditorLayout {
+    lines: Vec<EditorLine>,
+    visible_lines: usize,
+}
 
+/// A multi-line text editor. The text wraps to the editor's width and scrolls vertically, and it
+/// supports selecting text with the mouse or shift and the arrow keys.
+///
+/// Wrapping depends on measuring the text, so the lines used to move the caret up and down are
+/// the ones from when the editor was last drawn. Keep one `TextEditor` around, put a clone of it
+/// in the GUI each frame, and pass the original to `GuiEventResult::update_component`.
 #[derive(Clone)]
-pub struct TextEntry {
+pub struct TextEditor {
     id: WidgetId,
-    pub text: String,
-    placeholder_text: String,
-    text_color: Color4,
-    caret_pos: i32,
-    // TODO: support specifying the max length in pixels
-    max_len: usize,
+    text: String,
+    caret: usize,
+    // The other end of the selection
+    anchor: Option<usize>,
+    // The x position the caret tries to stay at when moving between lines
+    goal_x: Option<f32>,
+    dragging: bool,
+    // Whether to scroll to the caret when drawn; scrolling with the mouse wheel turns this off
+    // until the caret moves.
+    follow_caret: bool,
+    min_view_size: Vector2<i32>,
+    scroll: Rc<Cell<i32>>,
+    layout: Rc<RefCell<EditorLayout>>,
     stopwatch: Stopwatch,
-    use_placeholder_text_if_empty: bool,
-    continuous_updates: bool,
+    history: TextHistory,
+    // Edits made by `undo` and `redo` that haven't been returned by `update` yet
+    pending_edits: Vec<TextEdit>,
 }
 
-impl TextEntry {
-    /// Creates a new `TextEntry`.
-    ///
-    /// If `continuous_updates` is enabled, the widget sends an update each time the text is
-    /// changed, and isn't cleared when enter is pressed.
-    pub fn new(
-        start_text: &str,
-        placeholder_text: &str,
-        use_placeholder_text_if_empty: bool,
-        max_len: usize,
-        continuous_updates: bool,
-    ) -> Box<Self> {
-        assert!(placeholder_text.len() <= max_len);
-        Box::new(TextEntry {
+impl TextEditor {
+    pub fn new(text: &str) -> Box<Self> {
+        Box::new(TextEditor {
             id: WidgetId::new(),
-            text: start_text.to_string(),
-            placeholder_text: placeholder_text.to_string(),
-            text_color: Color4::BLACK,
-            caret_pos: 0,
-            max_len,
+            text: text.to_owned(),
+            caret: 0,
+            anchor: None,
+            goal_x: None,
+            dragging: false,
+            follow_caret: true,
+            min_view_size: vec2(200, 100),
+            scroll: Rc::new(Cell::new(0)),
+            layout: Rc::new(RefCell::new(EditorLayout::default())),
             stopwatch: Stopwatch::new(),
-            use_placeholder_text_if_empty,
-            continuous_updates,
+            history: TextHistory::default(),
+            pending_edits: vec![],
         })
     }
 
-    pub fn text_color(mut self: Box<Self>, color: Color4) -> Box<Self> {
-        self.text_color = color;
+    /// The minimum size of the area the text is shown in. Defaults to 200x100.
+    pub fn min_view_size(mut self: Box<Self>, min_view_size: Vector2<i32>) -> Box<Self> {
+        self.min_view_size = min_view_size;
         self
     }
 
-    pub fn cur_text(&self) -> &str {
-        if self.text.is_empty() && self.use_placeholder_text_if_empty {
-            &self.placeholder_text
-        } else {
-            &self.text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text, moving the caret to the start and clearing the selection and undo
+    /// history. This isn't reported as an edit.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_owned();
+        self.caret = 0;
+        self.anchor = None;
+        self.follow_caret = true;
+        self.history.clear();
+    }
+
+    /// Undoes the last edit. Returns false if there's nothing to undo. The changes to the text
+    /// are returned as edits by the next update.
+    pub fn undo(&mut self) -> bool {
+        let old_text = self.text.clone();
+        let res = self.history.undo(&mut self.text, &mut self.caret);
+        self.after_history_step(&old_text);
+        res
+    }
+
+    /// Redoes the last undone edit. Returns false if there's nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let old_text = self.text.clone();
+        let res = self.history.redo(&mut self.text, &mut self.caret);
+        self.after_history_step(&old_text);
+        res
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Reports the difference between the old and new text as edits.
+    fn after_history_step(&mut self, old_text: &str) {
+        self.anchor = None;
+        self.follow_caret = true;
+        let (deleted, inserted) = text_difference(old_text, &self.text);
+        if !deleted.is_empty() {
+            self.pending_edits.push(TextEdit::Delete { start: deleted.start, end: deleted.end });
+        }
+        if !inserted.is_empty() {
+            let text = self.text[inserted.clone()].to_owned();
+            self.pending_edits.push(TextEdit::Insert { pos: inserted.start, text });
         }
     }
 
-    /// Returns the current contents of the TextEntry, and clears the contents unless
-    /// `continuous_updates` is enabled.
-    fn take_cur_text(&mut self) -> String {
-        if self.text.is_empty() && self.use_placeholder_text_if_empty {
-            self.placeholder_text.clone()
-        } else if self.continuous_updates {
-            self.text.clone()
+    /// The caret's position, as a byte offset into the text.
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// The selected part of the text, if any.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.anchor
+            .filter(|&anchor| anchor != self.caret)
+            .map(|anchor| anchor.min(self.caret)..anchor.max(self.caret))
+    }
+
+    /// Selects `range`, leaving the caret at its end.
+    pub fn select(&mut self, range: Range<usize>) {
+        self.move_caret(range.start, false);
+        self.move_caret(range.end, true);
+    }
+
+    /// Moves the caret, extending the selection from the old position if `extend` is true.
+    fn move_caret(&mut self, pos: usize, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.caret);
         } else {
-            mem::take(&mut self.text)
+            self.anchor = None;
+        }
+        // The position can come from an out of date layout, so it might not be valid.
+        let mut pos = pos.min(self.text.len());
+        while !self.text.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        self.caret = pos;
+        self.follow_caret = true;
+        self.stopwatch = Stopwatch::new();
+    }
+
+    fn prev_boundary(&self, pos: usize) -> usize {
+        self.text[..pos].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, pos: usize) -> usize {
+        pos + self.text[pos..].chars().next().map_or(0, char::len_utf8)
+    }
+
+    fn delete(&mut self, range: Range<usize>, edits: &mut Vec<TextEdit>) {
+        if !range.is_empty() {
+            self.text.replace_range(range.clone(), "");
+            edits.push(TextEdit::Delete { start: range.start, end: range.end });
+        }
+        self.move_caret(range.start, false);
+    }
+
+    /// Replaces the selection, if any, with `text`.
+    fn insert(&mut self, text: &str, edits: &mut Vec<TextEdit>) {
+        if let Some(selection) = self.selection() {
+            self.delete(selection, edits);
+        }
+        self.text.insert_str(self.caret, text);
+        edits.push(TextEdit::Insert { pos: self.caret, text: text.to_owned() });
+        self.move_caret(self.caret + text.len(), false);
+    }
+
+    /// Moves the caret up or down by the given number of lines, staying as close as possible to
+    /// the x position it started from.
+    fn move_lines(&mut self, lines: i32, goal_x: Option<f32>, extend: bool) {
+        let layout = self.layout.clone();
+        let layout = layout.borrow();
+        if layout.lines.is_empty() {
+            return;
+        }
+        let line = editor_line_index(&layout.lines, self.caret);
+        let goal_x = goal_x.unwrap_or_else(|| layout.lines[line].x(self.caret));
+        let target = (line as i32 + lines).clamp(0, layout.lines.len() as i32 - 1) as usize;
+        self.move_caret(layout.lines[target].position_at_x(goal_x), extend);
+        self.goal_x = Some(goal_x);
+    }
+
+    /// Returns the position closest to a point relative to the editor.
+    fn position_at_point(&self, theme: &Theme, pos: Point2<i32>) -> usize {
+        let layout = self.layout.borrow();
+        if layout.lines.is_empty() {
+            return self.caret;
         }
+        let style = theme.style(WidgetKind::TextEditor, WidgetState::Normal);
+        let y = pos.y - style.padding + self.scroll.get();
+        let line = y.div_euclid(style.font.advance_y()).clamp(0, layout.lines.len() as i32 - 1);
+        layout.lines[line as usize].position_at_x((pos.x - style.padding) as f32)
     }
 }
 
-impl Component for TextEntry {
-    type Res = TextEntryResult;
+impl Component for TextEditor {
+    type Res = TextEditorResult;
 
-    fn update(&mut self, _theme: &Theme, events: Vec<Event>) -> TextEntryResult {
-        let mut res = None;
+    fn update(&mut self, theme: &Theme, events: Vec<Event>) -> TextEditorResult {
         for event in events {
+            let goal_x = self.goal_x.take();
+            match undo_shortcut(&event) {
+                Some(false) => {
+                    self.undo();
+                    continue;
+                }
+                Some(true) => {
+                    self.redo();
+                    continue;
+                }
+                None => (),
+            }
+            let mut edits = vec![];
+            let typing = matches!(event, Event::CharEntered(_));
+            let before = match event {
+                Event::KeyDown(_) | Event::CharEntered(_) | Event::CompositionEnd(_) => {
+                    Some((self.text.clone(), self.caret))
+                }
+                _ => None,
+            };
             match event {
-                Event::KeyDown(key) => match key.code.as_ref() {
-                    "Backspace" => {
-                        if self.caret_pos > 0 {
-                            self.text.remove(self.caret_pos as usize - 1);
-                            self.caret_pos -= 1;
+                Event::KeyDown(key) => {
+                    let extend = key.shift;
+                    let selection = self.selection();
+                    match key.code.as_ref() {
+                        "ArrowLeft" => match selection {
+                            Some(selection) if !extend => self.move_caret(selection.start, false),
+                            _ => self.move_caret(self.prev_boundary(self.caret), extend),
+                        },
+                        "ArrowRight" => match selection {
+                            Some(selection) if !extend => self.move_caret(selection.end, false),
+                            _ => self.move_caret(self.next_boundary(self.caret), extend),
+                        },
+                        "ArrowUp" => self.move_lines(-1, goal_x, extend),
+                        "ArrowDown" => self.move_lines(1, goal_x, extend),
+                        "PageUp" | "PageDown" => {
+                            let page = self.layout.borrow().visible_lines.max(1) as i32;
+                            let lines = if key.code == "PageUp" { -page } else { page };
+                            self.move_lines(lines, goal_x, extend);
                         }
+                        "Home" | "End" if key.ctrl => {
+                            let pos = if key.code == "Home" { 0 } else { self.text.len() };
+                            self.move_caret(pos, extend);
+                        }
+                        "Home" | "End" => {
+                            let layout = self.layout.clone();
+                            let layout = layout.borrow();
+                            if let Some(line) =
+                                layout.lines.get(editor_line_index(&layout.lines, self.caret))
+                            {
+                                let pos = if key.code == "Home" {
+                                    line.start
+                                } else if line.soft_wrapped {
+                                    // Before the char the line was wrapped after, so that the
+                                    // caret stays on this line
+                                    line.boundaries[line.boundaries.len() - 2]
+                                } else {
+                                    line.end
+                                };
+                                self.move_caret(pos, extend);
+                            }
+                        }
+                        "KeyA" if key.ctrl => self.select(0..self.text.len()),
+                        "Backspace" => {
+                            let range = selection
+                                .unwrap_or_else(|| self.prev_boundary(self.caret)..self.caret);
+                            self.delete(range, &mut edits);
+                        }
+                        "Delete" => {
+                            let range = selection
+                                .unwrap_or_else(|| self.caret..self.next_boundary(self.caret));
+                            self.delete(range, &mut edits);
+                        }
+                        "Enter" => self.insert("\n", &mut edits),
+                        _ => (),
                     }
-                    "ArrowLeft" => self.caret_pos = (self.caret_pos - 1).max(0),
-                    "ArrowRight" => {
-                        self.caret_pos = (self.caret_pos + 1).min(self.text.len() as i32)
-                    }
-                    "Enter" => {
-                        res = Some(self.take_cur_text());
-                        self.caret_pos = 0;
-                    }
-                    _ => (),
-                },
-                Event::CharEntered(c) => {
-                    if self.text.len() < self.max_len {
-                        self.text.insert(self.caret_pos as usize, c);
-                        self.caret_pos += 1;
-                    }
+                }
+                Event::CharEntered(c) if !c.is_control() => {
+                    self.insert(c.encode_utf8(&mut [0; 4]), &mut edits)
+                }
+                Event::CompositionEnd(text) if !text.is_empty() => self.insert(&text, &mut edits),
+                Event::MouseDown(MouseButton::Left, pos) => {
+                    self.move_caret(self.position_at_point(theme, pos), false);
+                    self.anchor = Some(self.caret);
+                    self.dragging = true;
+                }
+                Event::MouseMove { pos, .. } if self.dragging => {
+                    self.move_caret(self.position_at_point(theme, pos), true);
+                }
+                Event::MouseUp(MouseButton::Left, _) | Event::FocusLost => self.dragging = false,
+                Event::Scroll { delta, unit } => {
+                    let font = theme.style(WidgetKind::TextEditor, WidgetState::Normal).font;
+                    let distance = unit.to_pixels(delta, font.advance_y() as f64).y;
+                    self.scroll.set(self.scroll.get() + distance.round() as i32);
+                    self.follow_caret = false;
                 }
                 _ => (),
             }
+            if let Some((text, caret)) = before {
+                if !edits.is_empty() {
+                    self.history.record(text, caret, self.caret, typing);
+                }
+            }
+            self.pending_edits.append(&mut edits);
         }
-        if self.continuous_updates {
-            res = Some(self.cur_text().to_owned());
-        }
-        TextEntryResult { text: res }
+        TextEditorResult { edits: mem::take(&mut self.pending_edits) }
     }
 }
 
-impl Widget for TextEntry {
+impl Widget for TextEditor {
     fn id(&self) -> WidgetId {
         self.id
     }
@@ -1061,6 +4466,18 @@ impl Widget for TextEntry {
         true
     }
 
+    fn captures_pointer(&self) -> bool {
+        true
+    }
+
+    fn cursor_icon(&self, _theme: &Theme, _pos: Point2<i32>) -> Option<CursorIcon> {
+        Some(CursorIcon::Text)
+    }
+
+    fn handles_scroll(&self) -> bool {
+        true
+    }
+
     fn draw(
         &self,
         context: &GlContext,
@@ -1068,40 +4485,93 @@ impl Widget for TextEntry {
         rect: Rect<i32>,
         theme: &Theme,
         draw_2d: &mut Draw2d,
-        _cursor_pos: Option<Point2<i32>>,
+        cursor_pos: Option<Point2<i32>>,
         is_active: bool,
     ) {
-        let fill_color = theme.button_fill_color;
-        let (drawn_text, drawn_text_color) = if self.text.is_empty() {
-            (&self.placeholder_text, theme.button_text_color * 0.8)
-        } else {
-            (&self.text, theme.button_text_color)
-        };
-        draw_2d.fill_rect(rect, fill_color);
-        draw_2d.outline_rect(rect, theme.button_border_color, 1.0);
-        theme.font.draw_string(context, drawn_text, rect.start + vec2(2, 1), drawn_text_color);
-        if self.stopwatch.get_time().rem_euclid(CARET_BLINK_RATE) < CARET_BLINK_RATE * 0.5
-            && is_active
-        {
-            let caret_x_offset =
-                theme.font.string_width(context, &drawn_text[0..self.caret_pos as usize]) + 2.0;
-            draw_2d.draw_line(
-                point2(caret_x_offset + rect.start.x as f32, rect.start.y as f32 + 2.0),
-                point2(caret_x_offset + rect.start.x as f32, rect.end.y as f32 - 2.0),
-                theme.button_text_color,
-                1.0,
+        let style = theme.style(WidgetKind::TextEditor, widget_state(rect, cursor_pos, is_active));
+        let padding = vec2(style.padding, style.padding);
+        let view = Rect::new(rect.start + padding, rect.end - padding);
+        let advance_y = style.font.advance_y();
+        let lines = wrap_text(context, &style.font, &self.text, view.size().x as f32);
+        let caret_line = editor_line_index(&lines, self.caret);
+
+        let view_height = view.size().y;
+        let mut scroll = self.scroll.get();
+        if self.follow_caret {
+            let caret_y = caret_line as i32 * advance_y;
+            scroll = scroll.min(caret_y).max(caret_y + advance_y - view_height);
+        }
+        scroll = scroll.clamp(0, (lines.len() as i32 * advance_y - view_height).max(0));
+        self.scroll.set(scroll);
+
+        draw_2d.fill_rounded_rect(rect, style.corner_radius, style.fill_color);
+        draw_2d.outline_rounded_rect(
+            rect,
+            style.corner_radius,
+            style.border_color,
+            style.border_width,
+        );
+        draw_2d.push_clip(view);
+        let first = (scroll / advance_y) as usize;
+        let last = (((scroll + view_height) / advance_y + 1) as usize).min(lines.len());
+        let line_y = |i: usize| view.start.y + i as i32 * advance_y - scroll;
+        if let Some(selection) = self.selection() {
+            for (i, line) in lines.iter().enumerate().take(last).skip(first) {
+                if selection.end < line.start || selection.start > line.end {
+                    continue;
+                }
+                let start = line.x(selection.start.max(line.start));
+                let mut end = line.x(selection.end.min(line.end));
+                // Show that the newline at the end of the line is selected.
+                if selection.end > line.end && !line.soft_wrapped {
+                    end += (advance_y / 3) as f32;
+                }
+                let y = line_y(i);
+                let highlight = Rect::new(
+                    point2(view.start.x + start as i32, y),
+                    point2(view.start.x + end as i32, y + advance_y),
+                );
+                draw_2d.fill_rect(highlight, style.accent_color);
+            }
+        }
+        for (i, line) in lines.iter().enumerate().take(last).skip(first) {
+            draw_2d.draw_string(
+                context,
+                &style.font,
+                &self.text[line.start..line.end],
+                point2(view.start.x, line_y(i)),
+                style.text_color,
             );
         }
+        if is_active
+            && self.stopwatch.get_time().rem_euclid(CARET_BLINK_RATE) < CARET_BLINK_RATE * 0.5
+        {
+            if let Some(line) = lines.get(caret_line) {
+                let x = view.start.x as f32 + line.x(self.caret);
+                let y = line_y(caret_line) as f32;
+                draw_2d.draw_line(
+                    point2(x, y),
+                    point2(x, y + advance_y as f32),
+                    style.text_color,
+                    1.0,
+                );
+            }
+        }
+        draw_2d.pop_clip();
+
+        let visible_lines = (view_height / advance_y).max(0) as usize;
+        *self.layout.borrow_mut() = EditorLayout { lines, visible_lines };
     }
 
     fn min_size(
         &self,
-        context: &GlContext,
+        _context: &GlContext,
         theme: &Theme,
         _min_sizes: &FxHashMap<WidgetId, Vector2<i32>>,
         _window_size: Vector2<i32>,
     ) -> Vector2<i32> {
-        let drawn_text = if self.text.is_empty() { &self.placeholder_text } else { &self.text };
-        theme.font.string_size(context, drawn_text) + vec2(4, 2)
+        let style = theme.style(WidgetKind::TextEditor, WidgetState::Normal);
+        let padding = vec2(style.padding, style.padding) * 2;
+        vec2(self.min_view_size.x, self.min_view_size.y.max(style.font.advance_y())) + padding
     }
 }
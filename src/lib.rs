@@ -8,3 +8,5 @@ mod screenshot;
 
 pub use gl::*;
 pub use gui::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use screenshot::PendingScreenshot;
@@ -1,5 +1,6 @@
 #![cfg(not(target_arch = "wasm32"))]
 
+use cgmath::*;
 use glow::HasContext;
 
 use crate::gl::*;
@@ -18,7 +19,8 @@ pub fn take_screenshot(
     let mut pixels =
         vec![0; (window_size.x * window_size.y * (if include_alpha { 4 } else { 3 })) as usize];
     unsafe {
-        context.inner().read_buffer(glow::FRONT);
+        // OpenGL ES can't read from the front buffer.
+        context.inner().read_buffer(if context.is_gles() { glow::BACK } else { glow::FRONT });
         context.inner().read_pixels(
             0,
             0,
@@ -30,6 +32,40 @@ pub fn take_screenshot(
         );
     }
 
+    save_screenshot(pixels, window_size, path, include_alpha);
+}
+
+/// A screenshot that's being read from the GPU; created with
+/// `ScreenSurface::take_screenshot_async`.
+pub struct PendingScreenshot {
+    readback: PixelReadback,
+    path: Option<PathBuf>,
+}
+
+impl PendingScreenshot {
+    pub(crate) fn new(readback: PixelReadback, path: Option<PathBuf>) -> Self {
+        Self { readback, path }
+    }
+
+    /// Saves the screenshot if it's ready. Returns true once it's been saved.
+    pub fn poll(&mut self) -> bool {
+        match self.readback.poll() {
+            Some(pixels) => {
+                let (size, include_alpha) = (self.readback.size(), self.readback.has_alpha());
+                save_screenshot(pixels, size, self.path.take(), include_alpha);
+                true
+            }
+            None => !self.readback.is_pending(),
+        }
+    }
+}
+
+fn save_screenshot(
+    pixels: Vec<u8>,
+    size: Vector2<u32>,
+    path: Option<PathBuf>,
+    include_alpha: bool,
+) {
     let path = match path {
         Some(path) => path,
         None => {
@@ -55,21 +91,11 @@ pub fn take_screenshot(
             if include_alpha {
                 // TODO: there's some redundant conversions here
                 // TODO: why is flipping the image necessary?
-                let image_buf = image::ImageBuffer::from_raw(
-                    window_size.x as u32,
-                    window_size.y as u32,
-                    pixels,
-                )
-                .unwrap();
+                let image_buf = image::ImageBuffer::from_raw(size.x, size.y, pixels).unwrap();
                 let img = DynamicImage::ImageRgba8(image_buf).flipv();
                 img.to_rgba8().save(&path).unwrap();
             } else {
-                let image_buf = image::ImageBuffer::from_raw(
-                    window_size.x as u32,
-                    window_size.y as u32,
-                    pixels,
-                )
-                .unwrap();
+                let image_buf = image::ImageBuffer::from_raw(size.x, size.y, pixels).unwrap();
                 let img = DynamicImage::ImageRgb8(image_buf).flipv();
                 img.to_rgb8().save(&path).unwrap();
             }